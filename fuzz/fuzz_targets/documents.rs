@@ -0,0 +1,13 @@
+#![no_main]
+use libfuzzer_sys::fuzz_target;
+use unstructured::Document;
+
+fuzz_target!(|input: (Document, String)| {
+    let (mut doc, sel) = input;
+    let json = serde_json::to_string(&doc).unwrap();
+    let _: Document = serde_json::from_str(&json).unwrap();
+    let _ = doc.select(&sel);
+    let _ = doc.select_all(&sel);
+    let _ = doc.select_mut(&sel);
+    assert_eq!(doc.clone(), doc);
+});
@@ -0,0 +1,13 @@
+#![no_main]
+use libfuzzer_sys::fuzz_target;
+use unstructured::Document;
+
+const DOCS: [&str; 2] = [r#"{"a": [1, 2, 3, 4], "b": {"c": "x"}}"#, r#"[{"a": 1}, null]"#];
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok(sel) = std::str::from_utf8(data) {
+        let docs: Vec<Document> = DOCS.iter().map(|d| serde_json::from_str(d).unwrap()).collect();
+        let _ = Document::filter(&docs, sel);
+        let _ = Document::filter(&[], sel);
+    }
+});
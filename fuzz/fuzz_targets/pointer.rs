@@ -0,0 +1,15 @@
+#![no_main]
+use libfuzzer_sys::fuzz_target;
+use unstructured::{Document, Path};
+
+const DOC: &str = r#"{"a": [1, {"b": "x"}], "c~d": {"e/f": true}}"#;
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok(pointer) = std::str::from_utf8(data) {
+        let mut doc: Document = serde_json::from_str(DOC).unwrap();
+        let _ = Path::parse(pointer).map(|path| path.to_string());
+        let _ = doc.pointer(pointer);
+        let _ = doc.pointer_mut(pointer);
+        let _ = doc.pointer_remove(pointer);
+    }
+});
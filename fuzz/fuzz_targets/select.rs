@@ -0,0 +1,20 @@
+#![no_main]
+use libfuzzer_sys::fuzz_target;
+use unstructured::{CompiledSelector, Document, SelectorVersion};
+
+const DOC: &str = r#"{"a": [1, {"b": "x"}, [2, 3]], "c": {"d": null, "1": true, "e.f": 2.5}}"#;
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok(sel) = std::str::from_utf8(data) {
+        let mut doc: Document = serde_json::from_str(DOC).unwrap();
+        let _ = doc.select(sel);
+        let _ = doc.select_all(sel);
+        let _ = CompiledSelector::compile_with(sel, SelectorVersion::V1);
+        if let Ok(compiled) = CompiledSelector::compile(sel) {
+            let _ = compiled.explain(&doc);
+            let _ = compiled.apply_mut(&mut doc);
+            let _ = compiled.take(&mut doc);
+        }
+        let _ = doc.select_mut(sel);
+    }
+});
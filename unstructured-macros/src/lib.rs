@@ -0,0 +1,232 @@
+/*!
+Procedural macros for [unstructured](https://docs.rs/unstructured), these are re-exported by that
+crate when the `macros` feature is enabled and should not be depended on directly.
+*/
+
+extern crate proc_macro;
+
+use proc_macro::{TokenStream, TokenTree};
+use std::fmt::Write;
+
+enum Segment {
+    Key(String),
+    Index(usize),
+}
+
+/// Parse a selector at compile time into an `unstructured::Path`.
+///
+/// Both JSON Pointer (`"/path/to/3/key"`) and the jq inspired syntax (`".path.to[3].key"`) are
+/// supported. Only plain key and index segments can be used since the result is a single path,
+/// an invalid selector is reported as a compile error.
+#[proc_macro]
+pub fn sel(input: TokenStream) -> TokenStream {
+    let result = literal(input).and_then(|selector| {
+        if selector.is_empty() || selector.starts_with('/') {
+            parse_pointer(&selector)
+        } else {
+            parse_jq(&selector)
+        }
+    });
+    let code = match result {
+        Ok(segments) => {
+            let mut code = String::from("::unstructured::Path::from(::std::vec![");
+            for segment in segments {
+                match segment {
+                    Segment::Key(k) => write!(
+                        code,
+                        "::unstructured::Segment::Key(::std::string::String::from({:?})),",
+                        k
+                    ),
+                    Segment::Index(i) => {
+                        write!(code, "::unstructured::Segment::Index({}usize),", i)
+                    }
+                }
+                .expect("writing to a String cannot fail");
+            }
+            code.push_str("])");
+            code
+        }
+        Err(e) => format!("::std::compile_error!({:?})", e),
+    };
+    code.parse().expect("generated code is valid")
+}
+
+/// Extract the value of the single string literal passed to the macro
+fn literal(input: TokenStream) -> Result<String, String> {
+    let mut tokens = input.into_iter();
+    let lit = match (tokens.next(), tokens.next()) {
+        (Some(TokenTree::Literal(lit)), None) => lit.to_string(),
+        _ => return Err("sel! expects a single string literal".to_string()),
+    };
+    if let Some(raw) = lit.strip_prefix('r') {
+        let hashes = raw.len() - raw.trim_start_matches('#').len();
+        return Ok(raw[hashes + 1..raw.len() - hashes - 1].to_string());
+    }
+    match lit.strip_prefix('"').and_then(|l| l.strip_suffix('"')) {
+        Some(inner) => unescape_rust(inner),
+        None => Err("sel! expects a single string literal".to_string()),
+    }
+}
+
+fn unescape_rust(s: &str) -> Result<String, String> {
+    let mut result = String::with_capacity(s.len());
+    let mut chars = s.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            result.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') => result.push('\n'),
+            Some('r') => result.push('\r'),
+            Some('t') => result.push('\t'),
+            Some('0') => result.push('\0'),
+            Some('\\') => result.push('\\'),
+            Some('"') => result.push('"'),
+            Some('\'') => result.push('\''),
+            Some('\n') => {
+                while chars.peek().is_some_and(|c| c.is_whitespace()) {
+                    chars.next();
+                }
+            }
+            Some('x') => {
+                let hex: String = chars.by_ref().take(2).collect();
+                let code = u8::from_str_radix(&hex, 16).map_err(|e| e.to_string())?;
+                result.push(code as char);
+            }
+            Some('u') => {
+                let hex: String = chars
+                    .by_ref()
+                    .skip(1)
+                    .take_while(|c| *c != '}')
+                    .collect();
+                let code = u32::from_str_radix(&hex, 16).map_err(|e| e.to_string())?;
+                result.push(std::char::from_u32(code).ok_or("Invalid unicode escape")?);
+            }
+            _ => return Err(format!("Invalid escape in string literal {}", s)),
+        }
+    }
+    Ok(result)
+}
+
+fn parse_pointer(pointer: &str) -> Result<Vec<Segment>, String> {
+    if pointer.is_empty() {
+        return Ok(vec![]);
+    }
+    pointer[1..]
+        .split('/')
+        .map(|token| {
+            let mut key = String::with_capacity(token.len());
+            let mut chars = token.chars();
+            while let Some(c) = chars.next() {
+                if c != '~' {
+                    key.push(c);
+                    continue;
+                }
+                match chars.next() {
+                    Some('0') => key.push('~'),
+                    Some('1') => key.push('/'),
+                    _ => return Err(format!("Invalid escape sequence in pointer token {}", token)),
+                }
+            }
+            let is_index = !key.is_empty()
+                && key.chars().all(|c| c.is_ascii_digit())
+                && (key == "0" || !key.starts_with('0'));
+            match key.parse::<usize>() {
+                Ok(i) if is_index => Ok(Segment::Index(i)),
+                _ => Ok(Segment::Key(key)),
+            }
+        })
+        .collect()
+}
+
+fn parse_jq(selector: &str) -> Result<Vec<Segment>, String> {
+    let mut segments = vec![];
+    let mut rest = selector.trim();
+    while !rest.is_empty() {
+        if let Some(r) = rest.strip_prefix('.') {
+            rest = r;
+            if rest.is_empty() && segments.is_empty() {
+                break;
+            }
+        }
+        if let Some(r) = rest.strip_prefix('[') {
+            let r = r.trim_start();
+            let (segment, r) = if let Some(quoted) = r.strip_prefix('"') {
+                let end = closing_quote(quoted)
+                    .ok_or_else(|| format!("Unterminated string in selector {}", selector))?;
+                (Segment::Key(unescape_json(&quoted[..end])?), &quoted[end + 1..])
+            } else {
+                let end = r.find(|c: char| !c.is_ascii_digit()).unwrap_or(r.len());
+                let index = r[..end].parse::<usize>().map_err(|_| {
+                    format!(
+                        "Invalid index in selector {}, only non-negative indices are supported",
+                        selector
+                    )
+                })?;
+                (Segment::Index(index), &r[end..])
+            };
+            rest = r
+                .trim_start()
+                .strip_prefix(']')
+                .ok_or_else(|| format!("Expected ']' in selector {}", selector))?;
+            segments.push(segment);
+        } else {
+            let end = rest.find(['.', '[']).unwrap_or(rest.len());
+            let key = &rest[..end];
+            if key.is_empty() {
+                return Err(format!("Empty key in selector {}", selector));
+            }
+            if !key.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+                return Err(format!("Invalid key {} in selector {}", key, selector));
+            }
+            segments.push(Segment::Key(key.to_string()));
+            rest = &rest[end..];
+        }
+    }
+    Ok(segments)
+}
+
+fn closing_quote(s: &str) -> Option<usize> {
+    let mut escaped = false;
+    for (i, c) in s.char_indices() {
+        match c {
+            '\\' if !escaped => escaped = true,
+            '"' if !escaped => return Some(i),
+            _ => escaped = false,
+        }
+    }
+    None
+}
+
+fn unescape_json(s: &str) -> Result<String, String> {
+    let mut result = String::with_capacity(s.len());
+    let mut chars = s.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            result.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('"') => result.push('"'),
+            Some('\\') => result.push('\\'),
+            Some('/') => result.push('/'),
+            Some('b') => result.push('\u{8}'),
+            Some('f') => result.push('\u{c}'),
+            Some('n') => result.push('\n'),
+            Some('r') => result.push('\r'),
+            Some('t') => result.push('\t'),
+            Some('u') => {
+                let hex: String = chars.by_ref().take(4).collect();
+                let code = u32::from_str_radix(&hex, 16)
+                    .map_err(|_| format!("Invalid unicode escape \\u{}", hex))?;
+                result.push(
+                    std::char::from_u32(code)
+                        .ok_or_else(|| format!("Invalid unicode escape \\u{}", hex))?,
+                );
+            }
+            _ => return Err(format!("Invalid escape sequence in {}", s)),
+        }
+    }
+    Ok(result)
+}
@@ -0,0 +1,127 @@
+use crate::*;
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, HashMap};
+
+/// A small LRU cache of parsed documents keyed by a SHA-256 digest of their source bytes, the
+/// same digest used for canonical hashes. Entries don't keep a copy of their source, and a
+/// lookup only hits for an identical payload since no two payloads are known to share a
+/// digest.
+///
+/// This is intended for cases where identical payloads are parsed repeatedly (e.g. webhook
+/// retries), allowing the parse step to be skipped entirely on a cache hit.
+///
+/// ```
+/// use unstructured::{DocCache, Document};
+///
+/// let mut cache = DocCache::new(16);
+/// let payload = br#"{"id": 1}"#;
+/// let doc = cache
+///     .get_or_parse(payload, |src| serde_json::from_slice::<Document>(src))
+///     .unwrap();
+/// assert_eq!(doc["id"], 1);
+/// assert!(cache.contains(payload));
+/// ```
+#[derive(Clone)]
+pub struct DocCache<T: UnstructuredDataTrait = UnstructuredType> {
+    capacity: usize,
+    tick: u64,
+    entries: HashMap<Key, (u64, Unstructured<T>)>,
+    /// The key of every entry by when it was last used, oldest first
+    recency: BTreeMap<u64, Key>,
+}
+
+type Key = [u8; 32];
+
+fn key(source: &[u8]) -> Key {
+    Sha256::digest(source).into()
+}
+
+impl<T: UnstructuredDataTrait> DocCache<T> {
+    /// Create a new cache holding at most `capacity` documents (minimum of 1)
+    pub fn new(capacity: usize) -> Self {
+        let capacity = capacity.max(1);
+        DocCache {
+            capacity,
+            tick: 0,
+            entries: HashMap::with_capacity(capacity),
+            recency: BTreeMap::new(),
+        }
+    }
+
+    /// Return the cached document for `source`, parsing and caching it with `parse` on a miss
+    pub fn get_or_parse<E, F>(&mut self, source: &[u8], parse: F) -> Result<&Unstructured<T>, E>
+    where
+        F: FnOnce(&[u8]) -> Result<Unstructured<T>, E>,
+    {
+        let key = key(source);
+        let doc = match self.take(&key) {
+            Some(doc) => doc,
+            None => parse(source)?,
+        };
+        Ok(self.store(key, doc))
+    }
+
+    /// Return the cached document for `source`, if present
+    pub fn get(&mut self, source: &[u8]) -> Option<&Unstructured<T>> {
+        let key = key(source);
+        let (used, doc) = self.entries.get_mut(&key)?;
+        self.recency.remove(used);
+        self.tick += 1;
+        *used = self.tick;
+        self.recency.insert(self.tick, key);
+        Some(doc)
+    }
+
+    /// Insert an already parsed document for `source`, evicting the least recently used entry
+    /// if the cache is full
+    pub fn insert(&mut self, source: &[u8], doc: Unstructured<T>) {
+        let key = key(source);
+        self.take(&key);
+        self.store(key, doc);
+    }
+
+    /// Remove the cached document for `source`, returning it if present
+    pub fn remove(&mut self, source: &[u8]) -> Option<Unstructured<T>> {
+        self.take(&key(source))
+    }
+
+    pub fn contains(&self, source: &[u8]) -> bool {
+        self.entries.contains_key(&key(source))
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+        self.recency.clear();
+    }
+
+    fn take(&mut self, key: &Key) -> Option<Unstructured<T>> {
+        let (used, doc) = self.entries.remove(key)?;
+        self.recency.remove(&used);
+        Some(doc)
+    }
+
+    /// Add an entry that isn't in the cache as the most recently used one, evicting the least
+    /// recently used entry if the cache is full
+    fn store(&mut self, key: Key, doc: Unstructured<T>) -> &Unstructured<T> {
+        if self.entries.len() >= self.capacity {
+            if let Some((_, oldest)) = self.recency.pop_first() {
+                self.entries.remove(&oldest);
+            }
+        }
+        self.tick += 1;
+        self.recency.insert(self.tick, key);
+        &self.entries.entry(key).or_insert((self.tick, doc)).1
+    }
+}
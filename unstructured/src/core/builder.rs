@@ -0,0 +1,162 @@
+use crate::*;
+
+/// Builds a document one value at a time, for documents whose shape is only known at runtime.
+/// A value following [`key`](DocumentBuilder::key) is added to a map, any other value is pushed
+/// onto a sequence, or becomes the document if nothing has been added yet. The first misuse is
+/// kept and returned by [`build`](DocumentBuilder::build), and later calls do nothing.
+///
+/// ```
+/// use unstructured::{Document, DocumentBuilder};
+///
+/// let doc: Document = DocumentBuilder::new()
+///     .key("a")
+///     .value(1)
+///     .key("b")
+///     .seq(|s| s.push(1).push(2))
+///     .key("c")
+///     .map(|m| m.key("d").value(true))
+///     .build()
+///     .unwrap();
+/// let expected: Document = serde_json::from_str(r#"{"a": 1, "b": [1, 2], "c": {"d": true}}"#)
+///     .unwrap();
+/// assert_eq!(doc, expected);
+/// ```
+#[derive(Clone)]
+pub struct DocumentBuilder<T: UnstructuredDataTrait = UnstructuredType> {
+    doc: Unstructured<T>,
+    key: Option<Unstructured<T>>,
+    error: Option<String>,
+}
+
+impl<T: UnstructuredDataTrait> Default for DocumentBuilder<T> {
+    fn default() -> Self {
+        DocumentBuilder {
+            doc: Unstructured::<T>::Unassigned,
+            key: None,
+            error: None,
+        }
+    }
+}
+
+impl<T: UnstructuredDataTrait> DocumentBuilder<T> {
+    /// A builder with nothing added yet
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the key the next value is added under, making the document a map.
+    ///
+    /// It's an error if the previous key hasn't been given a value.
+    pub fn key<U: Into<Unstructured<T>>>(mut self, key: U) -> Self {
+        if self.error.is_some() {
+            return self;
+        }
+        if let Some(pending) = &self.key {
+            let msg = format!("Cannot add key {} before a value for {}", key.into(), pending);
+            return self.fail(msg);
+        }
+        self.key = Some(key.into());
+        self
+    }
+
+    /// Add a value under the pending key, onto the sequence, or as the whole document.
+    ///
+    /// It's an error if there is no pending key and the document is already a value other than
+    /// a sequence, or if there is a key and the document isn't a map.
+    pub fn value<U: Into<Unstructured<T>>>(mut self, val: U) -> Self {
+        if self.error.is_some() {
+            return self;
+        }
+        let val = val.into();
+        match self.key.take() {
+            Some(key) => {
+                if self.doc.is_missing() {
+                    self.doc = Unstructured::<T>::empty_map();
+                }
+                match &mut self.doc {
+                    Unstructured::<T>::Map(map) => {
+                        make_mut(map).insert(key, val);
+                    }
+                    other => {
+                        let msg = format!("Cannot add key {} to {}", key, other.type_name());
+                        return self.fail(msg);
+                    }
+                }
+            }
+            None if self.doc.is_missing() => self.doc = val,
+            None => return self.append(val),
+        }
+        self
+    }
+
+    /// Append a value to the sequence, making the document a sequence if nothing has been added.
+    ///
+    /// It's an error if there is a pending key or the document isn't a sequence.
+    pub fn push<U: Into<Unstructured<T>>>(self, val: U) -> Self {
+        if self.error.is_some() {
+            return self;
+        }
+        if let Some(key) = &self.key {
+            let msg = format!("Cannot push while key {} is waiting for a value", key);
+            return self.fail(msg);
+        }
+        self.append(val.into())
+    }
+
+    /// Add a sequence built by `f`, in the same place [`value`](DocumentBuilder::value) would
+    pub fn seq<F: FnOnce(Self) -> Self>(self, f: F) -> Self {
+        let seq = f(DocumentBuilder {
+            doc: Unstructured::<T>::empty_seq(),
+            key: None,
+            error: None,
+        });
+        self.nested(seq)
+    }
+
+    /// Add a map built by `f`, in the same place [`value`](DocumentBuilder::value) would
+    pub fn map<F: FnOnce(Self) -> Self>(self, f: F) -> Self {
+        let map = f(DocumentBuilder {
+            doc: Unstructured::<T>::empty_map(),
+            key: None,
+            error: None,
+        });
+        self.nested(map)
+    }
+
+    /// The finished document, `Unassigned` if nothing was added.
+    ///
+    /// Returns the first misuse of the builder, or an error if the last key wasn't given a value.
+    pub fn build(self) -> Result<Unstructured<T>, Error> {
+        if let Some(msg) = self.error {
+            return Err(Error::Build(msg));
+        }
+        if let Some(key) = self.key {
+            let msg = format!("Cannot build a document while key {} is waiting for a value", key);
+            return Err(Error::Build(msg));
+        }
+        Ok(self.doc)
+    }
+
+    fn append(mut self, val: Unstructured<T>) -> Self {
+        if !self.doc.push(val) {
+            let msg = format!("Cannot add a value to a {}", self.doc.type_name());
+            return self.fail(msg);
+        }
+        self
+    }
+
+    fn nested(self, inner: Self) -> Self {
+        if self.error.is_some() {
+            return self;
+        }
+        match inner.build() {
+            Ok(doc) => self.value(doc),
+            Err(e) => self.fail(e.to_string()),
+        }
+    }
+
+    fn fail(mut self, msg: String) -> Self {
+        self.error = Some(msg);
+        self
+    }
+}
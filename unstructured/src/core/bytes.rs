@@ -0,0 +1,114 @@
+use crate::*;
+use std::fmt;
+
+const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+/// How `Bytes` are written when a document is displayed
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum BytesFormat {
+    /// Lowercase hex, e.g. `00ff`
+    #[default]
+    Hex,
+    /// Standard base64 with padding, e.g. `AP8=`
+    Base64,
+}
+
+impl BytesFormat {
+    pub(crate) fn write<W: fmt::Write>(self, f: &mut W, bytes: &[u8]) -> fmt::Result {
+        match self {
+            BytesFormat::Hex => bytes.iter().try_for_each(|b| write!(f, "{:02x}", b)),
+            BytesFormat::Base64 => f.write_str(&encode_base64(bytes)),
+        }
+    }
+}
+
+/// Displays a document with `Bytes` written in a chosen [`BytesFormat`], see
+/// [`Unstructured::display_bytes_as`]
+pub struct BytesDisplay<'a, T: UnstructuredDataTrait> {
+    pub(crate) doc: &'a Unstructured<T>,
+    pub(crate) format: BytesFormat,
+}
+
+impl<'a, T: UnstructuredDataTrait> fmt::Display for BytesDisplay<'a, T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.doc.fmt_with(f, self.format)
+    }
+}
+
+impl<T: UnstructuredDataTrait> Unstructured<T> {
+    /// Display this document with `Bytes` in the given format, `{}` uses hex
+    ///
+    /// ```
+    /// use unstructured::{BytesFormat, Document};
+    ///
+    /// let doc = Document::from(vec![Document::Bytes(b"hi".to_vec())]);
+    /// assert_eq!(doc.to_string(), "[6869]");
+    /// assert_eq!(doc.display_bytes_as(BytesFormat::Base64).to_string(), "[aGk=]");
+    /// ```
+    pub fn display_bytes_as(&self, format: BytesFormat) -> BytesDisplay<'_, T> {
+        BytesDisplay { doc: self, format }
+    }
+
+    /// Encode `Bytes` as a base64 string, None for any other value
+    pub fn bytes_as_base64(&self) -> Option<String> {
+        match self {
+            Self::Bytes(b) => Some(encode_base64(b)),
+            _ => None,
+        }
+    }
+
+    /// Create `Bytes` from a base64 string, the reverse of
+    /// [`bytes_as_base64`](Unstructured::bytes_as_base64). Use this to read back bytes that
+    /// were written as strings with `SerializerOptions::bytes_as_base64`.
+    ///
+    /// ```
+    /// use unstructured::Document;
+    ///
+    /// let doc = Document::from_base64("aGk=").unwrap();
+    /// assert_eq!(doc, Document::Bytes(b"hi".to_vec()));
+    /// assert_eq!(doc.bytes_as_base64().unwrap(), "aGk=");
+    /// assert!(Document::from_base64("a*").is_err());
+    /// ```
+    pub fn from_base64(encoded: &str) -> Result<Self, Error> {
+        decode_base64(encoded).map(Self::Bytes).map_err(Error::Parse)
+    }
+}
+
+pub(crate) fn encode_base64(bytes: &[u8]) -> String {
+    let mut out = String::with_capacity((bytes.len() + 2) / 3 * 4);
+    for chunk in bytes.chunks(3) {
+        let n = chunk.iter().enumerate().fold(0u32, |n, (i, b)| n | (*b as u32) << (16 - 8 * i));
+        for i in 0..4 {
+            if i <= chunk.len() {
+                out.push(ALPHABET[(n >> (18 - 6 * i) & 0x3f) as usize] as char);
+            } else {
+                out.push('=');
+            }
+        }
+    }
+    out
+}
+
+/// Decode standard base64, padding is optional but must be correct if present
+fn decode_base64(encoded: &str) -> Result<Vec<u8>, String> {
+    let trimmed = encoded.trim_end_matches('=');
+    if encoded.len() - trimmed.len() > 2 || trimmed.len() % 4 == 1 {
+        return Err(format!("Invalid base64 length in {}", encoded));
+    }
+    if trimmed.len() != encoded.len() && encoded.len() % 4 != 0 {
+        return Err(format!("Invalid base64 padding in {}", encoded));
+    }
+    let mut out = Vec::with_capacity(trimmed.len() * 3 / 4);
+    for chunk in trimmed.as_bytes().chunks(4) {
+        let mut n = 0u32;
+        for (i, c) in chunk.iter().enumerate() {
+            let value = ALPHABET
+                .iter()
+                .position(|a| a == c)
+                .ok_or_else(|| format!("Invalid base64 character {:?}", *c as char))?;
+            n |= (value as u32) << (18 - 6 * i);
+        }
+        out.extend_from_slice(&n.to_be_bytes()[1..chunk.len()]);
+    }
+    Ok(out)
+}
@@ -0,0 +1,167 @@
+use crate::*;
+#[cfg(feature = "hash")]
+use sha2::digest::Update;
+
+impl<T: UnstructuredDataTrait> Unstructured<T> {
+    /// Rewrite the document into a canonical form, so that documents holding the same data
+    /// compare and hash the same regardless of the format they were read from. Numbers are
+    /// converted with [`Number::canonical`], `Newtype` and `Option` wrappers are replaced by
+    /// the value they hold (`None` becomes `Null`) and map keys are canonicalized the same way.
+    /// If `sort_seqs` is true the elements of every sequence are sorted as well.
+    ///
+    /// ```
+    /// use unstructured::{Document, Number};
+    ///
+    /// let mut json: Document = serde_json::from_str(r#"{"a": [3, 1.0, 2]}"#).unwrap();
+    /// let mut other: Document = serde_yaml::from_str("a: [1, 2, 3]").unwrap();
+    /// json.canonicalize(true);
+    /// other.canonicalize(true);
+    /// assert_eq!(json, other);
+    /// assert_eq!(json["a"][0], Document::Number(Number::U8(1)));
+    /// ```
+    pub fn canonicalize(&mut self, sort_seqs: bool) {
+        match self {
+            Self::Number(n) => *n = n.canonical(),
+            Self::Newtype(inner) => {
+                *self = inner.take();
+                self.canonicalize(sort_seqs);
+            }
+            Self::Option(Some(inner)) => {
+                *self = inner.take();
+                self.canonicalize(sort_seqs);
+            }
+            Self::Option(None) => *self = Self::Null,
+            Self::Seq(seq) => {
+                let seq = make_mut(seq);
+                for val in seq.iter_mut() {
+                    val.canonicalize(sort_seqs);
+                }
+                if sort_seqs {
+                    seq.sort();
+                }
+            }
+            Self::Map(map) => {
+                *map = share(
+                    unshare(std::mem::take(map))
+                        .into_iter()
+                        .map(|(mut key, mut val)| {
+                            key.canonicalize(sort_seqs);
+                            val.canonicalize(sort_seqs);
+                            (key, val)
+                        })
+                        .collect(),
+                );
+            }
+            _ => {}
+        }
+    }
+}
+
+#[cfg(feature = "hash")]
+impl<T: UnstructuredDataTrait> Unstructured<T> {
+    /// A SHA-256 hash of the document's data that is the same across processes, platforms and
+    /// crate versions, unlike [`Hash`](std::hash::Hash) which depends on the `Hasher`. Documents
+    /// that are equal after [`canonicalize(false)`](Unstructured::canonicalize) hash the same,
+    /// whatever order their maps were built in. `Err` and `Other` values only contribute their
+    /// variant.
+    ///
+    /// ```
+    /// use unstructured::Document;
+    ///
+    /// let json: Document = serde_json::from_str(r#"{"a": 1, "b": [1.0, "x"]}"#).unwrap();
+    /// let yaml: Document = serde_yaml::from_str("b: [1, x]\na: 1").unwrap();
+    /// assert_eq!(json.canonical_hash(), yaml.canonical_hash());
+    /// assert_ne!(json.canonical_hash(), Document::Null.canonical_hash());
+    /// ```
+    pub fn canonical_hash(&self) -> [u8; 32] {
+        use sha2::{Digest, Sha256};
+
+        let mut hasher = Sha256::new();
+        self.encode_canonical(&mut hasher);
+        hasher.finalize().into()
+    }
+
+    /// Feed the canonical encoding to `out`: a tag byte for the kind of value followed by its
+    /// data in big endian, with lengths as `u64` and map entries sorted by their encoded key.
+    /// Only map keys are buffered, everything else goes straight to `out`.
+    pub(crate) fn encode_canonical<D: Update>(&self, out: &mut D) {
+        match self {
+            Self::Unassigned => out.update(&[0]),
+            Self::Null | Self::Option(None) => out.update(&[1]),
+            Self::Bool(b) => out.update(&[2, *b as u8]),
+            Self::Number(n) => {
+                let n = n.canonical();
+                if n.is_float() {
+                    // Every NaN is the same value as far as the hash is concerned
+                    let f = f64::from(&n);
+                    let f = if f.is_nan() { f64::NAN } else { f };
+                    out.update(&[5]);
+                    out.update(&f.to_bits().to_be_bytes());
+                } else if n.is_signed() {
+                    out.update(&[4]);
+                    out.update(&i128::from(&n).to_be_bytes());
+                } else {
+                    out.update(&[3]);
+                    out.update(&u128::from(&n).to_be_bytes());
+                }
+            }
+            Self::String(s) => encode_len_prefixed(out, 6, s.as_bytes()),
+            Self::Char(c) => {
+                out.update(&[7]);
+                out.update(&(*c as u32).to_be_bytes());
+            }
+            Self::Bytes(b) => encode_len_prefixed(out, 8, b),
+            #[cfg(feature = "chrono")]
+            Self::DateTime(dt) => {
+                // Equal instants in different offsets are equal values, so hash them the same
+                let utc = dt.with_timezone(&chrono::Utc).to_rfc3339();
+                encode_len_prefixed(out, 9, utc.as_bytes())
+            }
+            Self::Seq(seq) => {
+                out.update(&[10]);
+                out.update(&(seq.len() as u64).to_be_bytes());
+                for val in seq.iter() {
+                    val.encode_canonical(out);
+                }
+            }
+            Self::Map(map) => {
+                let mut entries: Vec<_> = map
+                    .iter()
+                    .map(|(key, val)| {
+                        let mut encoded = Buffer(vec![]);
+                        key.encode_canonical(&mut encoded);
+                        (encoded.0, val)
+                    })
+                    .collect();
+                entries.sort_by(|a, b| a.0.cmp(&b.0));
+                out.update(&[11]);
+                out.update(&(entries.len() as u64).to_be_bytes());
+                for (key, val) in entries {
+                    out.update(&key);
+                    val.encode_canonical(out);
+                }
+            }
+            Self::Option(Some(inner)) | Self::Newtype(inner) => inner.encode_canonical(out),
+            Self::Err(_) => out.update(&[12]),
+            Self::Other(_) => out.update(&[13]),
+        }
+    }
+}
+
+/// Collects an encoding so map keys can be sorted before they are fed to the hash
+#[cfg(feature = "hash")]
+struct Buffer(Vec<u8>);
+
+#[cfg(feature = "hash")]
+impl Update for Buffer {
+    fn update(&mut self, data: &[u8]) {
+        self.0.extend_from_slice(data);
+    }
+}
+
+#[cfg(feature = "hash")]
+fn encode_len_prefixed<D: Update>(out: &mut D, tag: u8, data: &[u8]) {
+    out.update(&[tag]);
+    out.update(&(data.len() as u64).to_be_bytes());
+    out.update(data);
+}
@@ -1,55 +1,62 @@
 use crate::*;
+use std::cmp::Ordering;
 
+/// Comparisons against plain values look through `Option` and `Newtype` wrappers, so
+/// `doc["k"] == "value"` holds whether or not the value was wrapped when serialized
 macro_rules! impl_partial_eq {
-    ($($type:ty, $vrnt:ident);*) => {
+    ($($type:ty, $vrnt:ident, |$l:ident, $r:ident| $eq:expr);* $(;)?) => {
         $(
             impl<T: UnstructuredDataTrait> PartialEq<$type> for Unstructured<T> {
                 fn eq(&self, rhs: & $type) -> bool {
-                    match self {
-                        Self::$vrnt(i) => i == rhs,
+                    match self.unwrapped() {
+                        Self::$vrnt($l) => {
+                            let $r = rhs;
+                            $eq
+                        }
                         _ => false,
                     }
                 }
             }
-        )*
-    };
-}
-impl_partial_eq! { &str, String; String, String; bool, Bool; char, Char; Number, Number }
 
-macro_rules! impl_partial_eq {
-    ($($type:ty, $vrnt:ident);*) => {
-        $(
             impl<T: UnstructuredDataTrait> PartialEq<Unstructured<T>> for $type {
                 fn eq(&self, rhs: &Unstructured<T>) -> bool {
-                    match rhs {
-                        Unstructured::<T>::$vrnt(i) => i == self,
-                        _ => false,
-                    }
+                    rhs == self
                 }
             }
         )*
     };
 }
-impl_partial_eq! { &str, String; String, String; bool, Bool; char, Char }
+impl_partial_eq! {
+    &str, String, |l, r| l[..] == r[..];
+    &String, String, |l, r| l[..] == r[..];
+    String, String, |l, r| l[..] == r[..];
+    &[u8], Bytes, |l, r| l[..] == r[..];
+    Vec<u8>, Bytes, |l, r| l[..] == r[..];
+    bool, Bool, |l, r| l == r;
+    char, Char, |l, r| l == r;
+}
+
+impl<T: UnstructuredDataTrait> PartialEq<Number> for Unstructured<T> {
+    fn eq(&self, rhs: &Number) -> bool {
+        match self.unwrapped() {
+            Self::Number(i) => i == rhs,
+            _ => false,
+        }
+    }
+}
 
 macro_rules! impl_partial_eq_number {
     ( $( $type:ty )* ) => {
         $(
             impl<T: UnstructuredDataTrait> PartialEq<$type> for Unstructured<T> {
                 fn eq(&self, rhs: & $type) -> bool {
-                    match self {
-                        Self::Number(i) => i == &Number::from(rhs),
-                        _ => false,
-                    }
+                    self == &Number::from(rhs)
                 }
             }
 
             impl<T: UnstructuredDataTrait> PartialEq<Unstructured<T>> for $type {
                 fn eq(&self, rhs: &Unstructured<T>) -> bool {
-                    match rhs {
-                        Unstructured::<T>::Number(i) => i == &Number::from(self),
-                        _ => false,
-                    }
+                    rhs == &Number::from(self)
                 }
             }
         )*
@@ -57,23 +64,137 @@ macro_rules! impl_partial_eq_number {
 }
 foreach_numeric_primitive! { impl_partial_eq_number! }
 
+/// Comparing doesn't recurse, so it is safe for documents of any depth
 impl<T: UnstructuredDataTrait> PartialEq for Unstructured<T> {
     fn eq(&self, rhs: &Self) -> bool {
-        match (self, rhs) {
-            (&Self::Unassigned, &Self::Unassigned) => true,
-            (&Self::Null, &Self::Null) => true,
-            (&Self::Bool(v0), &Self::Bool(v1)) if v0 == v1 => true,
-            (&Self::Number(ref v0), &Self::Number(ref v1)) if v0 == v1 => true,
-            (&Self::Char(v0), &Self::Char(v1)) if v0 == v1 => true,
-            (&Self::String(ref v0), &Self::String(ref v1)) if v0 == v1 => true,
-            (&Self::Option(ref v0), &Self::Option(ref v1)) if v0 == v1 => true,
-            (&Self::Newtype(ref v0), &Self::Newtype(ref v1)) if v0 == v1 => true,
-            (&Self::Seq(ref v0), &Self::Seq(ref v1)) if v0 == v1 => true,
-            (&Self::Map(ref v0), &Self::Map(ref v1)) if v0 == v1 => true,
-            (&Self::Bytes(ref v0), &Self::Bytes(ref v1)) if v0 == v1 => true,
-            _ => false,
+        let mut pending = vec![(self, rhs)];
+        while let Some(pair) = pending.pop() {
+            let equal = match pair {
+                (Self::Unassigned, Self::Unassigned) => true,
+                (Self::Null, Self::Null) => true,
+                (Self::Bool(v0), Self::Bool(v1)) => v0 == v1,
+                (Self::Number(v0), Self::Number(v1)) => v0 == v1,
+                (Self::Char(v0), Self::Char(v1)) => v0 == v1,
+                (Self::String(v0), Self::String(v1)) => v0 == v1,
+                (Self::Option(None), Self::Option(None)) => true,
+                (Self::Option(Some(v0)), Self::Option(Some(v1)))
+                | (Self::Newtype(v0), Self::Newtype(v1)) => {
+                    pending.push((v0, v1));
+                    true
+                }
+                (Self::Seq(v0), Self::Seq(v1)) => {
+                    pending.extend(v0.iter().zip(v1.iter()));
+                    v0.len() == v1.len()
+                }
+                (Self::Map(v0), Self::Map(v1)) => v0.pair_entries(v1, &mut pending),
+                (Self::Bytes(v0), Self::Bytes(v1)) => v0 == v1,
+                #[cfg(feature = "chrono")]
+                (Self::DateTime(v0), Self::DateTime(v1)) => v0 == v1,
+                _ => false,
+            };
+            if !equal {
+                return false;
+            }
         }
+        true
     }
 }
 
 impl<T: UnstructuredDataTrait> Eq for Unstructured<T> {}
+
+/// Work left while ordering two documents
+enum CmpStep<'a, T: UnstructuredDataTrait> {
+    Values(&'a Unstructured<T>, &'a Unstructured<T>),
+    /// How two sequences or maps are ordered once all the children they share are equal
+    Lengths(Ordering),
+}
+
+/// Ordering doesn't recurse, so it is safe for documents of any depth
+impl<T: UnstructuredDataTrait> Ord for Unstructured<T> {
+    fn cmp(&self, rhs: &Self) -> Ordering {
+        let mut pending = vec![CmpStep::Values(self, rhs)];
+        while let Some(step) = pending.pop() {
+            let ordering = match step {
+                CmpStep::Lengths(ordering) => ordering,
+                CmpStep::Values(l, r) => match (l, r) {
+                    (Self::Bool(v0), Self::Bool(v1)) => v0.cmp(v1),
+                    (Self::Number(v0), Self::Number(v1)) => v0.cmp(v1),
+                    (Self::Char(v0), Self::Char(v1)) => v0.cmp(v1),
+                    (Self::String(v0), Self::String(v1)) => v0.cmp(v1),
+                    (Self::Null, Self::Null) => Ordering::Equal,
+                    (Self::Option(Some(v0)), Self::Option(Some(v1)))
+                    | (Self::Newtype(v0), Self::Newtype(v1)) => {
+                        pending.push(CmpStep::Values(v0, v1));
+                        Ordering::Equal
+                    }
+                    (Self::Option(v0), Self::Option(v1)) => v0.is_some().cmp(&v1.is_some()),
+                    (Self::Seq(v0), Self::Seq(v1)) => {
+                        pending.push(CmpStep::Lengths(v0.len().cmp(&v1.len())));
+                        let pairs = v0.iter().zip(v1.iter()).rev();
+                        pending.extend(pairs.map(|(l, r)| CmpStep::Values(l, r)));
+                        Ordering::Equal
+                    }
+                    (Self::Map(v0), Self::Map(v1)) => {
+                        pending.push(CmpStep::Lengths(v0.len().cmp(&v1.len())));
+                        let pairs = v0.sorted_entries().into_iter().zip(v1.sorted_entries());
+                        for ((k0, v0), (k1, v1)) in pairs.rev() {
+                            pending.push(CmpStep::Values(v0, v1));
+                            pending.push(CmpStep::Values(k0, k1));
+                        }
+                        Ordering::Equal
+                    }
+                    (Self::Bytes(v0), Self::Bytes(v1)) => v0.cmp(v1),
+                    #[cfg(feature = "chrono")]
+                    (Self::DateTime(v0), Self::DateTime(v1)) => v0.cmp(v1),
+                    (v0, v1) => v0.discriminant().cmp(&v1.discriminant()),
+                },
+            };
+            if ordering != Ordering::Equal {
+                return ordering;
+            }
+        }
+        Ordering::Equal
+    }
+}
+
+impl<T: UnstructuredDataTrait> Unstructured<T> {
+    /// The value inside any `Option(Some(..))` and `Newtype` wrappers
+    fn unwrapped(&self) -> &Self {
+        let mut val = self;
+        while let Self::Option(Some(inner)) | Self::Newtype(inner) = val {
+            val = inner;
+        }
+        val
+    }
+
+    /// Compare two documents requiring numbers to have the same type as well as the same
+    /// value, unlike `==` where `Number::U8(1)` equals `Number::I64(1)`
+    ///
+    /// ```
+    /// use unstructured::{Document, Number};
+    ///
+    /// let small = Document::Seq(vec![Document::Number(Number::U8(1))].into());
+    /// let large = Document::Seq(vec![Document::Number(Number::I64(1))].into());
+    /// assert_eq!(small, large);
+    /// assert!(!small.strict_eq(&large));
+    /// assert!(small.strict_eq(&small.clone()));
+    /// ```
+    pub fn strict_eq(&self, rhs: &Self) -> bool {
+        match (self, rhs) {
+            (Self::Number(v0), Self::Number(v1)) => v0.strict_eq(v1),
+            (Self::Option(Some(v0)), Self::Option(Some(v1))) => v0.strict_eq(v1),
+            (Self::Newtype(v0), Self::Newtype(v1)) => v0.strict_eq(v1),
+            (Self::Seq(v0), Self::Seq(v1)) => {
+                v0.len() == v1.len() && v0.iter().zip(v1.iter()).all(|(l, r)| l.strict_eq(r))
+            }
+            (Self::Map(v0), Self::Map(v1)) => {
+                v0.len() == v1.len()
+                    && v0
+                        .iter()
+                        .zip(v1.iter())
+                        .all(|((k0, l), (k1, r))| k0.strict_eq(k1) && l.strict_eq(r))
+            }
+            (v0, v1) => v0 == v1,
+        }
+    }
+}
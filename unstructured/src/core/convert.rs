@@ -1,6 +1,68 @@
 use crate::*;
+use std::collections::BTreeMap;
+use std::convert::TryFrom;
+use std::error::Error;
+use std::fmt;
+
+/// The reason a value could not be cast, see [`CastError`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CastFailure {
+    /// The value is of a type that can't be converted to the target
+    Mismatch,
+    /// The number is outside the range of the target type
+    Overflow,
+    /// The number can't be represented exactly by the target type, e.g. `1.5` as an integer
+    Precision,
+    /// The string could not be parsed as the target type
+    Parse(String),
+}
+
+/// Error returned by [`Unstructured::try_cast`] describing why a conversion failed
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CastError {
+    /// The type of the value that was found, e.g. `"string"` or `"f64"`
+    pub found: &'static str,
+    /// The type the value was being cast to
+    pub target: &'static str,
+    pub reason: CastFailure,
+}
+
+impl CastError {
+    pub(crate) fn mismatch<T: UnstructuredDataTrait>(found: &Unstructured<T>, target: &'static str) -> Self {
+        CastError {
+            found: Self::found(found),
+            target,
+            reason: CastFailure::Mismatch,
+        }
+    }
+
+    /// Numbers are reported by their primitive type rather than just `number`
+    fn found<T: UnstructuredDataTrait>(val: &Unstructured<T>) -> &'static str {
+        match val {
+            Unstructured::<T>::Number(n) => n.type_name(),
+            other => other.type_name(),
+        }
+    }
+}
+
+impl fmt::Display for CastError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Cannot cast {} to {}: ", self.found, self.target)?;
+        match &self.reason {
+            CastFailure::Mismatch => f.write_str("incompatible type"),
+            CastFailure::Overflow => f.write_str("value out of range"),
+            CastFailure::Precision => f.write_str("value would lose precision"),
+            CastFailure::Parse(e) => write!(f, "failed to parse, {}", e),
+        }
+    }
+}
+
+impl Error for CastError {}
 
 pub trait DocumentConvertible<T: UnstructuredDataTrait>: Sized {
+    /// The name of the type in a [`CastError`], e.g. `"string"` or `"u8"`
+    const NAME: &'static str;
+
     fn is(val: &Unstructured<T>) -> bool;
 
     fn into_unstructured(self) -> Unstructured<T>;
@@ -10,6 +72,17 @@ pub trait DocumentConvertible<T: UnstructuredDataTrait>: Sized {
     fn castable(val: &Unstructured<T>) -> bool;
 
     fn cast(val: Unstructured<T>) -> Option<Self>;
+
+    /// The same as [`cast`](DocumentConvertible::cast) but reports why the cast failed.
+    /// Implementations that don't override this only report a type mismatch.
+    fn try_cast(val: Unstructured<T>) -> Result<Self, CastError> {
+        let found = CastError::found(&val);
+        Self::cast(val).ok_or(CastError {
+            found,
+            target: Self::NAME,
+            reason: CastFailure::Mismatch,
+        })
+    }
 }
 
 impl<Q: UnstructuredDataTrait> Unstructured<Q> {
@@ -17,6 +90,12 @@ impl<Q: UnstructuredDataTrait> Unstructured<Q> {
         T::is(self)
     }
 
+    /// Convert the document to its native type.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the document is not of type `T`, use [`cast`](Unstructured::cast) or
+    /// [`try_cast`](Unstructured::try_cast) to handle this instead.
     pub fn unwrap<T: DocumentConvertible<Q>>(self) -> T {
         T::into_native(self).unwrap()
     }
@@ -24,19 +103,98 @@ impl<Q: UnstructuredDataTrait> Unstructured<Q> {
     pub fn cast<T: DocumentConvertible<Q>>(self) -> Option<T> {
         T::cast(self)
     }
+
+    /// Cast the value to another type, reporting the type found and the reason on failure
+    ///
+    /// ```
+    /// use unstructured::{CastFailure, Document};
+    ///
+    /// assert_eq!(Document::from("42").try_cast::<u8>(), Ok(42));
+    /// let err = Document::from(300u64).try_cast::<u8>().unwrap_err();
+    /// assert_eq!(err.to_string(), "Cannot cast u64 to u8: value out of range");
+    /// assert_eq!((err.found, err.reason), ("u64", CastFailure::Overflow));
+    /// ```
+    pub fn try_cast<T: DocumentConvertible<Q>>(self) -> Result<T, CastError> {
+        T::try_cast(self)
+    }
+
+    /// Cast every element of a sequence, returning the index of the first element that fails.
+    /// If this is not a sequence the error is reported at index 0.
+    ///
+    /// ```
+    /// use unstructured::Document;
+    ///
+    /// let doc: Document = serde_json::from_str("[1, 2, \"3\"]").unwrap();
+    /// assert_eq!(doc.clone().cast_seq::<u8>().unwrap(), vec![1, 2, 3]);
+    /// let (index, err) = doc.cast_seq::<bool>().unwrap_err();
+    /// assert_eq!((index, err.found), (0, "u64"));
+    /// ```
+    pub fn cast_seq<T: DocumentConvertible<Q>>(self) -> Result<Vec<T>, (usize, CastError)> {
+        match self {
+            Self::Seq(seq) => unshare(seq)
+                .into_iter()
+                .enumerate()
+                .map(|(i, val)| T::try_cast(val).map_err(|e| (i, e)))
+                .collect(),
+            other => Err((0, CastError::mismatch(&other, "seq"))),
+        }
+    }
+
+    /// Cast every key and value of a map, returning the key of the first entry that fails.
+    /// If this is not a map the error is reported with an `Unassigned` key.
+    ///
+    /// ```
+    /// use unstructured::Document;
+    ///
+    /// let doc: Document = serde_json::from_str(r#"{"a": 1, "b": -1}"#).unwrap();
+    /// assert_eq!(doc.clone().cast_map::<String, i8>().unwrap()["b"], -1);
+    /// let (key, err) = doc.cast_map::<String, u8>().unwrap_err();
+    /// assert_eq!(key, "b");
+    /// ```
+    #[allow(clippy::result_large_err)]
+    pub fn cast_map<K, V>(self) -> Result<BTreeMap<K, V>, (Self, CastError)>
+    where
+        K: DocumentConvertible<Q> + Ord,
+        V: DocumentConvertible<Q>,
+    {
+        match self {
+            Self::Map(map) => unshare(map)
+                .into_iter()
+                .map(|(key, val)| {
+                    let native_key = K::try_cast(key.clone()).map_err(|e| (key.clone(), e))?;
+                    let native_val = V::try_cast(val).map_err(|e| (key, e))?;
+                    Ok((native_key, native_val))
+                })
+                .collect(),
+            other => Err((Self::Unassigned, CastError::mismatch(&other, "map"))),
+        }
+    }
+}
+
+/// Work out why a number could not be cast to the primitive type named `target`
+fn number_failure(n: &Number, target: &str) -> CastFailure {
+    let f = f64::from(n);
+    match target {
+        "f32" if f.is_finite() && f.abs() > f64::from(f32::MAX) => CastFailure::Overflow,
+        "f32" | "f64" => CastFailure::Precision,
+        _ if n.is_float() && (f.is_nan() || f.fract() != 0.0) => CastFailure::Precision,
+        _ => CastFailure::Overflow,
+    }
 }
 
 macro_rules! impl_document_convertible {
     ( $( $t:ty : $variant:ident ( $u:ty ) => $( $variant2:ident ( $variant2_ty:ident ) )* , )* ) => {
         $(
             impl<T: UnstructuredDataTrait> DocumentConvertible<T> for $t {
+                const NAME: &'static str = stringify!($t);
+
                 fn into_unstructured(self) -> Unstructured<T> {
-                    Unstructured::<T>::Number(Number::$variant(self as $u))
+                    Unstructured::<T>::Number(Number::from(self as $u))
                 }
 
                 fn into_native(val: Unstructured<T>) -> Option<Self> {
                     match val {
-                        Unstructured::<T>::Number(Number::$variant(v)) => Some(v as $t),
+                        Unstructured::<T>::Number(ref n @ Number::$variant(_)) => Some(<$u>::from(n) as $t),
                         _ => None,
                     }
                 }
@@ -48,25 +206,37 @@ macro_rules! impl_document_convertible {
                     }
                 }
 
-                #[allow(clippy::float_cmp)]
+                #[allow(clippy::float_cmp, unused_comparisons)]
                 fn castable(val: &Unstructured<T>) -> bool {
                     match val {
-                        $( Unstructured::<T>::Number(Number::$variant2(v)) => *v == (*v as $t) as $variant2_ty, )*
+                        $( Unstructured::<T>::Number(n @ Number::$variant2(_)) => {
+                            let v = $variant2_ty::from(n);
+                            v == (v as $t) as $variant2_ty && (v < 0 as $variant2_ty) == ((v as $t) < 0 as $t)
+                        } )*
                         Unstructured::<T>::String(s) => match s.parse::< $t >() { Ok(_) => true, Err(_) => false },
-                        Unstructured::<T>::Option(inner) => match inner { Some(v) => <$t>::castable(v), None => false },
+                        Unstructured::<T>::Option(Some(v)) => <$t>::castable(v),
                         Unstructured::<T>::Newtype(inner) => <$t>::castable(inner),
                         _ => false,
                     }
                 }
 
-                #[allow(clippy::float_cmp)]
                 fn cast(val: Unstructured<T>) -> Option<Self> {
+                    Self::try_cast(val).ok()
+                }
+
+                #[allow(clippy::float_cmp, unused_comparisons)]
+                fn try_cast(val: Unstructured<T>) -> Result<Self, CastError> {
+                    let target = stringify!($t);
                     match val {
-                        $( Unstructured::<T>::Number(Number::$variant2(v)) => if v == (v as $t) as $variant2_ty { Some(v as $t) } else { None }, )*
-                        Unstructured::<T>::String(s) => match s.parse::< $t >() { Ok(v) => Some(v), Err(_) => None },
-                        Unstructured::<T>::Option(inner) => match inner { Some(v) => v.cast(), None => None },
-                        Unstructured::<T>::Newtype(inner) => inner.cast(),
-                        _ => None,
+                        $( Unstructured::<T>::Number(ref n @ Number::$variant2(_)) if {
+                            let v = $variant2_ty::from(n);
+                            v == (v as $t) as $variant2_ty && (v < 0 as $variant2_ty) == ((v as $t) < 0 as $t)
+                        } => Ok($variant2_ty::from(n) as $t), )*
+                        Unstructured::<T>::Number(n) => Err(CastError { found: n.type_name(), target, reason: number_failure(&n, target) }),
+                        Unstructured::<T>::String(s) => s.parse::< $t >().map_err(|e| CastError { found: "string", target, reason: CastFailure::Parse(e.to_string()) }),
+                        Unstructured::<T>::Option(Some(v)) => v.try_cast(),
+                        Unstructured::<T>::Newtype(inner) => inner.try_cast(),
+                        other => Err(CastError::mismatch(&other, target)),
                     }
                 }
             }
@@ -103,13 +273,15 @@ impl_document_convertible! {
 impl<T: UnstructuredDataTrait> DocumentConvertible<T>
     for Mapping<T>
 {
+    const NAME: &'static str = "map";
+
     fn into_unstructured(self) -> Unstructured<T> {
-        Unstructured::<T>::Map(self)
+        Unstructured::<T>::Map(share(self))
     }
 
     fn into_native(val: Unstructured<T>) -> Option<Self> {
         match val {
-            Unstructured::<T>::Map(v) => Some(v),
+            Unstructured::<T>::Map(v) => Some(unshare(v)),
             _ => None,
         }
     }
@@ -123,21 +295,27 @@ impl<T: UnstructuredDataTrait> DocumentConvertible<T>
     }
 
     fn cast(val: Unstructured<T>) -> Option<Self> {
+        Self::try_cast(val).ok()
+    }
+
+    fn try_cast(val: Unstructured<T>) -> Result<Self, CastError> {
         match val {
-            Unstructured::<T>::Map(m) => Some(m),
-            _ => None,
+            Unstructured::<T>::Map(m) => Ok(unshare(m)),
+            other => Err(CastError::mismatch(&other, "map")),
         }
     }
 }
 
 impl<T: UnstructuredDataTrait> DocumentConvertible<T> for Sequence<T> {
+    const NAME: &'static str = "seq";
+
     fn into_unstructured(self) -> Unstructured<T> {
-        Unstructured::<T>::Seq(self)
+        Unstructured::<T>::Seq(share(self))
     }
 
     fn into_native(val: Unstructured<T>) -> Option<Self> {
         match val {
-            Unstructured::<T>::Seq(v) => Some(v),
+            Unstructured::<T>::Seq(v) => Some(unshare(v)),
             _ => None,
         }
     }
@@ -151,14 +329,20 @@ impl<T: UnstructuredDataTrait> DocumentConvertible<T> for Sequence<T> {
     }
 
     fn cast(val: Unstructured<T>) -> Option<Self> {
+        Self::try_cast(val).ok()
+    }
+
+    fn try_cast(val: Unstructured<T>) -> Result<Self, CastError> {
         match val {
-            Unstructured::<T>::Seq(m) => Some(m),
-            _ => None,
+            Unstructured::<T>::Seq(m) => Ok(unshare(m)),
+            other => Err(CastError::mismatch(&other, "seq")),
         }
     }
 }
 
 impl<T: UnstructuredDataTrait> DocumentConvertible<T> for String {
+    const NAME: &'static str = "string";
+
     fn into_unstructured(self) -> Unstructured<T> {
         Unstructured::<T>::String(self)
     }
@@ -179,14 +363,20 @@ impl<T: UnstructuredDataTrait> DocumentConvertible<T> for String {
     }
 
     fn cast(val: Unstructured<T>) -> Option<Self> {
+        Self::try_cast(val).ok()
+    }
+
+    fn try_cast(val: Unstructured<T>) -> Result<Self, CastError> {
         match val {
-            Unstructured::<T>::String(m) => Some(m),
-            _ => None,
+            Unstructured::<T>::String(m) => Ok(m),
+            other => Err(CastError::mismatch(&other, "string")),
         }
     }
 }
 
 impl<T: UnstructuredDataTrait> DocumentConvertible<T> for bool {
+    const NAME: &'static str = "bool";
+
     fn into_unstructured(self) -> Unstructured<T> {
         Unstructured::<T>::Bool(self)
     }
@@ -207,9 +397,159 @@ impl<T: UnstructuredDataTrait> DocumentConvertible<T> for bool {
     }
 
     fn cast(val: Unstructured<T>) -> Option<Self> {
+        Self::try_cast(val).ok()
+    }
+
+    fn try_cast(val: Unstructured<T>) -> Result<Self, CastError> {
         match val {
-            Unstructured::<T>::Bool(m) => Some(m),
-            _ => None,
+            Unstructured::<T>::Bool(m) => Ok(m),
+            other => Err(CastError::mismatch(&other, "bool")),
+        }
+    }
+}
+
+macro_rules! try_from_imp {
+    ( $($ty:ty)* ) => {
+        $(
+            impl<T: UnstructuredDataTrait> TryFrom<Unstructured<T>> for $ty {
+                type Error = CastError;
+
+                fn try_from(val: Unstructured<T>) -> Result<Self, CastError> {
+                    val.try_cast()
+                }
+            }
+
+            impl<T: UnstructuredDataTrait> TryFrom<&Unstructured<T>> for $ty {
+                type Error = CastError;
+
+                fn try_from(val: &Unstructured<T>) -> Result<Self, CastError> {
+                    val.clone().try_cast()
+                }
+            }
+        )*
+    };
+}
+
+try_from_imp!(i8 i16 i32 i64 i128 isize u8 u16 u32 u64 u128 usize f32 f64 bool String);
+
+/// Converts every element, failing with the error of the first element that can't be converted
+///
+/// ```
+/// use std::convert::TryFrom;
+/// use unstructured::Document;
+///
+/// let doc: Document = serde_json::from_str(r#"[1, "2"]"#).unwrap();
+/// assert_eq!(Vec::<u8>::try_from(&doc), Ok(vec![1, 2]));
+/// let err = Vec::<bool>::try_from(doc).unwrap_err();
+/// assert_eq!(err.to_string(), "Cannot cast u64 to bool: incompatible type");
+/// ```
+impl<T: UnstructuredDataTrait, V> TryFrom<Unstructured<T>> for Vec<V>
+where
+    V: TryFrom<Unstructured<T>, Error = CastError>,
+{
+    type Error = CastError;
+
+    fn try_from(val: Unstructured<T>) -> Result<Self, CastError> {
+        match val {
+            Unstructured::<T>::Seq(seq) => unshare(seq).into_iter().map(V::try_from).collect(),
+            Unstructured::<T>::Option(Some(inner)) | Unstructured::<T>::Newtype(inner) => {
+                Self::try_from(*inner)
+            }
+            other => Err(CastError::mismatch(&other, "seq")),
+        }
+    }
+}
+
+impl<'a, T: UnstructuredDataTrait, V> TryFrom<&'a Unstructured<T>> for Vec<V>
+where
+    V: TryFrom<&'a Unstructured<T>, Error = CastError>,
+{
+    type Error = CastError;
+
+    fn try_from(val: &'a Unstructured<T>) -> Result<Self, CastError> {
+        match val {
+            Unstructured::<T>::Seq(seq) => seq.iter().map(V::try_from).collect(),
+            Unstructured::<T>::Option(Some(inner)) | Unstructured::<T>::Newtype(inner) => {
+                Self::try_from(&**inner)
+            }
+            other => Err(CastError::mismatch(other, "seq")),
+        }
+    }
+}
+
+impl<T: UnstructuredDataTrait, K, V> TryFrom<Unstructured<T>> for BTreeMap<K, V>
+where
+    K: TryFrom<Unstructured<T>, Error = CastError> + Ord,
+    V: TryFrom<Unstructured<T>, Error = CastError>,
+{
+    type Error = CastError;
+
+    fn try_from(val: Unstructured<T>) -> Result<Self, CastError> {
+        match val {
+            Unstructured::<T>::Map(map) => unshare(map)
+                .into_iter()
+                .map(|(key, val)| Ok((K::try_from(key)?, V::try_from(val)?)))
+                .collect(),
+            Unstructured::<T>::Option(Some(inner)) | Unstructured::<T>::Newtype(inner) => {
+                Self::try_from(*inner)
+            }
+            other => Err(CastError::mismatch(&other, "map")),
+        }
+    }
+}
+
+impl<'a, T: UnstructuredDataTrait, K, V> TryFrom<&'a Unstructured<T>> for BTreeMap<K, V>
+where
+    K: TryFrom<&'a Unstructured<T>, Error = CastError> + Ord,
+    V: TryFrom<&'a Unstructured<T>, Error = CastError>,
+{
+    type Error = CastError;
+
+    fn try_from(val: &'a Unstructured<T>) -> Result<Self, CastError> {
+        match val {
+            Unstructured::<T>::Map(map) => map
+                .iter()
+                .map(|(key, val)| Ok((K::try_from(key)?, V::try_from(val)?)))
+                .collect(),
+            Unstructured::<T>::Option(Some(inner)) | Unstructured::<T>::Newtype(inner) => {
+                Self::try_from(&**inner)
+            }
+            other => Err(CastError::mismatch(other, "map")),
+        }
+    }
+}
+
+/// `Null`, `None` and `Unassigned` become `None`, anything else is converted to `V`
+impl<T: UnstructuredDataTrait, V> TryFrom<Unstructured<T>> for Option<V>
+where
+    V: TryFrom<Unstructured<T>, Error = CastError>,
+{
+    type Error = CastError;
+
+    fn try_from(val: Unstructured<T>) -> Result<Self, CastError> {
+        match val {
+            Unstructured::<T>::Null
+            | Unstructured::<T>::Unassigned
+            | Unstructured::<T>::Option(None) => Ok(None),
+            Unstructured::<T>::Option(Some(inner)) => V::try_from(*inner).map(Some),
+            other => V::try_from(other).map(Some),
+        }
+    }
+}
+
+impl<'a, T: UnstructuredDataTrait, V> TryFrom<&'a Unstructured<T>> for Option<V>
+where
+    V: TryFrom<&'a Unstructured<T>, Error = CastError>,
+{
+    type Error = CastError;
+
+    fn try_from(val: &'a Unstructured<T>) -> Result<Self, CastError> {
+        match val {
+            Unstructured::<T>::Null
+            | Unstructured::<T>::Unassigned
+            | Unstructured::<T>::Option(None) => Ok(None),
+            Unstructured::<T>::Option(Some(inner)) => V::try_from(inner).map(Some),
+            other => V::try_from(other).map(Some),
         }
     }
 }
@@ -0,0 +1,192 @@
+use crate::*;
+use std::ptr;
+
+/// A position within a document that can be moved up and down the tree, see
+/// [`Unstructured::cursor`]
+#[derive(Clone)]
+pub struct Cursor<'a, T: UnstructuredDataTrait> {
+    path: Path,
+    /// Every value from the root down to the current one
+    stack: Vec<&'a Unstructured<T>>,
+}
+
+impl<'a, T: UnstructuredDataTrait> Cursor<'a, T> {
+    /// The value at the cursor
+    pub fn value(&self) -> &'a Unstructured<T> {
+        self.stack[self.stack.len() - 1]
+    }
+
+    /// The path from the root of the document to the cursor
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// A cursor on the child under a key or index, if there is one
+    pub fn down<S: Into<Segment>>(&self, segment: S) -> Option<Self> {
+        let segment = segment.into();
+        let child = segment.child(self.value())?;
+        Some(self.child(segment, child))
+    }
+
+    /// A cursor on the value holding this one, None at the root
+    pub fn up(&self) -> Option<Self> {
+        let mut parent = self.clone();
+        parent.path.pop()?;
+        parent.stack.pop();
+        Some(parent)
+    }
+
+    /// Cursors on every child of the value, in order
+    pub fn children(&self) -> Vec<Self> {
+        match self.value() {
+            Unstructured::<T>::Seq(seq) => seq
+                .iter()
+                .enumerate()
+                .map(|(i, val)| self.child(i.into(), val))
+                .collect(),
+            Unstructured::<T>::Map(map) => map
+                .iter()
+                .map(|(key, val)| self.child(Segment::from_key(key), val))
+                .collect(),
+            _ => vec![],
+        }
+    }
+
+    /// Cursors on the other children of the value holding this one, in order
+    pub fn siblings(&self) -> Vec<Self> {
+        match self.up() {
+            Some(parent) => parent
+                .children()
+                .into_iter()
+                .filter(|sibling| !ptr::eq(sibling.value(), self.value()))
+                .collect(),
+            None => vec![],
+        }
+    }
+
+    fn child(&self, segment: Segment, val: &'a Unstructured<T>) -> Self {
+        let mut child = self.clone();
+        child.path.push(segment);
+        child.stack.push(val);
+        child
+    }
+}
+
+/// A position within a mutable document that can be moved up and down the tree, see
+/// [`Unstructured::cursor_mut`]
+pub struct CursorMut<'a, T: UnstructuredDataTrait> {
+    root: &'a mut Unstructured<T>,
+    path: Path,
+}
+
+impl<'a, T: UnstructuredDataTrait> CursorMut<'a, T> {
+    /// The value at the cursor
+    pub fn value(&self) -> &Unstructured<T> {
+        self.root
+            .get_at(&self.path)
+            .expect("Cursor moved to a missing value")
+    }
+
+    /// A mutable reference to the value at the cursor
+    pub fn value_mut(&mut self) -> &mut Unstructured<T> {
+        self.root
+            .get_at_mut(&self.path)
+            .expect("Cursor moved to a missing value")
+    }
+
+    /// Consume the cursor, returning a mutable reference to the value it was at
+    pub fn into_mut(self) -> &'a mut Unstructured<T> {
+        self.root
+            .get_at_mut(&self.path)
+            .expect("Cursor moved to a missing value")
+    }
+
+    /// The path from the root of the document to the cursor
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Move to the child under a key or index. Returns None and stays put if there isn't one.
+    pub fn down<S: Into<Segment>>(&mut self, segment: S) -> Option<&mut Self> {
+        let segment = segment.into();
+        segment.child(self.value())?;
+        self.path.push(segment);
+        Some(self)
+    }
+
+    /// Move to the value holding this one. Returns None and stays put at the root.
+    pub fn up(&mut self) -> Option<&mut Self> {
+        self.path.pop()?;
+        Some(self)
+    }
+
+    /// The other children of the value holding this one, in order, along with their key or
+    /// index
+    pub fn siblings(&mut self) -> Vec<(Segment, &mut Unstructured<T>)> {
+        let parent = match self.path.parent() {
+            Some(parent) => parent,
+            None => return vec![],
+        };
+        // Unshare the path first so the parent's children keep their addresses
+        let current: *const Unstructured<T> = self.value_mut();
+        let children: Vec<(Segment, &mut Unstructured<T>)> = match self.root.get_at_mut(&parent) {
+            Some(Unstructured::<T>::Seq(seq)) => make_mut(seq)
+                .iter_mut()
+                .enumerate()
+                .map(|(i, val)| (i.into(), val))
+                .collect(),
+            Some(Unstructured::<T>::Map(map)) => make_mut(map)
+                .iter_mut()
+                .map(|(key, val)| (Segment::from_key(key), val))
+                .collect(),
+            _ => vec![],
+        };
+        children
+            .into_iter()
+            .filter(|(_, val)| !ptr::eq(&**val, current))
+            .collect()
+    }
+}
+
+impl<T: UnstructuredDataTrait> Unstructured<T> {
+    /// A cursor at the root of the document, for navigating it without resolving paths by hand
+    ///
+    /// ```
+    /// use unstructured::Document;
+    ///
+    /// let doc: Document = serde_json::from_str(r#"{"a": {"b": [1, 2, 3]}}"#).unwrap();
+    /// let cursor = doc.cursor().down("a").and_then(|a| a.down("b")).unwrap();
+    /// let second = cursor.down(1).unwrap();
+    /// assert_eq!(second.path().to_string(), "/a/b/1");
+    /// assert_eq!(second.siblings().len(), 2);
+    /// assert_eq!(second.up().unwrap().value(), cursor.value());
+    /// ```
+    pub fn cursor(&self) -> Cursor<'_, T> {
+        Cursor {
+            path: Path::new(),
+            stack: vec![self],
+        }
+    }
+
+    /// A cursor at the root of the document that can modify the values it moves to
+    ///
+    /// ```
+    /// use unstructured::Document;
+    ///
+    /// let mut doc: Document = serde_json::from_str(r#"{"a": [1, 2, 3]}"#).unwrap();
+    /// let mut cursor = doc.cursor_mut();
+    /// cursor.down("a").and_then(|c| c.down(0)).unwrap();
+    /// *cursor.value_mut() = 10.into();
+    /// for (_, sibling) in cursor.siblings() {
+    ///     *sibling = 0.into();
+    /// }
+    /// assert_eq!(doc["a"][0], 10);
+    /// assert_eq!(doc["a"][2], 0);
+    /// ```
+    pub fn cursor_mut(&mut self) -> CursorMut<'_, T> {
+        CursorMut {
+            root: self,
+            path: Path::new(),
+        }
+    }
+}
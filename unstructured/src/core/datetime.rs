@@ -0,0 +1,83 @@
+use crate::*;
+use chrono::{DateTime, FixedOffset, Utc};
+
+/// The key TOML deserializers use to pass datetimes through serde
+const TOML_DATETIME_KEY: &str = "$__toml_private_datetime";
+
+impl<T: UnstructuredDataTrait> From<DateTime<FixedOffset>> for Unstructured<T> {
+    fn from(v: DateTime<FixedOffset>) -> Self {
+        Self::DateTime(v)
+    }
+}
+
+impl<T: UnstructuredDataTrait> From<DateTime<Utc>> for Unstructured<T> {
+    fn from(v: DateTime<Utc>) -> Self {
+        Self::DateTime(v.into())
+    }
+}
+
+impl<T: UnstructuredDataTrait> DocumentConvertible<T> for DateTime<FixedOffset> {
+    const NAME: &'static str = "datetime";
+
+    fn into_unstructured(self) -> Unstructured<T> {
+        Unstructured::<T>::DateTime(self)
+    }
+
+    fn into_native(val: Unstructured<T>) -> Option<Self> {
+        match val {
+            Unstructured::<T>::DateTime(v) => Some(v),
+            _ => None,
+        }
+    }
+
+    fn is(val: &Unstructured<T>) -> bool {
+        matches!(val, Unstructured::<T>::DateTime(_))
+    }
+
+    fn castable(val: &Unstructured<T>) -> bool {
+        match val {
+            Unstructured::<T>::DateTime(_) => true,
+            Unstructured::<T>::String(s) => DateTime::parse_from_rfc3339(s).is_ok(),
+            _ => false,
+        }
+    }
+
+    fn cast(val: Unstructured<T>) -> Option<Self> {
+        Self::try_cast(val).ok()
+    }
+
+    /// Strings are parsed as RFC 3339
+    fn try_cast(val: Unstructured<T>) -> Result<Self, CastError> {
+        match val {
+            Unstructured::<T>::DateTime(v) => Ok(v),
+            Unstructured::<T>::String(s) => {
+                DateTime::parse_from_rfc3339(&s).map_err(|e| CastError {
+                    found: "string",
+                    target: "datetime",
+                    reason: CastFailure::Parse(e.to_string()),
+                })
+            }
+            other => Err(CastError::mismatch(&other, "datetime")),
+        }
+    }
+}
+
+impl<T: UnstructuredDataTrait> Unstructured<T> {
+    /// Convert the map TOML deserializers use for datetimes, returning None for any other map.
+    /// Datetimes without an offset can't be represented and are kept as strings.
+    pub(crate) fn from_toml_datetime(map: &mut Mapping<T>) -> Option<Self> {
+        if map.len() != 1 {
+            return None;
+        }
+        match map.remove(&Self::from(TOML_DATETIME_KEY))? {
+            Self::String(s) => Some(match DateTime::parse_from_rfc3339(&s) {
+                Ok(v) => Self::DateTime(v),
+                Err(_) => Self::String(s),
+            }),
+            other => {
+                map.insert(Self::from(TOML_DATETIME_KEY), other);
+                None
+            }
+        }
+    }
+}
@@ -1,5 +1,6 @@
 use serde::de;
 use std::error::Error;
+use std::cell::Cell;
 use std::fmt;
 use std::marker::PhantomData;
 
@@ -88,6 +89,8 @@ pub enum DeserializerError {
     UnknownField(String, &'static [&'static str]),
     MissingField(&'static str),
     DuplicateField(&'static str),
+    /// An error raised below the root of the document, with the path to where it happened
+    At(Path, Box<DeserializerError>),
 }
 
 impl de::Error for DeserializerError {
@@ -139,18 +142,44 @@ impl DeserializerError {
             DeserializerError::UnknownField(ref field, exp) => E::unknown_field(field, exp),
             DeserializerError::MissingField(field) => E::missing_field(field),
             DeserializerError::DuplicateField(field) => E::missing_field(field),
+            DeserializerError::At(..) => E::custom(self),
         }
     }
 
     pub fn into_error<E: de::Error>(self) -> E {
         self.to_error()
     }
+
+    /// The path to the value that failed to deserialize, None if it was the root
+    pub fn path(&self) -> Option<&Path> {
+        match self {
+            DeserializerError::At(path, _) => Some(path),
+            _ => None,
+        }
+    }
+
+    /// Prefix the path of this error with the segment of the value it came from
+    fn at(self, segment: Segment) -> Self {
+        let (mut segments, error) = match self {
+            DeserializerError::At(path, error) => (path.segments().to_vec(), error),
+            error => (vec![], Box::new(error)),
+        };
+        segments.insert(0, segment);
+        DeserializerError::At(segments.into(), error)
+    }
 }
 
 impl Error for DeserializerError {
     fn description(&self) -> &str {
         "Document deserializer error"
     }
+
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            DeserializerError::At(_, error) => Some(error.as_ref()),
+            _ => None,
+        }
+    }
 }
 
 impl fmt::Display for DeserializerError {
@@ -186,6 +215,7 @@ impl fmt::Display for DeserializerError {
             ),
             DeserializerError::MissingField(field) => write!(f, "Missing field {}", field),
             DeserializerError::DuplicateField(field) => write!(f, "Duplicate field {}", field),
+            DeserializerError::At(ref path, ref error) => write!(f, "{} at {}", error, path),
         }
     }
 }
@@ -196,9 +226,102 @@ impl From<de::value::Error> for DeserializerError {
     }
 }
 
-pub struct DocumentVisitor<T: UnstructuredDataTrait>(std::marker::PhantomData<T>);
+/// Limits on the documents read by [`Unstructured::deserialize_with_limits`], to protect against
+/// hostile input. `None` means unlimited.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Limits {
+    /// How deeply sequences, maps, options and newtypes can be nested
+    pub max_depth: Option<usize>,
+    /// The total number of values, counting map keys
+    pub max_nodes: Option<usize>,
+    /// The length in bytes of strings and byte buffers
+    pub max_string_len: Option<usize>,
+}
+
+/// The limits being enforced and the number of values read so far
+pub(crate) struct Guard {
+    limits: Limits,
+    nodes: Cell<usize>,
+}
+
+impl Guard {
+    pub(crate) fn new(limits: Limits) -> Self {
+        Guard {
+            limits,
+            nodes: Cell::new(0),
+        }
+    }
+}
+
+pub struct DocumentVisitor<'a, T: UnstructuredDataTrait> {
+    guard: Option<&'a Guard>,
+    depth: usize,
+    marker: PhantomData<T>,
+}
+
+impl<'a, T: UnstructuredDataTrait> Clone for DocumentVisitor<'a, T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<'a, T: UnstructuredDataTrait> Copy for DocumentVisitor<'a, T> {}
+
+impl<'a, T: UnstructuredDataTrait> DocumentVisitor<'a, T> {
+    pub(crate) fn new() -> Self {
+        DocumentVisitor {
+            guard: None,
+            depth: 0,
+            marker: PhantomData,
+        }
+    }
+
+    pub(crate) fn limited(guard: &'a Guard) -> Self {
+        DocumentVisitor {
+            guard: Some(guard),
+            ..Self::new()
+        }
+    }
+
+    /// The visitor for values inside a container, failing if they would be too deep
+    fn nested<E: de::Error>(self) -> Result<Self, E> {
+        let depth = self.depth + 1;
+        if let Some(max) = self.guard.and_then(|g| g.limits.max_depth) {
+            if depth > max {
+                return Err(E::custom(format!("Document is nested deeper than {}", max)));
+            }
+        }
+        Ok(DocumentVisitor { depth, ..self })
+    }
+
+    fn check_len<E: de::Error>(&self, len: usize) -> Result<(), E> {
+        match self.guard.and_then(|g| g.limits.max_string_len) {
+            Some(max) if len > max => Err(E::custom(format!(
+                "String of {} bytes is longer than {}",
+                len, max
+            ))),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Reading each value through the seed counts it against the node limit
+impl<'de, 'a, T: UnstructuredDataTrait> de::DeserializeSeed<'de> for DocumentVisitor<'a, T> {
+    type Value = Unstructured<T>;
 
-impl<'de, T: UnstructuredDataTrait> de::Visitor<'de> for DocumentVisitor<T> {
+    fn deserialize<D: de::Deserializer<'de>>(self, d: D) -> Result<Unstructured<T>, D::Error> {
+        if let Some(guard) = self.guard {
+            let nodes = guard.nodes.get() + 1;
+            guard.nodes.set(nodes);
+            if let Some(max) = guard.limits.max_nodes.filter(|max| nodes > *max) {
+                return Err(de::Error::custom(format!("Document has more than {} values", max)));
+            }
+        }
+        d.deserialize_any(self).map(read_other)
+    }
+}
+
+impl<'de, 'a, T: UnstructuredDataTrait> de::Visitor<'de> for DocumentVisitor<'a, T> {
     type Value = Unstructured<T>;
 
     fn expecting(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
@@ -226,7 +349,7 @@ impl<'de, T: UnstructuredDataTrait> de::Visitor<'de> for DocumentVisitor<T> {
     }
 
     fn visit_i128<E>(self, value: i128) -> Result<Unstructured<T>, E> {
-        Ok(Unstructured::<T>::Number(Number::I128(value)))
+        Ok(Unstructured::<T>::Number(Number::from(value)))
     }
 
     fn visit_u8<E>(self, value: u8) -> Result<Unstructured<T>, E> {
@@ -246,7 +369,7 @@ impl<'de, T: UnstructuredDataTrait> de::Visitor<'de> for DocumentVisitor<T> {
     }
 
     fn visit_u128<E>(self, value: u128) -> Result<Unstructured<T>, E> {
-        Ok(Unstructured::<T>::Number(Number::U128(value)))
+        Ok(Unstructured::<T>::Number(Number::from(value)))
     }
 
     fn visit_f32<E>(self, value: f32) -> Result<Unstructured<T>, E> {
@@ -261,11 +384,13 @@ impl<'de, T: UnstructuredDataTrait> de::Visitor<'de> for DocumentVisitor<T> {
         Ok(Unstructured::<T>::Char(value))
     }
 
-    fn visit_str<E>(self, value: &str) -> Result<Unstructured<T>, E> {
+    fn visit_str<E: de::Error>(self, value: &str) -> Result<Unstructured<T>, E> {
+        self.check_len(value.len())?;
         Ok(Unstructured::<T>::String(value.into()))
     }
 
-    fn visit_string<E>(self, value: String) -> Result<Unstructured<T>, E> {
+    fn visit_string<E: de::Error>(self, value: String) -> Result<Unstructured<T>, E> {
+        self.check_len(value.len())?;
         Ok(Unstructured::<T>::String(value))
     }
 
@@ -278,7 +403,7 @@ impl<'de, T: UnstructuredDataTrait> de::Visitor<'de> for DocumentVisitor<T> {
     }
 
     fn visit_some<D: de::Deserializer<'de>>(self, d: D) -> Result<Unstructured<T>, D::Error> {
-        d.deserialize_any(DocumentVisitor::<T>(PhantomData))
+        de::DeserializeSeed::deserialize(self.nested()?, d)
             .map(|v| Unstructured::<T>::Option(Some(Box::new(v))))
     }
 
@@ -286,38 +411,69 @@ impl<'de, T: UnstructuredDataTrait> de::Visitor<'de> for DocumentVisitor<T> {
         self,
         d: D,
     ) -> Result<Unstructured<T>, D::Error> {
-        d.deserialize_any(DocumentVisitor::<T>(PhantomData))
+        de::DeserializeSeed::deserialize(self.nested()?, d)
             .map(|v| Unstructured::<T>::Newtype(Box::new(v)))
     }
 
     fn visit_seq<V: de::SeqAccess<'de>>(self, mut visitor: V) -> Result<Unstructured<T>, V::Error> {
+        let nested = self.nested()?;
         let mut documents = Vec::new();
-        while let Some(elem) = visitor.next_element()? {
+        while let Some(elem) = visitor.next_element_seed(nested)? {
             documents.push(elem);
         }
-        Ok(Unstructured::<T>::Seq(documents))
+        Ok(Unstructured::<T>::Seq(share(documents)))
     }
 
     fn visit_map<V: de::MapAccess<'de>>(self, mut visitor: V) -> Result<Unstructured<T>, V::Error> {
+        let nested = self.nested()?;
         let mut documents = Mapping::new();
-        while let Some((key, document)) = visitor.next_entry()? {
+        while let Some((key, document)) = visitor.next_entry_seed(nested, nested)? {
             documents.insert(key, document);
         }
-        Ok(Unstructured::<T>::Map(documents))
+        #[cfg(feature = "chrono")]
+        if let Some(datetime) = Unstructured::<T>::from_toml_datetime(&mut documents) {
+            return Ok(datetime);
+        }
+        Ok(Unstructured::<T>::Map(share(documents)))
     }
 
-    fn visit_bytes<E>(self, v: &[u8]) -> Result<Unstructured<T>, E> {
+    fn visit_bytes<E: de::Error>(self, v: &[u8]) -> Result<Unstructured<T>, E> {
+        self.check_len(v.len())?;
         Ok(Unstructured::<T>::Bytes(v.into()))
     }
 
-    fn visit_byte_buf<E>(self, v: Vec<u8>) -> Result<Unstructured<T>, E> {
+    fn visit_byte_buf<E: de::Error>(self, v: Vec<u8>) -> Result<Unstructured<T>, E> {
+        self.check_len(v.len())?;
         Ok(Unstructured::<T>::Bytes(v))
     }
 }
 
 impl<'de, T: UnstructuredDataTrait> de::Deserialize<'de> for Unstructured<T> {
     fn deserialize<D: de::Deserializer<'de>>(d: D) -> Result<Self, D::Error> {
-        d.deserialize_any(DocumentVisitor::<T>(PhantomData))
+        d.deserialize_any(DocumentVisitor::<T>::new()).map(read_other)
+    }
+}
+
+/// Let [`UnstructuredDataTrait::deserialize_other`] replace a value that was just read
+fn read_other<T: UnstructuredDataTrait>(doc: Unstructured<T>) -> Unstructured<T> {
+    match T::deserialize_other(&doc) {
+        Some(other) => Unstructured::<T>::Other(other),
+        None => doc,
+    }
+}
+
+impl<T: UnstructuredDataTrait> Unstructured<T> {
+    /// The document [`UnstructuredDataTrait::serialize_other`] writes for an `Other` value
+    fn from_other(other: &T::OtherType) -> Result<Self, DeserializerError> {
+        struct SerializeOther<'a, T: UnstructuredDataTrait>(&'a T::OtherType);
+
+        impl<'a, T: UnstructuredDataTrait> serde::Serialize for SerializeOther<'a, T> {
+            fn serialize<S: serde::Serializer>(&self, s: S) -> Result<S::Ok, S::Error> {
+                T::serialize_other(self.0, s)
+            }
+        }
+
+        Self::new(SerializeOther::<T>(other)).map_err(|e| DeserializerError::Custom(e.to_string()))
     }
 }
 
@@ -354,7 +510,7 @@ where
     fn deserialize_any<V: de::Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
         match self.document {
             Unstructured::<T>::Bool(v) => visitor.visit_bool(v),
-            Unstructured::<T>::Number(v) => Ok(v.deserialize_any(visitor).unwrap()),
+            Unstructured::<T>::Number(v) => v.deserialize_any(visitor).map_err(E::custom),
             Unstructured::<T>::Char(v) => visitor.visit_char(v),
             Unstructured::<T>::String(v) => visitor.visit_string(v),
             Unstructured::<T>::Null => visitor.visit_unit(),
@@ -364,19 +520,22 @@ where
                 visitor.visit_newtype_struct(DocumentDeserializer::new(*v))
             }
             Unstructured::<T>::Seq(v) => visitor.visit_seq(de::value::SeqDeserializer::new(
-                v.into_iter().map(DocumentDeserializer::new),
+                unshare(v).into_iter().map(DocumentDeserializer::new),
             )),
             Unstructured::<T>::Map(v) => visitor
-                .visit_map(de::value::MapDeserializer::new(v.into_iter().map(
+                .visit_map(de::value::MapDeserializer::new(unshare(v).into_iter().map(
                     |(k, v)| (DocumentDeserializer::new(k), DocumentDeserializer::new(v)),
                 ))),
             Unstructured::<T>::Bytes(v) => visitor.visit_byte_buf(v),
+            #[cfg(feature = "chrono")]
+            Unstructured::<T>::DateTime(v) => visitor.visit_string(v.to_rfc3339()),
             Unstructured::<T>::Unassigned => visitor.visit_unit(),
             Unstructured::<T>::Err(e) => {
                 Err(DeserializerError::Custom(format!("{}", e)).to_error())
             }
-            Unstructured::<T>::Other(..) => {
-                Err(DeserializerError::Custom("other".into()).to_error())
+            Unstructured::<T>::Other(o) => {
+                let doc = Unstructured::<T>::from_other(&o).map_err(E::custom)?;
+                DocumentDeserializer::new(doc).deserialize_any(visitor)
             }
         }
     }
@@ -384,20 +543,26 @@ where
     fn deserialize_option<V: de::Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
         match self.document {
             Unstructured::<T>::Option(..) => self.deserialize_any(visitor),
-            Unstructured::<T>::Null => visitor.visit_unit(),
+            Unstructured::<T>::Null | Unstructured::<T>::Unassigned => visitor.visit_unit(),
             _ => visitor.visit_some(self),
         }
     }
 
     fn deserialize_enum<V: de::Visitor<'de>>(
         self,
-        _name: &'static str,
-        _variants: &'static [&'static str],
+        name: &'static str,
+        variants: &'static [&'static str],
         visitor: V,
     ) -> Result<V::Value, Self::Error> {
         let (variant, document) = match self.document {
+            // Internally and adjacently tagged enums go through deserialize_any, externally
+            // tagged ones may still be wrapped
+            Unstructured::<T>::Option(Some(v)) | Unstructured::<T>::Newtype(v) => {
+                return DocumentDeserializer::<E, T>::new(*v)
+                    .deserialize_enum(name, variants, visitor);
+            }
             Unstructured::<T>::Map(document) => {
-                let mut iter = document.into_iter();
+                let mut iter = unshare(document).into_iter();
                 let (variant, document) = match iter.next() {
                     Some(v) => v,
                     None => {
@@ -464,15 +629,32 @@ where
     }
 }
 
+// Deserializing a document directly tracks where errors happen, DocumentDeserializer can't as
+// its error type is generic
 impl<'de, T: UnstructuredDataTrait> de::Deserializer<'de> for Unstructured<T> {
     type Error = DeserializerError;
 
     fn deserialize_any<V: de::Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
-        DocumentDeserializer::new(self).deserialize_any(visitor)
+        match self {
+            Unstructured::<T>::Option(Some(v)) => visitor.visit_some(*v),
+            Unstructured::<T>::Newtype(v) => visitor.visit_newtype_struct(*v),
+            Unstructured::<T>::Seq(v) => visitor.visit_seq(SeqAccess {
+                iter: unshare(v).into_iter().enumerate(),
+            }),
+            Unstructured::<T>::Map(v) => visitor.visit_map(MapAccess {
+                iter: unshare(v).into_iter(),
+                value: None,
+            }),
+            other => DocumentDeserializer::new(other).deserialize_any(visitor),
+        }
     }
 
     fn deserialize_option<V: de::Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
-        DocumentDeserializer::new(self).deserialize_option(visitor)
+        match self {
+            Unstructured::<T>::Option(..) => self.deserialize_any(visitor),
+            Unstructured::<T>::Null | Unstructured::<T>::Unassigned => visitor.visit_unit(),
+            _ => visitor.visit_some(self),
+        }
     }
 
     fn deserialize_enum<V: de::Visitor<'de>>(
@@ -486,10 +668,13 @@ impl<'de, T: UnstructuredDataTrait> de::Deserializer<'de> for Unstructured<T> {
 
     fn deserialize_newtype_struct<V: de::Visitor<'de>>(
         self,
-        name: &'static str,
+        _name: &'static str,
         visitor: V,
     ) -> Result<V::Value, Self::Error> {
-        DocumentDeserializer::new(self).deserialize_newtype_struct(name, visitor)
+        match self {
+            Unstructured::<T>::Newtype(v) => visitor.visit_newtype_struct(*v),
+            _ => visitor.visit_newtype_struct(self),
+        }
     }
 
     forward_to_deserialize_any! {
@@ -499,6 +684,68 @@ impl<'de, T: UnstructuredDataTrait> de::Deserializer<'de> for Unstructured<T> {
     }
 }
 
+struct SeqAccess<T: UnstructuredDataTrait> {
+    iter: std::iter::Enumerate<std::vec::IntoIter<Unstructured<T>>>,
+}
+
+impl<'de, T: UnstructuredDataTrait> de::SeqAccess<'de> for SeqAccess<T> {
+    type Error = DeserializerError;
+
+    fn next_element_seed<S: de::DeserializeSeed<'de>>(
+        &mut self,
+        seed: S,
+    ) -> Result<Option<S::Value>, Self::Error> {
+        match self.iter.next() {
+            Some((i, v)) => seed
+                .deserialize(v)
+                .map(Some)
+                .map_err(|e| e.at(Segment::Index(i))),
+            None => Ok(None),
+        }
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.iter.len())
+    }
+}
+
+struct MapAccess<T: UnstructuredDataTrait> {
+    iter: <Mapping<T> as IntoIterator>::IntoIter,
+    value: Option<(Segment, Unstructured<T>)>,
+}
+
+impl<'de, T: UnstructuredDataTrait> de::MapAccess<'de> for MapAccess<T> {
+    type Error = DeserializerError;
+
+    fn next_key_seed<S: de::DeserializeSeed<'de>>(
+        &mut self,
+        seed: S,
+    ) -> Result<Option<S::Value>, Self::Error> {
+        match self.iter.next() {
+            Some((k, v)) => {
+                let segment = Segment::from_key(&k);
+                self.value = Some((segment.clone(), v));
+                seed.deserialize(k).map(Some).map_err(|e| e.at(segment))
+            }
+            None => Ok(None),
+        }
+    }
+
+    fn next_value_seed<S: de::DeserializeSeed<'de>>(
+        &mut self,
+        seed: S,
+    ) -> Result<S::Value, Self::Error> {
+        match self.value.take() {
+            Some((segment, v)) => seed.deserialize(v).map_err(|e| e.at(segment)),
+            None => Err(de::Error::custom("value is missing")),
+        }
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.iter.len())
+    }
+}
+
 struct EnumDeserializer<E, T: UnstructuredDataTrait> {
     variant: Unstructured<T>,
     document: Option<Unstructured<T>>,
@@ -566,7 +813,9 @@ where
     {
         match self.document {
             Some(Unstructured::<T>::Seq(v)) => de::Deserializer::deserialize_any(
-                de::value::SeqDeserializer::new(v.into_iter().map(DocumentDeserializer::new)),
+                de::value::SeqDeserializer::new(
+                    unshare(v).into_iter().map(DocumentDeserializer::new),
+                ),
                 visitor,
             ),
             Some(other) => Err(de::Error::invalid_type(
@@ -591,11 +840,274 @@ where
         match self.document {
             Some(Unstructured::<T>::Map(v)) => de::Deserializer::deserialize_any(
                 de::value::MapDeserializer::new(
-                    v.into_iter()
+                    unshare(v)
+                        .into_iter()
                         .map(|(k, v)| (DocumentDeserializer::new(k), DocumentDeserializer::new(v))),
                 ),
                 visitor,
             ),
+            Some(Unstructured::<T>::Seq(v)) => de::Deserializer::deserialize_any(
+                de::value::SeqDeserializer::new(
+                    unshare(v).into_iter().map(DocumentDeserializer::new),
+                ),
+                visitor,
+            ),
+            Some(other) => Err(de::Error::invalid_type(
+                other.unexpected(),
+                &"struct variant",
+            )),
+            None => Err(de::Error::invalid_type(
+                de::Unexpected::UnitVariant,
+                &"struct variant",
+            )),
+        }
+    }
+}
+
+// Deserializing from a reference borrows strings and bytes from the document rather than
+// consuming it
+impl<'de, T: UnstructuredDataTrait> de::Deserializer<'de> for &'de Unstructured<T> {
+    type Error = DeserializerError;
+
+    fn deserialize_any<V: de::Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        match self {
+            Unstructured::<T>::Bool(v) => visitor.visit_bool(*v),
+            Unstructured::<T>::Number(v) => de::Deserializer::deserialize_any(v, visitor),
+            Unstructured::<T>::Char(v) => visitor.visit_char(*v),
+            Unstructured::<T>::String(v) => visitor.visit_borrowed_str(v),
+            Unstructured::<T>::Null | Unstructured::<T>::Unassigned => visitor.visit_unit(),
+            Unstructured::<T>::Option(None) => visitor.visit_none(),
+            Unstructured::<T>::Option(Some(v)) => visitor.visit_some(&**v),
+            Unstructured::<T>::Newtype(v) => visitor.visit_newtype_struct(&**v),
+            Unstructured::<T>::Seq(v) => visitor.visit_seq(SeqRefAccess {
+                iter: v.iter().enumerate(),
+            }),
+            Unstructured::<T>::Map(v) => visitor.visit_map(MapRefAccess {
+                iter: v.iter(),
+                value: None,
+            }),
+            Unstructured::<T>::Bytes(v) => visitor.visit_borrowed_bytes(v),
+            #[cfg(feature = "chrono")]
+            Unstructured::<T>::DateTime(v) => visitor.visit_string(v.to_rfc3339()),
+            Unstructured::<T>::Err(e) => Err(DeserializerError::Custom(format!("{}", e))),
+            Unstructured::<T>::Other(o) => {
+                de::Deserializer::deserialize_any(Unstructured::<T>::from_other(o)?, visitor)
+            }
+        }
+    }
+
+    fn deserialize_option<V: de::Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        match self {
+            Unstructured::<T>::Option(..) => self.deserialize_any(visitor),
+            Unstructured::<T>::Null | Unstructured::<T>::Unassigned => visitor.visit_unit(),
+            _ => visitor.visit_some(self),
+        }
+    }
+
+    fn deserialize_enum<V: de::Visitor<'de>>(
+        self,
+        name: &'static str,
+        variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        match self {
+            Unstructured::<T>::Option(Some(v)) | Unstructured::<T>::Newtype(v) => {
+                de::Deserializer::deserialize_enum(&**v, name, variants, visitor)
+            }
+            // enums are encoded as maps with a single key:Document pair
+            Unstructured::<T>::Map(v) if v.len() == 1 => {
+                let (variant, document) = v.iter().next().unwrap();
+                visitor.visit_enum(EnumRefAccess {
+                    variant,
+                    document: Some(document),
+                })
+            }
+            Unstructured::<T>::Map(_) => Err(de::Error::invalid_value(
+                de::Unexpected::Map,
+                &"map with a single key",
+            )),
+            Unstructured::<T>::String(_) => visitor.visit_enum(EnumRefAccess {
+                variant: self,
+                document: None,
+            }),
+            other => Err(de::Error::invalid_type(
+                other.unexpected(),
+                &"string or map",
+            )),
+        }
+    }
+
+    fn deserialize_newtype_struct<V: de::Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        match self {
+            Unstructured::<T>::Newtype(v) => visitor.visit_newtype_struct(&**v),
+            _ => visitor.visit_newtype_struct(self),
+        }
+    }
+
+    forward_to_deserialize_any! {
+        bool u8 u16 u32 u64 u128 i8 i16 i32 i64 i128 f32 f64 char str string unit
+        seq bytes byte_buf map unit_struct
+        tuple_struct struct tuple ignored_any identifier
+    }
+}
+
+struct SeqRefAccess<'de, T: UnstructuredDataTrait> {
+    iter: std::iter::Enumerate<std::slice::Iter<'de, Unstructured<T>>>,
+}
+
+impl<'de, T: UnstructuredDataTrait> de::SeqAccess<'de> for SeqRefAccess<'de, T> {
+    type Error = DeserializerError;
+
+    fn next_element_seed<S: de::DeserializeSeed<'de>>(
+        &mut self,
+        seed: S,
+    ) -> Result<Option<S::Value>, Self::Error> {
+        match self.iter.next() {
+            Some((i, v)) => seed
+                .deserialize(v)
+                .map(Some)
+                .map_err(|e| e.at(Segment::Index(i))),
+            None => Ok(None),
+        }
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.iter.len())
+    }
+}
+
+struct MapRefAccess<'de, T: UnstructuredDataTrait> {
+    iter: <&'de Mapping<T> as IntoIterator>::IntoIter,
+    value: Option<(Segment, &'de Unstructured<T>)>,
+}
+
+impl<'de, T: UnstructuredDataTrait> de::MapAccess<'de> for MapRefAccess<'de, T> {
+    type Error = DeserializerError;
+
+    fn next_key_seed<S: de::DeserializeSeed<'de>>(
+        &mut self,
+        seed: S,
+    ) -> Result<Option<S::Value>, Self::Error> {
+        match self.iter.next() {
+            Some((k, v)) => {
+                let segment = Segment::from_key(k);
+                self.value = Some((segment.clone(), v));
+                seed.deserialize(k).map(Some).map_err(|e| e.at(segment))
+            }
+            None => Ok(None),
+        }
+    }
+
+    fn next_value_seed<S: de::DeserializeSeed<'de>>(
+        &mut self,
+        seed: S,
+    ) -> Result<S::Value, Self::Error> {
+        match self.value.take() {
+            Some((segment, v)) => seed.deserialize(v).map_err(|e| e.at(segment)),
+            None => Err(de::Error::custom("value is missing")),
+        }
+    }
+
+    fn next_entry_seed<K, V>(
+        &mut self,
+        kseed: K,
+        vseed: V,
+    ) -> Result<Option<(K::Value, V::Value)>, Self::Error>
+    where
+        K: de::DeserializeSeed<'de>,
+        V: de::DeserializeSeed<'de>,
+    {
+        match self.iter.next() {
+            Some((k, v)) => {
+                let at = |e: DeserializerError| e.at(Segment::from_key(k));
+                let key = kseed.deserialize(k).map_err(at)?;
+                Ok(Some((key, vseed.deserialize(v).map_err(at)?)))
+            }
+            None => Ok(None),
+        }
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.iter.len())
+    }
+}
+
+struct EnumRefAccess<'de, T: UnstructuredDataTrait> {
+    variant: &'de Unstructured<T>,
+    document: Option<&'de Unstructured<T>>,
+}
+
+impl<'de, T: UnstructuredDataTrait> de::EnumAccess<'de> for EnumRefAccess<'de, T> {
+    type Error = DeserializerError;
+    type Variant = Self;
+
+    fn variant_seed<V>(self, seed: V) -> Result<(V::Value, Self), Self::Error>
+    where
+        V: de::DeserializeSeed<'de>,
+    {
+        seed.deserialize(self.variant).map(|v| (v, self))
+    }
+}
+
+impl<'de, T: UnstructuredDataTrait> de::VariantAccess<'de> for EnumRefAccess<'de, T> {
+    type Error = DeserializerError;
+
+    fn unit_variant(self) -> Result<(), Self::Error> {
+        match self.document {
+            Some(document) => de::Deserialize::deserialize(document),
+            None => Ok(()),
+        }
+    }
+
+    fn newtype_variant_seed<Q>(self, seed: Q) -> Result<Q::Value, Self::Error>
+    where
+        Q: de::DeserializeSeed<'de>,
+    {
+        match self.document {
+            Some(document) => seed.deserialize(document),
+            None => Err(de::Error::invalid_type(
+                de::Unexpected::UnitVariant,
+                &"newtype variant",
+            )),
+        }
+    }
+
+    fn tuple_variant<V>(self, _len: usize, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: de::Visitor<'de>,
+    {
+        match self.document {
+            Some(document @ Unstructured::<T>::Seq(_)) => {
+                de::Deserializer::deserialize_any(document, visitor)
+            }
+            Some(other) => Err(de::Error::invalid_type(
+                other.unexpected(),
+                &"tuple variant",
+            )),
+            None => Err(de::Error::invalid_type(
+                de::Unexpected::UnitVariant,
+                &"tuple variant",
+            )),
+        }
+    }
+
+    fn struct_variant<V>(
+        self,
+        _fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Self::Error>
+    where
+        V: de::Visitor<'de>,
+    {
+        match self.document {
+            Some(document @ Unstructured::<T>::Map(_))
+            | Some(document @ Unstructured::<T>::Seq(_)) => {
+                de::Deserializer::deserialize_any(document, visitor)
+            }
             Some(other) => Err(de::Error::invalid_type(
                 other.unexpected(),
                 &"struct variant",
@@ -0,0 +1,185 @@
+use crate::*;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+
+/// Repeated subtrees found by [`Unstructured::subtree_stats`] or shared by
+/// [`Unstructured::dedup_subtrees`]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct DedupStats {
+    /// Non-empty sequences and maps in the document
+    pub containers: usize,
+    /// Sequences and maps identical to one earlier in the document
+    pub duplicates: usize,
+    /// Values, including map keys, inside the duplicates. Duplicates nested in another
+    /// duplicate are only counted once.
+    pub values_saved: usize,
+}
+
+/// What walking a subtree found out about it
+struct Walk {
+    hash: u64,
+    size: usize,
+    saved: usize,
+}
+
+impl<T: UnstructuredDataTrait> Unstructured<T> {
+    /// Count the sequences and maps that are identical to one earlier in the document, without
+    /// changing it. Values must match exactly, including their number types and map order.
+    ///
+    /// ```
+    /// use unstructured::Document;
+    ///
+    /// let doc: Document = serde_json::from_str(
+    ///     r#"[{"meta": {"host": "a"}, "v": 1}, {"meta": {"host": "a"}, "v": 2}]"#,
+    /// ).unwrap();
+    /// let stats = doc.subtree_stats();
+    /// assert_eq!((stats.containers, stats.duplicates, stats.values_saved), (5, 1, 3));
+    /// ```
+    pub fn subtree_stats(&self) -> DedupStats {
+        let mut stats = DedupStats::default();
+        self.find_duplicates(&mut HashMap::new(), &mut stats);
+        stats
+    }
+
+    /// Make identical sequences and maps share one allocation, so a block repeated thousands
+    /// of times is only stored once. Shared contents are copied again when they are mutated.
+    ///
+    /// ```
+    /// use unstructured::Document;
+    ///
+    /// let mut doc: Document = serde_json::from_str(
+    ///     r#"[{"meta": {"host": "a"}, "v": 1}, {"meta": {"host": "a"}, "v": 2}]"#,
+    /// ).unwrap();
+    /// assert_eq!(doc.dedup_subtrees(), doc.subtree_stats());
+    /// doc[1]["meta"]["host"] = Document::from("b");
+    /// assert_eq!(doc[0]["meta"]["host"], "a");
+    /// ```
+    #[cfg(feature = "shared")]
+    pub fn dedup_subtrees(&mut self) -> DedupStats {
+        let mut stats = DedupStats::default();
+        self.share_duplicates(&mut HashMap::new(), &mut stats);
+        stats
+    }
+
+    fn find_duplicates<'a>(
+        &'a self,
+        seen: &mut HashMap<u64, Vec<&'a Self>>,
+        stats: &mut DedupStats,
+    ) -> Walk {
+        let walk = match self {
+            Self::Seq(seq) => {
+                let children: Vec<_> = seq.iter().map(|v| v.find_duplicates(seen, stats)).collect();
+                combine(self.discriminant(), children)
+            }
+            Self::Map(map) => {
+                let children: Vec<_> = map
+                    .iter()
+                    .flat_map(|(k, v)| vec![leaf(k), v.find_duplicates(seen, stats)])
+                    .collect();
+                combine(self.discriminant(), children)
+            }
+            Self::Option(Some(v)) | Self::Newtype(v) => {
+                let children = vec![v.find_duplicates(seen, stats)];
+                combine(self.discriminant(), children)
+            }
+            _ => return leaf(self),
+        };
+        if !self.is_empty_container() {
+            stats.containers += 1;
+            let bucket = seen.entry(walk.hash).or_default();
+            if bucket.iter().any(|other| other.strict_eq(self)) {
+                return duplicate(walk, stats);
+            }
+            bucket.push(self);
+        }
+        walk
+    }
+
+    #[cfg(feature = "shared")]
+    fn share_duplicates(
+        &mut self,
+        seen: &mut HashMap<u64, Vec<Self>>,
+        stats: &mut DedupStats,
+    ) -> Walk {
+        let discriminant = self.discriminant();
+        let walk = match self {
+            Self::Seq(seq) => {
+                let children: Vec<_> = make_mut(seq)
+                    .iter_mut()
+                    .map(|v| v.share_duplicates(seen, stats))
+                    .collect();
+                combine(discriminant, children)
+            }
+            Self::Map(map) => {
+                let children: Vec<_> = make_mut(map)
+                    .iter_mut()
+                    .flat_map(|(k, v)| vec![leaf(k), v.share_duplicates(seen, stats)])
+                    .collect();
+                combine(discriminant, children)
+            }
+            Self::Option(Some(v)) | Self::Newtype(v) => {
+                let children = vec![v.share_duplicates(seen, stats)];
+                combine(discriminant, children)
+            }
+            _ => return leaf(self),
+        };
+        if !self.is_empty_container() {
+            stats.containers += 1;
+            let bucket = seen.entry(walk.hash).or_default();
+            if let Some(other) = bucket.iter().find(|other| other.strict_eq(self)) {
+                *self = other.clone();
+                return duplicate(walk, stats);
+            }
+            // Cloning only copies the reference, so later duplicates share these contents
+            bucket.push(self.clone());
+        }
+        walk
+    }
+
+    fn is_empty_container(&self) -> bool {
+        match self {
+            Self::Seq(seq) => seq.is_empty(),
+            Self::Map(map) => map.is_empty(),
+            _ => true,
+        }
+    }
+}
+
+fn leaf<T: UnstructuredDataTrait>(doc: &Unstructured<T>) -> Walk {
+    let mut hasher = DefaultHasher::new();
+    doc.hash(&mut hasher);
+    Walk {
+        hash: hasher.finish(),
+        size: 1,
+        saved: 0,
+    }
+}
+
+/// Hash a container from the hashes of its children, so each value is only hashed once
+fn combine(discriminant: usize, children: Vec<Walk>) -> Walk {
+    let mut hasher = DefaultHasher::new();
+    discriminant.hash(&mut hasher);
+    let mut walk = Walk {
+        hash: 0,
+        size: 1,
+        saved: 0,
+    };
+    for child in children {
+        child.hash.hash(&mut hasher);
+        walk.size += child.size;
+        walk.saved += child.saved;
+    }
+    walk.hash = hasher.finish();
+    walk
+}
+
+/// Count a duplicate, replacing the savings already counted for duplicates inside it
+fn duplicate(walk: Walk, stats: &mut DedupStats) -> Walk {
+    stats.duplicates += 1;
+    stats.values_saved += walk.size - walk.saved;
+    Walk {
+        saved: walk.size,
+        ..walk
+    }
+}
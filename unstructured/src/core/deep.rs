@@ -0,0 +1,205 @@
+use super::shared::contents::Contents;
+use crate::*;
+use std::mem;
+
+/// A value part way through being cloned, holding the children still to clone and the clones
+/// made so far. Keeping these on a heap allocated stack lets documents nested far deeper than
+/// the thread's stack be cloned.
+enum CloneFrame<'a, T: UnstructuredDataTrait> {
+    #[cfg(not(feature = "shared"))]
+    Seq {
+        rest: std::slice::Iter<'a, Unstructured<T>>,
+        out: Sequence<T>,
+    },
+    #[cfg(not(feature = "shared"))]
+    Map {
+        rest: <&'a Mapping<T> as IntoIterator>::IntoIter,
+        out: Mapping<T>,
+        value: Option<&'a Unstructured<T>>,
+        key: Option<Unstructured<T>>,
+    },
+    Option {
+        inner: Option<&'a Unstructured<T>>,
+        out: Option<Unstructured<T>>,
+    },
+    Newtype {
+        inner: Option<&'a Unstructured<T>>,
+        out: Option<Unstructured<T>>,
+    },
+}
+
+impl<'a, T: UnstructuredDataTrait> CloneFrame<'a, T> {
+    fn next_child(&mut self) -> Option<&'a Unstructured<T>> {
+        match self {
+            #[cfg(not(feature = "shared"))]
+            CloneFrame::Seq { rest, .. } => rest.next(),
+            #[cfg(not(feature = "shared"))]
+            CloneFrame::Map { rest, value, .. } => value.take().or_else(|| {
+                let (k, v) = rest.next()?;
+                *value = Some(v);
+                Some(k)
+            }),
+            CloneFrame::Option { inner, .. } | CloneFrame::Newtype { inner, .. } => inner.take(),
+        }
+    }
+
+    fn push(&mut self, cloned: Unstructured<T>) {
+        match self {
+            #[cfg(not(feature = "shared"))]
+            CloneFrame::Seq { out, .. } => out.push(cloned),
+            #[cfg(not(feature = "shared"))]
+            CloneFrame::Map { out, key, .. } => match key.take() {
+                Some(k) => {
+                    out.insert(k, cloned);
+                }
+                None => *key = Some(cloned),
+            },
+            CloneFrame::Option { out, .. } | CloneFrame::Newtype { out, .. } => {
+                *out = Some(cloned)
+            }
+        }
+    }
+
+    fn finish(self) -> Unstructured<T> {
+        match self {
+            #[cfg(not(feature = "shared"))]
+            CloneFrame::Seq { out, .. } => Unstructured::<T>::Seq(share(out)),
+            #[cfg(not(feature = "shared"))]
+            CloneFrame::Map { out, .. } => Unstructured::<T>::Map(share(out)),
+            CloneFrame::Option { out, .. } => Unstructured::<T>::Option(out.map(Box::new)),
+            CloneFrame::Newtype { out, .. } => {
+                Unstructured::<T>::Newtype(Box::new(out.unwrap_or_default()))
+            }
+        }
+    }
+}
+
+/// Cloning doesn't recurse, so it is safe for documents of any depth. With the `shared`
+/// feature sequences and maps are not copied, only the reference to them.
+impl<T: UnstructuredDataTrait> Clone for Unstructured<T> {
+    fn clone(&self) -> Self {
+        let mut stack = vec![];
+        let mut next = Some(self);
+        loop {
+            let mut cloned = None;
+            if let Some(doc) = next.take() {
+                match doc.start_clone() {
+                    Ok(frame) => stack.push(frame),
+                    Err(leaf) => cloned = Some(leaf),
+                }
+            }
+            // Hand finished clones to their parents until one has another child to clone
+            while next.is_none() {
+                let frame = match stack.last_mut() {
+                    Some(frame) => frame,
+                    None => return cloned.unwrap_or_default(),
+                };
+                if let Some(cloned) = cloned.take() {
+                    frame.push(cloned);
+                }
+                next = frame.next_child();
+                if next.is_none() {
+                    cloned = stack.pop().map(CloneFrame::finish);
+                }
+            }
+        }
+    }
+}
+
+impl<T: UnstructuredDataTrait> Unstructured<T> {
+    /// Start cloning a value with children, or clone it outright if it has nothing to clone
+    /// one at a time
+    fn start_clone(&self) -> Result<CloneFrame<'_, T>, Self> {
+        Ok(match self {
+            #[cfg(not(feature = "shared"))]
+            Self::Seq(seq) => CloneFrame::Seq {
+                rest: seq.iter(),
+                out: Sequence::with_capacity(seq.len()),
+            },
+            #[cfg(not(feature = "shared"))]
+            Self::Map(map) => CloneFrame::Map {
+                rest: map.iter(),
+                out: Mapping::with_capacity(map.len()),
+                value: None,
+                key: None,
+            },
+            Self::Option(Some(inner)) => CloneFrame::Option {
+                inner: Some(inner),
+                out: None,
+            },
+            Self::Newtype(inner) => CloneFrame::Newtype {
+                inner: Some(inner),
+                out: None,
+            },
+            Self::Unassigned => return Err(Self::Unassigned),
+            Self::Null => return Err(Self::Null),
+            Self::Bool(b) => return Err(Self::Bool(*b)),
+            Self::Number(n) => return Err(Self::Number(n.clone())),
+            Self::String(s) => return Err(Self::String(s.clone())),
+            Self::Char(c) => return Err(Self::Char(*c)),
+            Self::Bytes(b) => return Err(Self::Bytes(b.clone())),
+            #[cfg(feature = "chrono")]
+            Self::DateTime(dt) => return Err(Self::DateTime(*dt)),
+            #[cfg(feature = "shared")]
+            Self::Seq(seq) => return Err(Self::Seq(seq.clone())),
+            #[cfg(feature = "shared")]
+            Self::Map(map) => return Err(Self::Map(map.clone())),
+            Self::Option(None) => return Err(Self::Option(None)),
+            Self::Err(e) => return Err(Self::Err(e.clone())),
+            Self::Other(o) => return Err(Self::Other(o.clone())),
+        })
+    }
+
+    /// Drop a document without recursing. Plain `drop` only recurses through a chain of
+    /// `Option` and `Newtype` wrappers with no sequence or map between them, as `Unstructured`
+    /// can't implement `Drop` without stopping values being moved out of its variants, so this
+    /// is only needed for documents that are such a chain thousands of wrappers deep.
+    ///
+    /// ```
+    /// use unstructured::Document;
+    ///
+    /// let mut doc = Document::Null;
+    /// for _ in 0..100_000 {
+    ///     doc = Document::Newtype(Box::new(doc));
+    /// }
+    /// doc.drop_deep();
+    /// ```
+    pub fn drop_deep(self) {
+        Self::drop_all(vec![self]);
+    }
+
+    /// Drop the documents, taking the contents out of each one as it is dropped so that
+    /// nothing is left for it to drop recursively. With the `shared` feature, contents still
+    /// used by another document are left alone.
+    fn drop_all(mut stack: Vec<Self>) {
+        while let Some(mut doc) = stack.pop() {
+            match &mut doc {
+                Self::Seq(seq) => stack.extend(seq.take_unique().into_iter().flatten()),
+                Self::Map(map) => {
+                    if let Some(map) = map.take_unique() {
+                        stack.extend(map.into_iter().flat_map(|(k, v)| [k, v]));
+                    }
+                }
+                Self::Option(inner) => stack.extend(inner.take().map(|inner| *inner)),
+                Self::Newtype(inner) => stack.push(mem::take(&mut **inner)),
+                _ => {}
+            }
+        }
+    }
+}
+
+impl<T: UnstructuredDataTrait> Contents for Sequence<T> {
+    fn drop_flat(self) {
+        if !self.is_empty() {
+            Unstructured::drop_all(self);
+        }
+    }
+}
+
+impl<T: UnstructuredDataTrait> Contents for Mapping<T> {
+    fn drop_flat(self) {
+        if !self.is_empty() {
+            Unstructured::drop_all(self.into_iter().flat_map(|(k, v)| [k, v]).collect());
+        }
+    }
+}
@@ -0,0 +1,357 @@
+use crate::*;
+use serde::de::{self, DeserializeSeed, Visitor};
+use std::fmt;
+
+/// A single step of walking through a document, see [`Unstructured::into_events`]. Maps and
+/// sequences are bracketed by start and end events, `Option(Some)` and `Newtype` wrappers are
+/// announced before the events of the value they hold and everything else is a `Value`.
+#[derive(Clone)]
+pub enum DocumentEvent<T: UnstructuredDataTrait> {
+    StartMap,
+    /// A map key, followed by the events of its value
+    Key(Unstructured<T>),
+    EndMap,
+    StartSeq,
+    EndSeq,
+    /// The next value is wrapped in `Option(Some)`
+    WrapSome,
+    /// The next value is wrapped in `Newtype`
+    WrapNewtype,
+    Value(Unstructured<T>),
+}
+
+impl<T: UnstructuredDataTrait> fmt::Debug for DocumentEvent<T>
+where
+    Unstructured<T>: fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            DocumentEvent::StartMap => f.write_str("StartMap"),
+            DocumentEvent::Key(key) => f.debug_tuple("Key").field(key).finish(),
+            DocumentEvent::EndMap => f.write_str("EndMap"),
+            DocumentEvent::StartSeq => f.write_str("StartSeq"),
+            DocumentEvent::EndSeq => f.write_str("EndSeq"),
+            DocumentEvent::WrapSome => f.write_str("WrapSome"),
+            DocumentEvent::WrapNewtype => f.write_str("WrapNewtype"),
+            DocumentEvent::Value(val) => f.debug_tuple("Value").field(val).finish(),
+        }
+    }
+}
+
+impl<T: UnstructuredDataTrait> PartialEq for DocumentEvent<T> {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (DocumentEvent::Key(l), DocumentEvent::Key(r)) => l == r,
+            (DocumentEvent::Value(l), DocumentEvent::Value(r)) => l == r,
+            (l, r) => std::mem::discriminant(l) == std::mem::discriminant(r),
+        }
+    }
+}
+
+enum Frame<T: UnstructuredDataTrait> {
+    Seq(<Sequence<T> as IntoIterator>::IntoIter),
+    Map(<Mapping<T> as IntoIterator>::IntoIter),
+}
+
+/// Iterator over the events of a document, see [`Unstructured::into_events`]
+pub struct Events<T: UnstructuredDataTrait> {
+    stack: Vec<Frame<T>>,
+    next: Option<Unstructured<T>>,
+}
+
+impl<T: UnstructuredDataTrait> Events<T> {
+    fn open(&mut self, doc: Unstructured<T>) -> DocumentEvent<T> {
+        match doc {
+            doc @ Unstructured::<T>::Seq(_) => {
+                self.stack.push(Frame::Seq(doc.into_seq_iter()));
+                DocumentEvent::StartSeq
+            }
+            doc @ Unstructured::<T>::Map(_) => {
+                self.stack.push(Frame::Map(doc.into_map_iter()));
+                DocumentEvent::StartMap
+            }
+            Unstructured::<T>::Option(Some(inner)) => {
+                self.next = Some(*inner);
+                DocumentEvent::WrapSome
+            }
+            Unstructured::<T>::Newtype(inner) => {
+                self.next = Some(*inner);
+                DocumentEvent::WrapNewtype
+            }
+            doc => DocumentEvent::Value(doc),
+        }
+    }
+}
+
+impl<T: UnstructuredDataTrait> Iterator for Events<T> {
+    type Item = DocumentEvent<T>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if let Some(doc) = self.next.take() {
+            return Some(self.open(doc));
+        }
+        let event = match self.stack.last_mut()? {
+            Frame::Seq(seq) => match seq.next() {
+                Some(doc) => return Some(self.open(doc)),
+                None => DocumentEvent::EndSeq,
+            },
+            Frame::Map(map) => match map.next() {
+                Some((key, val)) => {
+                    self.next = Some(val);
+                    return Some(DocumentEvent::Key(key));
+                }
+                None => DocumentEvent::EndMap,
+            },
+        };
+        self.stack.pop();
+        Some(event)
+    }
+}
+
+enum Partial<T: UnstructuredDataTrait> {
+    Seq(Sequence<T>),
+    Map(Mapping<T>, Option<Unstructured<T>>),
+    Some,
+    Newtype,
+}
+
+/// Rebuilds documents from [`DocumentEvent`]s, see [`Unstructured::from_events`]
+pub struct EventBuilder<T: UnstructuredDataTrait> {
+    stack: Vec<Partial<T>>,
+}
+
+impl<T: UnstructuredDataTrait> Default for EventBuilder<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: UnstructuredDataTrait> EventBuilder<T> {
+    pub fn new() -> Self {
+        EventBuilder { stack: vec![] }
+    }
+
+    /// Add the next event, returning the document once its last event has been pushed. The
+    /// builder can then be reused for the next document.
+    pub fn push(&mut self, event: DocumentEvent<T>) -> Result<Option<Unstructured<T>>, Error> {
+        let doc = match event {
+            DocumentEvent::StartMap => {
+                self.stack.push(Partial::Map(Mapping::new(), None));
+                return Ok(None);
+            }
+            DocumentEvent::StartSeq => {
+                self.stack.push(Partial::Seq(vec![]));
+                return Ok(None);
+            }
+            DocumentEvent::WrapSome => {
+                self.stack.push(Partial::Some);
+                return Ok(None);
+            }
+            DocumentEvent::WrapNewtype => {
+                self.stack.push(Partial::Newtype);
+                return Ok(None);
+            }
+            DocumentEvent::Key(key) => match self.stack.last_mut() {
+                Some(Partial::Map(_, pending @ None)) => {
+                    *pending = Some(key);
+                    return Ok(None);
+                }
+                _ => return Err(Error::Build(format!("Unexpected map key {}", key))),
+            },
+            DocumentEvent::EndMap => match self.stack.pop() {
+                Some(Partial::Map(map, None)) => Unstructured::<T>::Map(share(map)),
+                _ => return Err(Error::Build("Unexpected end of map".into())),
+            },
+            DocumentEvent::EndSeq => match self.stack.pop() {
+                Some(Partial::Seq(seq)) => Unstructured::<T>::Seq(share(seq)),
+                _ => return Err(Error::Build("Unexpected end of sequence".into())),
+            },
+            DocumentEvent::Value(doc) => doc,
+        };
+        self.complete(doc)
+    }
+
+    fn complete(&mut self, mut doc: Unstructured<T>) -> Result<Option<Unstructured<T>>, Error> {
+        loop {
+            match self.stack.last_mut() {
+                None => return Ok(Some(doc)),
+                Some(Partial::Seq(seq)) => seq.push(doc),
+                Some(Partial::Map(map, pending)) => match pending.take() {
+                    Some(key) => {
+                        map.insert(key, doc);
+                    }
+                    None => return Err(Error::Build("Map value without a key".into())),
+                },
+                Some(Partial::Some) => {
+                    self.stack.pop();
+                    doc = Unstructured::<T>::Option(Some(Box::new(doc)));
+                    continue;
+                }
+                Some(Partial::Newtype) => {
+                    self.stack.pop();
+                    doc = Unstructured::<T>::Newtype(Box::new(doc));
+                    continue;
+                }
+            }
+            return Ok(None);
+        }
+    }
+}
+
+impl<T: UnstructuredDataTrait> Unstructured<T> {
+    /// Turn the document into a stream of events, the inverse of
+    /// [`from_events`](Unstructured::from_events)
+    ///
+    /// ```
+    /// use unstructured::{Document, DocumentEvent};
+    ///
+    /// let doc: Document = serde_json::from_str(r#"{"a": [1]}"#).unwrap();
+    /// let events: Vec<_> = doc.clone().into_events().collect();
+    /// assert_eq!(events[0], DocumentEvent::StartMap);
+    /// assert_eq!(events[1], DocumentEvent::Key("a".into()));
+    /// assert_eq!(events[2], DocumentEvent::StartSeq);
+    /// assert_eq!(events.len(), 6);
+    /// assert_eq!(Document::from_events(events).unwrap(), doc);
+    /// ```
+    pub fn into_events(self) -> Events<T> {
+        Events {
+            stack: vec![],
+            next: Some(self),
+        }
+    }
+
+    /// Build a document from the events of [`into_events`](Unstructured::into_events) or
+    /// [`stream_events`](Unstructured::stream_events). Events after the end of the first
+    /// document are not read.
+    pub fn from_events<I: IntoIterator<Item = DocumentEvent<T>>>(events: I) -> Result<Self, Error> {
+        let mut builder = EventBuilder::new();
+        for event in events {
+            if let Some(doc) = builder.push(event)? {
+                return Ok(doc);
+            }
+        }
+        Err(Error::Build("Events ended before the document was complete".into()))
+    }
+
+    /// Read a value from any serde `Deserializer`, passing its events to `f` as they are parsed
+    /// instead of building a document. Only map keys are held in memory, so this can be used for
+    /// inputs too large to read into a document.
+    ///
+    /// ```
+    /// use unstructured::{Document, DocumentEvent};
+    ///
+    /// let mut de = serde_json::Deserializer::from_str(r#"[{"id": 1}, {"id": 2}]"#);
+    /// let mut values = 0;
+    /// Document::stream_events(&mut de, |event| {
+    ///     if let DocumentEvent::Value(_) = event {
+    ///         values += 1;
+    ///     }
+    /// })
+    /// .unwrap();
+    /// assert_eq!(values, 2);
+    /// ```
+    pub fn stream_events<'de, D, F>(d: D, mut f: F) -> Result<(), D::Error>
+    where
+        D: serde::Deserializer<'de>,
+        F: FnMut(DocumentEvent<T>),
+    {
+        EventVisitor(&mut f).deserialize(d)
+    }
+}
+
+struct EventVisitor<'a, T: UnstructuredDataTrait>(&'a mut dyn FnMut(DocumentEvent<T>));
+
+impl<'a, T: UnstructuredDataTrait> EventVisitor<'a, T> {
+    fn value<E>(self, doc: Unstructured<T>) -> Result<(), E> {
+        (self.0)(DocumentEvent::Value(doc));
+        Ok(())
+    }
+}
+
+impl<'a, 'de, T: UnstructuredDataTrait> DeserializeSeed<'de> for EventVisitor<'a, T> {
+    type Value = ();
+
+    fn deserialize<D: de::Deserializer<'de>>(self, d: D) -> Result<(), D::Error> {
+        d.deserialize_any(self)
+    }
+}
+
+macro_rules! visit_values {
+    ($($method:ident($ty:ty)),*) => {
+        $(
+            fn $method<E>(self, v: $ty) -> Result<(), E> {
+                self.value(v.into())
+            }
+        )*
+    };
+}
+
+impl<'a, 'de, T: UnstructuredDataTrait> Visitor<'de> for EventVisitor<'a, T> {
+    type Value = ();
+
+    fn expecting(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt.write_str("any value")
+    }
+
+    visit_values!(
+        visit_bool(bool),
+        visit_i8(i8),
+        visit_i16(i16),
+        visit_i32(i32),
+        visit_i64(i64),
+        visit_i128(i128),
+        visit_u8(u8),
+        visit_u16(u16),
+        visit_u32(u32),
+        visit_u64(u64),
+        visit_u128(u128),
+        visit_f32(f32),
+        visit_f64(f64),
+        visit_char(char),
+        visit_str(&str),
+        visit_string(String)
+    );
+
+    fn visit_bytes<E>(self, v: &[u8]) -> Result<(), E> {
+        self.value(Unstructured::<T>::Bytes(v.into()))
+    }
+
+    fn visit_byte_buf<E>(self, v: Vec<u8>) -> Result<(), E> {
+        self.value(Unstructured::<T>::Bytes(v))
+    }
+
+    fn visit_unit<E>(self) -> Result<(), E> {
+        self.value(Unstructured::<T>::Null)
+    }
+
+    fn visit_none<E>(self) -> Result<(), E> {
+        self.value(Unstructured::<T>::Option(None))
+    }
+
+    fn visit_some<D: de::Deserializer<'de>>(self, d: D) -> Result<(), D::Error> {
+        (self.0)(DocumentEvent::WrapSome);
+        d.deserialize_any(self)
+    }
+
+    fn visit_newtype_struct<D: de::Deserializer<'de>>(self, d: D) -> Result<(), D::Error> {
+        (self.0)(DocumentEvent::WrapNewtype);
+        d.deserialize_any(self)
+    }
+
+    fn visit_seq<V: de::SeqAccess<'de>>(self, mut seq: V) -> Result<(), V::Error> {
+        (self.0)(DocumentEvent::StartSeq);
+        while seq.next_element_seed(EventVisitor(&mut *self.0))?.is_some() {}
+        (self.0)(DocumentEvent::EndSeq);
+        Ok(())
+    }
+
+    fn visit_map<V: de::MapAccess<'de>>(self, mut map: V) -> Result<(), V::Error> {
+        (self.0)(DocumentEvent::StartMap);
+        while let Some(key) = map.next_key()? {
+            (self.0)(DocumentEvent::Key(key));
+            map.next_value_seed(EventVisitor(&mut *self.0))?;
+        }
+        (self.0)(DocumentEvent::EndMap);
+        Ok(())
+    }
+}
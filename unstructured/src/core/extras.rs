@@ -0,0 +1,136 @@
+use crate::*;
+use serde::de::{Deserialize, Deserializer, MapAccess, Visitor};
+use serde::ser::{Serialize, SerializeMap, Serializer};
+use std::fmt;
+use std::marker::PhantomData;
+use std::ops::{Deref, DerefMut};
+
+/// The fields of a struct that it doesn't declare, for use with `#[serde(flatten)]`. Unlike
+/// flattening into an [`Unstructured`] this is always a map, even when there are no extra
+/// fields, and it is only ever read and written as a map, so the remaining fields can't be
+/// mistaken for another kind of value.
+///
+/// ```
+/// use serde::{Deserialize, Serialize};
+/// use unstructured::{Document, Extras};
+///
+/// #[derive(Deserialize, Serialize)]
+/// struct Config {
+///     name: String,
+///     #[serde(flatten)]
+///     extras: Extras,
+/// }
+///
+/// let doc: Document = serde_json::from_str(r#"{"name": "a", "debug": true}"#).unwrap();
+/// let config: Config = doc.try_into().unwrap();
+/// assert_eq!(config.extras.get("debug"), Some(&Document::Bool(true)));
+/// assert_eq!(config.extras.len(), 1);
+///
+/// let doc = Document::new(&config).unwrap();
+/// assert_eq!(doc["debug"], true);
+/// ```
+#[derive(Clone)]
+pub struct Extras<T: UnstructuredDataTrait = UnstructuredType>(Mapping<T>);
+
+impl<T: UnstructuredDataTrait> Extras<T> {
+    pub fn new() -> Self {
+        Extras(Mapping::<T>::new())
+    }
+
+    /// The value of an extra field, if there is one
+    pub fn get(&self, key: &str) -> Option<&Unstructured<T>> {
+        self.0.get(&Unstructured::<T>::from(key))
+    }
+
+    /// The extra fields as a map document
+    pub fn into_document(self) -> Unstructured<T> {
+        Unstructured::<T>::Map(share(self.0))
+    }
+}
+
+impl<T: UnstructuredDataTrait> Default for Extras<T> {
+    fn default() -> Self {
+        Extras::new()
+    }
+}
+
+impl<T: UnstructuredDataTrait> fmt::Debug for Extras<T>
+where
+    Unstructured<T>: fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_tuple("Extras").field(&self.0).finish()
+    }
+}
+
+impl<T: UnstructuredDataTrait> PartialEq for Extras<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.0 == other.0
+    }
+}
+
+impl<T: UnstructuredDataTrait> Eq for Extras<T> {}
+
+impl<T: UnstructuredDataTrait> Deref for Extras<T> {
+    type Target = Mapping<T>;
+
+    fn deref(&self) -> &Mapping<T> {
+        &self.0
+    }
+}
+
+impl<T: UnstructuredDataTrait> DerefMut for Extras<T> {
+    fn deref_mut(&mut self) -> &mut Mapping<T> {
+        &mut self.0
+    }
+}
+
+impl<T: UnstructuredDataTrait> From<Mapping<T>> for Extras<T> {
+    fn from(map: Mapping<T>) -> Self {
+        Extras(map)
+    }
+}
+
+impl<T: UnstructuredDataTrait> From<Extras<T>> for Unstructured<T> {
+    fn from(extras: Extras<T>) -> Self {
+        extras.into_document()
+    }
+}
+
+impl<T: UnstructuredDataTrait> Serialize for Extras<T> {
+    fn serialize<S: Serializer>(&self, s: S) -> Result<S::Ok, S::Error> {
+        let mut map = s.serialize_map(Some(self.0.len()))?;
+        for (key, val) in self.0.iter() {
+            map.serialize_entry(key, val)?;
+        }
+        map.end()
+    }
+}
+
+struct ExtrasVisitor<T: UnstructuredDataTrait>(PhantomData<T>);
+
+impl<'de, T: UnstructuredDataTrait> Visitor<'de> for ExtrasVisitor<T> {
+    type Value = Extras<T>;
+
+    fn expecting(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt.write_str("a map of extra fields")
+    }
+
+    fn visit_unit<E>(self) -> Result<Extras<T>, E> {
+        Ok(Extras::new())
+    }
+
+    fn visit_map<V: MapAccess<'de>>(self, mut visitor: V) -> Result<Extras<T>, V::Error> {
+        let mut map = Mapping::<T>::new();
+        while let Some((key, val)) = visitor.next_entry()? {
+            map.insert(key, val);
+        }
+        Ok(Extras(map))
+    }
+}
+
+impl<'de, T: UnstructuredDataTrait> Deserialize<'de> for Extras<T> {
+    fn deserialize<D: Deserializer<'de>>(d: D) -> Result<Self, D::Error> {
+        d.deserialize_map(ExtrasVisitor(PhantomData))
+    }
+}
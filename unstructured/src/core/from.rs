@@ -32,8 +32,6 @@ from_imp! {
         char,Char
         String,String str,String
         Vec<u8>,Bytes
-        Sequence<T>,Seq
-        Mapping<T>,Map
         Option<Box<Unstructured<T>>>,Option
         Box<Unstructured<T>>,Newtype
     }
@@ -43,9 +41,112 @@ from_imp! {
         char,Char
         String,String
         Vec<u8>,Bytes
-        Sequence<T>,Seq
-        Mapping<T>,Map
         Option<Box<Unstructured<T>>>,Option
         Box<Unstructured<T>>,Newtype
     }
 }
+
+macro_rules! from_shared_imp {
+    ( $($ty:ty, $v:ident)* ) => {
+        $(
+            impl<T: UnstructuredDataTrait> From<&$ty> for Unstructured<T> {
+                fn from(n: &$ty) -> Self {
+                    Unstructured::<T>::$v(share(n.to_owned()))
+                }
+            }
+
+            impl<T: UnstructuredDataTrait> From<$ty> for Unstructured<T> {
+                fn from(n: $ty) -> Self {
+                    Unstructured::<T>::$v(share(n))
+                }
+            }
+        )*
+    };
+}
+
+from_shared_imp! {
+    Sequence<T>,Seq
+    Mapping<T>,Map
+}
+
+macro_rules! from_tuple_imp {
+    ( $( ( $($name:ident),* ) )* ) => {
+        $(
+            impl<T: UnstructuredDataTrait, $($name: Into<Unstructured<T>>),*> From<($($name,)*)>
+                for Unstructured<T>
+            {
+                #[allow(non_snake_case)]
+                fn from(($($name,)*): ($($name,)*)) -> Self {
+                    Unstructured::<T>::Seq(share(vec![$($name.into()),*]))
+                }
+            }
+        )*
+    };
+}
+
+from_tuple_imp! {
+    (A, B)
+    (A, B, C)
+    (A, B, C, D)
+    (A, B, C, D, E)
+    (A, B, C, D, E, F)
+}
+
+impl<T: UnstructuredDataTrait, V: Into<Unstructured<T>>, const N: usize> From<[V; N]>
+    for Unstructured<T>
+{
+    fn from(n: [V; N]) -> Self {
+        Unstructured::<T>::Seq(share(IntoIterator::into_iter(n).map(Into::into).collect()))
+    }
+}
+
+impl<T, K, V, S> From<std::collections::HashMap<K, V, S>> for Unstructured<T>
+where
+    T: UnstructuredDataTrait,
+    K: Into<Unstructured<T>>,
+    V: Into<Unstructured<T>>,
+{
+    fn from(n: std::collections::HashMap<K, V, S>) -> Self {
+        n.into_iter().collect()
+    }
+}
+
+/// Collect key value pairs into a map
+///
+/// ```
+/// use unstructured::Document;
+///
+/// let doc: Document = vec![("a", 1), ("b", 2)].into_iter().collect();
+/// assert_eq!(doc["b"], 2);
+/// ```
+impl<T, K, V> std::iter::FromIterator<(K, V)> for Unstructured<T>
+where
+    T: UnstructuredDataTrait,
+    K: Into<Unstructured<T>>,
+    V: Into<Unstructured<T>>,
+{
+    fn from_iter<I: IntoIterator<Item = (K, V)>>(iter: I) -> Self {
+        Unstructured::<T>::Map(share(
+            iter.into_iter()
+                .map(|(key, val)| (key.into(), val.into()))
+                .collect(),
+        ))
+    }
+}
+
+macro_rules! from_option_imp {
+    ( $($ty:ty)* ) => {
+        $(
+            impl<T: UnstructuredDataTrait> From<Option<$ty>> for Unstructured<T> {
+                fn from(n: Option<$ty>) -> Self {
+                    Unstructured::<T>::Option(n.map(|v| Box::new(v.into())))
+                }
+            }
+        )*
+    };
+}
+
+from_option_imp! {
+    Unstructured<T> bool char String &str Vec<u8>
+    i8 i16 i32 i64 i128 isize u8 u16 u32 u64 u128 usize f32 f64
+}
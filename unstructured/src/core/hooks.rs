@@ -0,0 +1,105 @@
+use crate::*;
+use std::sync::OnceLock;
+use std::time::{Duration, Instant};
+
+/// A document processing stage reported to [`ObservabilityHooks`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Operation {
+    /// Reading a document with [`Unstructured::from_deserializer`] or one of the format helpers
+    /// such as `from_json_str`
+    Parse,
+    /// [`Unstructured::merge`], [`Unstructured::merge_with`], [`Unstructured::merge_checked`] and
+    /// [`Unstructured::merge_resolve`]
+    Merge,
+    /// [`Unstructured::filter`] and `filter_into`
+    Filter,
+}
+
+/// What an operation did, passed to [`ObservabilityHooks::on_end`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct OperationStats {
+    /// How long the operation took
+    pub elapsed: Duration,
+    /// The number of values in the resulting document, 0 if the operation failed
+    pub nodes: usize,
+    /// A rough estimate of the memory held by the resulting document in bytes, the same as
+    /// [`Unstructured::approximate_size`]
+    pub bytes: usize,
+    /// Whether the operation succeeded
+    pub success: bool,
+}
+
+/// Callbacks for exporting metrics about document processing, installed for the whole process
+/// with [`set_hooks`]. Stats are only collected while hooks are installed.
+///
+/// ```
+/// use std::sync::atomic::{AtomicUsize, Ordering};
+/// use unstructured::{Document, ObservabilityHooks, Operation, OperationStats};
+///
+/// static PARSED_NODES: AtomicUsize = AtomicUsize::new(0);
+///
+/// struct Metrics;
+///
+/// impl ObservabilityHooks for Metrics {
+///     fn on_end(&self, op: Operation, stats: &OperationStats) {
+///         if op == Operation::Parse {
+///             PARSED_NODES.fetch_add(stats.nodes, Ordering::Relaxed);
+///         }
+///     }
+/// }
+///
+/// unstructured::set_hooks(Metrics).ok().unwrap();
+/// let mut de = serde_json::Deserializer::from_str("[1, 2]");
+/// Document::from_deserializer(&mut de).unwrap();
+/// assert_eq!(PARSED_NODES.load(Ordering::Relaxed), 3);
+/// ```
+pub trait ObservabilityHooks: Send + Sync {
+    /// Called when an operation starts
+    fn on_start(&self, _op: Operation) {}
+
+    /// Called when an operation finishes
+    fn on_end(&self, _op: Operation, _stats: &OperationStats) {}
+}
+
+static HOOKS: OnceLock<Box<dyn ObservabilityHooks>> = OnceLock::new();
+
+/// Install hooks for the rest of the process. Hooks can only be installed once, if some already
+/// were the new hooks are returned as the error.
+pub fn set_hooks<H: ObservabilityHooks + 'static>(
+    hooks: H,
+) -> Result<(), Box<dyn ObservabilityHooks>> {
+    HOOKS.set(Box::new(hooks))
+}
+
+/// Times an operation, a no-op unless hooks are installed
+pub(crate) struct Observation(Option<(&'static dyn ObservabilityHooks, Operation, Instant)>);
+
+impl Observation {
+    pub(crate) fn start(op: Operation) -> Self {
+        Observation(HOOKS.get().map(|hooks| {
+            hooks.on_start(op);
+            (hooks.as_ref(), op, Instant::now())
+        }))
+    }
+
+    /// Report the end of the operation, `result` is None if it failed
+    pub(crate) fn end<T: UnstructuredDataTrait>(self, result: Option<&Unstructured<T>>) {
+        if let Some((hooks, op, started)) = self.0 {
+            let elapsed = started.elapsed();
+            let (nodes, bytes) = result.map(Unstructured::measure).unwrap_or_default();
+            let success = result.is_some();
+            hooks.on_end(op, &OperationStats { elapsed, nodes, bytes, success });
+        }
+    }
+}
+
+/// Run an operation that produces a document, reporting it to the hooks
+pub(crate) fn observe<T: UnstructuredDataTrait, E>(
+    op: Operation,
+    f: impl FnOnce() -> Result<Unstructured<T>, E>,
+) -> Result<Unstructured<T>, E> {
+    let observation = Observation::start(op);
+    let result = f();
+    observation.end(result.as_ref().ok());
+    result
+}
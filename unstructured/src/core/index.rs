@@ -1,4 +1,4 @@
-use std::collections::BTreeMap;
+use std::convert::TryFrom;
 use std::ops;
 
 use crate::*;
@@ -17,28 +17,20 @@ pub trait Index<T: UnstructuredDataTrait>: private::Sealed {
 impl<T: UnstructuredDataTrait> Index<T> for Unstructured<T>
 {
     fn index_into<'v>(&self, v: &'v Unstructured<T>) -> Option<&'v Unstructured<T>> {
-        match (v, self.as_usize()) {
-            (Unstructured::<T>::Seq(ref s), Some(i)) => {
-                if i >= s.len() {
-                    None
-                } else {
-                    Some(&s[i])
-                }
-            }
-            (Unstructured::<T>::Map(ref map), _) => map.get(self),
+        match v {
+            Unstructured::<T>::Seq(ref s) => self.seq_position(s.len()).and_then(|i| s.get(i)),
+            Unstructured::<T>::Map(ref map) => map.get(self),
             _ => None,
         }
     }
     fn index_into_mut<'v>(&self, v: &'v mut Unstructured<T>) -> Option<&'v mut Unstructured<T>> {
-        match (v, self.as_usize()) {
-            (Unstructured::<T>::Seq(ref mut s), Some(i)) => {
-                if i >= s.len() {
-                    None
-                } else {
-                    Some(&mut s[i])
-                }
+        match v {
+            Unstructured::<T>::Seq(ref mut s) => {
+                let len = s.len();
+                let s = make_mut(s);
+                self.seq_position(len).and_then(move |i| s.get_mut(i))
             }
-            (Unstructured::<T>::Map(ref mut map), _) => map.get_mut(self),
+            Unstructured::<T>::Map(ref mut map) => make_mut(map).get_mut(self),
             _ => None,
         }
     }
@@ -48,32 +40,53 @@ impl<T: UnstructuredDataTrait> Index<T> for Unstructured<T>
             && !(v.is::<Sequence<T>>()
                 || v.is::<Mapping<T>>())
         {
-            *v = Unstructured::<T>::Seq(vec![]);
+            *v = Unstructured::<T>::Seq(Default::default());
         } else if !self.is_number() && !v.is::<Mapping<T>>() {
-            *v = Unstructured::<T>::Map(BTreeMap::default());
+            *v = Unstructured::<T>::Map(Default::default());
         }
-        match *v {
-            Unstructured::<T>::Map(ref mut map) => {
-                map.entry(self.clone()).or_insert(Unstructured::<T>::Null)
+        match v {
+            Unstructured::<T>::Map(map) => {
+                make_mut(map).get_or_insert_with(self.clone(), || Unstructured::<T>::Null)
             }
-            Unstructured::<T>::Seq(ref mut seq) => {
-                if let Some(i) = self.as_usize() {
-                    let size = seq.len();
-                    if i >= size {
+            Unstructured::<T>::Seq(seq) => {
+                let seq = make_mut(seq);
+                let size = seq.len();
+                match self.seq_position(size) {
+                    Some(i) if i < size => &mut seq[i],
+                    _ if self.is_negative() => {
+                        seq.insert(0, Unstructured::<T>::Null);
+                        &mut seq[0]
+                    }
+                    _ => {
                         seq.push(Unstructured::<T>::Null);
                         &mut seq[size]
-                    } else {
-                        &mut seq[i]
                     }
-                } else {
-                    unreachable!()
                 }
             }
-            _ => unreachable!(),
+            // v was replaced with a container above
+            other => other,
         }
     }
 }
 
+impl<T: UnstructuredDataTrait> Unstructured<T> {
+    /// Resolve a numeric index to a position within a sequence of the given length,
+    /// negative indices count back from the end
+    pub(crate) fn seq_position(&self, len: usize) -> Option<usize> {
+        match self {
+            Unstructured::<T>::Number(n) if self.is_negative() => {
+                let back = usize::try_from(i128::from(n).unsigned_abs()).ok()?;
+                len.checked_sub(back)
+            }
+            _ => self.as_usize(),
+        }
+    }
+
+    fn is_negative(&self) -> bool {
+        matches!(self, Unstructured::<T>::Number(n) if n.is_signed() && i128::from(n) < 0)
+    }
+}
+
 macro_rules! impl_index {
     ($( $type:ty ),*) => {
 
@@ -107,7 +120,7 @@ macro_rules! impl_index {
 
 impl_index!(str, String, usize, u128, u64, u32, u16, u8, isize, i128, i64, i32, i16, i8, f64, f32);
 
-impl<'a, T: ?Sized, Q: UnstructuredDataTrait> Index<Q> for &'a T
+impl<T: ?Sized, Q: UnstructuredDataTrait> Index<Q> for &T
 where
     T: Index<Q>,
 {
@@ -128,9 +141,12 @@ where
 {
     type Output = Self;
 
+    // Indexing into an `Err` gives the `Err`, so errors carry through a chain of indexing
     fn index(&self, index: I) -> &Self {
-        // static NULL: Unstructured<T> = Unstructured::<T>::Null;
-        index.index_into(self).unwrap_or(&Unstructured::<T>::Null)
+        match self {
+            Self::Err(_) => self,
+            _ => index.index_into(self).unwrap_or_else(|| Self::missing_ref()),
+        }
     }
 }
 
@@ -142,3 +158,50 @@ where
         index.index_or_insert(self)
     }
 }
+
+impl<T: UnstructuredDataTrait> Unstructured<T> {
+    /// The value indexing returns for a key or index that doesn't exist, `Unassigned` so it can
+    /// be told apart from a present `Null`
+    pub const MISSING: Self = Self::Unassigned;
+
+    /// A reference to [`MISSING`](Unstructured::MISSING) for any lifetime. `Unassigned` has
+    /// nothing to drop, so the constant is promoted to a static for every `T` and the
+    /// reference never points at a temporary.
+    ///
+    /// ```
+    /// use unstructured::Document;
+    ///
+    /// let doc = Document::Null;
+    /// assert_eq!(&doc["missing"], Document::missing_ref());
+    /// assert!(Document::missing_ref().is_missing());
+    /// ```
+    pub fn missing_ref<'a>() -> &'a Self {
+        &Self::MISSING
+    }
+
+    /// Get the value for a map key or sequence index. A missing value is None, where indexing
+    /// returns `Unassigned`.
+    ///
+    /// ```
+    /// use unstructured::Document;
+    ///
+    /// let doc: Document = serde_json::from_str(r#"{"a": null, "b": [1, 2]}"#).unwrap();
+    /// assert_eq!(doc.get("a"), Some(&Document::Null));
+    /// assert_eq!(doc.get("c"), None);
+    /// assert_eq!(doc["c"], Document::Unassigned);
+    /// assert_eq!(doc["b"].get(-1), Some(&Document::from(2)));
+    /// ```
+    pub fn get<I: Index<T>>(&self, index: I) -> Option<&Self> {
+        index.index_into(self)
+    }
+
+    /// Get a mutable reference to the value for a map key or sequence index, if it exists
+    pub fn get_mut<I: Index<T>>(&mut self, index: I) -> Option<&mut Self> {
+        index.index_into_mut(self)
+    }
+
+    /// Whether a value exists for a map key or sequence index
+    pub fn contains_key<I: Index<T>>(&self, index: I) -> bool {
+        self.get(index).is_some()
+    }
+}
@@ -0,0 +1,315 @@
+use crate::*;
+use std::slice;
+use std::vec;
+
+/// Iterator over the children of a document, see [`Unstructured::iter`]
+pub enum Iter<'a, T: UnstructuredDataTrait> {
+    Seq(slice::Iter<'a, Unstructured<T>>),
+    Map(MapValues<'a, T>),
+    Empty,
+}
+
+impl<'a, T: UnstructuredDataTrait> Iterator for Iter<'a, T> {
+    type Item = &'a Unstructured<T>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self {
+            Iter::Seq(i) => i.next(),
+            Iter::Map(i) => i.next(),
+            Iter::Empty => None,
+        }
+    }
+}
+
+/// Mutable iterator over the children of a document, see [`Unstructured::iter_mut`]
+pub enum IterMut<'a, T: UnstructuredDataTrait> {
+    Seq(slice::IterMut<'a, Unstructured<T>>),
+    Map(MapValuesMut<'a, T>),
+    Empty,
+}
+
+impl<'a, T: UnstructuredDataTrait> Iterator for IterMut<'a, T> {
+    type Item = &'a mut Unstructured<T>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self {
+            IterMut::Seq(i) => i.next(),
+            IterMut::Map(i) => i.next(),
+            IterMut::Empty => None,
+        }
+    }
+}
+
+/// Owning iterator over the children of a document, see [`Unstructured::into_iter`]
+pub enum IntoIter<T: UnstructuredDataTrait> {
+    Seq(vec::IntoIter<Unstructured<T>>),
+    Map(MapIntoValues<T>),
+    Empty,
+}
+
+impl<T: UnstructuredDataTrait> Iterator for IntoIter<T> {
+    type Item = Unstructured<T>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self {
+            IntoIter::Seq(i) => i.next(),
+            IntoIter::Map(i) => i.next(),
+            IntoIter::Empty => None,
+        }
+    }
+}
+
+/// Iterator over the keys of a map, see [`Unstructured::keys`]
+pub enum Keys<'a, T: UnstructuredDataTrait> {
+    Map(MapKeys<'a, T>),
+    Empty,
+}
+
+impl<'a, T: UnstructuredDataTrait> Iterator for Keys<'a, T> {
+    type Item = &'a Unstructured<T>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self {
+            Keys::Map(i) => i.next(),
+            Keys::Empty => None,
+        }
+    }
+}
+
+/// Depth-first iterator over every node of a document, see [`Unstructured::walk`]
+pub struct Walk<'a, T: UnstructuredDataTrait> {
+    stack: Vec<(Path, &'a Unstructured<T>)>,
+}
+
+impl<'a, T: UnstructuredDataTrait> Iterator for Walk<'a, T> {
+    type Item = (Path, &'a Unstructured<T>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let (path, node) = self.stack.pop()?;
+        match node {
+            Unstructured::<T>::Seq(seq) => {
+                for (i, child) in seq.iter().enumerate().rev() {
+                    let mut child_path = path.clone();
+                    child_path.push(i);
+                    self.stack.push((child_path, child));
+                }
+            }
+            Unstructured::<T>::Map(map) => {
+                for (key, child) in map.iter().rev() {
+                    let mut child_path = path.clone();
+                    child_path.push(Segment::from_key(key));
+                    self.stack.push((child_path, child));
+                }
+            }
+            Unstructured::<T>::Option(Some(inner)) | Unstructured::<T>::Newtype(inner) => {
+                self.stack.push((path.clone(), inner));
+            }
+            _ => {}
+        }
+        Some((path, node))
+    }
+}
+
+/// Depth-first iterator over every node of a document in the same order as [`Walk`], without
+/// building the path of each one
+#[cfg(feature = "selector")]
+pub(crate) struct Nodes<'a, T: UnstructuredDataTrait> {
+    stack: Vec<&'a Unstructured<T>>,
+}
+
+#[cfg(feature = "selector")]
+impl<'a, T: UnstructuredDataTrait> Iterator for Nodes<'a, T> {
+    type Item = &'a Unstructured<T>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let node = self.stack.pop()?;
+        match node {
+            Unstructured::<T>::Seq(seq) => self.stack.extend(seq.iter().rev()),
+            Unstructured::<T>::Map(map) => self.stack.extend(map.iter().rev().map(|(_, v)| v)),
+            Unstructured::<T>::Option(Some(inner)) | Unstructured::<T>::Newtype(inner) => {
+                self.stack.push(inner);
+            }
+            _ => {}
+        }
+        Some(node)
+    }
+}
+
+impl<T: UnstructuredDataTrait> Unstructured<T> {
+    /// Iterate over the elements of a sequence or the values of a map.
+    /// Any other document yields nothing.
+    pub fn iter(&self) -> Iter<'_, T> {
+        match self {
+            Self::Seq(seq) => Iter::Seq(seq.iter()),
+            Self::Map(map) => Iter::Map(map.values()),
+            _ => Iter::Empty,
+        }
+    }
+
+    /// Mutably iterate over the elements of a sequence or the values of a map.
+    /// Any other document yields nothing.
+    pub fn iter_mut(&mut self) -> IterMut<'_, T> {
+        match self {
+            Self::Seq(seq) => IterMut::Seq(make_mut(seq).iter_mut()),
+            Self::Map(map) => IterMut::Map(make_mut(map).values_mut()),
+            _ => IterMut::Empty,
+        }
+    }
+
+    /// Iterate over the keys of a map. Any other document yields nothing.
+    pub fn keys(&self) -> Keys<'_, T> {
+        match self {
+            Self::Map(map) => Keys::Map(map.keys()),
+            _ => Keys::Empty,
+        }
+    }
+
+    /// Iterate over the values of a map. Any other document yields nothing.
+    pub fn values(&self) -> Iter<'_, T> {
+        match self {
+            Self::Map(map) => Iter::Map(map.values()),
+            _ => Iter::Empty,
+        }
+    }
+
+    /// Mutably iterate over the values of a map. Any other document yields nothing.
+    pub fn values_mut(&mut self) -> IterMut<'_, T> {
+        match self {
+            Self::Map(map) => IterMut::Map(make_mut(map).values_mut()),
+            _ => IterMut::Empty,
+        }
+    }
+
+    /// Consume the document, iterating over the entries of a map.
+    /// Any other document yields nothing.
+    pub fn into_map_iter(self) -> MapIntoIter<T> {
+        match self {
+            Self::Map(map) => unshare(map).into_iter(),
+            _ => Mapping::new().into_iter(),
+        }
+    }
+
+    /// Consume the document, iterating over the elements of a sequence.
+    /// Any other document yields nothing.
+    pub fn into_seq_iter(self) -> vec::IntoIter<Self> {
+        match self {
+            Self::Seq(seq) => unshare(seq).into_iter(),
+            _ => vec![].into_iter(),
+        }
+    }
+
+    /// Take all entries out of a map, leaving it empty.
+    /// Any other document is left untouched and yields nothing.
+    ///
+    /// ```
+    /// use unstructured::Document;
+    ///
+    /// let mut doc: Document = serde_json::from_str(r#"{"a": 1, "b": 2}"#).unwrap();
+    /// let keys: Vec<Document> = doc.drain_map().map(|(k, _)| k).collect();
+    /// assert_eq!(keys, vec![Document::from("a"), "b".into()]);
+    /// assert_eq!(doc, Document::Map(Default::default()));
+    /// ```
+    pub fn drain_map(&mut self) -> MapIntoIter<T> {
+        match self {
+            Self::Map(map) => unshare(std::mem::take(map)).into_iter(),
+            _ => Mapping::new().into_iter(),
+        }
+    }
+
+    /// Take all elements out of a sequence, leaving it empty.
+    /// Any other document is left untouched and yields nothing.
+    pub fn drain_seq(&mut self) -> vec::IntoIter<Self> {
+        match self {
+            Self::Seq(seq) => unshare(std::mem::take(seq)).into_iter(),
+            _ => vec![].into_iter(),
+        }
+    }
+
+    /// Walk every node in the document depth-first, starting with the document itself.
+    /// Each node is yielded along with its path from the root. The contents of `Option`
+    /// and `Newtype` wrappers are yielded after the wrapper, under the same path.
+    ///
+    /// ```
+    /// use unstructured::{Document, Path};
+    ///
+    /// let doc: Document = serde_json::from_str(r#"{"a": [1, 2], "b": true}"#).unwrap();
+    /// let nodes: Vec<_> = doc.walk().collect();
+    /// assert_eq!(nodes.len(), 5);
+    /// assert_eq!(nodes[3].0, Path::from(vec!["a".into(), 1.into()]));
+    /// assert_eq!(*nodes[3].1, 2);
+    /// ```
+    pub fn walk(&self) -> Walk<'_, T> {
+        Walk {
+            stack: vec![(Path::new(), self)],
+        }
+    }
+
+    /// Every node in the document in the same order as [`walk`](Unstructured::walk), for
+    /// searches that don't need paths, which get longer the deeper a document is
+    #[cfg(feature = "selector")]
+    pub(crate) fn nodes(&self) -> Nodes<'_, T> {
+        Nodes { stack: vec![self] }
+    }
+
+    /// Search the document depth-first for values matching `f`, which is given the key or index
+    /// each value is under (None for the document itself) and the value.
+    ///
+    /// ```
+    /// use unstructured::{Document, Segment};
+    ///
+    /// let doc: Document = serde_json::from_str(r#"{"a": [1, 20], "b": {"c": 30}}"#).unwrap();
+    /// let found = doc.find_all(|_, val| matches!(val.as_usize(), Some(n) if n > 10));
+    /// let paths: Vec<String> = found.iter().map(|(path, _)| path.to_string()).collect();
+    /// assert_eq!(paths, vec!["/a/1", "/b/c"]);
+    /// ```
+    pub fn find_all<F>(&self, mut f: F) -> Vec<(Path, &Self)>
+    where
+        F: FnMut(Option<&Segment>, &Self) -> bool,
+    {
+        self.walk().filter(|(path, val)| f(path.last(), val)).collect()
+    }
+
+    /// Search the document depth-first for map entries with the given key, e.g. to find every
+    /// `password` field. A value wrapped in an `Option` or `Newtype` is found once, as the
+    /// wrapper.
+    pub fn find_key(&self, key: &str) -> Vec<(Path, &Self)> {
+        let mut found =
+            self.find_all(|segment, _| matches!(segment, Some(Segment::Key(k)) if k == key));
+        // The contents of a wrapper are walked right after it with the same path
+        found.dedup_by(|(path, _), (previous, _)| path == previous);
+        found
+    }
+}
+
+impl<T: UnstructuredDataTrait> IntoIterator for Unstructured<T> {
+    type Item = Unstructured<T>;
+    type IntoIter = IntoIter<T>;
+
+    /// Consume the document, iterating over the elements of a sequence or the values of a map.
+    /// Any other document yields nothing.
+    fn into_iter(self) -> Self::IntoIter {
+        match self {
+            Self::Seq(seq) => IntoIter::Seq(unshare(seq).into_iter()),
+            Self::Map(map) => IntoIter::Map(unshare(map).into_values()),
+            _ => IntoIter::Empty,
+        }
+    }
+}
+
+impl<'a, T: UnstructuredDataTrait> IntoIterator for &'a Unstructured<T> {
+    type Item = &'a Unstructured<T>;
+    type IntoIter = Iter<'a, T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl<'a, T: UnstructuredDataTrait> IntoIterator for &'a mut Unstructured<T> {
+    type Item = &'a mut Unstructured<T>;
+    type IntoIter = IterMut<'a, T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter_mut()
+    }
+}
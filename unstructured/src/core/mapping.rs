@@ -0,0 +1,402 @@
+use crate::*;
+#[cfg(feature = "preserve_order")]
+use indexmap::map as entries;
+use std::cmp::Ordering;
+#[cfg(not(feature = "preserve_order"))]
+use std::collections::btree_map as entries;
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::iter::FromIterator;
+
+#[cfg(not(feature = "preserve_order"))]
+type Entries<T> = std::collections::BTreeMap<Unstructured<T>, Unstructured<T>>;
+#[cfg(feature = "preserve_order")]
+type Entries<T> = indexmap::IndexMap<Unstructured<T>, Unstructured<T>>;
+
+/// The entries of a map document. Keys are kept sorted, or in insertion order with the
+/// `preserve_order` feature. The API is the same either way, so enabling the feature somewhere in
+/// a build never breaks code elsewhere.
+///
+/// ```
+/// use unstructured::{Document, Mapping};
+///
+/// let mut map = Mapping::new();
+/// map.insert("b".into(), 1.into());
+/// map.insert("a".into(), 2.into());
+/// assert_eq!(map.remove(&"b".into()), Some(Document::from(1)));
+/// assert_eq!(map.keys().collect::<Vec<_>>(), vec![&Document::from("a")]);
+/// ```
+pub struct Mapping<T: UnstructuredDataTrait> {
+    entries: Entries<T>,
+}
+
+impl<T: UnstructuredDataTrait> Mapping<T> {
+    pub fn new() -> Self {
+        Mapping {
+            entries: Entries::new(),
+        }
+    }
+
+    /// Create an empty map with room for `capacity` entries, where the map supports it
+    #[cfg(not(feature = "preserve_order"))]
+    pub fn with_capacity(capacity: usize) -> Self {
+        let _ = capacity;
+        Self::new()
+    }
+
+    /// Create an empty map with room for `capacity` entries, where the map supports it
+    #[cfg(feature = "preserve_order")]
+    pub fn with_capacity(capacity: usize) -> Self {
+        Mapping {
+            entries: Entries::with_capacity(capacity),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear()
+    }
+
+    pub fn get(&self, key: &Unstructured<T>) -> Option<&Unstructured<T>> {
+        self.entries.get(key)
+    }
+
+    pub fn get_mut(&mut self, key: &Unstructured<T>) -> Option<&mut Unstructured<T>> {
+        self.entries.get_mut(key)
+    }
+
+    pub fn contains_key(&self, key: &Unstructured<T>) -> bool {
+        self.entries.contains_key(key)
+    }
+
+    /// Insert a value, returning the one it replaced. A replaced key keeps its position.
+    pub fn insert(
+        &mut self,
+        key: Unstructured<T>,
+        value: Unstructured<T>,
+    ) -> Option<Unstructured<T>> {
+        self.entries.insert(key, value)
+    }
+
+    /// The value for `key`, inserting the result of `default` first if it is missing
+    pub fn get_or_insert_with<F>(
+        &mut self,
+        key: Unstructured<T>,
+        default: F,
+    ) -> &mut Unstructured<T>
+    where
+        F: FnOnce() -> Unstructured<T>,
+    {
+        self.entries.entry(key).or_insert_with(default)
+    }
+
+    /// Remove a key, keeping the order of the remaining entries
+    #[cfg(not(feature = "preserve_order"))]
+    pub fn remove(&mut self, key: &Unstructured<T>) -> Option<Unstructured<T>> {
+        self.entries.remove(key)
+    }
+
+    /// Remove a key, keeping the order of the remaining entries
+    #[cfg(feature = "preserve_order")]
+    pub fn remove(&mut self, key: &Unstructured<T>) -> Option<Unstructured<T>> {
+        self.entries.shift_remove(key)
+    }
+
+    /// Keep only the entries for which `keep` returns true
+    pub fn retain<F>(&mut self, keep: F)
+    where
+        F: FnMut(&Unstructured<T>, &mut Unstructured<T>) -> bool,
+    {
+        self.entries.retain(keep)
+    }
+
+    pub fn iter(&self) -> MapIter<'_, T> {
+        MapIter(self.entries.iter())
+    }
+
+    pub fn iter_mut(&mut self) -> MapIterMut<'_, T> {
+        MapIterMut(self.entries.iter_mut())
+    }
+
+    pub fn keys(&self) -> MapKeys<'_, T> {
+        MapKeys(self.entries.keys())
+    }
+
+    pub fn values(&self) -> MapValues<'_, T> {
+        MapValues(self.entries.values())
+    }
+
+    pub fn values_mut(&mut self) -> MapValuesMut<'_, T> {
+        MapValuesMut(self.entries.values_mut())
+    }
+
+    pub fn into_values(self) -> MapIntoValues<T> {
+        MapIntoValues(self.entries.into_values())
+    }
+
+    /// Remove the first entry
+    #[cfg(not(feature = "preserve_order"))]
+    #[cfg_attr(not(feature = "selector"), allow(dead_code))]
+    pub(crate) fn pop_front(&mut self) -> Option<(Unstructured<T>, Unstructured<T>)> {
+        self.entries.pop_first()
+    }
+
+    /// Remove the first entry
+    #[cfg(feature = "preserve_order")]
+    #[cfg_attr(not(feature = "selector"), allow(dead_code))]
+    pub(crate) fn pop_front(&mut self) -> Option<(Unstructured<T>, Unstructured<T>)> {
+        self.entries.shift_remove_index(0)
+    }
+
+    /// Queue up the values that must be equal for the maps to be equal, false if the maps
+    /// already differ
+    #[cfg(not(feature = "preserve_order"))]
+    pub(crate) fn pair_entries<'a>(
+        &'a self,
+        other: &'a Self,
+        pairs: &mut Vec<(&'a Unstructured<T>, &'a Unstructured<T>)>,
+    ) -> bool {
+        for ((k0, v0), (k1, v1)) in self.iter().zip(other.iter()) {
+            pairs.push((k0, k1));
+            pairs.push((v0, v1));
+        }
+        self.len() == other.len()
+    }
+
+    /// Queue up the values that must be equal for the maps to be equal, false if the maps
+    /// already differ
+    #[cfg(feature = "preserve_order")]
+    pub(crate) fn pair_entries<'a>(
+        &'a self,
+        other: &'a Self,
+        pairs: &mut Vec<(&'a Unstructured<T>, &'a Unstructured<T>)>,
+    ) -> bool {
+        self.len() == other.len()
+            && self.iter().all(|(k, v0)| match other.get(k) {
+                Some(v1) => {
+                    pairs.push((v0, v1));
+                    true
+                }
+                None => false,
+            })
+    }
+
+    /// Sort the entries by key, maps without `preserve_order` are always sorted
+    #[cfg(not(feature = "preserve_order"))]
+    pub(crate) fn sort_entries(&mut self) {}
+
+    /// Sort the entries by key, maps without `preserve_order` are always sorted
+    #[cfg(feature = "preserve_order")]
+    pub(crate) fn sort_entries(&mut self) {
+        self.entries.sort_keys()
+    }
+
+    /// The entries in key order, which is how maps are compared and hashed so that maps with the
+    /// same entries in a different order are equal
+    #[cfg(not(feature = "preserve_order"))]
+    pub(crate) fn sorted_entries(&self) -> Vec<(&Unstructured<T>, &Unstructured<T>)> {
+        self.iter().collect()
+    }
+
+    /// The entries in key order, which is how maps are compared and hashed so that maps with the
+    /// same entries in a different order are equal. Keys are unique, so only they are compared.
+    #[cfg(feature = "preserve_order")]
+    pub(crate) fn sorted_entries(&self) -> Vec<(&Unstructured<T>, &Unstructured<T>)> {
+        let mut entries: Vec<_> = self.iter().collect();
+        entries.sort_by(|a, b| a.0.cmp(b.0));
+        entries
+    }
+
+    /// Estimated bytes the map allocates for its entries, not counting what the keys and values
+    /// allocate themselves. B-tree nodes hold up to 11 entries plus a parent pointer and
+    /// lengths, and are around two thirds full on average.
+    #[cfg(not(feature = "preserve_order"))]
+    pub(crate) fn heap_size(&self) -> usize {
+        let entry = 2 * std::mem::size_of::<Unstructured<T>>();
+        self.len() * (entry + std::mem::size_of::<usize>()) * 3 / 2
+    }
+
+    /// Estimated bytes the map allocates for its entries, not counting what the keys and values
+    /// allocate themselves. Entries are stored in a vector alongside their hash, and the hash
+    /// table holds an index and a control byte per slot, with slots at most 7/8 full.
+    #[cfg(feature = "preserve_order")]
+    pub(crate) fn heap_size(&self) -> usize {
+        let entry = 2 * std::mem::size_of::<Unstructured<T>>() + std::mem::size_of::<u64>();
+        let slot = std::mem::size_of::<usize>() + 1;
+        self.entries.capacity() * entry + self.entries.capacity() * slot * 8 / 7
+    }
+}
+
+impl<T: UnstructuredDataTrait> Default for Mapping<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: UnstructuredDataTrait> Clone for Mapping<T> {
+    fn clone(&self) -> Self {
+        Mapping {
+            entries: self.entries.clone(),
+        }
+    }
+}
+
+impl<T: UnstructuredDataTrait> fmt::Debug for Mapping<T>
+where
+    Unstructured<T>: fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_map().entries(self.iter()).finish()
+    }
+}
+
+impl<T: UnstructuredDataTrait> serde::Serialize for Mapping<T> {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_map(self.iter())
+    }
+}
+
+/// Maps with the same entries are equal whatever order they were inserted in
+impl<T: UnstructuredDataTrait> PartialEq for Mapping<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.len() == other.len() && self.iter().all(|(k, v)| other.get(k) == Some(v))
+    }
+}
+
+impl<T: UnstructuredDataTrait> Eq for Mapping<T> {}
+
+impl<T: UnstructuredDataTrait> PartialOrd for Mapping<T> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<T: UnstructuredDataTrait> Ord for Mapping<T> {
+    #[cfg(not(feature = "preserve_order"))]
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.entries.cmp(&other.entries)
+    }
+
+    #[cfg(feature = "preserve_order")]
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.sorted_entries().cmp(&other.sorted_entries())
+    }
+}
+
+impl<T: UnstructuredDataTrait> Hash for Mapping<T> {
+    #[cfg(not(feature = "preserve_order"))]
+    fn hash<H: Hasher>(&self, hasher: &mut H) {
+        self.entries.hash(hasher)
+    }
+
+    #[cfg(feature = "preserve_order")]
+    fn hash<H: Hasher>(&self, hasher: &mut H) {
+        self.sorted_entries().hash(hasher)
+    }
+}
+
+impl<T: UnstructuredDataTrait> FromIterator<(Unstructured<T>, Unstructured<T>)> for Mapping<T> {
+    fn from_iter<I: IntoIterator<Item = (Unstructured<T>, Unstructured<T>)>>(iter: I) -> Self {
+        Mapping {
+            entries: iter.into_iter().collect(),
+        }
+    }
+}
+
+impl<T: UnstructuredDataTrait> Extend<(Unstructured<T>, Unstructured<T>)> for Mapping<T> {
+    fn extend<I: IntoIterator<Item = (Unstructured<T>, Unstructured<T>)>>(&mut self, iter: I) {
+        self.entries.extend(iter)
+    }
+}
+
+impl<T: UnstructuredDataTrait> IntoIterator for Mapping<T> {
+    type Item = (Unstructured<T>, Unstructured<T>);
+    type IntoIter = MapIntoIter<T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        MapIntoIter(self.entries.into_iter())
+    }
+}
+
+impl<'a, T: UnstructuredDataTrait> IntoIterator for &'a Mapping<T> {
+    type Item = (&'a Unstructured<T>, &'a Unstructured<T>);
+    type IntoIter = MapIter<'a, T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl<'a, T: UnstructuredDataTrait> IntoIterator for &'a mut Mapping<T> {
+    type Item = (&'a Unstructured<T>, &'a mut Unstructured<T>);
+    type IntoIter = MapIterMut<'a, T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter_mut()
+    }
+}
+
+macro_rules! map_iterator {
+    ($(#[$attr:meta])* $name:ident<$($lt:lifetime,)? T>($inner:ident) -> $item:ty) => {
+        $(#[$attr])*
+        pub struct $name<$($lt,)? T: UnstructuredDataTrait>(
+            entries::$inner<$($lt,)? Unstructured<T>, Unstructured<T>>,
+        );
+
+        impl<$($lt,)? T: UnstructuredDataTrait> Iterator for $name<$($lt,)? T> {
+            type Item = $item;
+
+            fn next(&mut self) -> Option<Self::Item> {
+                self.0.next()
+            }
+
+            fn size_hint(&self) -> (usize, Option<usize>) {
+                self.0.size_hint()
+            }
+        }
+
+        impl<$($lt,)? T: UnstructuredDataTrait> DoubleEndedIterator for $name<$($lt,)? T> {
+            fn next_back(&mut self) -> Option<Self::Item> {
+                self.0.next_back()
+            }
+        }
+
+        impl<$($lt,)? T: UnstructuredDataTrait> ExactSizeIterator for $name<$($lt,)? T> {}
+    };
+}
+
+map_iterator!(
+    /// Iterator over the entries of a [`Mapping`]
+    MapIter<'a, T>(Iter) -> (&'a Unstructured<T>, &'a Unstructured<T>)
+);
+map_iterator!(
+    /// Mutable iterator over the entries of a [`Mapping`]
+    MapIterMut<'a, T>(IterMut) -> (&'a Unstructured<T>, &'a mut Unstructured<T>)
+);
+map_iterator!(
+    /// Owning iterator over the entries of a [`Mapping`]
+    MapIntoIter<T>(IntoIter) -> (Unstructured<T>, Unstructured<T>)
+);
+map_iterator!(
+    /// Iterator over the keys of a [`Mapping`]
+    MapKeys<'a, T>(Keys) -> &'a Unstructured<T>
+);
+map_iterator!(
+    /// Iterator over the values of a [`Mapping`]
+    MapValues<'a, T>(Values) -> &'a Unstructured<T>
+);
+map_iterator!(
+    /// Mutable iterator over the values of a [`Mapping`]
+    MapValuesMut<'a, T>(ValuesMut) -> &'a mut Unstructured<T>
+);
+map_iterator!(
+    /// Owning iterator over the values of a [`Mapping`]
+    MapIntoValues<T>(IntoValues) -> Unstructured<T>
+);
@@ -0,0 +1,390 @@
+use crate::*;
+use std::collections::BTreeMap;
+use std::fmt;
+
+const PATCH_DIRECTIVE: &str = "$patch";
+
+/// A value that was overwritten with a different value during [`Unstructured::merge_checked`]
+#[derive(Clone, PartialEq)]
+pub struct Conflict<T: UnstructuredDataTrait = UnstructuredType> {
+    pub path: Path,
+    pub old: Unstructured<T>,
+    pub new: Unstructured<T>,
+}
+
+impl<T: UnstructuredDataTrait> fmt::Debug for Conflict<T>
+where
+    Unstructured<T>: fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Conflict")
+            .field("path", &self.path)
+            .field("old", &self.old)
+            .field("new", &self.new)
+            .finish()
+    }
+}
+
+/// The cause of an [`Error::Merge`] from [`Unstructured::try_merge_with`], when the other
+/// document is nested deeper than the allowed maximum
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MergeError {
+    /// Path of the first node found beyond the maximum depth
+    pub path: Path,
+    pub max_depth: usize,
+}
+
+impl fmt::Display for MergeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "Document exceeds the maximum merge depth of {} at {}",
+            self.max_depth, self.path
+        )
+    }
+}
+
+impl std::error::Error for MergeError {}
+
+/// Controls how sequences are combined by [`Unstructured::merge_with`]. Maps are always merged
+/// recursively and any other values are overwritten.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum MergeStrategy<'a> {
+    /// Append the elements of the other sequence, the same as [`Unstructured::merge`]
+    #[default]
+    Append,
+    /// Replace the existing sequence with the other one
+    Replace,
+    /// Deep merge map elements that have the same value for the given key and append the rest.
+    /// Elements missing the key are always appended.
+    SeqMergeBy(&'a str),
+    /// Kubernetes style strategic merge patch. Each pair is a field name and the key used to
+    /// match the elements of sequences under that field (the `patchMergeKey`), sequences under
+    /// any other field are replaced. `null` values delete keys, and `$patch: replace` or
+    /// `$patch: delete` directives replace or remove the map or sequence element they are in.
+    Strategic(&'a [(&'a str, &'a str)]),
+}
+
+impl<T: UnstructuredDataTrait> Unstructured<T> {
+    /// Merge another document into this one, combining sequences according to `strategy`.
+    ///
+    /// ```
+    /// use unstructured::{Document, MergeStrategy};
+    ///
+    /// let mut doc: Document =
+    ///     serde_json::from_str(r#"[{"name": "a", "val": 1}, {"name": "b", "val": 2}]"#).unwrap();
+    /// let other: Document =
+    ///     serde_json::from_str(r#"[{"name": "b", "val": 3}, {"name": "c", "val": 4}]"#).unwrap();
+    /// doc.merge_with(other, MergeStrategy::SeqMergeBy("name"));
+    /// assert_eq!(doc[1]["val"], 3);
+    /// assert_eq!(doc[2]["name"], "c");
+    /// ```
+    pub fn merge_with(&mut self, other: Self, strategy: MergeStrategy) {
+        self.observe_merge(|doc| doc.merge_with_imp(other, strategy))
+    }
+
+    /// Run one of the merge operations on this document, reporting it to the hooks
+    pub(crate) fn observe_merge<R>(&mut self, merge: impl FnOnce(&mut Self) -> R) -> R {
+        let observation = Observation::start(Operation::Merge);
+        let result = merge(self);
+        observation.end(Some(self));
+        result
+    }
+
+    fn merge_with_imp(&mut self, other: Self, strategy: MergeStrategy) {
+        if let MergeStrategy::Strategic(merge_keys) = strategy {
+            return self.merge_strategic(other, merge_keys, None);
+        }
+        match (&mut *self, other) {
+            (Self::Map(map), Self::Map(other_map)) => {
+                let map = make_mut(map);
+                for (key, val) in unshare(other_map).into_iter() {
+                    if let Some(loc) = map.get_mut(&key) {
+                        loc.merge_with_imp(val, strategy);
+                    } else {
+                        map.insert(key, val);
+                    }
+                }
+            }
+            (Self::Seq(seq), Self::Seq(other_seq)) => match strategy {
+                MergeStrategy::Append => make_mut(seq).extend(unshare(other_seq)),
+                MergeStrategy::Replace | MergeStrategy::Strategic(_) => *seq = other_seq,
+                MergeStrategy::SeqMergeBy(key) => {
+                    let key = Self::String(key.to_string());
+                    let seq = make_mut(seq);
+                    // Where the first element with each key is, so matching is a lookup
+                    let mut positions = BTreeMap::new();
+                    for (i, existing) in seq.iter().enumerate() {
+                        if let Some(id) = existing.merge_key(&key) {
+                            positions.entry(id.clone()).or_insert(i);
+                        }
+                    }
+                    for item in unshare(other_seq).into_iter() {
+                        let id = item.merge_key(&key);
+                        match id.and_then(|id| positions.get(id)) {
+                            Some(&i) => seq[i].merge_with_imp(item, strategy),
+                            None => {
+                                if let Some(id) = id {
+                                    positions.insert(id.clone(), seq.len());
+                                }
+                                seq.push(item);
+                            }
+                        }
+                    }
+                }
+            },
+            (Self::Seq(seq), other) if strategy != MergeStrategy::Replace => {
+                make_mut(seq).push(other)
+            }
+            (_, other) => *self = other,
+        }
+    }
+
+    /// Insert the entries of another map into this one, replacing the values of existing keys
+    /// rather than merging them. Nothing happens unless both documents are maps.
+    ///
+    /// ```
+    /// use unstructured::Document;
+    ///
+    /// let mut doc: Document = serde_json::from_str(r#"{"a": {"b": 1}, "c": 2}"#).unwrap();
+    /// let other: Document = serde_json::from_str(r#"{"a": {"d": 3}}"#).unwrap();
+    /// doc.merge_keys(other);
+    /// assert_eq!(doc.get("a").unwrap().get("b"), None);
+    /// assert_eq!(doc["c"], 2);
+    /// ```
+    pub fn merge_keys(&mut self, other: Self) {
+        if let (Self::Map(map), Self::Map(other_map)) = (&mut *self, other) {
+            make_mut(map).extend(unshare(other_map));
+        }
+    }
+
+    /// Merge another document into this one as [`merge_with`](Unstructured::merge_with) does,
+    /// but first check that the other document is nested no deeper than `max_depth` maps and
+    /// sequences. Merging recurses as deep as the other document, so this protects against stack
+    /// exhaustion when merging untrusted input. Nothing is merged if the limit is exceeded.
+    ///
+    /// ```
+    /// use unstructured::{Document, Error, MergeStrategy};
+    ///
+    /// let mut doc: Document = serde_json::from_str(r#"{"a": {"b": 1}}"#).unwrap();
+    /// let other: Document = serde_json::from_str(r#"{"a": {"b": {"c": [2]}}}"#).unwrap();
+    /// let err = doc.try_merge_with(other.clone(), MergeStrategy::Append, 3).unwrap_err();
+    /// assert!(matches!(err, Error::Merge(e) if e.path.to_string() == "/a/b/c/0"));
+    /// assert!(doc.try_merge_with(other, MergeStrategy::Append, 4).is_ok());
+    /// assert_eq!(doc["a"]["b"]["c"][0], 2);
+    /// ```
+    pub fn try_merge_with(
+        &mut self,
+        other: Self,
+        strategy: MergeStrategy,
+        max_depth: usize,
+    ) -> Result<(), Error> {
+        if let Some((path, _)) = other.walk().find(|(path, _)| path.len() > max_depth) {
+            return Err(Error::Merge(MergeError { path, max_depth }));
+        }
+        self.merge_with(other, strategy);
+        Ok(())
+    }
+
+    fn merge_strategic(&mut self, patch: Self, merge_keys: &[(&str, &str)], field: Option<&Self>) {
+        match (&mut *self, patch) {
+            (Self::Map(map), Self::Map(mut patch_map)) => {
+                match make_mut(&mut patch_map).remove(&Self::from(PATCH_DIRECTIVE)) {
+                    Some(directive) if directive == "replace" => {
+                        *self = Self::Map(patch_map).strip_directives();
+                        return;
+                    }
+                    Some(directive) if directive == "delete" => {
+                        *self = Self::Null;
+                        return;
+                    }
+                    _ => {}
+                }
+                let map = make_mut(map);
+                for (key, val) in unshare(patch_map).into_iter() {
+                    if val.is_null() || val.patch_directive() == Some("delete") {
+                        map.remove(&key);
+                    } else if let Some(loc) = map.get_mut(&key) {
+                        loc.merge_strategic(val, merge_keys, Some(&key));
+                    } else {
+                        map.insert(key, val.strip_directives());
+                    }
+                }
+            }
+            (Self::Seq(seq), Self::Seq(patch_seq)) => {
+                let merge_key = merge_keys
+                    .iter()
+                    .find(|(name, _)| field.is_some_and(|f| f == name))
+                    .map(|(_, key)| Self::from(*key));
+                let replace = patch_seq
+                    .iter()
+                    .any(|item| item.patch_directive() == Some("replace"));
+                let merge_key = match merge_key {
+                    Some(key) if !replace => key,
+                    _ => {
+                        *seq = share(
+                            unshare(patch_seq)
+                                .into_iter()
+                                .filter(|item| item.patch_directive().is_none())
+                                .map(Self::strip_directives)
+                                .collect(),
+                        );
+                        return;
+                    }
+                };
+                let seq = make_mut(seq);
+                for item in unshare(patch_seq).into_iter() {
+                    let id = match item.merge_key(&merge_key) {
+                        Some(id) => id.clone(),
+                        None if item.patch_directive().is_some() => continue,
+                        None => {
+                            seq.push(item.strip_directives());
+                            continue;
+                        }
+                    };
+                    if item.patch_directive() == Some("delete") {
+                        seq.retain(|e| e.merge_key(&merge_key) != Some(&id));
+                    } else if let Some(existing) =
+                        seq.iter_mut().find(|e| e.merge_key(&merge_key) == Some(&id))
+                    {
+                        existing.merge_strategic(item, merge_keys, None);
+                    } else {
+                        seq.push(item.strip_directives());
+                    }
+                }
+            }
+            (_, patch) => *self = patch.strip_directives(),
+        }
+    }
+
+    fn patch_directive(&self) -> Option<&str> {
+        match self {
+            Self::Map(map) => match map.get(&Self::from(PATCH_DIRECTIVE)) {
+                Some(Self::String(s)) => Some(s.as_str()),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+
+    /// Remove directives and null values from a patch value that has nothing to merge into
+    fn strip_directives(self) -> Self {
+        match self {
+            Self::Map(map) => Self::Map(share(
+                unshare(map)
+                    .into_iter()
+                    .filter(|(k, v)| *k != PATCH_DIRECTIVE && !v.is_null())
+                    .map(|(k, v)| (k, v.strip_directives()))
+                    .collect(),
+            )),
+            Self::Seq(seq) => Self::Seq(share(
+                unshare(seq)
+                    .into_iter()
+                    .filter(|item| item.patch_directive().is_none())
+                    .map(Self::strip_directives)
+                    .collect(),
+            )),
+            other => other,
+        }
+    }
+
+    fn merge_key(&self, key: &Self) -> Option<&Self> {
+        match self {
+            Self::Map(map) => map.get(key).filter(|v| !matches!(v, Self::Null | Self::Unassigned)),
+            _ => None,
+        }
+    }
+
+    /// Merge another document into this one, letting `resolve` decide the result wherever both
+    /// documents hold a value at the same path. Maps on both sides are merged recursively and
+    /// keys only present in the other document are inserted as-is. For any other pair of values
+    /// (including sequences), `resolve` is called with the path, the existing value and the
+    /// incoming value, and its return value is stored at that path.
+    ///
+    /// ```
+    /// use unstructured::Document;
+    ///
+    /// let mut doc: Document = serde_json::from_str(r#"{"a": {"b": 1, "c": 2}}"#).unwrap();
+    /// let other: Document = serde_json::from_str(r#"{"a": {"b": 10, "d": 4}}"#).unwrap();
+    /// doc.merge_resolve(other, |_path, existing, _incoming| existing.clone());
+    /// assert_eq!(doc["a"]["b"], 1);
+    /// assert_eq!(doc["a"]["d"], 4);
+    /// ```
+    pub fn merge_resolve<F>(&mut self, other: Self, mut resolve: F)
+    where
+        F: FnMut(&Path, &Self, Self) -> Self,
+    {
+        self.observe_merge(|doc| {
+            doc.merge_at(other, &mut Path::new(), &mut |path, current, incoming| {
+                let existing = current.take();
+                *current = resolve(path, &existing, incoming);
+            })
+        })
+    }
+
+    /// Merge another document into this one exactly as [`merge`](Unstructured::merge) does,
+    /// returning every path where an existing scalar value was overwritten with a different one.
+    ///
+    /// ```
+    /// use unstructured::Document;
+    ///
+    /// let mut doc: Document = serde_json::from_str(r#"{"a": 1, "b": [1]}"#).unwrap();
+    /// let other: Document = serde_json::from_str(r#"{"a": 2, "b": [2]}"#).unwrap();
+    /// let conflicts = doc.merge_checked(other);
+    /// assert_eq!(conflicts.len(), 1);
+    /// assert_eq!(conflicts[0].old, 1);
+    /// assert_eq!(conflicts[0].new, 2);
+    /// ```
+    pub fn merge_checked(&mut self, other: Self) -> Vec<Conflict<T>> {
+        let mut conflicts = vec![];
+        self.observe_merge(|doc| {
+            doc.merge_at(other, &mut Path::new(), &mut |path, current, incoming| match current {
+                Self::Seq(seq) => {
+                    if let Self::Seq(other_seq) = incoming {
+                        make_mut(seq).extend(unshare(other_seq));
+                    } else {
+                        make_mut(seq).push(incoming);
+                    }
+                }
+                Self::Map(_) => *current = incoming,
+                _ => {
+                    if *current != incoming {
+                        let old = current.replace(incoming.clone());
+                        conflicts.push(Conflict {
+                            path: path.clone(),
+                            old,
+                            new: incoming,
+                        });
+                    } else {
+                        *current = incoming;
+                    }
+                }
+            })
+        });
+        conflicts
+    }
+
+    pub(crate) fn merge_at(
+        &mut self,
+        other: Self,
+        path: &mut Path,
+        resolve: &mut dyn FnMut(&Path, &mut Self, Self),
+    ) {
+        match (&mut *self, other) {
+            (Self::Map(map), Self::Map(other_map)) => {
+                let map = make_mut(map);
+                for (key, val) in unshare(other_map).into_iter() {
+                    if let Some(loc) = map.get_mut(&key) {
+                        path.push(Segment::from_key(&key));
+                        loc.merge_at(val, path, resolve);
+                        path.pop();
+                    } else {
+                        map.insert(key, val);
+                    }
+                }
+            }
+            (Self::Unassigned, other) => *self = other,
+            (current, other) => resolve(path, current, other),
+        }
+    }
+}
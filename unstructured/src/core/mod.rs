@@ -1,21 +1,64 @@
+mod builder;
+mod bytes;
+mod canonical;
 mod convert;
+mod cursor;
+#[cfg(feature = "chrono")]
+mod datetime;
 pub(crate) mod de;
+mod dedup;
+mod deep;
+mod events;
+mod extras;
 mod from;
-mod index;
+mod hooks;
+pub(crate) mod index;
+mod iter;
+mod mapping;
+mod merge;
+#[cfg(any(feature = "selector", feature = "query", feature = "jmespath"))]
+mod nesting;
+mod path;
+mod pretty;
+mod project;
+mod schema;
 pub(crate) mod ser;
+mod shared;
+mod size;
+mod transform;
 mod cmp;
 
 use std::mem;
-use std::collections::BTreeMap;
 use de::*;
-use ser::*;
 use serde::{Deserialize, Serialize};
 use std::cmp::Ordering;
 use std::fmt;
 use std::hash::{Hash, Hasher};
-use crate::Number;
+use crate::{Error, Number};
 
+pub use builder::DocumentBuilder;
+pub use bytes::*;
 pub use convert::*;
+pub use cursor::*;
+pub use dedup::DedupStats;
+pub use events::*;
+pub use extras::Extras;
+pub use hooks::{set_hooks, ObservabilityHooks, Operation, OperationStats};
+pub(crate) use hooks::{observe, Observation};
+pub use iter::*;
+pub use mapping::{
+    MapIntoIter, MapIntoValues, MapIter, MapIterMut, MapKeys, MapValues, MapValuesMut, Mapping,
+};
+pub use merge::*;
+#[cfg(any(feature = "selector", feature = "query", feature = "jmespath"))]
+pub(crate) use nesting::{nested, Nesting};
+pub use path::*;
+pub use pretty::DocumentFormatter;
+pub use schema::*;
+pub use de::Limits;
+pub use ser::{DedupPolicy, Serializer, SerializerOptions, WithOptions};
+pub use shared::Shared;
+pub(crate) use shared::{make_mut, share, unshare};
 
 
 #[derive(Debug, Clone)]
@@ -24,10 +67,49 @@ pub struct UnstructuredType;
 impl UnstructuredDataTrait for UnstructuredType {
     type ErrorType = UnstructuredError;
     type OtherType = DefaultOther;
+
+    fn error_from_message(message: String) -> Option<UnstructuredError> {
+        Some(UnstructuredError::Message(message))
+    }
 }
 
 pub type Document = Unstructured<UnstructuredType>;
 
+/// A shared `Null` document, for APIs that need to return `&Document` for a missing value
+pub static NULL: Document = Document::Null;
+
+/// A shared `Unassigned` document, for APIs that need to return `&Document`
+pub static UNASSIGNED: Document = Document::Unassigned;
+
+impl Document {
+    /// A shared empty sequence, for APIs that need to return `&Document`
+    ///
+    /// ```
+    /// use unstructured::{Document, UNASSIGNED};
+    ///
+    /// fn tags(doc: &Document) -> &Document {
+    ///     match &doc["tags"] {
+    ///         tags @ Document::Seq(_) => tags,
+    ///         _ => Document::empty_seq_ref(),
+    ///     }
+    /// }
+    ///
+    /// let doc: Document = serde_json::from_str(r#"{"name": "x"}"#).unwrap();
+    /// assert_eq!(tags(&doc), &Document::empty_seq());
+    /// assert_eq!(&doc["missing"], &UNASSIGNED);
+    /// ```
+    pub fn empty_seq_ref() -> &'static Self {
+        static EMPTY_SEQ: std::sync::OnceLock<Document> = std::sync::OnceLock::new();
+        EMPTY_SEQ.get_or_init(Self::empty_seq)
+    }
+
+    /// A shared empty map, for APIs that need to return `&Document`
+    pub fn empty_map_ref() -> &'static Self {
+        static EMPTY_MAP: std::sync::OnceLock<Document> = std::sync::OnceLock::new();
+        EMPTY_MAP.get_or_init(Self::empty_map)
+    }
+}
+
 #[derive(Clone, Debug)]
 pub struct DefaultOther;
 
@@ -41,27 +123,108 @@ impl std::fmt::Display for DefaultOther {
 pub enum UnstructuredError {
     Serializer,
     Deserializer,
+    /// An operation on a document failed, such as dividing by zero
+    Message(String),
 }
 
 impl std::error::Error for UnstructuredError {}
 
 impl std::fmt::Display for UnstructuredError {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "{:?}", self)
+        match self {
+            UnstructuredError::Message(message) => f.write_str(message),
+            other => write!(f, "{:?}", other),
+        }
     }
 }
 
+/// Customizes an [`Unstructured`] document. [`Document`] uses [`UnstructuredType`], other
+/// implementations can carry their own values in the `Other` variant, such as database specific
+/// types, and use the hooks to control how those values are read, written and displayed.
+///
+/// ```
+/// use std::fmt;
+/// use unstructured::{Unstructured, UnstructuredDataTrait, UnstructuredError};
+///
+/// #[derive(Clone, Debug, PartialEq)]
+/// struct ObjectId(String);
+///
+/// impl fmt::Display for ObjectId {
+///     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+///         write!(f, "ObjectId({})", self.0)
+///     }
+/// }
+///
+/// #[derive(Clone, Debug)]
+/// struct Mongo;
+///
+/// impl UnstructuredDataTrait for Mongo {
+///     type ErrorType = UnstructuredError;
+///     type OtherType = ObjectId;
+///
+///     fn serialize_other<S: serde::Serializer>(id: &ObjectId, s: S) -> Result<S::Ok, S::Error> {
+///         use serde::ser::SerializeMap;
+///         let mut map = s.serialize_map(Some(1))?;
+///         map.serialize_entry("$oid", &id.0)?;
+///         map.end()
+///     }
+///
+///     fn deserialize_other(value: &Unstructured<Self>) -> Option<ObjectId> {
+///         match (value, value.get("$oid")?) {
+///             (Unstructured::Map(map), Unstructured::String(id)) if map.len() == 1 => {
+///                 Some(ObjectId(id.clone()))
+///             }
+///             _ => None,
+///         }
+///     }
+/// }
+///
+/// let json = r#"{"_id":{"$oid":"5f1d"},"n":1}"#;
+/// let doc: Unstructured<Mongo> = serde_json::from_str(json).unwrap();
+/// assert!(matches!(&doc["_id"], Unstructured::Other(ObjectId(id)) if id == "5f1d"));
+/// assert_eq!(doc["_id"].to_string(), "ObjectId(5f1d)");
+/// assert_eq!(serde_json::to_string(&doc).unwrap(), json);
+/// ```
 pub trait UnstructuredDataTrait: Clone {
     type ErrorType: std::error::Error + Clone + Send + Sync;
     type OtherType: std::fmt::Display + Clone + Send + Sync;
+
+    /// Write an `Other` value with a serde `Serializer`, the string `"other"` by default. This
+    /// is also used to read `Other` values into other types with serde.
+    fn serialize_other<S: serde::Serializer>(
+        other: &Self::OtherType,
+        s: S,
+    ) -> Result<S::Ok, S::Error> {
+        let _ = other;
+        s.serialize_str("other")
+    }
+
+    /// Replace a value that was just deserialized with an `Other` value, called for every value
+    /// in the document from the innermost out. Returns `None` to keep the value by default.
+    fn deserialize_other(value: &Unstructured<Self>) -> Option<Self::OtherType> {
+        let _ = value;
+        None
+    }
+
+    /// Display an `Other` value, using its `Display` impl by default
+    fn display_other(other: &Self::OtherType, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Display::fmt(other, f)
+    }
+
+    /// Create an error for a failed operation, which is carried through the document in an `Err`
+    /// value. Returns `None` by default, in which case failed operations give `Null` instead.
+    fn error_from_message(message: String) -> Option<Self::ErrorType> {
+        let _ = message;
+        None
+    }
 }
 
 pub type Sequence<T> = Vec<Unstructured<T>>;
-pub type Mapping<T> = BTreeMap<Unstructured<T>, Unstructured<T>>;
 
-#[derive(Clone, Debug)]
+#[derive(Debug, Default)]
 pub enum Unstructured<T: UnstructuredDataTrait>
 {
+    #[default]
     Unassigned,
     Null,
     Bool(bool),
@@ -69,34 +232,57 @@ pub enum Unstructured<T: UnstructuredDataTrait>
     String(String),
     Char(char),
     Bytes(Vec<u8>),
-    Seq(Sequence<T>),
-    Map(Mapping<T>),
+    /// A timestamp with a UTC offset, serialized as an RFC 3339 string
+    #[cfg(feature = "chrono")]
+    DateTime(chrono::DateTime<chrono::FixedOffset>),
+    Seq(Shared<Sequence<T>>),
+    Map(Shared<Mapping<T>>),
     Option(Option<Box<Unstructured<T>>>),
     Newtype(Box<Unstructured<T>>),
     Err(T::ErrorType),
     Other(T::OtherType),
 }
 
+/// Hashing doesn't recurse, so it is safe for documents of any depth
 impl<T: UnstructuredDataTrait> Hash for Unstructured<T> {
     fn hash<H>(&self, hasher: &mut H)
     where
         H: Hasher,
     {
-        self.discriminant().hash(hasher);
-        match *self {
-            Self::Bool(v) => v.hash(hasher),
-            Self::Number(ref n) => n.hash(hasher),
-            Self::Char(v) => v.hash(hasher),
-            Self::String(ref v) => v.hash(hasher),
-            Self::Null => ().hash(hasher),
-            Self::Option(ref v) => v.hash(hasher),
-            Self::Newtype(ref v) => v.hash(hasher),
-            Self::Seq(ref v) => v.hash(hasher),
-            Self::Map(ref v) => v.hash(hasher),
-            Self::Bytes(ref v) => v.hash(hasher),
-            Self::Unassigned => ().hash(hasher),
-            Self::Err(ref e) => format!("{}", e).hash(hasher),
-            Self::Other(..) => 100.hash(hasher),
+        let mut pending = vec![self];
+        while let Some(val) = pending.pop() {
+            val.discriminant().hash(hasher);
+            match val {
+                Self::Bool(v) => v.hash(hasher),
+                Self::Number(n) => n.hash(hasher),
+                Self::Char(v) => v.hash(hasher),
+                Self::String(v) => v.hash(hasher),
+                Self::Null => {}
+                Self::Option(v) => {
+                    v.is_some().hash(hasher);
+                    pending.extend(v.as_deref());
+                }
+                Self::Newtype(v) => pending.push(v),
+                Self::Seq(v) => {
+                    v.len().hash(hasher);
+                    pending.extend(v.iter().rev());
+                }
+                Self::Map(v) => {
+                    v.len().hash(hasher);
+                    for (key, val) in v.sorted_entries().into_iter().rev() {
+                        pending.push(val);
+                        pending.push(key);
+                    }
+                }
+                Self::Bytes(v) => v.hash(hasher),
+                #[cfg(feature = "chrono")]
+                Self::DateTime(v) => v.hash(hasher),
+                Self::Unassigned => {}
+                // Errors never compare equal and their messages may not be stable, so only the
+                // discriminant is hashed
+                Self::Err(..) => {}
+                Self::Other(..) => 100.hash(hasher),
+            }
         }
     }
 }
@@ -107,38 +293,89 @@ impl<T: UnstructuredDataTrait> PartialOrd for Unstructured<T> {
     }
 }
 
-impl<T: UnstructuredDataTrait> Default for Unstructured<T> {
-    fn default() -> Self {
-        Self::Unassigned
-    }
-}
-
+/// Adding numbers gives their sum and adding strings concatenates them, any other values are
+/// combined with [`merge`](Unstructured::merge). Adding an `Err` gives that `Err`.
 impl<T: UnstructuredDataTrait> std::ops::Add<Unstructured<T>> for Unstructured<T>
 {
     type Output = Unstructured<T>;
 
-    fn add(mut self, rhs: Unstructured<T>) -> Unstructured<T> {
-        self.merge(rhs);
-        self
+    fn add(self, rhs: Unstructured<T>) -> Unstructured<T> {
+        match (self, rhs) {
+            (err @ Self::Err(_), _) | (_, err @ Self::Err(_)) => err,
+            (Self::Number(l), Self::Number(r)) => Self::Number(l + r),
+            (Self::String(l), Self::String(r)) => Self::String(l + &r),
+            (mut l, r) => {
+                l.merge(r);
+                l
+            }
+        }
     }
 }
 
-impl<T: UnstructuredDataTrait> Ord for Unstructured<T> {
-    fn cmp(&self, rhs: &Self) -> Ordering {
-        match (self, rhs) {
-            (&Self::Bool(v0), &Self::Bool(ref v1)) => v0.cmp(v1),
-            (&Self::Number(ref v0), &Self::Number(ref v1)) => v0.cmp(v1),
-            (&Self::Char(v0), &Self::Char(ref v1)) => v0.cmp(v1),
-            (&Self::String(ref v0), &Self::String(ref v1)) => v0.cmp(v1),
-            (&Self::Null, &Self::Null) => Ordering::Equal,
-            (&Self::Option(ref v0), &Self::Option(ref v1)) => v0.cmp(v1),
-            (&Self::Newtype(ref v0), &Self::Newtype(ref v1)) => v0.cmp(v1),
-            (&Self::Seq(ref v0), &Self::Seq(ref v1)) => v0.cmp(v1),
-            (&Self::Map(ref v0), &Self::Map(ref v1)) => v0.cmp(v1),
-            (&Self::Bytes(ref v0), &Self::Bytes(ref v1)) => v0.cmp(v1),
-            (ref v0, ref v1) => v0.discriminant().cmp(&v1.discriminant()),
+macro_rules! impl_numeric_op {
+    (
+        $( $(#[$attr:meta])* $trait:ident $method:ident $op:tt $verb:literal $divides:literal ),*
+    ) => {
+        $(
+            $(#[$attr])*
+            impl<T: UnstructuredDataTrait> std::ops::$trait<Unstructured<T>> for Unstructured<T> {
+                type Output = Unstructured<T>;
+
+                fn $method(self, rhs: Unstructured<T>) -> Unstructured<T> {
+                    match (self, rhs) {
+                        (err @ Self::Err(_), _) | (_, err @ Self::Err(_)) => err,
+                        (Self::Number(l), Self::Number(r)) => {
+                            let by_zero = l.is_integer() && r.is_integer() && f64::from(&r) == 0.0;
+                            if $divides && by_zero {
+                                return Self::error_or_null("Division by zero".into());
+                            }
+                            Self::Number(l $op r)
+                        }
+                        (l, r) => Self::error_or_null(format!(
+                            "Cannot {} {} and {}",
+                            $verb,
+                            l.type_name(),
+                            r.type_name()
+                        )),
+                    }
+                }
+            }
+        )*
+    };
+}
+
+impl_numeric_op! {
+    /// Subtract numbers. Any other values give an `Err`, or `Null` if `T` has no errors for
+    /// failed operations, and an `Err` operand gives that `Err`.
+    Sub sub - "subtract" false,
+    /// Multiply numbers. Any other values give an `Err`, or `Null` if `T` has no errors for
+    /// failed operations, and an `Err` operand gives that `Err`.
+    Mul mul * "multiply" false,
+    /// Divide numbers. Any other values give an `Err`, or `Null` if `T` has no errors for
+    /// failed operations, and an `Err` operand gives that `Err`. Integer division by zero is
+    /// also an `Err`, or `Null` if `T` has no errors.
+    Div div / "divide" true,
+    /// Remainder of dividing numbers, with the same errors as division
+    Rem rem % "divide" true
+}
+
+macro_rules! impl_const_number {
+    ( $( $method:ident $type:ident $variant:ident ),* ) => {
+        impl<T: UnstructuredDataTrait> Unstructured<T> {
+            $(
+                #[doc = concat!("Create a `", stringify!($type), "` document in a const context")]
+                pub const fn $method(v: $type) -> Self {
+                    Self::Number(Number::$variant(v))
+                }
+            )*
         }
-    }
+    };
+}
+
+impl_const_number! {
+    const_u8 u8 U8, const_u16 u16 U16, const_u32 u32 U32, const_u64 u64 U64,
+    const_i8 i8 I8, const_i16 i16 I16, const_i32 i32 I32, const_i64 i64 I64,
+    const_f32 f32 F32, const_f64 f64 F64
 }
 
 impl<T: UnstructuredDataTrait> Unstructured<T> {
@@ -176,10 +413,180 @@ impl<T: UnstructuredDataTrait> Unstructured<T> {
         mem::replace(self, Self::Unassigned)
     }
 
+    /// Append a value to a sequence. A `Null` or unassigned document becomes a sequence first.
+    /// Returns false, leaving the document unchanged, if it holds any other kind of value.
+    ///
+    /// ```
+    /// use unstructured::Document;
+    ///
+    /// let mut doc = Document::Null;
+    /// assert!(doc.push(1));
+    /// assert!(doc.push("two"));
+    /// assert_eq!(doc, Document::from((1, "two")));
+    /// assert!(!Document::from("text").push(3));
+    /// ```
+    pub fn push<U: Into<Self>>(&mut self, val: U) -> bool {
+        match self.seq_or_insert() {
+            Some(seq) => {
+                seq.push(val.into());
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Remove the last value of a sequence, None if it's empty or this isn't a sequence
+    pub fn pop(&mut self) -> Option<Self> {
+        match self {
+            Self::Seq(seq) => make_mut(seq).pop(),
+            _ => None,
+        }
+    }
+
+    /// Insert a value into a sequence at `index`, shifting the values after it. A `Null` or
+    /// unassigned document becomes a sequence first. Returns false, leaving the document
+    /// unchanged, if `index` is past the end of the sequence or the document holds any other
+    /// kind of value.
+    pub fn insert<U: Into<Self>>(&mut self, index: usize, val: U) -> bool {
+        let len = match self {
+            Self::Seq(seq) => seq.len(),
+            Self::Null | Self::Unassigned => 0,
+            _ => return false,
+        };
+        if index > len {
+            return false;
+        }
+        match self.seq_or_insert() {
+            Some(seq) => {
+                seq.insert(index, val.into());
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Remove the value at `index` from a sequence, shifting the values after it. None if the
+    /// index is out of bounds or this isn't a sequence.
+    pub fn remove(&mut self, index: usize) -> Option<Self> {
+        match self {
+            Self::Seq(seq) if index < seq.len() => Some(make_mut(seq).remove(index)),
+            _ => None,
+        }
+    }
+
+    /// Keep only the values of a sequence for which `f` returns true, other documents are left
+    /// unchanged
+    pub fn retain<F: FnMut(&Self) -> bool>(&mut self, f: F) {
+        if let Self::Seq(seq) = self {
+            make_mut(seq).retain(f);
+        }
+    }
+
+    /// Remove a key from a map, returning its value. None if the key is missing or this isn't
+    /// a map.
+    pub fn remove_key<K: Into<Self>>(&mut self, key: K) -> Option<Self> {
+        match self {
+            Self::Map(map) => make_mut(map).remove(&key.into()),
+            _ => None,
+        }
+    }
+
+    /// Keep only the entries of a map for which `f` returns true, other documents are left
+    /// unchanged
+    ///
+    /// ```
+    /// use unstructured::Document;
+    ///
+    /// let mut doc: Document = serde_json::from_str(r#"{"a": 1, "_b": 2, "c": null}"#).unwrap();
+    /// doc.retain_entries(|key, val| !val.is_null() && *key != "_b");
+    /// assert_eq!(doc.keys().cloned().collect::<Vec<_>>(), vec!["a"]);
+    /// ```
+    pub fn retain_entries<F: FnMut(&Self, &mut Self) -> bool>(&mut self, f: F) {
+        if let Self::Map(map) = self {
+            make_mut(map).retain(f);
+        }
+    }
+
+    /// An `Err` with the message, or `Null` if `T` has no errors for failed operations
+    pub(crate) fn error_or_null(message: String) -> Self {
+        T::error_from_message(message).map_or(Self::Null, Self::Err)
+    }
+
+    /// The document, or the first `Err` found in it. Operations that fail carry their error in
+    /// an `Err` value instead of failing straight away, so a pipeline of operations can be
+    /// checked once at the end.
+    ///
+    /// ```
+    /// use unstructured::Document;
+    ///
+    /// let doc: Document = serde_json::from_str(r#"{"a": 10, "b": 0, "c": "x"}"#).unwrap();
+    /// let total = doc["a"].clone() / doc["b"].clone() + doc["a"].clone();
+    /// assert_eq!(total.ok().unwrap_err().to_string(), "Division by zero");
+    /// let product = doc["a"].clone() * doc["c"].clone();
+    /// assert_eq!(product.ok().unwrap_err().to_string(), "Cannot multiply number and string");
+    /// assert_eq!((doc["a"].clone() - doc["b"].clone()).ok().unwrap(), 10);
+    /// ```
+    pub fn ok(self) -> Result<Self, T::ErrorType> {
+        let mut pending = vec![&self];
+        while let Some(doc) = pending.pop() {
+            match doc {
+                Self::Err(e) => return Err(e.clone()),
+                Self::Seq(seq) => pending.extend(seq.iter()),
+                Self::Map(map) => pending.extend(map.iter().flat_map(|(k, v)| [k, v])),
+                Self::Option(Some(inner)) | Self::Newtype(inner) => pending.push(inner),
+                _ => {}
+            }
+        }
+        Ok(self)
+    }
+
+    fn seq_or_insert(&mut self) -> Option<&mut Sequence<T>> {
+        if matches!(self, Self::Null | Self::Unassigned) {
+            *self = Self::empty_seq();
+        }
+        match self {
+            Self::Seq(seq) => Some(make_mut(seq)),
+            _ => None,
+        }
+    }
+
+    /// Whether this is a `Null` value that is present in the document, see
+    /// [`is_missing`](Unstructured::is_missing) for values that are absent
     pub fn is_null(&self) -> bool {
         matches!(self, Self::Null)
     }
 
+    /// Whether this is `Unassigned`, which is what indexing returns for a key or index that
+    /// doesn't exist
+    ///
+    /// ```
+    /// use unstructured::Document;
+    ///
+    /// let doc: Document = serde_json::from_str(r#"{"a": null, "b": [1]}"#).unwrap();
+    /// assert!(doc["a"].is_null() && !doc["a"].is_missing());
+    /// assert!(doc["c"].is_missing() && !doc["c"].is_null());
+    /// assert!(doc["b"][5].is_missing());
+    /// ```
+    pub fn is_missing(&self) -> bool {
+        matches!(self, Self::Unassigned)
+    }
+
+    /// The number of entries in a map, elements in a sequence or characters in a string, 0 for
+    /// any other value
+    pub fn len(&self) -> usize {
+        match self {
+            Self::Map(m) => m.len(),
+            Self::Seq(s) => s.len(),
+            Self::String(s) => s.chars().count(),
+            _ => 0,
+        }
+    }
+
+    /// Whether [`len`](Self::len) is 0
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
     pub fn is_number(&self) -> bool {
         matches!(self, Self::Number(_))
     }
@@ -207,10 +614,31 @@ impl<T: UnstructuredDataTrait> Unstructured<T> {
         }
     }
 
+    /// Return the name of the type of value held by this document, e.g. `"map"` or `"string"`
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Self::Unassigned => "unassigned",
+            Self::Null => "null",
+            Self::Bool(_) => "bool",
+            Self::Number(_) => "number",
+            Self::String(_) => "string",
+            Self::Char(_) => "char",
+            Self::Bytes(_) => "bytes",
+            #[cfg(feature = "chrono")]
+            Self::DateTime(_) => "datetime",
+            Self::Seq(_) => "seq",
+            Self::Map(_) => "map",
+            Self::Option(_) => "option",
+            Self::Newtype(_) => "newtype",
+            Self::Err(_) => "err",
+            Self::Other(_) => "other",
+        }
+    }
+
     fn discriminant(&self) -> usize {
         match *self {
             Self::Bool(..) => 0,
-            Self::Number(ref n) => n.discriminant(),
+            Self::Number(..) => 1,
             Self::Char(..) => 13,
             Self::String(..) => 14,
             Self::Null => 15,
@@ -222,11 +650,13 @@ impl<T: UnstructuredDataTrait> Unstructured<T> {
             Self::Unassigned => 21,
             Self::Err(..) => 22,
             Self::Other(..) => 23,
+            #[cfg(feature = "chrono")]
+            Self::DateTime(..) => 24,
         }
     }
 
     #[allow(clippy::cast_lossless)]
-    fn unexpected(&self) -> serde::de::Unexpected {
+    fn unexpected(&self) -> serde::de::Unexpected<'_> {
         match *self {
             Self::Bool(b) => serde::de::Unexpected::Bool(b),
             Self::Number(ref n) => n.unexpected(),
@@ -238,6 +668,8 @@ impl<T: UnstructuredDataTrait> Unstructured<T> {
             Self::Seq(_) => serde::de::Unexpected::Seq,
             Self::Map(_) => serde::de::Unexpected::Map,
             Self::Bytes(ref b) => serde::de::Unexpected::Bytes(b),
+            #[cfg(feature = "chrono")]
+            Self::DateTime(_) => serde::de::Unexpected::Other("DateTime"),
             Self::Unassigned => serde::de::Unexpected::Other("Unassigned"),
             Self::Err(_) => serde::de::Unexpected::Other("Err"),
             Self::Other(..) => serde::de::Unexpected::Other("Other"),
@@ -245,13 +677,145 @@ impl<T: UnstructuredDataTrait> Unstructured<T> {
     }
 
     /// This attempts to deserialize the document into a type that implements Deserialize
-    pub fn try_into<'de, Q: Deserialize<'de>>(self) -> Result<Q, DeserializerError> {
-        Q::deserialize(self)
+    pub fn try_into<'de, Q: Deserialize<'de>>(self) -> Result<Q, Error> {
+        Q::deserialize(self).map_err(Error::Deserialize)
     }
 
-    /// This creates a new document from a type that implements Serialize
-    pub fn new<Q: Serialize>(value: Q) -> Result<Self, SerializerError> {
-        value.serialize(Serializer::new())
+    /// Deserialize into a type that implements Deserialize without consuming the document.
+    /// Strings and bytes are borrowed from the document when the type allows it.
+    pub fn to_value<'de, Q: Deserialize<'de>>(&'de self) -> Result<Q, Error> {
+        Q::deserialize(self).map_err(Error::Deserialize)
+    }
+
+    /// This creates a new document from a type that implements Serialize. Pass a reference to
+    /// serialize a value without moving or cloning it, e.g. `Document::new(&value)`.
+    pub fn new<Q: Serialize>(value: Q) -> Result<Self, Error> {
+        value.serialize(Serializer::new()).map_err(Error::Serialize)
+    }
+
+    /// Read a document straight from any serde `Deserializer`
+    ///
+    /// ```
+    /// use unstructured::Document;
+    ///
+    /// let mut de = serde_json::Deserializer::from_str(r#"{"a": [1, 2]}"#);
+    /// let doc = Document::from_deserializer(&mut de).unwrap();
+    /// assert_eq!(doc["a"][1], 2u64);
+    ///
+    /// let mut out = vec![];
+    /// doc.transcode_to(&mut serde_json::Serializer::new(&mut out)).unwrap();
+    /// assert_eq!(out, br#"{"a":[1,2]}"#);
+    /// ```
+    pub fn from_deserializer<'de, D: serde::Deserializer<'de>>(d: D) -> Result<Self, D::Error> {
+        observe(Operation::Parse, || Self::deserialize(d))
+    }
+
+    /// Read a document from any serde `Deserializer`, failing as soon as it goes past one of
+    /// the [`Limits`]. Use this for untrusted input, where a deeply nested document could
+    /// overflow the stack or a huge one exhaust memory.
+    ///
+    /// ```
+    /// use unstructured::{Document, Limits};
+    ///
+    /// let limits = Limits {
+    ///     max_depth: Some(2),
+    ///     ..Limits::default()
+    /// };
+    /// let parse = |s| {
+    ///     Document::deserialize_with_limits(&mut serde_json::Deserializer::from_str(s), limits)
+    /// };
+    /// assert!(parse("[[1]]").is_ok());
+    /// assert!(parse("[[[1]]]").is_err());
+    /// ```
+    pub fn deserialize_with_limits<'de, D: serde::Deserializer<'de>>(
+        d: D,
+        limits: Limits,
+    ) -> Result<Self, D::Error> {
+        use serde::de::DeserializeSeed;
+
+        let guard = Guard::new(limits);
+        observe(Operation::Parse, || DocumentVisitor::limited(&guard).deserialize(d))
+    }
+
+    /// Write this document to any serde `Serializer`
+    pub fn transcode_to<S: serde::Serializer>(&self, s: S) -> Result<S::Ok, S::Error> {
+        self.serialize(s)
+    }
+
+    /// Like [`Unstructured::new`], normalizing values with `options` as they are written
+    ///
+    /// ```
+    /// use unstructured::{DedupPolicy, Document, SerializerOptions};
+    ///
+    /// let options = SerializerOptions {
+    ///     bytes_as_base64: true,
+    ///     dedup_policy: DedupPolicy::Error,
+    ///     ..Default::default()
+    /// };
+    /// let doc = Document::new_with(Document::Bytes(b"hi".to_vec()), options).unwrap();
+    /// assert_eq!(doc, "aGk=");
+    ///
+    /// let pairs = vec![("a", 1), ("a", 2)];
+    /// let dup = Document::new_with(Mapped(pairs), options);
+    /// assert!(dup.is_err());
+    /// # struct Mapped(Vec<(&'static str, i32)>);
+    /// # impl serde::Serialize for Mapped {
+    /// #     fn serialize<S: serde::Serializer>(&self, s: S) -> Result<S::Ok, S::Error> {
+    /// #         s.collect_map(self.0.iter().cloned())
+    /// #     }
+    /// # }
+    /// ```
+    pub fn new_with<Q: Serialize>(
+        value: Q,
+        options: SerializerOptions,
+    ) -> Result<Self, Error> {
+        value.serialize(Serializer::with_options(options)).map_err(Error::Serialize)
+    }
+
+    /// Serialize this document with `options` applied. `sort_keys` and `bytes_as_base64` are
+    /// applied to the output, keys are already unique and `Null` is always written as a unit
+    pub fn with_options(&self, options: SerializerOptions) -> WithOptions<'_, T> {
+        WithOptions::new(self, options)
+    }
+
+    /// Create a `Null` document in a const context
+    ///
+    /// ```
+    /// use unstructured::Document;
+    ///
+    /// static DEFAULT_PORT: Document = Document::const_u16(8080);
+    /// const DISABLED: Document = Document::const_bool(false);
+    /// assert_eq!(DEFAULT_PORT, 8080);
+    /// assert_eq!(DISABLED, false);
+    /// assert_eq!(Document::const_unit(), Document::Null);
+    /// ```
+    pub const fn const_unit() -> Self {
+        Self::Null
+    }
+
+    /// Create a bool document in a const context
+    pub const fn const_bool(v: bool) -> Self {
+        Self::Bool(v)
+    }
+
+    /// Create a char document in a const context
+    pub const fn const_char(v: char) -> Self {
+        Self::Char(v)
+    }
+
+    /// Create a `Null` document
+    pub const fn null() -> Self {
+        Self::Null
+    }
+
+    /// Create an empty sequence
+    pub fn empty_seq() -> Self {
+        Self::Seq(Default::default())
+    }
+
+    /// Create an empty map
+    pub fn empty_map() -> Self {
+        Self::Map(Default::default())
     }
 
     /// Merge another document into this one, consuming both documents into the result.
@@ -262,23 +826,27 @@ impl<T: UnstructuredDataTrait> Unstructured<T> {
     /// If this document is a map and the other document is also be a map, merging
     /// maps will cause values from the other document to overwrite this one.
     /// Otherwise, the value from the other document will overwrite this one.
-    pub fn merge(&mut self, mut other: Self)
-    {
+    pub fn merge(&mut self, other: Self) {
+        self.observe_merge(|doc| doc.merge_imp(other))
+    }
+
+    pub(crate) fn merge_imp(&mut self, other: Self) {
         match self {
             Self::Seq(s) => {
-                if let Self::Seq(ref mut o) = other {
-                    s.append(o);
+                if let Self::Seq(o) = other {
+                    make_mut(s).extend(unshare(o));
                 } else {
-                    s.push(other);
+                    make_mut(s).push(other);
                 }
             }
             Self::Map(ref mut m) => {
                 if let Self::Map(o) = other {
-                    for (key, val) in o.into_iter() {
+                    let m = make_mut(m);
+                    for (key, val) in unshare(o).into_iter() {
                         if let Some(loc) = m.get_mut(&key) {
-                            loc.merge(val);
+                            loc.merge_imp(val);
                         } else {
-                            m.insert(key, val.clone());
+                            m.insert(key, val);
                         }
                     }
                 } else {
@@ -292,26 +860,37 @@ impl<T: UnstructuredDataTrait> Unstructured<T> {
 
 impl<T: UnstructuredDataTrait> fmt::Display for Unstructured<T> {
     fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        self.fmt_with(fmt, BytesFormat::default())
+    }
+}
+
+impl<T: UnstructuredDataTrait> Unstructured<T> {
+    pub(crate) fn fmt_with(&self, fmt: &mut fmt::Formatter, bytes: BytesFormat) -> fmt::Result {
+        if fmt.alternate() {
+            return DocumentFormatter::new().bytes(bytes).write_to(fmt, self);
+        }
         match self {
             Self::Null => fmt.write_str("<null>"),
-            Self::Bool(b) => b.fmt(fmt),
-            Self::Number(n) => n.fmt(fmt),
-            Self::Char(c) => c.fmt(fmt),
-            Self::String(ref s) => s.fmt(fmt),
-            Self::Newtype(t) => t.fmt(fmt),
-            Self::Bytes(_) => fmt.write_str("b[...]"),
+            Self::Bool(b) => fmt::Display::fmt(b, fmt),
+            Self::Number(n) => fmt::Display::fmt(n, fmt),
+            Self::Char(c) => fmt::Display::fmt(c, fmt),
+            Self::String(ref s) => fmt::Display::fmt(s, fmt),
+            Self::Newtype(t) => t.fmt_with(fmt, bytes),
+            Self::Bytes(b) => bytes.write(fmt, b),
+            #[cfg(feature = "chrono")]
+            Self::DateTime(d) => fmt.write_str(&d.to_rfc3339()),
             Self::Unassigned => fmt.write_str("(Unassigned)"),
-            Self::Err(e) => e.fmt(fmt),
-            Self::Other(o) => o.fmt(fmt),
+            Self::Err(e) => fmt::Display::fmt(e, fmt),
+            Self::Other(o) => T::display_other(o, fmt),
             Self::Option(o) => o
                 .as_ref()
-                .map(|v| v.fmt(fmt))
+                .map(|v| v.fmt_with(fmt, bytes))
                 .unwrap_or_else(|| fmt.write_str("None")),
             Self::Seq(s) => {
                 fmt.write_str("[")?;
                 fmt.write_str(
                     &s.iter()
-                        .map(|doc| doc.to_string())
+                        .map(|doc| doc.display_bytes_as(bytes).to_string())
                         .collect::<Vec<String>>()
                         .join(","),
                 )?;
@@ -321,7 +900,13 @@ impl<T: UnstructuredDataTrait> fmt::Display for Unstructured<T> {
                 fmt.write_str("{")?;
                 fmt.write_str(
                     &m.iter()
-                        .map(|(k, v)| format!("{} => {}", k, v))
+                        .map(|(k, v)| {
+                            format!(
+                                "{} => {}",
+                                k.display_bytes_as(bytes),
+                                v.display_bytes_as(bytes)
+                            )
+                        })
                         .collect::<Vec<String>>()
                         .join(","),
                 )?;
@@ -0,0 +1,75 @@
+/// How deeply selector predicates, queries and JMESPath expressions may nest, which keeps their
+/// recursive parsers and evaluators well within the stack of any thread
+pub(crate) const MAX_NESTING: usize = 128;
+
+/// The tokens an expression language nests with, for rejecting expressions that nest deeper
+/// than [`MAX_NESTING`] before they reach a recursive parser
+pub(crate) struct Nesting {
+    /// The characters strings are quoted with, only `\(...)` interpolations nest inside them
+    pub(crate) quotes: &'static [char],
+    /// Prefix operators such as `!`, each one nests until the operand it applies to
+    pub(crate) prefixes: &'static [char],
+    /// Whether `if` opens a level closed by `end`
+    pub(crate) if_blocks: bool,
+}
+
+impl Nesting {
+    pub(crate) fn check(&self, expression: &str) -> Result<(), String> {
+        // Each open bracket with the quote to return to when it closes and the levels it adds,
+        // which include the prefix operators before it
+        let mut open: Vec<(Option<char>, usize)> = vec![];
+        let (mut depth, mut prefixes) = (0, 0);
+        let mut quote = None;
+        let mut chars = expression.chars().peekable();
+        while let Some(c) = chars.next() {
+            if let Some(q) = quote {
+                if c == '\\' && chars.next() == Some('(') {
+                    open.push((Some(q), 1));
+                    depth += 1;
+                    quote = None;
+                } else if c == q {
+                    quote = None;
+                }
+            } else if self.prefixes.contains(&c) {
+                prefixes += 1;
+                depth += 1;
+            } else if !c.is_whitespace() {
+                let mut word = String::new();
+                if c.is_ascii_alphabetic() || c == '_' {
+                    word.push(c);
+                    while let Some(c) = chars.next_if(|c| c.is_ascii_alphanumeric() || *c == '_') {
+                        word.push(c);
+                    }
+                }
+                if matches!(c, '(' | '[' | '{') || self.if_blocks && word == "if" {
+                    open.push((None, prefixes + 1));
+                    depth += 1;
+                    prefixes = 0;
+                } else {
+                    if matches!(c, ')' | ']' | '}') || self.if_blocks && word == "end" {
+                        if let Some((closes_into, levels)) = open.pop() {
+                            depth -= levels;
+                            quote = closes_into;
+                        }
+                    } else if self.quotes.contains(&c) {
+                        quote = Some(c);
+                    }
+                    depth -= prefixes;
+                    prefixes = 0;
+                }
+            }
+            nested(depth, 0)?;
+        }
+        Ok(())
+    }
+}
+
+/// The depth of an expression `levels` below one at `depth`, failing past [`MAX_NESTING`]
+pub(crate) fn nested(depth: usize, levels: usize) -> Result<usize, String> {
+    match depth + levels {
+        depth if depth > MAX_NESTING => {
+            Err(format!("Expression nests more than {} levels deep", MAX_NESTING))
+        }
+        depth => Ok(depth),
+    }
+}
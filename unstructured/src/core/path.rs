@@ -0,0 +1,560 @@
+use crate::*;
+use std::fmt;
+use std::str::FromStr;
+
+/// A single step in a [`Path`], either a map key or a sequence index
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Segment {
+    Key(String),
+    Index(usize),
+}
+
+impl Segment {
+    pub(crate) fn from_key<T: UnstructuredDataTrait>(key: &Unstructured<T>) -> Self {
+        match key {
+            Unstructured::<T>::String(s) => Segment::Key(s.clone()),
+            other => Segment::Key(other.to_string()),
+        }
+    }
+
+    fn from_pointer_token(token: &str) -> Result<Self, String> {
+        let key = unescape_pointer(token)?;
+        let is_index = !key.is_empty()
+            && key.chars().all(|c| c.is_ascii_digit())
+            && (key == "0" || !key.starts_with('0'));
+        match key.parse::<usize>() {
+            Ok(i) if is_index => Ok(Segment::Index(i)),
+            _ => Ok(Segment::Key(key)),
+        }
+    }
+
+    pub(crate) fn child<'a, T: UnstructuredDataTrait>(
+        &self,
+        doc: &'a Unstructured<T>,
+    ) -> Option<&'a Unstructured<T>> {
+        match (self, doc) {
+            (Segment::Key(k), Unstructured::<T>::Map(map)) => {
+                map.get(&Unstructured::<T>::from(k.as_str()))
+            }
+            (Segment::Index(i), Unstructured::<T>::Seq(seq)) => seq.get(*i),
+            (Segment::Index(i), Unstructured::<T>::Map(map)) => map.get(&Self::index_key(map, *i)),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn child_mut<'a, T: UnstructuredDataTrait>(
+        &self,
+        doc: &'a mut Unstructured<T>,
+    ) -> Option<&'a mut Unstructured<T>> {
+        match (self, doc) {
+            (Segment::Key(k), Unstructured::<T>::Map(map)) => {
+                make_mut(map).get_mut(&Unstructured::<T>::from(k.as_str()))
+            }
+            (Segment::Index(i), Unstructured::<T>::Seq(seq)) => make_mut(seq).get_mut(*i),
+            (Segment::Index(i), Unstructured::<T>::Map(map)) => {
+                let key = Self::index_key(map, *i);
+                make_mut(map).get_mut(&key)
+            }
+            _ => None,
+        }
+    }
+
+    /// Get the child for this segment, replacing the document with a container if it can't
+    /// hold one. Returns None if a sequence can't be grown to reach the index.
+    fn child_or_insert<'a, T: UnstructuredDataTrait>(
+        &self,
+        doc: &'a mut Unstructured<T>,
+    ) -> Option<&'a mut Unstructured<T>> {
+        match (self, doc) {
+            (Segment::Key(k), Unstructured::<T>::Map(map)) => {
+                let key = Unstructured::<T>::from(k.as_str());
+                Some(make_mut(map).get_or_insert_with(key, || Unstructured::<T>::Null))
+            }
+            (Segment::Index(i), Unstructured::<T>::Seq(seq)) => {
+                let seq = make_mut(seq);
+                if *i >= seq.len() {
+                    let len = i.checked_add(1)?;
+                    seq.try_reserve(len - seq.len()).ok()?;
+                    seq.resize(len, Unstructured::<T>::Null);
+                }
+                seq.get_mut(*i)
+            }
+            (Segment::Index(i), Unstructured::<T>::Map(map)) => {
+                let key = Self::index_key(map, *i);
+                Some(make_mut(map).get_or_insert_with(key, || Unstructured::<T>::Null))
+            }
+            (Segment::Key(_), doc) => {
+                *doc = Unstructured::<T>::Map(Default::default());
+                self.child_or_insert(doc)
+            }
+            (Segment::Index(_), doc) => {
+                *doc = Unstructured::<T>::Seq(Default::default());
+                self.child_or_insert(doc)
+            }
+        }
+    }
+
+    pub(crate) fn remove_from<T: UnstructuredDataTrait>(
+        &self,
+        doc: &mut Unstructured<T>,
+    ) -> Option<Unstructured<T>> {
+        match (self, doc) {
+            (Segment::Key(k), Unstructured::<T>::Map(map)) => {
+                make_mut(map).remove(&Unstructured::<T>::from(k.as_str()))
+            }
+            (Segment::Index(i), Unstructured::<T>::Seq(seq)) if *i < seq.len() => {
+                Some(make_mut(seq).remove(*i))
+            }
+            (Segment::Index(i), Unstructured::<T>::Map(map)) => {
+                let key = Self::index_key(map, *i);
+                make_mut(map).remove(&key)
+            }
+            _ => None,
+        }
+    }
+
+    /// Maps may be indexed by either a string or a numeric key, prefer whichever exists
+    fn index_key<T: UnstructuredDataTrait>(map: &Mapping<T>, i: usize) -> Unstructured<T> {
+        let numeric: Unstructured<T> = i.into();
+        if map.contains_key(&numeric) {
+            numeric
+        } else {
+            i.to_string().into()
+        }
+    }
+}
+
+impl fmt::Display for Segment {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Segment::Key(k) => f.write_str(&k.replace('~', "~0").replace('/', "~1")),
+            Segment::Index(i) => i.fmt(f),
+        }
+    }
+}
+
+impl From<&str> for Segment {
+    fn from(key: &str) -> Self {
+        Segment::Key(key.to_string())
+    }
+}
+
+impl From<String> for Segment {
+    fn from(key: String) -> Self {
+        Segment::Key(key)
+    }
+}
+
+impl From<usize> for Segment {
+    fn from(index: usize) -> Self {
+        Segment::Index(index)
+    }
+}
+
+/// A location within a document, made up of key and index segments.
+///
+/// Paths can be parsed from either [JSON Pointer](https://tools.ietf.org/html/rfc6901) syntax
+/// (`/path/to/0`) or the jq inspired selector syntax (`.path.to[0]`), and are displayed as
+/// JSON Pointers.
+///
+/// ```
+/// use unstructured::{Document, Path};
+///
+/// let mut doc = Document::Null;
+/// let path: Path = ".users[1][\"first name\"]".parse().unwrap();
+/// doc.set_at(&path, "Bob");
+/// assert_eq!(path.to_string(), "/users/1/first name");
+/// let name = doc.get_at(&"/users/1/first name".parse().unwrap());
+/// assert_eq!(name, Some(&Document::from("Bob")));
+/// assert_eq!(doc["users"][0], Document::Null);
+/// ```
+#[derive(Clone, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Path(Vec<Segment>);
+
+impl Path {
+    /// Create an empty path, referring to the root of a document
+    pub fn new() -> Self {
+        Path(vec![])
+    }
+
+    /// Parse a path from a JSON Pointer, e.g. `/path/to/0`.
+    /// Tokens that are valid array indices become [`Segment::Index`], all others are keys.
+    pub fn from_pointer(pointer: &str) -> Result<Self, Error> {
+        if pointer.is_empty() {
+            return Ok(Path::new());
+        }
+        if !pointer.starts_with('/') {
+            return Err(Error::Parse(format!("JSON pointer must start with '/': {}", pointer)));
+        }
+        pointer[1..]
+            .split('/')
+            .map(Segment::from_pointer_token)
+            .collect::<Result<Vec<Segment>, String>>()
+            .map(Path)
+            .map_err(Error::Parse)
+    }
+
+    /// Parse a path from the jq inspired selector syntax, e.g. `.path.to[0]` or `.["path"].[0]`
+    pub fn from_jq(selector: &str) -> Result<Self, Error> {
+        jq_segments(selector).map(Path).map_err(Error::Parse)
+    }
+
+    /// Parse a path from either syntax, JSON Pointers are detected by a leading `/`
+    pub fn parse(path: &str) -> Result<Self, Error> {
+        if path.is_empty() || path.starts_with('/') {
+            Self::from_pointer(path)
+        } else {
+            Self::from_jq(path)
+        }
+    }
+
+    pub fn push<S: Into<Segment>>(&mut self, segment: S) {
+        self.0.push(segment.into());
+    }
+
+    pub fn pop(&mut self) -> Option<Segment> {
+        self.0.pop()
+    }
+
+    /// Return a new path with the given segment appended
+    pub fn join<S: Into<Segment>>(&self, segment: S) -> Self {
+        let mut path = self.clone();
+        path.push(segment);
+        path
+    }
+
+    /// Return the path of the parent node, or None for the root
+    pub fn parent(&self) -> Option<Self> {
+        self.0
+            .split_last()
+            .map(|(_, parent)| Path(parent.to_vec()))
+    }
+
+    pub fn last(&self) -> Option<&Segment> {
+        self.0.last()
+    }
+
+    pub fn segments(&self) -> &[Segment] {
+        &self.0
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+impl fmt::Display for Path {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for segment in self.0.iter() {
+            write!(f, "/{}", segment)?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for Path {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Path::parse(s)
+    }
+}
+
+impl From<Vec<Segment>> for Path {
+    fn from(segments: Vec<Segment>) -> Self {
+        Path(segments)
+    }
+}
+
+impl<T: UnstructuredDataTrait> Unstructured<T> {
+    /// Get a reference to the value at the given path, if it exists
+    pub fn get_at(&self, path: &Path) -> Option<&Self> {
+        path.0
+            .iter()
+            .try_fold(self, |current, segment| segment.child(current))
+    }
+
+    /// Get a mutable reference to the value at the given path, if it exists
+    pub fn get_at_mut(&mut self, path: &Path) -> Option<&mut Self> {
+        path.0
+            .iter()
+            .try_fold(self, |current, segment| segment.child_mut(current))
+    }
+
+    /// Whether a value exists at the given path
+    pub fn contains(&self, path: &Path) -> bool {
+        self.get_at(path).is_some()
+    }
+
+    /// Set the value at the given path, creating any missing maps and sequences along the way.
+    /// Sequences are padded with `Null` if the index is past the end. The value is not set if
+    /// an index is too large for the sequence to be allocated.
+    pub fn set_at<U: Into<Self>>(&mut self, path: &Path, val: U) {
+        let target = path
+            .0
+            .iter()
+            .try_fold(self, |current, segment| segment.child_or_insert(current));
+        if let Some(target) = target {
+            *target = val.into();
+        }
+    }
+
+    /// Remove and return the value at the given path, if it exists.
+    /// Removing the root path is not supported and returns None.
+    pub fn remove_at(&mut self, path: &Path) -> Option<Self> {
+        let (last, parent) = path.0.split_last()?;
+        let parent = parent
+            .iter()
+            .try_fold(self, |current, segment| segment.child_mut(current))?;
+        last.remove_from(parent)
+    }
+
+    /// Get a reference to the value at a JSON Pointer, e.g. `/path/to/0`. Returns None if the
+    /// pointer is invalid or nothing exists at it.
+    pub fn pointer(&self, pointer: &str) -> Option<&Self> {
+        self.get_at(&Path::from_pointer(pointer).ok()?)
+    }
+
+    /// Get a mutable reference to the value at a JSON Pointer, e.g. `/path/to/0`
+    pub fn pointer_mut(&mut self, pointer: &str) -> Option<&mut Self> {
+        self.get_at_mut(&Path::from_pointer(pointer).ok()?)
+    }
+
+    /// Get a mutable reference to the value at a JSON Pointer, creating any missing maps and
+    /// sequences along the way. As in [RFC 6902](https://tools.ietf.org/html/rfc6902), a `-`
+    /// token appends a new element when the value is a sequence.
+    ///
+    /// ```
+    /// use unstructured::Document;
+    ///
+    /// let mut doc = Document::Null;
+    /// *doc.pointer_or_insert("/users/0/name").unwrap() = "a".into();
+    /// *doc.pointer_or_insert("/users/-").unwrap() = "b".into();
+    /// assert_eq!(doc["users"][1], "b");
+    /// assert_eq!(doc.pointer_remove("/users/0/name"), Some("a".into()));
+    /// ```
+    pub fn pointer_or_insert(&mut self, pointer: &str) -> Result<&mut Self, Error> {
+        let path = Path::from_pointer(pointer)?;
+        path.0.iter().try_fold(self, |current, segment| {
+            let child = if current.is::<Sequence<T>>() && segment == &Segment::Key("-".into()) {
+                current.append_null()
+            } else {
+                segment.child_or_insert(current)
+            };
+            child.ok_or_else(|| Error::Select(format!("Index {} is too large to insert", segment)))
+        })
+    }
+
+    fn append_null(&mut self) -> Option<&mut Self> {
+        match self {
+            Self::Seq(seq) => {
+                let seq = make_mut(seq);
+                seq.push(Self::Null);
+                seq.last_mut()
+            }
+            _ => None,
+        }
+    }
+
+    /// Remove and return the value at a JSON Pointer, if it exists
+    pub fn pointer_remove(&mut self, pointer: &str) -> Option<Self> {
+        self.remove_at(&Path::from_pointer(pointer).ok()?)
+    }
+
+    /// Flatten the document into a single level map from JSON Pointers to values. Empty maps
+    /// and sequences are kept as values so that [`unflatten`](Unstructured::unflatten)
+    /// restores them.
+    ///
+    /// ```
+    /// use unstructured::Document;
+    ///
+    /// let doc: Document = serde_json::from_str(r#"{"a": {"b": [1, 2]}, "c": {}}"#).unwrap();
+    /// let flat = doc.flatten();
+    /// assert_eq!(flat["/a/b/1"], 2);
+    /// assert_eq!(flat["/c"], Document::Map(Default::default()));
+    /// assert_eq!(flat.unflatten().unwrap(), doc);
+    /// ```
+    pub fn flatten(&self) -> Self {
+        Self::Map(share(
+            self.walk()
+                .filter(|(_, node)| match node {
+                    Self::Map(map) => map.is_empty(),
+                    Self::Seq(seq) => seq.is_empty(),
+                    _ => true,
+                })
+                .map(|(path, node)| (path.to_string().into(), node.clone()))
+                .collect(),
+        ))
+    }
+
+    /// Rebuild a document from a map of JSON Pointers to values, as produced by
+    /// [`flatten`](Unstructured::flatten). Tokens that are valid indices create sequences, so
+    /// maps with only integer keys are restored as sequences.
+    pub fn unflatten(&self) -> Result<Self, Error> {
+        let map = match self {
+            Self::Map(map) => map,
+            other => {
+                let msg = format!("Cannot unflatten {}, expected a map", other.type_name());
+                return Err(Error::Build(msg));
+            }
+        };
+        let mut result = Self::Unassigned;
+        for (pointer, val) in map.iter() {
+            let pointer = match pointer {
+                Self::String(s) => s,
+                other => {
+                    let msg = format!("Invalid pointer {}, expected a string", other);
+                    return Err(Error::Build(msg));
+                }
+            };
+            result.set_at(&Path::from_pointer(pointer)?, val.clone());
+        }
+        Ok(result)
+    }
+
+    /// Find the path of a value borrowed from this document. Values are matched by identity
+    /// rather than equality, so a reference to an equal value elsewhere returns None. With the
+    /// `shared` feature a clone of this document shares its contents, so values borrowed from
+    /// the clone are found too.
+    ///
+    /// ```
+    /// use unstructured::{Document, Path};
+    ///
+    /// let doc: Document = serde_json::from_str(r#"{"users": [{"id": 1}, {"id": "x"}]}"#).unwrap();
+    /// let mut users = doc["users"].iter();
+    /// let bad = users.find(|user| matches!(user["id"], Document::String(_))).unwrap();
+    /// assert_eq!(doc.path_of(bad), Some("/users/1".parse::<Path>().unwrap()));
+    /// assert_eq!(doc.path_of(&bad.clone()), None);
+    /// ```
+    pub fn path_of(&self, node: &Self) -> Option<Path> {
+        self.walk()
+            .find(|(_, candidate)| std::ptr::eq(*candidate, node))
+            .map(|(path, _)| path)
+    }
+}
+
+/// The segments of a jq style selector, see [`Path::from_jq`]
+fn jq_segments(selector: &str) -> Result<Vec<Segment>, String> {
+    let sel = selector.trim();
+    let mut segments = vec![];
+    let mut rest = sel;
+    while !rest.is_empty() {
+        if let Some(r) = rest.strip_prefix('.') {
+            rest = r;
+            if rest.is_empty() && segments.is_empty() {
+                break;
+            }
+        }
+        if let Some(r) = rest.strip_prefix('[') {
+            let r = r.trim_start();
+            let (segment, r) = if let Some(quoted) = r.strip_prefix('"') {
+                let end = find_closing_quote(quoted)
+                    .ok_or_else(|| format!("Unterminated string in selector {}", selector))?;
+                (Segment::Key(unescape(&quoted[..end])?), &quoted[end + 1..])
+            } else {
+                let end = r.find(|c: char| !c.is_ascii_digit()).unwrap_or(r.len());
+                let index = r[..end]
+                    .parse::<usize>()
+                    .map_err(|e| format!("Invalid index in selector {}: {}", selector, e))?;
+                (Segment::Index(index), &r[end..])
+            };
+            rest = r
+                .trim_start()
+                .strip_prefix(']')
+                .ok_or_else(|| format!("Expected ']' in selector {}", selector))?;
+            segments.push(segment);
+        } else {
+            let end = rest.find(['.', '[']).unwrap_or(rest.len());
+            if end == 0 {
+                return Err(format!("Empty key in selector {}", selector));
+            }
+            segments.push(Segment::Key(rest[..end].to_string()));
+            rest = &rest[end..];
+        }
+    }
+    Ok(segments)
+}
+
+fn find_closing_quote(s: &str) -> Option<usize> {
+    let mut escaped = false;
+    for (i, c) in s.char_indices() {
+        match c {
+            '\\' if !escaped => escaped = true,
+            '"' if !escaped => return Some(i),
+            _ => escaped = false,
+        }
+    }
+    None
+}
+
+/// Resolve the `~0` and `~1` escape sequences in a JSON Pointer token
+pub(crate) fn unescape_pointer(token: &str) -> Result<String, String> {
+    let mut key = String::with_capacity(token.len());
+    let mut chars = token.chars();
+    while let Some(c) = chars.next() {
+        if c != '~' {
+            key.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('0') => key.push('~'),
+            Some('1') => key.push('/'),
+            _ => return Err(format!("Invalid escape sequence in pointer token {}", token)),
+        }
+    }
+    Ok(key)
+}
+
+/// Resolve JSON style escape sequences in a quoted key
+pub(crate) fn unescape(s: &str) -> Result<String, String> {
+    let mut result = String::with_capacity(s.len());
+    let mut chars = s.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            result.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('"') => result.push('"'),
+            Some('\\') => result.push('\\'),
+            Some('/') => result.push('/'),
+            Some('b') => result.push('\u{8}'),
+            Some('f') => result.push('\u{c}'),
+            Some('n') => result.push('\n'),
+            Some('r') => result.push('\r'),
+            Some('t') => result.push('\t'),
+            Some('u') => {
+                let mut code = parse_hex4(&mut chars)?;
+                if (0xD800..0xDC00).contains(&code) {
+                    if chars.next() != Some('\\') || chars.next() != Some('u') {
+                        return Err(format!("Unpaired surrogate in {}", s));
+                    }
+                    let low = parse_hex4(&mut chars)?;
+                    if !(0xDC00..0xE000).contains(&low) {
+                        return Err(format!("Unpaired surrogate in {}", s));
+                    }
+                    code = 0x10000 + ((code - 0xD800) << 10) + (low - 0xDC00);
+                }
+                result.push(
+                    std::char::from_u32(code)
+                        .ok_or_else(|| format!("Invalid unicode escape in {}", s))?,
+                );
+            }
+            _ => return Err(format!("Invalid escape sequence in {}", s)),
+        }
+    }
+    Ok(result)
+}
+
+fn parse_hex4(chars: &mut std::str::Chars) -> Result<u32, String> {
+    let hex: String = chars.take(4).collect();
+    // from_str_radix alone would also accept a sign, e.g. `\u+041`
+    if hex.len() != 4 || !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+        return Err(format!("Invalid unicode escape \\u{}", hex));
+    }
+    u32::from_str_radix(&hex, 16).map_err(|e| format!("Invalid unicode escape \\u{}: {}", hex, e))
+}
@@ -0,0 +1,257 @@
+use crate::*;
+use std::fmt::{self, Write};
+use std::io;
+
+/// Formats documents for humans, with control over how much of a large document is shown.
+/// `{:#}` uses the default settings.
+///
+/// ```
+/// use unstructured::{Document, DocumentFormatter};
+///
+/// let doc: Document =
+///     serde_json::from_str(r#"{"id": "abcdef", "tags": [1, 2, 3, 4], "deep": {"a": {"b": 1}}}"#)
+///         .unwrap();
+/// let summary = DocumentFormatter::new()
+///     .indent("")
+///     .max_depth(2)
+///     .max_elements(3)
+///     .max_string_len(3)
+///     .sort_keys(true)
+///     .format(&doc);
+/// assert_eq!(summary, r#"{"deep": {"a": {...}}, "id": "abc"..., "tags": [1, 2, 3, ... 1 more]}"#);
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DocumentFormatter {
+    indent: String,
+    max_depth: Option<usize>,
+    max_elements: Option<usize>,
+    max_string_len: Option<usize>,
+    sort_keys: bool,
+    bytes: BytesFormat,
+}
+
+impl Default for DocumentFormatter {
+    fn default() -> Self {
+        DocumentFormatter {
+            indent: "  ".to_string(),
+            max_depth: None,
+            max_elements: None,
+            max_string_len: None,
+            sort_keys: false,
+            bytes: BytesFormat::default(),
+        }
+    }
+}
+
+impl DocumentFormatter {
+    /// A formatter that indents with two spaces and shows everything
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The string written once per level of nesting, an empty string writes a single line
+    pub fn indent(mut self, indent: &str) -> Self {
+        self.indent = indent.to_string();
+        self
+    }
+
+    /// Containers nested deeper than this are written as `[...]` or `{...}`
+    pub fn max_depth(mut self, depth: usize) -> Self {
+        self.max_depth = Some(depth);
+        self
+    }
+
+    /// Only show the first `count` elements of sequences and entries of maps
+    pub fn max_elements(mut self, count: usize) -> Self {
+        self.max_elements = Some(count);
+        self
+    }
+
+    /// Only show the first `len` characters of string values, map keys are always shown in full
+    pub fn max_string_len(mut self, len: usize) -> Self {
+        self.max_string_len = Some(len);
+        self
+    }
+
+    /// Write map entries sorted by key, maps are already sorted without `preserve_order`
+    pub fn sort_keys(mut self, sort: bool) -> Self {
+        self.sort_keys = sort;
+        self
+    }
+
+    /// How `Bytes` are written
+    pub fn bytes(mut self, format: BytesFormat) -> Self {
+        self.bytes = format;
+        self
+    }
+
+    /// Format a document into a new string
+    pub fn format<T: UnstructuredDataTrait>(&self, doc: &Unstructured<T>) -> String {
+        let mut out = String::new();
+        // Writing to a String can't fail
+        let _ = self.write(&mut out, doc, 0);
+        out
+    }
+
+    /// Write a formatted document to a `fmt::Write`, such as a `String` or a `Formatter`
+    pub fn write_to<T: UnstructuredDataTrait, W: Write>(
+        &self,
+        out: &mut W,
+        doc: &Unstructured<T>,
+    ) -> fmt::Result {
+        self.write(out, doc, 0)
+    }
+
+    /// Write a formatted document to an `io::Write`, such as a file or a socket
+    pub fn write_io<T: UnstructuredDataTrait, W: io::Write>(
+        &self,
+        out: &mut W,
+        doc: &Unstructured<T>,
+    ) -> io::Result<()> {
+        let mut adapter = IoAdapter { out, error: None };
+        match self.write(&mut adapter, doc, 0) {
+            Ok(()) => Ok(()),
+            Err(_) => Err(adapter
+                .error
+                .unwrap_or_else(|| io::Error::new(io::ErrorKind::Other, "formatter error"))),
+        }
+    }
+
+    fn write<T: UnstructuredDataTrait, W: Write>(
+        &self,
+        out: &mut W,
+        doc: &Unstructured<T>,
+        depth: usize,
+    ) -> fmt::Result {
+        match doc {
+            Unstructured::<T>::Null | Unstructured::<T>::Option(None) => out.write_str("null"),
+            Unstructured::<T>::Unassigned => out.write_str("(unassigned)"),
+            Unstructured::<T>::String(s) => self.write_string(out, s),
+            Unstructured::<T>::Char(c) => write_quoted(out, c.encode_utf8(&mut [0; 4]), '\''),
+            Unstructured::<T>::Bytes(b) => self.bytes.write(out, b),
+            Unstructured::<T>::Option(Some(v)) | Unstructured::<T>::Newtype(v) => {
+                self.write(out, v, depth)
+            }
+            Unstructured::<T>::Seq(seq) if seq.is_empty() => out.write_str("[]"),
+            Unstructured::<T>::Map(map) if map.is_empty() => out.write_str("{}"),
+            Unstructured::<T>::Seq(_) if self.max_depth.is_some_and(|max| depth >= max) => {
+                out.write_str("[...]")
+            }
+            Unstructured::<T>::Map(_) if self.max_depth.is_some_and(|max| depth >= max) => {
+                out.write_str("{...}")
+            }
+            Unstructured::<T>::Seq(seq) => {
+                self.write_entries(out, ('[', ']'), seq.iter().map(|v| (None, v)), depth)
+            }
+            Unstructured::<T>::Map(map) => {
+                let mut entries: Vec<_> = map.iter().collect();
+                if self.sort_keys {
+                    entries.sort_by(|a, b| a.0.cmp(b.0));
+                }
+                let entries = entries.into_iter().map(|(k, v)| (Some(k), v));
+                self.write_entries(out, ('{', '}'), entries, depth)
+            }
+            other => write!(out, "{}", other),
+        }
+    }
+
+    fn write_entries<'d, T, W, I>(
+        &self,
+        out: &mut W,
+        (open, close): (char, char),
+        entries: I,
+        depth: usize,
+    ) -> fmt::Result
+    where
+        T: UnstructuredDataTrait + 'd,
+        W: Write,
+        I: ExactSizeIterator<Item = (Option<&'d Unstructured<T>>, &'d Unstructured<T>)>,
+    {
+        let len = entries.len();
+        let shown = self.max_elements.map_or(len, |max| max.min(len));
+        out.write_char(open)?;
+        for (i, (key, val)) in entries.take(shown).enumerate() {
+            self.write_separator(out, i == 0, depth + 1)?;
+            // Keys are never truncated, only values
+            match key {
+                Some(Unstructured::<T>::String(key)) => write_quoted(out, key, '"')?,
+                Some(key) => self.write(out, key, depth + 1)?,
+                None => {}
+            }
+            if key.is_some() {
+                out.write_str(": ")?;
+            }
+            self.write(out, val, depth + 1)?;
+        }
+        if shown < len {
+            self.write_separator(out, shown == 0, depth + 1)?;
+            write!(out, "... {} more", len - shown)?;
+        }
+        if !self.indent.is_empty() {
+            out.write_char('\n')?;
+            self.write_indent(out, depth)?;
+        }
+        out.write_char(close)
+    }
+
+    fn write_separator<W: Write>(&self, out: &mut W, first: bool, depth: usize) -> fmt::Result {
+        match (first, self.indent.is_empty()) {
+            (true, true) => Ok(()),
+            (false, true) => out.write_str(", "),
+            (true, false) => {
+                out.write_char('\n')?;
+                self.write_indent(out, depth)
+            }
+            (false, false) => {
+                out.write_str(",\n")?;
+                self.write_indent(out, depth)
+            }
+        }
+    }
+
+    fn write_indent<W: Write>(&self, out: &mut W, depth: usize) -> fmt::Result {
+        (0..depth).try_for_each(|_| out.write_str(&self.indent))
+    }
+
+    fn write_string<W: Write>(&self, out: &mut W, s: &str) -> fmt::Result {
+        match self.max_string_len.and_then(|max| s.char_indices().nth(max)) {
+            Some((end, _)) => {
+                write_quoted(out, &s[..end], '"')?;
+                out.write_str("...")
+            }
+            None => write_quoted(out, s, '"'),
+        }
+    }
+}
+
+/// Quote a string, escaping the quote, backslashes and control characters
+fn write_quoted<W: Write>(out: &mut W, s: &str, quote: char) -> fmt::Result {
+    out.write_char(quote)?;
+    for c in s.chars() {
+        match c {
+            '\\' => out.write_str("\\\\")?,
+            '\n' => out.write_str("\\n")?,
+            '\r' => out.write_str("\\r")?,
+            '\t' => out.write_str("\\t")?,
+            c if c == quote => write!(out, "\\{}", c)?,
+            c if c.is_control() => write!(out, "\\u{{{:04x}}}", c as u32)?,
+            c => out.write_char(c)?,
+        }
+    }
+    out.write_char(quote)
+}
+
+/// Lets the formatter write to an `io::Write`, keeping the io error that stopped it
+struct IoAdapter<'a, W: io::Write> {
+    out: &'a mut W,
+    error: Option<io::Error>,
+}
+
+impl<'a, W: io::Write> Write for IoAdapter<'a, W> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        self.out.write_all(s.as_bytes()).map_err(|e| {
+            self.error = Some(e);
+            fmt::Error
+        })
+    }
+}
@@ -0,0 +1,58 @@
+use crate::*;
+
+impl<T: UnstructuredDataTrait> Unstructured<T> {
+    /// Build a new document holding only the parts of this one that `shape` asks for, in the
+    /// style of a GraphQL selection set:
+    ///
+    /// - A map shape keeps the keys it lists that exist in the document, each projected with
+    ///   the shape it maps to. Applied to a sequence, it projects every element
+    /// - A sequence shape `[shape]` projects every element of a sequence with `shape`, an empty
+    ///   sequence shape keeps the sequence as it is
+    /// - `false` leaves a key out, any other value keeps it as it is, so unit values and `null`
+    ///   can be used for the leaves of a shape
+    ///
+    /// Values that don't fit the shape, such as a string where a map is expected, become null.
+    ///
+    /// ```
+    /// use unstructured::Document;
+    ///
+    /// let doc: Document = serde_json::from_str(
+    ///     r#"{"id": 1, "secret": "x", "owner": {"name": "a", "email": "b"},
+    ///         "tags": [{"name": "t", "color": "red"}]}"#,
+    /// )
+    /// .unwrap();
+    /// let shape: Document = serde_json::from_str(
+    ///     r#"{"id": null, "owner": {"name": null}, "tags": [{"name": null}]}"#,
+    /// )
+    /// .unwrap();
+    /// let expected: Document =
+    ///     serde_json::from_str(r#"{"id": 1, "owner": {"name": "a"}, "tags": [{"name": "t"}]}"#)
+    ///         .unwrap();
+    /// assert_eq!(doc.project(&shape), expected);
+    /// ```
+    pub fn project(&self, shape: &Self) -> Self {
+        match (self, shape) {
+            (_, Self::Option(Some(shape))) | (_, Self::Newtype(shape)) => self.project(shape),
+            (Self::Option(Some(inner)), _) => Self::Option(Some(Box::new(inner.project(shape)))),
+            (Self::Newtype(inner), _) => Self::Newtype(Box::new(inner.project(shape))),
+            (Self::Map(map), Self::Map(fields)) => {
+                let mut projected = Mapping::<T>::new();
+                for (key, field) in fields.iter() {
+                    if let (Some(val), false) = (map.get(key), matches!(field, Self::Bool(false))) {
+                        projected.insert(key.clone(), val.project(field));
+                    }
+                }
+                Self::Map(share(projected))
+            }
+            (Self::Seq(seq), Self::Map(_)) => {
+                Self::Seq(share(seq.iter().map(|val| val.project(shape)).collect()))
+            }
+            (Self::Seq(seq), Self::Seq(shapes)) => match shapes.first() {
+                Some(shape) => Self::Seq(share(seq.iter().map(|val| val.project(shape)).collect())),
+                None => self.clone(),
+            },
+            (_, Self::Map(_)) | (_, Self::Seq(_)) => Self::Null,
+            _ => self.clone(),
+        }
+    }
+}
@@ -0,0 +1,197 @@
+use crate::*;
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fmt;
+
+/// The expected shape of a document, used by [`Unstructured::coerce`]
+#[derive(Debug, Clone, PartialEq)]
+pub enum Schema {
+    /// Any value is accepted as is
+    Any,
+    Bool,
+    /// A signed or unsigned integer
+    Integer,
+    Float,
+    String,
+    /// A timestamp, strings are parsed as RFC 3339
+    #[cfg(feature = "chrono")]
+    DateTime,
+    /// `null`, or a value matching the inner schema. Empty strings, such as empty CSV fields,
+    /// become `null`.
+    Optional(Box<Schema>),
+    /// A sequence where every element matches the inner schema
+    Seq(Box<Schema>),
+    /// A map where every value matches the inner schema
+    Map(Box<Schema>),
+    /// A map with known fields, fields that are missing or not listed are left alone
+    Struct(BTreeMap<String, Schema>),
+}
+
+impl Schema {
+    /// The name of the type the schema expects
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Schema::Any => "any",
+            Schema::Bool => "bool",
+            Schema::Integer => "integer",
+            Schema::Float => "float",
+            Schema::String => "string",
+            #[cfg(feature = "chrono")]
+            Schema::DateTime => "datetime",
+            Schema::Optional(_) => "optional",
+            Schema::Seq(_) => "seq",
+            Schema::Map(_) | Schema::Struct(_) => "map",
+        }
+    }
+}
+
+/// A value that could not be coerced to the type expected by a [`Schema`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CoerceError {
+    /// Location of the value within the document
+    pub path: Path,
+    /// The type the schema expected
+    pub expected: &'static str,
+    /// The type of the value that was found
+    pub found: &'static str,
+}
+
+impl fmt::Display for CoerceError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Cannot coerce {} to {}", self.found, self.expected)?;
+        if !self.path.is_empty() {
+            write!(f, " at {}", self.path)?;
+        }
+        Ok(())
+    }
+}
+
+impl Error for CoerceError {}
+
+impl<T: UnstructuredDataTrait> Unstructured<T> {
+    /// Convert values in place to the types expected by the schema where this can be done
+    /// without losing information, e.g. the string `"42"` to a number, `1` to `true` or `2.0`
+    /// to an integer. This is useful for formats where every value is a string, such as CSV.
+    /// Every value that could not be coerced is reported with its path and left unchanged.
+    ///
+    /// ```
+    /// use unstructured::{Document, Schema};
+    ///
+    /// let mut doc: Document =
+    ///     serde_json::from_str(r#"{"port": "8080", "debug": "0", "ratio": "half"}"#).unwrap();
+    /// let schema = Schema::Struct(
+    ///     vec![
+    ///         ("port".to_string(), Schema::Integer),
+    ///         ("debug".to_string(), Schema::Bool),
+    ///         ("ratio".to_string(), Schema::Float),
+    ///     ]
+    ///     .into_iter()
+    ///     .collect(),
+    /// );
+    /// let errors = doc.coerce(&schema).unwrap_err();
+    /// assert_eq!(doc["port"], 8080);
+    /// assert_eq!(doc["debug"], false);
+    /// assert_eq!(errors[0].to_string(), "Cannot coerce string to float at /ratio");
+    /// ```
+    pub fn coerce(&mut self, schema: &Schema) -> Result<(), Vec<CoerceError>> {
+        let mut errors = vec![];
+        self.coerce_at(schema, &mut Path::new(), &mut errors);
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+
+    /// The same as [`coerce`](Unstructured::coerce)
+    #[deprecated(note = "use coerce")]
+    pub fn coerce_to_schema(&mut self, schema: &Schema) -> Result<(), Vec<CoerceError>> {
+        self.coerce(schema)
+    }
+
+    fn coerce_at(&mut self, schema: &Schema, path: &mut Path, errors: &mut Vec<CoerceError>) {
+        let coerced = match (schema, &mut *self) {
+            (Schema::Any, _) => true,
+            (Schema::Optional(_), Self::Null | Self::Unassigned | Self::Option(None)) => true,
+            (Schema::Optional(_), Self::String(s)) if s.is_empty() => {
+                *self = Self::Null;
+                true
+            }
+            (Schema::Optional(inner), _) => return self.coerce_at(inner, path, errors),
+            (Schema::Seq(inner), Self::Seq(seq)) => {
+                for (i, val) in make_mut(seq).iter_mut().enumerate() {
+                    path.push(i);
+                    val.coerce_at(inner, path, errors);
+                    path.pop();
+                }
+                true
+            }
+            (Schema::Map(inner), Self::Map(map)) => {
+                for (key, val) in make_mut(map).iter_mut() {
+                    path.push(Segment::from_key(key));
+                    val.coerce_at(inner, path, errors);
+                    path.pop();
+                }
+                true
+            }
+            (Schema::Struct(fields), Self::Map(map)) => {
+                let map = make_mut(map);
+                for (name, field) in fields.iter() {
+                    if let Some(val) = map.get_mut(&Self::from(name.as_str())) {
+                        path.push(name.as_str());
+                        val.coerce_at(field, path, errors);
+                        path.pop();
+                    }
+                }
+                true
+            }
+            (Schema::Seq(_) | Schema::Map(_) | Schema::Struct(_), _) => false,
+            (Schema::Bool, Self::Bool(_))
+            | (Schema::String, Self::String(_))
+            | (Schema::Float, Self::Number(Number::F32(_) | Number::F64(_))) => true,
+            (Schema::Integer, Self::Number(n)) if !n.is_float() => true,
+            #[cfg(feature = "chrono")]
+            (Schema::DateTime, Self::DateTime(_)) => true,
+            (_, scalar) => match scalar.coerce_scalar(schema) {
+                Some(val) => {
+                    *scalar = val;
+                    true
+                }
+                None => false,
+            },
+        };
+        if !coerced {
+            errors.push(CoerceError {
+                path: path.clone(),
+                expected: schema.type_name(),
+                found: self.type_name(),
+            });
+        }
+    }
+
+    /// Convert a single value to a scalar schema, or None if it can't be done losslessly
+    fn coerce_scalar(&self, schema: &Schema) -> Option<Self> {
+        match (schema, self) {
+            (Schema::Bool, Self::String(s)) if s == "true" || s == "false" => {
+                Some(Self::Bool(s == "true"))
+            }
+            (Schema::Bool, _) => match self.clone().cast::<u8>()? {
+                0 => Some(Self::Bool(false)),
+                1 => Some(Self::Bool(true)),
+                _ => None,
+            },
+            (Schema::Integer, _) => self
+                .clone()
+                .cast::<u64>()
+                .map(Self::from)
+                .or_else(|| self.clone().cast::<i64>().map(Self::from)),
+            (Schema::Float, _) => self.clone().cast::<f64>().map(Self::from),
+            #[cfg(feature = "chrono")]
+            (Schema::DateTime, _) => self.clone().cast().map(Self::DateTime),
+            (Schema::String, Self::Number(n)) => Some(Self::String(n.to_string())),
+            (Schema::String, Self::Bool(b)) => Some(Self::String(b.to_string())),
+            (Schema::String, Self::Char(c)) => Some(Self::String(c.to_string())),
+            _ => None,
+        }
+    }
+}
@@ -1,5 +1,4 @@
 use serde::ser;
-use std::collections::BTreeMap;
 use std::error::Error;
 use std::fmt;
 use std::marker::PhantomData;
@@ -45,18 +44,75 @@ impl<T: UnstructuredDataTrait> ser::Serialize for Unstructured<T> {
             Unstructured::<T>::Seq(ref v) => v.serialize(s),
             Unstructured::<T>::Map(ref v) => v.serialize(s),
             Unstructured::<T>::Bytes(ref v) => s.serialize_bytes(v),
+            #[cfg(feature = "chrono")]
+            Unstructured::<T>::DateTime(ref v) => s.serialize_str(&v.to_rfc3339()),
             Unstructured::<T>::Unassigned => s.serialize_unit(),
             Unstructured::<T>::Err(ref e) => s.serialize_str(e.to_string().as_str()),
-            Unstructured::<T>::Other(..) => s.serialize_str("other"),
+            Unstructured::<T>::Other(ref o) => T::serialize_other(o, s),
         }
     }
 }
 
-pub struct Serializer<T: UnstructuredDataTrait>(std::marker::PhantomData<T>);
+/// What to do when a map being serialized has the same key more than once
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DedupPolicy {
+    /// Keep the last value written for the key
+    #[default]
+    LastWins,
+    /// Keep the first value written for the key
+    FirstWins,
+    /// Fail serialization
+    Error,
+}
+
+/// Options for normalizing values while they are serialized into a document, see
+/// [`Unstructured::new_with`] and [`Unstructured::with_options`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SerializerOptions {
+    /// Sort map keys, only needed with the `preserve_order` feature as maps are sorted otherwise
+    pub sort_keys: bool,
+    /// How duplicate map keys are handled
+    pub dedup_policy: DedupPolicy,
+    /// Write `()` and unit structs as `Null`, otherwise they become `Unassigned`. Unit variants
+    /// are always written as their variant name.
+    pub unit_as_null: bool,
+    /// Write bytes as a base64 `String` instead of `Bytes`
+    pub bytes_as_base64: bool,
+}
+
+impl Default for SerializerOptions {
+    fn default() -> Self {
+        SerializerOptions {
+            sort_keys: false,
+            dedup_policy: DedupPolicy::LastWins,
+            unit_as_null: true,
+            bytes_as_base64: false,
+        }
+    }
+}
+
+pub struct Serializer<T: UnstructuredDataTrait> {
+    options: SerializerOptions,
+    marker: PhantomData<T>,
+}
 
 impl<T: UnstructuredDataTrait> Serializer<T> {
     pub fn new() -> Self {
-        Serializer(PhantomData)
+        Self::with_options(SerializerOptions::default())
+    }
+
+    /// A serializer that applies `options` to every value it writes
+    pub fn with_options(options: SerializerOptions) -> Self {
+        Serializer {
+            options,
+            marker: PhantomData,
+        }
+    }
+}
+
+impl<T: UnstructuredDataTrait> Default for Serializer<T> {
+    fn default() -> Self {
+        Self::new()
     }
 }
 
@@ -92,7 +148,7 @@ impl<T: UnstructuredDataTrait> ser::Serializer for Serializer<T> {
     }
 
     fn serialize_i128(self, v: i128) -> Result<Self::Ok, Self::Error> {
-        Ok(Unstructured::<T>::Number(Number::I128(v)))
+        Ok(Unstructured::<T>::Number(Number::from(v)))
     }
 
     fn serialize_u8(self, v: u8) -> Result<Self::Ok, Self::Error> {
@@ -112,7 +168,7 @@ impl<T: UnstructuredDataTrait> ser::Serializer for Serializer<T> {
     }
 
     fn serialize_u128(self, v: u128) -> Result<Self::Ok, Self::Error> {
-        Ok(Unstructured::<T>::Number(Number::U128(v)))
+        Ok(Unstructured::<T>::Number(Number::from(v)))
     }
 
     fn serialize_f32(self, v: f32) -> Result<Self::Ok, Self::Error> {
@@ -132,279 +188,399 @@ impl<T: UnstructuredDataTrait> ser::Serializer for Serializer<T> {
     }
 
     fn serialize_bytes(self, v: &[u8]) -> Result<Self::Ok, Self::Error> {
-        Ok(Unstructured::<T>::Bytes(v.to_vec()))
+        if self.options.bytes_as_base64 {
+            Ok(Unstructured::<T>::String(encode_base64(v)))
+        } else {
+            Ok(Unstructured::<T>::Bytes(v.to_vec()))
+        }
     }
 
     fn serialize_none(self) -> Result<Self::Ok, Self::Error> {
         Ok(Unstructured::<T>::Option(None))
     }
 
-    fn serialize_some<Q: ?Sized>(self, document: &Q) -> Result<Self::Ok, Self::Error>
+    fn serialize_some<Q>(self, document: &Q) -> Result<Self::Ok, Self::Error>
     where
-        Q: ser::Serialize,
+        Q: ser::Serialize + ?Sized,
     {
         document
-            .serialize(Serializer(PhantomData))
+            .serialize(Serializer::with_options(self.options))
             .map(|v| Unstructured::<T>::Option(Some(Box::new(v))))
     }
 
     fn serialize_unit(self) -> Result<Self::Ok, Self::Error> {
-        Ok(Unstructured::<T>::Null)
+        if self.options.unit_as_null {
+            Ok(Unstructured::<T>::Null)
+        } else {
+            Ok(Unstructured::<T>::Unassigned)
+        }
     }
 
     fn serialize_unit_struct(self, _name: &'static str) -> Result<Self::Ok, Self::Error> {
-        Ok(Unstructured::<T>::Null)
+        self.serialize_unit()
     }
 
     fn serialize_unit_variant(
         self,
         _name: &'static str,
         _variant_index: u32,
-        _variant: &'static str,
+        variant: &'static str,
     ) -> Result<Self::Ok, Self::Error> {
-        Ok(Unstructured::<T>::Null)
+        // Adjacently tagged enums write their tag this way, so it has to keep the name
+        Ok(Unstructured::<T>::String(variant.to_string()))
     }
 
-    fn serialize_newtype_struct<Q: ?Sized>(
+    fn serialize_newtype_struct<Q>(
         self,
         _name: &'static str,
         document: &Q,
     ) -> Result<Self::Ok, Self::Error>
     where
-        Q: ser::Serialize,
+        Q: ser::Serialize + ?Sized,
     {
         document
-            .serialize(Serializer(PhantomData))
+            .serialize(Serializer::with_options(self.options))
             .map(|v| Unstructured::<T>::Newtype(Box::new(v)))
     }
 
-    fn serialize_newtype_variant<Q: ?Sized>(
+    fn serialize_newtype_variant<Q>(
         self,
         _name: &'static str,
         _variant_index: u32,
-        _variant: &'static str,
+        variant: &'static str,
         document: &Q,
     ) -> Result<Self::Ok, Self::Error>
     where
-        Q: ser::Serialize,
+        Q: ser::Serialize + ?Sized,
     {
-        document
-            .serialize(Serializer(PhantomData))
-            .map(|v| Unstructured::<T>::Newtype(Box::new(v)))
+        let document = document.serialize(Serializer::with_options(self.options))?;
+        Ok(tag_variant(variant, document))
     }
 
-    fn serialize_seq(self, _len: Option<usize>) -> Result<Self::SerializeSeq, Self::Error> {
-        Ok(SerializeSeq(vec![]))
+    fn serialize_seq(self, len: Option<usize>) -> Result<Self::SerializeSeq, Self::Error> {
+        Ok(SerializeSeq(Vec::with_capacity(len.unwrap_or(0)), self.options))
     }
 
-    fn serialize_tuple(self, _len: usize) -> Result<Self::SerializeTuple, Self::Error> {
-        Ok(SerializeTuple(vec![]))
+    fn serialize_tuple(self, len: usize) -> Result<Self::SerializeTuple, Self::Error> {
+        Ok(SerializeTuple(Vec::with_capacity(len), self.options))
     }
 
     fn serialize_tuple_struct(
         self,
         _name: &'static str,
-        _len: usize,
+        len: usize,
     ) -> Result<Self::SerializeTupleStruct, Self::Error> {
-        Ok(SerializeTupleStruct(vec![]))
+        Ok(SerializeTupleStruct(Vec::with_capacity(len), self.options))
     }
 
     fn serialize_tuple_variant(
         self,
         _name: &'static str,
         _variant_index: u32,
-        _variant: &'static str,
-        _len: usize,
+        variant: &'static str,
+        len: usize,
     ) -> Result<Self::SerializeTupleVariant, Self::Error> {
-        Ok(SerializeTupleVariant(vec![]))
+        Ok(SerializeTupleVariant(variant, Vec::with_capacity(len), self.options))
     }
 
-    fn serialize_map(self, _len: Option<usize>) -> Result<Self::SerializeMap, Self::Error> {
+    fn serialize_map(self, len: Option<usize>) -> Result<Self::SerializeMap, Self::Error> {
         Ok(SerializeMap {
-            map: BTreeMap::new(),
+            map: Mapping::with_capacity(len.unwrap_or(0)),
             key: None,
+            options: self.options,
         })
     }
 
     fn serialize_struct(
         self,
         _name: &'static str,
-        _len: usize,
+        len: usize,
     ) -> Result<Self::SerializeStruct, Self::Error> {
-        Ok(SerializeStruct(BTreeMap::new()))
+        Ok(SerializeStruct(Mapping::with_capacity(len), self.options))
     }
 
     fn serialize_struct_variant(
         self,
         _name: &'static str,
         _variant_index: u32,
-        _variant: &'static str,
-        _len: usize,
+        variant: &'static str,
+        len: usize,
     ) -> Result<Self::SerializeStructVariant, Self::Error> {
-        Ok(SerializeStructVariant(BTreeMap::new()))
+        Ok(SerializeStructVariant(variant, Mapping::with_capacity(len), self.options))
     }
 }
 
-pub struct SerializeSeq<T: UnstructuredDataTrait>(Sequence<T>);
+pub struct SerializeSeq<T: UnstructuredDataTrait>(Sequence<T>, SerializerOptions);
 
 impl<T: UnstructuredDataTrait> ser::SerializeSeq for SerializeSeq<T> {
     type Ok = Unstructured<T>;
     type Error = SerializerError;
 
-    fn serialize_element<Q: ?Sized>(&mut self, document: &Q) -> Result<(), Self::Error>
+    fn serialize_element<Q>(&mut self, document: &Q) -> Result<(), Self::Error>
     where
-        Q: ser::Serialize,
+        Q: ser::Serialize + ?Sized,
     {
-        let document = document.serialize(Serializer(PhantomData))?;
+        let document = document.serialize(Serializer::with_options(self.1))?;
         self.0.push(document);
         Ok(())
     }
 
     fn end(self) -> Result<Self::Ok, Self::Error> {
-        Ok(Unstructured::<T>::Seq(self.0))
+        Ok(Unstructured::<T>::Seq(share(self.0)))
     }
 }
 
-pub struct SerializeTuple<T: UnstructuredDataTrait>(Sequence<T>);
+pub struct SerializeTuple<T: UnstructuredDataTrait>(Sequence<T>, SerializerOptions);
 
 impl<T: UnstructuredDataTrait> ser::SerializeTuple for SerializeTuple<T> {
     type Ok = Unstructured<T>;
     type Error = SerializerError;
 
-    fn serialize_element<Q: ?Sized>(&mut self, document: &Q) -> Result<(), Self::Error>
+    fn serialize_element<Q>(&mut self, document: &Q) -> Result<(), Self::Error>
     where
-        Q: ser::Serialize,
+        Q: ser::Serialize + ?Sized,
     {
-        let document = document.serialize(Serializer(PhantomData))?;
+        let document = document.serialize(Serializer::with_options(self.1))?;
         self.0.push(document);
         Ok(())
     }
 
     fn end(self) -> Result<Self::Ok, Self::Error> {
-        Ok(Unstructured::<T>::Seq(self.0))
+        Ok(Unstructured::<T>::Seq(share(self.0)))
     }
 }
 
-pub struct SerializeTupleStruct<T: UnstructuredDataTrait>(Sequence<T>);
+pub struct SerializeTupleStruct<T: UnstructuredDataTrait>(Sequence<T>, SerializerOptions);
 
 impl<T: UnstructuredDataTrait> ser::SerializeTupleStruct for SerializeTupleStruct<T> {
     type Ok = Unstructured<T>;
     type Error = SerializerError;
 
-    fn serialize_field<Q: ?Sized>(&mut self, document: &Q) -> Result<(), Self::Error>
+    fn serialize_field<Q>(&mut self, document: &Q) -> Result<(), Self::Error>
     where
-        Q: ser::Serialize,
+        Q: ser::Serialize + ?Sized,
     {
-        let document = document.serialize(Serializer(PhantomData))?;
+        let document = document.serialize(Serializer::with_options(self.1))?;
         self.0.push(document);
         Ok(())
     }
 
     fn end(self) -> Result<Self::Ok, Self::Error> {
-        Ok(Unstructured::<T>::Seq(self.0))
+        Ok(Unstructured::<T>::Seq(share(self.0)))
     }
 }
 
-pub struct SerializeTupleVariant<T: UnstructuredDataTrait>(Sequence<T>);
+pub struct SerializeTupleVariant<T: UnstructuredDataTrait>(
+    &'static str,
+    Sequence<T>,
+    SerializerOptions,
+);
 
 impl<Q: UnstructuredDataTrait> ser::SerializeTupleVariant for SerializeTupleVariant<Q> {
     type Ok = Unstructured<Q>;
     type Error = SerializerError;
 
-    fn serialize_field<T: ?Sized>(&mut self, document: &T) -> Result<(), Self::Error>
+    fn serialize_field<T>(&mut self, document: &T) -> Result<(), Self::Error>
     where
-        T: ser::Serialize,
+        T: ser::Serialize + ?Sized,
     {
-        let document = document.serialize(Serializer(PhantomData))?;
-        self.0.push(document);
+        let document = document.serialize(Serializer::with_options(self.2))?;
+        self.1.push(document);
         Ok(())
     }
 
     fn end(self) -> Result<Self::Ok, Self::Error> {
-        Ok(Unstructured::<Q>::Seq(self.0))
+        Ok(tag_variant(self.0, Unstructured::<Q>::Seq(share(self.1))))
     }
 }
 
 pub struct SerializeMap<T: UnstructuredDataTrait> {
     map: Mapping<T>,
     key: Option<Unstructured<T>>,
+    options: SerializerOptions,
 }
 
 impl<R: UnstructuredDataTrait> ser::SerializeMap for SerializeMap<R> {
     type Ok = Unstructured<R>;
     type Error = SerializerError;
 
-    fn serialize_key<Q: ?Sized>(&mut self, key: &Q) -> Result<(), Self::Error>
+    fn serialize_key<Q>(&mut self, key: &Q) -> Result<(), Self::Error>
     where
-        Q: ser::Serialize,
+        Q: ser::Serialize + ?Sized,
     {
-        let key = key.serialize(Serializer(PhantomData))?;
+        let key = key.serialize(Serializer::with_options(self.options))?;
         self.key = Some(key);
         Ok(())
     }
 
-    fn serialize_value<Q: ?Sized>(&mut self, value: &Q) -> Result<(), Self::Error>
+    fn serialize_value<Q>(&mut self, value: &Q) -> Result<(), Self::Error>
     where
-        Q: ser::Serialize,
+        Q: ser::Serialize + ?Sized,
     {
-        let value = value.serialize(Serializer(PhantomData))?;
-        self.map.insert(self.key.take().unwrap(), value);
-        Ok(())
+        let value = value.serialize(Serializer::with_options(self.options))?;
+        let key = self
+            .key
+            .take()
+            .ok_or_else(|| SerializerError::Custom("Map value serialized without a key".into()))?;
+        insert_entry(&mut self.map, key, value, self.options)
+    }
+
+    // Serialize the key and value together, rather than holding the key until the value arrives
+    fn serialize_entry<K, V>(&mut self, key: &K, value: &V) -> Result<(), Self::Error>
+    where
+        K: ser::Serialize + ?Sized,
+        V: ser::Serialize + ?Sized,
+    {
+        let key = key.serialize(Serializer::with_options(self.options))?;
+        let value = value.serialize(Serializer::with_options(self.options))?;
+        insert_entry(&mut self.map, key, value, self.options)
     }
 
     fn end(self) -> Result<Self::Ok, Self::Error> {
-        Ok(Unstructured::<R>::Map(self.map))
+        Ok(Unstructured::<R>::Map(share(finish_map(self.map, self.options))))
     }
 }
 
-pub struct SerializeStruct<T: UnstructuredDataTrait>(Mapping<T>);
+pub struct SerializeStruct<T: UnstructuredDataTrait>(Mapping<T>, SerializerOptions);
 
 impl<Q: UnstructuredDataTrait> ser::SerializeStruct for SerializeStruct<Q> {
     type Ok = Unstructured<Q>;
     type Error = SerializerError;
 
-    fn serialize_field<T: ?Sized>(
+    fn serialize_field<T>(
         &mut self,
         key: &'static str,
         document: &T,
     ) -> Result<(), Self::Error>
     where
-        T: ser::Serialize,
+        T: ser::Serialize + ?Sized,
     {
         let key = Unstructured::<Q>::String(key.to_string());
-        let document = document.serialize(Serializer(PhantomData))?;
-        self.0.insert(key, document);
-        Ok(())
+        let document = document.serialize(Serializer::with_options(self.1))?;
+        insert_entry(&mut self.0, key, document, self.1)
     }
 
     fn end(self) -> Result<Self::Ok, Self::Error> {
-        Ok(Unstructured::<Q>::Map(self.0))
+        Ok(Unstructured::<Q>::Map(share(finish_map(self.0, self.1))))
     }
 }
 
 pub struct SerializeStructVariant<T: UnstructuredDataTrait>(
+    &'static str,
     Mapping<T>,
+    SerializerOptions,
 );
 
 impl<Q: UnstructuredDataTrait> ser::SerializeStructVariant for SerializeStructVariant<Q> {
     type Ok = Unstructured<Q>;
     type Error = SerializerError;
 
-    fn serialize_field<T: ?Sized>(
+    fn serialize_field<T>(
         &mut self,
         key: &'static str,
         document: &T,
     ) -> Result<(), Self::Error>
     where
-        T: ser::Serialize,
+        T: ser::Serialize + ?Sized,
     {
         let key = Unstructured::<Q>::String(key.to_string());
-        let document = document.serialize(Serializer(PhantomData))?;
-        self.0.insert(key, document);
-        Ok(())
+        let document = document.serialize(Serializer::with_options(self.2))?;
+        insert_entry(&mut self.1, key, document, self.2)
     }
 
     fn end(self) -> Result<Self::Ok, Self::Error> {
-        Ok(Unstructured::<Q>::Map(self.0))
+        let fields = Unstructured::<Q>::Map(share(finish_map(self.1, self.2)));
+        Ok(tag_variant(self.0, fields))
+    }
+}
+
+/// Enum variants with data are written as a map with the variant name as its only key, which
+/// is what the deserializer expects and how `#[serde(flatten)]` writes them
+fn tag_variant<T: UnstructuredDataTrait>(
+    variant: &'static str,
+    document: Unstructured<T>,
+) -> Unstructured<T> {
+    let mut map = Mapping::new();
+    map.insert(Unstructured::<T>::String(variant.to_string()), document);
+    Unstructured::<T>::Map(share(map))
+}
+
+fn insert_entry<T: UnstructuredDataTrait>(
+    map: &mut Mapping<T>,
+    key: Unstructured<T>,
+    value: Unstructured<T>,
+    options: SerializerOptions,
+) -> Result<(), SerializerError> {
+    if map.contains_key(&key) {
+        match options.dedup_policy {
+            DedupPolicy::LastWins => {}
+            DedupPolicy::FirstWins => return Ok(()),
+            DedupPolicy::Error => {
+                return Err(SerializerError::Custom(format!("Duplicate map key {}", key)))
+            }
+        }
+    }
+    map.insert(key, value);
+    Ok(())
+}
+
+fn finish_map<T: UnstructuredDataTrait>(
+    mut map: Mapping<T>,
+    options: SerializerOptions,
+) -> Mapping<T> {
+    if options.sort_keys {
+        map.sort_entries();
+    }
+    map
+}
+
+/// Serializes a document with [`SerializerOptions`] applied, see [`Unstructured::with_options`]
+pub struct WithOptions<'a, T: UnstructuredDataTrait> {
+    doc: &'a Unstructured<T>,
+    options: SerializerOptions,
+}
+
+impl<'a, T: UnstructuredDataTrait> WithOptions<'a, T> {
+    pub(crate) fn new(doc: &'a Unstructured<T>, options: SerializerOptions) -> Self {
+        WithOptions { doc, options }
+    }
+
+    fn wrap(&self, doc: &'a Unstructured<T>) -> Self {
+        WithOptions {
+            doc,
+            options: self.options,
+        }
+    }
+}
+
+impl<'a, T: UnstructuredDataTrait> ser::Serialize for WithOptions<'a, T> {
+    fn serialize<S: ser::Serializer>(&self, s: S) -> Result<S::Ok, S::Error> {
+        use ser::{SerializeMap, SerializeSeq};
+        match self.doc {
+            Unstructured::<T>::Bytes(v) if self.options.bytes_as_base64 => {
+                s.serialize_str(&encode_base64(v))
+            }
+            Unstructured::<T>::Option(Some(v)) => s.serialize_some(&self.wrap(v)),
+            Unstructured::<T>::Newtype(v) => s.serialize_newtype_struct("", &self.wrap(v)),
+            Unstructured::<T>::Seq(v) => {
+                let mut seq = s.serialize_seq(Some(v.len()))?;
+                for val in v.iter() {
+                    seq.serialize_element(&self.wrap(val))?;
+                }
+                seq.end()
+            }
+            Unstructured::<T>::Map(v) => {
+                let mut entries: Vec<_> = v.iter().collect();
+                if self.options.sort_keys {
+                    entries.sort_by(|a, b| a.0.cmp(b.0));
+                }
+                let mut map = s.serialize_map(Some(entries.len()))?;
+                for (key, val) in entries {
+                    map.serialize_entry(&self.wrap(key), &self.wrap(val))?;
+                }
+                map.end()
+            }
+            doc => doc.serialize(s),
+        }
     }
 }
@@ -0,0 +1,196 @@
+use std::cmp::Ordering;
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::ops::{Deref, DerefMut};
+
+#[cfg(not(feature = "shared"))]
+type Inner<C> = C;
+#[cfg(feature = "shared")]
+type Inner<C> = std::sync::Arc<C>;
+
+pub(crate) mod contents {
+    /// What a [`Shared`](super::Shared) holds, a [`Sequence`](crate::Sequence) or a
+    /// [`Mapping`](crate::Mapping)
+    pub trait Contents: Default {
+        /// Drop the contents without recursing into the documents they hold
+        fn drop_flat(self);
+    }
+}
+
+use contents::Contents;
+
+/// Storage for sequence and map contents. With the `shared` feature these are reference
+/// counted, so cloning a document is O(1) and contents are only copied when they are mutated.
+/// The API is the same either way, so enabling the feature somewhere in a build never breaks
+/// code elsewhere. Dropping the contents doesn't recurse, so documents nested far deeper than
+/// the thread's stack can be dropped.
+///
+/// ```
+/// use unstructured::Document;
+///
+/// let mut doc = Document::Seq(vec![Document::from(1)].into());
+/// if let Document::Seq(seq) = &mut doc {
+///     seq.push(Document::from(2));
+/// }
+/// assert_eq!(doc, Document::from((1, 2)));
+/// ```
+pub struct Shared<C: Contents>(Inner<C>);
+
+impl<C: Contents> Shared<C> {
+    #[cfg(not(feature = "shared"))]
+    pub fn new(contents: C) -> Self {
+        Shared(contents)
+    }
+
+    #[cfg(feature = "shared")]
+    pub fn new(contents: C) -> Self {
+        Shared(std::sync::Arc::new(contents))
+    }
+
+    /// Whether both hold the same contents, rather than equal ones. Without the `shared` feature
+    /// contents are never held by more than one document.
+    pub fn ptr_eq(this: &Self, other: &Self) -> bool {
+        std::ptr::eq::<C>(&**this, &**other)
+    }
+
+    /// Take the contents if no other document shares them
+    pub fn into_unique(mut this: Self) -> Option<C> {
+        this.take_unique()
+    }
+
+    /// Take the contents if no other document shares them, leaving them empty
+    #[cfg(not(feature = "shared"))]
+    pub(crate) fn take_unique(&mut self) -> Option<C> {
+        Some(std::mem::take(&mut self.0))
+    }
+
+    /// Take the contents if no other document shares them, leaving them empty
+    #[cfg(feature = "shared")]
+    pub(crate) fn take_unique(&mut self) -> Option<C> {
+        std::sync::Arc::get_mut(&mut self.0).map(std::mem::take)
+    }
+}
+
+impl<C: Contents + Clone> Shared<C> {
+    /// Take the contents, copying them if another document shares them
+    pub fn into_inner(mut this: Self) -> C {
+        this.take_unique().unwrap_or_else(|| (*this).clone())
+    }
+}
+
+impl<C: Contents> Drop for Shared<C> {
+    fn drop(&mut self) {
+        if let Some(contents) = self.take_unique() {
+            contents.drop_flat();
+        }
+    }
+}
+
+impl<C: Contents> Deref for Shared<C> {
+    type Target = C;
+
+    fn deref(&self) -> &C {
+        &self.0
+    }
+}
+
+/// Mutable access copies the contents first if another document shares them
+impl<C: Contents + Clone> DerefMut for Shared<C> {
+    #[cfg(not(feature = "shared"))]
+    fn deref_mut(&mut self) -> &mut C {
+        &mut self.0
+    }
+
+    #[cfg(feature = "shared")]
+    fn deref_mut(&mut self) -> &mut C {
+        std::sync::Arc::make_mut(&mut self.0)
+    }
+}
+
+impl<C: Contents> From<C> for Shared<C> {
+    fn from(contents: C) -> Self {
+        Shared::new(contents)
+    }
+}
+
+impl<C: Contents + Clone> Clone for Shared<C> {
+    fn clone(&self) -> Self {
+        Shared(self.0.clone())
+    }
+}
+
+impl<C: Contents> Default for Shared<C> {
+    fn default() -> Self {
+        Shared::new(C::default())
+    }
+}
+
+impl<C: Contents + fmt::Debug> fmt::Debug for Shared<C> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&**self, f)
+    }
+}
+
+impl<C: Contents + PartialEq> PartialEq for Shared<C> {
+    fn eq(&self, other: &Self) -> bool {
+        **self == **other
+    }
+}
+
+impl<C: Contents + Eq> Eq for Shared<C> {}
+
+impl<C: Contents + PartialOrd> PartialOrd for Shared<C> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        (**self).partial_cmp(&**other)
+    }
+}
+
+impl<C: Contents + Ord> Ord for Shared<C> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        (**self).cmp(&**other)
+    }
+}
+
+impl<C: Contents + Hash> Hash for Shared<C> {
+    fn hash<H: Hasher>(&self, hasher: &mut H) {
+        (**self).hash(hasher)
+    }
+}
+
+impl<C: Contents + IntoIterator + Clone> IntoIterator for Shared<C> {
+    type Item = C::Item;
+    type IntoIter = C::IntoIter;
+
+    fn into_iter(self) -> Self::IntoIter {
+        Shared::into_inner(self).into_iter()
+    }
+}
+
+impl<'a, C: Contents> IntoIterator for &'a Shared<C>
+where
+    &'a C: IntoIterator,
+{
+    type Item = <&'a C as IntoIterator>::Item;
+    type IntoIter = <&'a C as IntoIterator>::IntoIter;
+
+    fn into_iter(self) -> Self::IntoIter {
+        (**self).into_iter()
+    }
+}
+
+#[inline]
+pub(crate) fn share<T: Contents>(v: T) -> Shared<T> {
+    Shared::new(v)
+}
+
+/// Mutable access to the contents, copying them first if another document shares them
+#[inline]
+pub(crate) fn make_mut<T: Contents + Clone>(v: &mut Shared<T>) -> &mut T {
+    v
+}
+
+/// Take the contents, copying them if another document shares them
+#[inline]
+pub(crate) fn unshare<T: Contents + Clone>(v: Shared<T>) -> T {
+    Shared::into_inner(v)
+}
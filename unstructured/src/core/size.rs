@@ -0,0 +1,97 @@
+use crate::*;
+use std::mem::size_of;
+
+impl<T: UnstructuredDataTrait> Unstructured<T> {
+    /// Estimate the bytes this document uses, including the value itself and everything it
+    /// allocates: string and byte buffers, sequence capacity and map storage. Map overhead is
+    /// an estimate, `Err` and `Other` values are counted without anything they allocate and
+    /// contents shared with the `shared` feature are only counted the first time they appear.
+    ///
+    /// ```
+    /// use unstructured::Document;
+    ///
+    /// let small: Document = serde_json::from_str(r#"{"a": "b"}"#).unwrap();
+    /// let large: Document = serde_json::from_str(r#"{"a": "b", "c": ["d", "e", "f"]}"#).unwrap();
+    /// assert!(small.approximate_size() > std::mem::size_of::<Document>());
+    /// assert!(large.approximate_size() > small.approximate_size());
+    /// ```
+    pub fn approximate_size(&self) -> usize {
+        self.measure().1
+    }
+
+    /// The number of values in the document, not counting map keys, and its
+    /// [`approximate_size`](Unstructured::approximate_size). Walks the document with an
+    /// explicit stack, so deeply nested documents can't overflow the call stack.
+    pub(crate) fn measure(&self) -> (usize, usize) {
+        let mut seen = Seen::new();
+        let mut nodes = 0;
+        let mut bytes = size_of::<Self>();
+        // Each value with whether it is a map key, which isn't counted as a node
+        let mut stack = vec![(self, false)];
+        while let Some((val, is_key)) = stack.pop() {
+            nodes += !is_key as usize;
+            bytes += match val {
+                Self::String(s) => s.capacity(),
+                Self::Bytes(b) => b.capacity(),
+                Self::Seq(seq) if seen.first_visit(&**seq) => {
+                    stack.extend(seq.iter().map(|v| (v, is_key)));
+                    shared_header() + seq.capacity() * size_of::<Self>()
+                }
+                Self::Map(map) if seen.first_visit(&**map) => {
+                    for (k, v) in map.iter() {
+                        stack.push((k, true));
+                        stack.push((v, is_key));
+                    }
+                    shared_header() + map.heap_size()
+                }
+                Self::Option(Some(v)) | Self::Newtype(v) => {
+                    stack.push((v, is_key));
+                    size_of::<Self>()
+                }
+                _ => 0,
+            };
+        }
+        (nodes, bytes)
+    }
+}
+
+/// The shared contents already counted, by address
+#[cfg(feature = "shared")]
+struct Seen(std::collections::HashSet<*const ()>);
+
+#[cfg(feature = "shared")]
+impl Seen {
+    fn new() -> Self {
+        Seen(Default::default())
+    }
+
+    fn first_visit<C>(&mut self, contents: &C) -> bool {
+        self.0.insert(contents as *const C as *const ())
+    }
+}
+
+/// Without the `shared` feature no contents are held twice, so there's nothing to track
+#[cfg(not(feature = "shared"))]
+struct Seen;
+
+#[cfg(not(feature = "shared"))]
+impl Seen {
+    fn new() -> Self {
+        Seen
+    }
+
+    fn first_visit<C>(&mut self, _: &C) -> bool {
+        true
+    }
+}
+
+/// The reference counts stored alongside `Arc` contents
+#[cfg(feature = "shared")]
+fn shared_header() -> usize {
+    2 * size_of::<usize>()
+}
+
+#[cfg(not(feature = "shared"))]
+fn shared_header() -> usize {
+    0
+}
@@ -0,0 +1,89 @@
+use crate::*;
+
+impl<T: UnstructuredDataTrait> Unstructured<T> {
+    /// Rewrite every value in the document, children before the values holding them. `f` is
+    /// given the path of each value and returns its replacement. The contents of `Option` and
+    /// `Newtype` wrappers are passed before the wrapper, under the same path.
+    ///
+    /// ```
+    /// use unstructured::{Document, Segment};
+    ///
+    /// let mut doc: Document =
+    ///     serde_json::from_str(r#"{"port": "8080", "auth": {"password": "hunter2"}}"#).unwrap();
+    /// doc.transform(|path, val| match (path.last(), val) {
+    ///     (Some(Segment::Key(k)), _) if k == "password" => "***".into(),
+    ///     (_, Document::String(s)) => s.parse::<u64>().map(Document::from).unwrap_or(s.into()),
+    ///     (_, val) => val,
+    /// });
+    /// assert_eq!(doc["port"], 8080);
+    /// assert_eq!(doc["auth"]["password"], "***");
+    /// ```
+    pub fn transform<F: FnMut(&Path, Self) -> Self>(&mut self, mut f: F) {
+        self.transform_at(&mut Path::new(), &mut f);
+    }
+
+    /// Rewrite every map key in the document. `f` is given the path of the map holding each key
+    /// and returns its replacement, if two keys of a map are replaced with the same key the last
+    /// one wins. Paths are those of the original document.
+    ///
+    /// ```
+    /// use unstructured::Document;
+    ///
+    /// let mut doc: Document = serde_json::from_str(r#"{"Name": {"First": "A"}}"#).unwrap();
+    /// doc.transform_keys(|_, key| match key {
+    ///     Document::String(s) => s.to_lowercase().into(),
+    ///     key => key,
+    /// });
+    /// assert_eq!(doc["name"]["first"], "A");
+    /// ```
+    pub fn transform_keys<F: FnMut(&Path, Self) -> Self>(&mut self, mut f: F) {
+        self.transform_keys_at(&mut Path::new(), &mut f);
+    }
+
+    fn transform_at(&mut self, path: &mut Path, f: &mut dyn FnMut(&Path, Self) -> Self) {
+        match self {
+            Self::Seq(seq) => {
+                for (i, child) in make_mut(seq).iter_mut().enumerate() {
+                    path.push(i);
+                    child.transform_at(path, f);
+                    path.pop();
+                }
+            }
+            Self::Map(map) => {
+                for (key, child) in make_mut(map).iter_mut() {
+                    path.push(Segment::from_key(key));
+                    child.transform_at(path, f);
+                    path.pop();
+                }
+            }
+            Self::Option(Some(inner)) | Self::Newtype(inner) => inner.transform_at(path, f),
+            _ => {}
+        }
+        let val = self.take();
+        *self = f(path, val);
+    }
+
+    fn transform_keys_at(&mut self, path: &mut Path, f: &mut dyn FnMut(&Path, Self) -> Self) {
+        match self {
+            Self::Seq(seq) => {
+                for (i, child) in make_mut(seq).iter_mut().enumerate() {
+                    path.push(i);
+                    child.transform_keys_at(path, f);
+                    path.pop();
+                }
+            }
+            Self::Map(map) => {
+                let entries = unshare(std::mem::take(map));
+                let map = make_mut(map);
+                for (key, mut child) in entries.into_iter() {
+                    path.push(Segment::from_key(&key));
+                    child.transform_keys_at(path, f);
+                    path.pop();
+                    map.insert(f(path, key), child);
+                }
+            }
+            Self::Option(Some(inner)) | Self::Newtype(inner) => inner.transform_keys_at(path, f),
+            _ => {}
+        }
+    }
+}
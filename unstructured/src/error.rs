@@ -0,0 +1,112 @@
+use crate::de::DeserializerError;
+use crate::ser::SerializerError;
+use crate::*;
+use std::fmt;
+
+/// The error returned throughout this crate. Errors specific to one operation, such as
+/// [`CastError`] or [`MergeError`], are wrapped so their details stay available through
+/// [`source`](std::error::Error::source).
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum Error {
+    /// Input such as a selector, path, filter, query, expression or base64 string is not valid
+    /// syntax
+    Parse(String),
+    /// A valid selector, path, query or expression could not be applied to the document
+    Select(String),
+    /// A valid filter could not be applied to the documents
+    Filter(String),
+    /// A document could not be assembled from its parts, such as events or flattened entries
+    Build(String),
+    /// Documents could not be merged
+    Merge(MergeError),
+    /// A document could not be cast to the requested type
+    Cast(CastError),
+    /// A document could not be deserialized into the requested type
+    Deserialize(DeserializerError),
+    /// A value could not be serialized into a document
+    Serialize(SerializerError),
+}
+
+impl Error {
+    /// The path to the value that caused the error, None if it was the root or isn't known
+    pub fn path(&self) -> Option<&Path> {
+        match self {
+            Error::Merge(e) => Some(&e.path),
+            Error::Deserialize(e) => e.path(),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Parse(msg) | Error::Select(msg) | Error::Filter(msg) | Error::Build(msg) => {
+                f.write_str(msg)
+            }
+            Error::Merge(e) => e.fmt(f),
+            Error::Cast(e) => e.fmt(f),
+            Error::Deserialize(e) => e.fmt(f),
+            Error::Serialize(e) => e.fmt(f),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Parse(_) | Error::Select(_) | Error::Filter(_) | Error::Build(_) => None,
+            Error::Merge(e) => Some(e),
+            Error::Cast(e) => Some(e),
+            Error::Deserialize(e) => Some(e),
+            Error::Serialize(e) => Some(e),
+        }
+    }
+}
+
+impl From<MergeError> for Error {
+    fn from(e: MergeError) -> Self {
+        Error::Merge(e)
+    }
+}
+
+impl From<CastError> for Error {
+    fn from(e: CastError) -> Self {
+        Error::Cast(e)
+    }
+}
+
+impl From<DeserializerError> for Error {
+    fn from(e: DeserializerError) -> Self {
+        Error::Deserialize(e)
+    }
+}
+
+impl From<SerializerError> for Error {
+    fn from(e: SerializerError) -> Self {
+        Error::Serialize(e)
+    }
+}
+
+#[cfg(feature = "selector")]
+impl From<ExtractError> for Error {
+    fn from(e: ExtractError) -> Self {
+        if e.unusable {
+            Error::Select(e.to_string())
+        } else {
+            Error::Parse(e.to_string())
+        }
+    }
+}
+
+#[cfg(feature = "selector")]
+impl From<ViewError> for Error {
+    fn from(e: ViewError) -> Self {
+        match e {
+            ViewError::Selector(e) => e.into(),
+            ViewError::Deserialize(e) => e.into(),
+            ViewError::Serialize(e) => e.into(),
+        }
+    }
+}
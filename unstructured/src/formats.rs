@@ -0,0 +1,164 @@
+use crate::*;
+
+#[cfg(feature = "serde_json")]
+impl<T: UnstructuredDataTrait> Unstructured<T> {
+    /// Parse a document from a JSON string
+    ///
+    /// ```
+    /// use unstructured::Document;
+    ///
+    /// let doc = Document::from_json_str(r#"{"a": [1, 2]}"#).unwrap();
+    /// assert_eq!(doc.to_json_string().unwrap(), r#"{"a":[1,2]}"#);
+    /// assert_eq!(doc.to_json_pretty().unwrap(), "{\n  \"a\": [\n    1,\n    2\n  ]\n}");
+    /// ```
+    pub fn from_json_str(s: &str) -> Result<Self, serde_json::Error> {
+        observe(Operation::Parse, || serde_json::from_str(s))
+    }
+
+    /// Parse a document from JSON read from a reader
+    pub fn from_json_reader<R: std::io::Read>(reader: R) -> Result<Self, serde_json::Error> {
+        observe(Operation::Parse, || serde_json::from_reader(reader))
+    }
+
+    /// Write the document as compact JSON
+    pub fn to_json_string(&self) -> Result<String, serde_json::Error> {
+        serde_json::to_string(self)
+    }
+
+    /// Write the document as indented JSON
+    pub fn to_json_pretty(&self) -> Result<String, serde_json::Error> {
+        serde_json::to_string_pretty(self)
+    }
+
+    /// Write the document as compact JSON to a writer
+    pub fn to_json_writer<W: std::io::Write>(&self, writer: W) -> Result<(), serde_json::Error> {
+        serde_json::to_writer(writer, self)
+    }
+}
+
+#[cfg(feature = "serde_yaml")]
+impl<T: UnstructuredDataTrait> Unstructured<T> {
+    /// Parse a document from a YAML string
+    ///
+    /// ```
+    /// use unstructured::Document;
+    ///
+    /// let doc = Document::from_yaml_str("a:\n  - 1\n  - x\n").unwrap();
+    /// assert_eq!(doc["a"][1], "x");
+    /// assert_eq!(Document::from_yaml_str(&doc.to_yaml_string().unwrap()).unwrap(), doc);
+    /// ```
+    pub fn from_yaml_str(s: &str) -> Result<Self, serde_yaml::Error> {
+        observe(Operation::Parse, || serde_yaml::from_str(s))
+    }
+
+    /// Parse a document from YAML read from a reader
+    pub fn from_yaml_reader<R: std::io::Read>(reader: R) -> Result<Self, serde_yaml::Error> {
+        observe(Operation::Parse, || serde_yaml::from_reader(reader))
+    }
+
+    /// Write the document as YAML
+    pub fn to_yaml_string(&self) -> Result<String, serde_yaml::Error> {
+        serde_yaml::to_string(self)
+    }
+
+    /// Write the document as YAML to a writer
+    pub fn to_yaml_writer<W: std::io::Write>(&self, writer: W) -> Result<(), serde_yaml::Error> {
+        serde_yaml::to_writer(writer, self)
+    }
+}
+
+#[cfg(feature = "toml")]
+impl<T: UnstructuredDataTrait> Unstructured<T> {
+    /// Parse a document from a TOML string
+    ///
+    /// ```
+    /// use unstructured::Document;
+    ///
+    /// let doc = Document::from_toml_str("[server]\nport = 80\n").unwrap();
+    /// assert_eq!(doc["server"]["port"], 80);
+    /// assert_eq!(doc.to_toml_string().unwrap(), "[server]\nport = 80\n");
+    /// ```
+    pub fn from_toml_str(s: &str) -> Result<Self, toml::de::Error> {
+        observe(Operation::Parse, || toml::from_str(s))
+    }
+
+    /// Write the document as TOML. The document must be a map, TOML has no other top level
+    /// value.
+    pub fn to_toml_string(&self) -> Result<String, toml::ser::Error> {
+        toml::to_string(self)
+    }
+
+    /// Write the document as TOML, with arrays spread across lines
+    pub fn to_toml_pretty(&self) -> Result<String, toml::ser::Error> {
+        toml::to_string_pretty(self)
+    }
+}
+
+#[cfg(feature = "ciborium")]
+impl<T: UnstructuredDataTrait> Unstructured<T> {
+    /// Parse a document from CBOR read from a reader
+    ///
+    /// ```
+    /// use unstructured::Document;
+    ///
+    /// let doc = Document::from_json_str(r#"{"a": [1, "x"]}"#).unwrap();
+    /// let bytes = doc.to_cbor_vec().unwrap();
+    /// assert_eq!(Document::from_cbor_reader(&bytes[..]).unwrap(), doc);
+    /// ```
+    pub fn from_cbor_reader<R: std::io::Read>(
+        reader: R,
+    ) -> Result<Self, ciborium::de::Error<std::io::Error>> {
+        observe(Operation::Parse, || ciborium::de::from_reader(reader))
+    }
+
+    /// Write the document as CBOR
+    pub fn to_cbor_vec(&self) -> Result<Vec<u8>, ciborium::ser::Error<std::io::Error>> {
+        let mut bytes = vec![];
+        self.to_cbor_writer(&mut bytes)?;
+        Ok(bytes)
+    }
+
+    /// Write the document as CBOR to a writer
+    pub fn to_cbor_writer<W: std::io::Write>(
+        &self,
+        writer: W,
+    ) -> Result<(), ciborium::ser::Error<std::io::Error>> {
+        ciborium::ser::into_writer(self, writer)
+    }
+}
+
+#[cfg(feature = "rmp-serde")]
+impl<T: UnstructuredDataTrait> Unstructured<T> {
+    /// Parse a document from MessagePack bytes
+    ///
+    /// ```
+    /// use unstructured::Document;
+    ///
+    /// let doc = Document::from_json_str(r#"{"a": [1, "x"]}"#).unwrap();
+    /// let bytes = doc.to_msgpack_vec().unwrap();
+    /// assert_eq!(Document::from_msgpack_slice(&bytes).unwrap(), doc);
+    /// ```
+    pub fn from_msgpack_slice(bytes: &[u8]) -> Result<Self, rmp_serde::decode::Error> {
+        observe(Operation::Parse, || rmp_serde::from_slice(bytes))
+    }
+
+    /// Parse a document from MessagePack read from a reader
+    pub fn from_msgpack_reader<R: std::io::Read>(
+        reader: R,
+    ) -> Result<Self, rmp_serde::decode::Error> {
+        observe(Operation::Parse, || rmp_serde::from_read(reader))
+    }
+
+    /// Write the document as MessagePack, with maps keyed by name
+    pub fn to_msgpack_vec(&self) -> Result<Vec<u8>, rmp_serde::encode::Error> {
+        rmp_serde::to_vec_named(self)
+    }
+
+    /// Write the document as MessagePack to a writer
+    pub fn to_msgpack_writer<W: std::io::Write>(
+        &self,
+        mut writer: W,
+    ) -> Result<(), rmp_serde::encode::Error> {
+        rmp_serde::encode::write_named(&mut writer, self)
+    }
+}
@@ -0,0 +1,125 @@
+use crate::*;
+use arbitrary::{Arbitrary, Result, Unstructured as Input};
+
+/// The limits used by the `Arbitrary` impl, small enough that fuzzers spend their time on
+/// varied documents rather than huge ones
+const DEFAULT_LIMITS: Limits = Limits {
+    max_depth: Some(8),
+    max_nodes: Some(256),
+    max_string_len: Some(64),
+};
+
+impl<'a> Arbitrary<'a> for Number {
+    fn arbitrary(u: &mut Input<'a>) -> Result<Self> {
+        Ok(match u.choose_index(12)? {
+            0 => Number::U8(u.arbitrary()?),
+            1 => Number::U16(u.arbitrary()?),
+            2 => Number::U32(u.arbitrary()?),
+            3 => Number::U64(u.arbitrary()?),
+            4 => Number::from(u.arbitrary::<u128>()?),
+            5 => Number::I8(u.arbitrary()?),
+            6 => Number::I16(u.arbitrary()?),
+            7 => Number::I32(u.arbitrary()?),
+            8 => Number::I64(u.arbitrary()?),
+            9 => Number::from(u.arbitrary::<i128>()?),
+            10 => Number::F32(u.arbitrary()?),
+            _ => Number::F64(u.arbitrary()?),
+        })
+    }
+}
+
+/// Generates well formed documents within a default set of [`Limits`], see
+/// [`Unstructured::arbitrary_with_limits`] to choose them
+impl<'a, T: UnstructuredDataTrait + 'a> Arbitrary<'a> for Unstructured<T> {
+    fn arbitrary(u: &mut Input<'a>) -> Result<Self> {
+        Self::arbitrary_with_limits(u, DEFAULT_LIMITS)
+    }
+}
+
+impl<T: UnstructuredDataTrait> Unstructured<T> {
+    /// Build a document from fuzzer input, staying within the limits. Documents only hold
+    /// values that can be serialized: no `Unassigned`, `Err` or `Other` values, and map keys
+    /// are always strings.
+    ///
+    /// ```
+    /// use unstructured::{Document, Limits};
+    ///
+    /// let bytes: Vec<u8> = (0..=255).cycle().take(4096).collect();
+    /// let limits = Limits {
+    ///     max_depth: Some(3),
+    ///     max_nodes: Some(50),
+    ///     max_string_len: Some(10),
+    /// };
+    /// let mut input = arbitrary::Unstructured::new(&bytes);
+    /// let doc = Document::arbitrary_with_limits(&mut input, limits).unwrap();
+    /// assert!(doc.subtree_stats().containers <= 50);
+    /// assert!(serde_json::to_string(&doc).is_ok());
+    /// ```
+    pub fn arbitrary_with_limits(u: &mut Input<'_>, limits: Limits) -> Result<Self> {
+        let mut nodes = limits.max_nodes.unwrap_or(usize::MAX);
+        Self::arbitrary_value(u, &limits, 0, &mut nodes)
+    }
+
+    fn arbitrary_value(
+        u: &mut Input<'_>,
+        limits: &Limits,
+        depth: usize,
+        nodes: &mut usize,
+    ) -> Result<Self> {
+        *nodes = nodes.saturating_sub(1);
+        let can_nest = *nodes > 0 && limits.max_depth.map_or(true, |max| depth < max);
+        let kinds = if can_nest { 11 } else { 7 };
+        Ok(match u.choose_index(kinds)? {
+            0 => Self::Null,
+            1 => Self::Bool(u.arbitrary()?),
+            2 => Self::Number(u.arbitrary()?),
+            3 => Self::String(arbitrary_string(u, limits)?),
+            4 => Self::Char(u.arbitrary()?),
+            5 => {
+                let mut bytes = Vec::<u8>::arbitrary(u)?;
+                bytes.truncate(limits.max_string_len.unwrap_or(usize::MAX));
+                Self::Bytes(bytes)
+            }
+            6 => Self::Option(None),
+            7 => {
+                let len = u.arbitrary_len::<u8>()?;
+                let mut seq = Sequence::new();
+                while seq.len() < len && *nodes > 0 {
+                    seq.push(Self::arbitrary_value(u, limits, depth + 1, nodes)?);
+                }
+                Self::Seq(share(seq))
+            }
+            8 => {
+                let len = u.arbitrary_len::<u8>()?;
+                let mut map = Mapping::new();
+                for _ in 0..len {
+                    // Keys count as values too, and need room for their value
+                    if *nodes < 2 {
+                        break;
+                    }
+                    *nodes -= 1;
+                    let key = Self::String(arbitrary_string(u, limits)?);
+                    let val = Self::arbitrary_value(u, limits, depth + 1, nodes)?;
+                    map.insert(key, val);
+                }
+                Self::Map(share(map))
+            }
+            9 => {
+                let inner = Self::arbitrary_value(u, limits, depth + 1, nodes)?;
+                Self::Option(Some(Box::new(inner)))
+            }
+            _ => Self::Newtype(Box::new(Self::arbitrary_value(u, limits, depth + 1, nodes)?)),
+        })
+    }
+}
+
+fn arbitrary_string(u: &mut Input<'_>, limits: &Limits) -> Result<String> {
+    let s = String::arbitrary(u)?;
+    Ok(match limits.max_string_len {
+        Some(max) if s.len() > max => {
+            let end = (0..=max).rev().find(|i| s.is_char_boundary(*i)).unwrap_or(0);
+            s[..end].to_string()
+        }
+        _ => s,
+    })
+}
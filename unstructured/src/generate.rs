@@ -0,0 +1,195 @@
+use crate::*;
+use rand::seq::SliceRandom;
+use rand::Rng;
+
+const FIRST_NAMES: &[&str] = &[
+    "Alice", "Bob", "Carol", "Dave", "Erin", "Frank", "Grace", "Heidi", "Ivan", "Judy", "Mallory",
+    "Niaj", "Olivia", "Peggy", "Rupert", "Sybil", "Trent", "Victor", "Walter", "Yolanda",
+];
+
+const LAST_NAMES: &[&str] = &[
+    "Smith", "Johnson", "Williams", "Brown", "Jones", "Garcia", "Miller", "Davis", "Lopez",
+    "Wilson", "Anderson", "Thomas", "Taylor", "Moore", "Martin", "Lee", "Thompson", "White",
+];
+
+const WORDS: &[&str] = &[
+    "alpha", "bravo", "charlie", "delta", "echo", "foxtrot", "golf", "hotel", "india", "juliet",
+    "kilo", "lima", "mike", "november", "oscar", "papa", "quebec", "romeo", "sierra", "tango",
+];
+
+impl<T: UnstructuredDataTrait> Unstructured<T> {
+    /// Generate a random document from a template using the thread local random number
+    /// generator, see [`generate_with`](Unstructured::generate_with)
+    pub fn generate(&self) -> Result<Self, Error> {
+        self.generate_with(&mut rand::thread_rng())
+    }
+
+    /// Generate a random document from a template. String values that start with `$` are
+    /// replaced with random data, everything else is copied as is:
+    ///
+    /// - `$uuid`: a random version 4 UUID
+    /// - `$int(1..100)`, `$int(1..=100)`: an integer within the range
+    /// - `$float(0..1)`: a float within the range
+    /// - `$bool`: true or false
+    /// - `$name`: a first and last name
+    /// - `$word`: a single lowercase word
+    ///
+    /// A leading `$$` is replaced with a literal `$`.
+    ///
+    /// ```
+    /// use rand::SeedableRng;
+    /// use unstructured::Document;
+    ///
+    /// let template: Document =
+    ///     serde_json::from_str(r#"{"id": "$uuid", "age": "$int(18..65)", "kind": "user"}"#)
+    ///         .unwrap();
+    /// let mut rng = rand::rngs::StdRng::seed_from_u64(1);
+    /// let doc = template.generate_with(&mut rng).unwrap();
+    /// assert!(doc["age"] >= Document::from(18) && doc["age"] < Document::from(65));
+    /// assert_eq!(doc["kind"], "user");
+    /// ```
+    pub fn generate_with<R: Rng + ?Sized>(&self, rng: &mut R) -> Result<Self, Error> {
+        match self {
+            Self::String(s) if s.starts_with("$$") => Ok(Self::String(s[1..].to_string())),
+            Self::String(s) if s.starts_with('$') => {
+                placeholder(&s[1..], rng).map_err(Error::Parse)
+            }
+            Self::Seq(seq) => seq
+                .iter()
+                .map(|val| val.generate_with(rng))
+                .collect::<Result<_, _>>()
+                .map(|seq| Self::Seq(share(seq))),
+            Self::Map(map) => map
+                .iter()
+                .map(|(key, val)| Ok((key.clone(), val.generate_with(rng)?)))
+                .collect::<Result<_, Error>>()
+                .map(|map| Self::Map(share(map))),
+            Self::Option(Some(val)) => Ok(Self::Option(Some(Box::new(val.generate_with(rng)?)))),
+            Self::Newtype(val) => Ok(Self::Newtype(Box::new(val.generate_with(rng)?))),
+            other => Ok(other.clone()),
+        }
+    }
+}
+
+impl Schema {
+    /// Generate a random document matching the schema. Sequences and maps have up to four
+    /// elements and optional values are `null` half of the time.
+    pub fn generate_with<T: UnstructuredDataTrait, R: Rng + ?Sized>(
+        &self,
+        rng: &mut R,
+    ) -> Unstructured<T> {
+        match self {
+            Schema::Any => Unstructured::<T>::Null,
+            Schema::Bool => Unstructured::<T>::Bool(rng.gen()),
+            Schema::Integer => rng.gen_range(0..1000u64).into(),
+            Schema::Float => rng.gen_range(0.0..1000.0f64).into(),
+            Schema::String => random_word(rng).into(),
+            #[cfg(feature = "chrono")]
+            Schema::DateTime => {
+                use chrono::TimeZone;
+                // Between 2000 and 2030
+                let secs = rng.gen_range(946_684_800..1_893_456_000);
+                chrono::Utc.timestamp_opt(secs, 0).unwrap().into()
+            }
+            Schema::Optional(inner) if rng.gen() => inner.generate_with(rng),
+            Schema::Optional(_) => Unstructured::<T>::Null,
+            Schema::Seq(inner) => {
+                let len = rng.gen_range(0..=4);
+                Unstructured::<T>::Seq(share((0..len).map(|_| inner.generate_with(rng)).collect()))
+            }
+            Schema::Map(inner) => {
+                let len = rng.gen_range(0..=4);
+                Unstructured::<T>::Map(share(
+                    (0..len)
+                        .map(|_| (random_word(rng).into(), inner.generate_with(rng)))
+                        .collect(),
+                ))
+            }
+            Schema::Struct(fields) => Unstructured::<T>::Map(share(
+                fields
+                    .iter()
+                    .map(|(name, field)| (name.as_str().into(), field.generate_with(rng)))
+                    .collect(),
+            )),
+        }
+    }
+}
+
+fn placeholder<T: UnstructuredDataTrait, R: Rng + ?Sized>(
+    placeholder: &str,
+    rng: &mut R,
+) -> Result<Unstructured<T>, String> {
+    let (name, args) = match placeholder.find('(') {
+        Some(i) if placeholder.ends_with(')') => {
+            (&placeholder[..i], Some(&placeholder[i + 1..placeholder.len() - 1]))
+        }
+        _ => (placeholder, None),
+    };
+    match (name, args) {
+        ("uuid", None) => Ok(random_uuid(rng).into()),
+        ("bool", None) => Ok(Unstructured::<T>::Bool(rng.gen())),
+        ("name", None) => Ok(format!(
+            "{} {}",
+            FIRST_NAMES.choose(rng).unwrap_or(&""),
+            LAST_NAMES.choose(rng).unwrap_or(&"")
+        )
+        .into()),
+        ("word", None) => Ok(random_word(rng).into()),
+        ("int", Some(range)) => {
+            let (start, end, inclusive) = parse_range::<i64>(range)?;
+            match inclusive {
+                true if start <= end => Ok(rng.gen_range(start..=end).into()),
+                false if start < end => Ok(rng.gen_range(start..end).into()),
+                _ => Err(format!("Empty range in ${}", placeholder)),
+            }
+        }
+        ("float", Some(range)) => {
+            let (start, end, inclusive) = parse_range::<f64>(range)?;
+            match inclusive {
+                true if start <= end => Ok(rng.gen_range(start..=end).into()),
+                false if start < end => Ok(rng.gen_range(start..end).into()),
+                _ => Err(format!("Empty range in ${}", placeholder)),
+            }
+        }
+        _ => Err(format!("Unknown placeholder ${}", placeholder)),
+    }
+}
+
+/// Parse `start..end` or `start..=end`, returning whether the end is inclusive
+fn parse_range<N: std::str::FromStr>(range: &str) -> Result<(N, N, bool), String>
+where
+    N::Err: std::fmt::Display,
+{
+    let (start, end, inclusive) = match range.split_once("..=") {
+        Some((start, end)) => (start, end, true),
+        None => match range.split_once("..") {
+            Some((start, end)) => (start, end, false),
+            None => return Err(format!("Invalid range {}", range)),
+        },
+    };
+    let parse = |s: &str| {
+        s.trim()
+            .parse::<N>()
+            .map_err(|e| format!("Invalid range {}: {}", range, e))
+    };
+    Ok((parse(start)?, parse(end)?, inclusive))
+}
+
+fn random_word<R: Rng + ?Sized>(rng: &mut R) -> String {
+    WORDS.choose(rng).unwrap_or(&"").to_string()
+}
+
+fn random_uuid<R: Rng + ?Sized>(rng: &mut R) -> String {
+    let mut bytes: [u8; 16] = rng.gen();
+    bytes[6] = (bytes[6] & 0x0f) | 0x40;
+    bytes[8] = (bytes[8] & 0x3f) | 0x80;
+    let hex: String = bytes.iter().map(|b| format!("{:02x}", b)).collect();
+    format!(
+        "{}-{}-{}-{}-{}",
+        &hex[..8],
+        &hex[8..12],
+        &hex[12..16],
+        &hex[16..20],
+        &hex[20..]
+    )
+}
@@ -0,0 +1,343 @@
+use crate::*;
+
+/// A number narrowed to the widest type most formats support
+#[cfg_attr(
+    not(any(feature = "serde_json", feature = "serde_yaml", feature = "rmpv")),
+    allow(dead_code)
+)]
+enum Widened {
+    U64(u64),
+    I64(i64),
+    F64(f64),
+}
+
+#[cfg_attr(
+    not(any(feature = "serde_json", feature = "serde_yaml", feature = "rmpv")),
+    allow(dead_code)
+)]
+fn widen(n: &Number) -> Widened {
+    match (n.as_u64(), n.as_i64()) {
+        (Some(u), _) => Widened::U64(u),
+        (_, Some(i)) => Widened::I64(i),
+        _ => Widened::F64(f64::from(n)),
+    }
+}
+
+/// Converts directly, without going through a serializer
+#[cfg(feature = "serde_json")]
+impl<T: UnstructuredDataTrait> From<serde_json::Value> for Unstructured<T> {
+    fn from(value: serde_json::Value) -> Self {
+        match value {
+            serde_json::Value::Null => Self::Null,
+            serde_json::Value::Bool(b) => Self::Bool(b),
+            serde_json::Value::Number(n) => match (n.as_u64(), n.as_i64()) {
+                (Some(u), _) => Self::Number(Number::U64(u)),
+                (_, Some(i)) => Self::Number(Number::I64(i)),
+                _ => Self::Number(Number::F64(n.as_f64().unwrap_or(f64::NAN))),
+            },
+            serde_json::Value::String(s) => Self::String(s),
+            serde_json::Value::Array(seq) => {
+                Self::Seq(share(seq.into_iter().map(Self::from).collect()))
+            }
+            serde_json::Value::Object(map) => Self::Map(share(
+                map.into_iter()
+                    .map(|(key, val)| (Self::String(key), Self::from(val)))
+                    .collect(),
+            )),
+        }
+    }
+}
+
+/// Converts directly, without going through a serializer. Values JSON has no type for are
+/// converted the same way they are serialized: bytes become arrays of numbers, `Newtype` and
+/// `Option` wrappers are dropped and non-string map keys are written as strings. Floats that
+/// JSON can't represent become `null`.
+#[cfg(feature = "serde_json")]
+impl<T: UnstructuredDataTrait> From<Unstructured<T>> for serde_json::Value {
+    fn from(doc: Unstructured<T>) -> Self {
+        match doc {
+            Unstructured::<T>::Null
+            | Unstructured::<T>::Unassigned
+            | Unstructured::<T>::Option(None) => serde_json::Value::Null,
+            Unstructured::<T>::Bool(b) => serde_json::Value::Bool(b),
+            Unstructured::<T>::Number(n) => match widen(&n) {
+                Widened::U64(u) => u.into(),
+                Widened::I64(i) => i.into(),
+                Widened::F64(f) => serde_json::Number::from_f64(f)
+                    .map(serde_json::Value::Number)
+                    .unwrap_or(serde_json::Value::Null),
+            },
+            Unstructured::<T>::Char(c) => c.to_string().into(),
+            Unstructured::<T>::String(s) => s.into(),
+            Unstructured::<T>::Bytes(b) => b.into(),
+            Unstructured::<T>::Option(Some(inner)) | Unstructured::<T>::Newtype(inner) => {
+                Self::from(*inner)
+            }
+            Unstructured::<T>::Seq(seq) => unshare(seq).into_iter().map(Self::from).collect(),
+            Unstructured::<T>::Map(map) => serde_json::Value::Object(
+                unshare(map)
+                    .into_iter()
+                    .map(|(key, val)| match key {
+                        Unstructured::<T>::String(key) => (key, Self::from(val)),
+                        key => (key.to_string(), Self::from(val)),
+                    })
+                    .collect(),
+            ),
+            #[cfg(feature = "chrono")]
+            Unstructured::<T>::DateTime(d) => d.to_rfc3339().into(),
+            Unstructured::<T>::Err(e) => e.to_string().into(),
+            Unstructured::<T>::Other(..) => "other".into(),
+        }
+    }
+}
+
+/// serde_yaml resolves tags while parsing, so a `Value` holds no tags to convert
+#[cfg(feature = "serde_yaml")]
+impl<T: UnstructuredDataTrait> From<serde_yaml::Value> for Unstructured<T> {
+    fn from(value: serde_yaml::Value) -> Self {
+        match value {
+            serde_yaml::Value::Null => Self::Null,
+            serde_yaml::Value::Bool(b) => Self::Bool(b),
+            serde_yaml::Value::Number(n) => match (n.as_u64(), n.as_i64()) {
+                (Some(u), _) => Self::Number(Number::U64(u)),
+                (_, Some(i)) => Self::Number(Number::I64(i)),
+                _ => Self::Number(Number::F64(n.as_f64().unwrap_or(f64::NAN))),
+            },
+            serde_yaml::Value::String(s) => Self::String(s),
+            serde_yaml::Value::Sequence(seq) => {
+                Self::Seq(share(seq.into_iter().map(Self::from).collect()))
+            }
+            serde_yaml::Value::Mapping(map) => Self::Map(share(
+                map.into_iter()
+                    .map(|(key, val)| (Self::from(key), Self::from(val)))
+                    .collect(),
+            )),
+        }
+    }
+}
+
+/// Values YAML has no type for are converted the same way they are serialized, e.g. bytes
+/// become sequences of numbers and `Newtype` and `Option` wrappers are dropped
+#[cfg(feature = "serde_yaml")]
+impl<T: UnstructuredDataTrait> From<Unstructured<T>> for serde_yaml::Value {
+    fn from(doc: Unstructured<T>) -> Self {
+        match doc {
+            Unstructured::<T>::Null
+            | Unstructured::<T>::Unassigned
+            | Unstructured::<T>::Option(None) => serde_yaml::Value::Null,
+            Unstructured::<T>::Bool(b) => serde_yaml::Value::Bool(b),
+            Unstructured::<T>::Number(n) => match widen(&n) {
+                Widened::U64(u) => u.into(),
+                Widened::I64(i) => i.into(),
+                Widened::F64(f) => f.into(),
+            },
+            Unstructured::<T>::Char(c) => c.to_string().into(),
+            Unstructured::<T>::String(s) => s.into(),
+            Unstructured::<T>::Bytes(b) => b.into_iter().map(Self::from).collect(),
+            Unstructured::<T>::Option(Some(inner)) | Unstructured::<T>::Newtype(inner) => {
+                Self::from(*inner)
+            }
+            Unstructured::<T>::Seq(seq) => unshare(seq).into_iter().map(Self::from).collect(),
+            Unstructured::<T>::Map(map) => serde_yaml::Value::Mapping(
+                unshare(map)
+                    .into_iter()
+                    .map(|(key, val)| (Self::from(key), Self::from(val)))
+                    .collect(),
+            ),
+            #[cfg(feature = "chrono")]
+            Unstructured::<T>::DateTime(d) => d.to_rfc3339().into(),
+            Unstructured::<T>::Err(e) => e.to_string().into(),
+            Unstructured::<T>::Other(..) => "other".into(),
+        }
+    }
+}
+
+/// Datetimes with an offset become `DateTime` with the `chrono` feature, other datetimes and
+/// those without the feature become strings
+#[cfg(feature = "toml")]
+impl<T: UnstructuredDataTrait> From<toml::Value> for Unstructured<T> {
+    fn from(value: toml::Value) -> Self {
+        match value {
+            toml::Value::String(s) => Self::String(s),
+            toml::Value::Integer(i) => Self::Number(Number::I64(i)),
+            toml::Value::Float(f) => Self::Number(Number::F64(f)),
+            toml::Value::Boolean(b) => Self::Bool(b),
+            #[cfg(feature = "chrono")]
+            toml::Value::Datetime(d) => match chrono::DateTime::parse_from_rfc3339(&d.to_string()) {
+                Ok(d) => Self::DateTime(d),
+                Err(_) => Self::String(d.to_string()),
+            },
+            #[cfg(not(feature = "chrono"))]
+            toml::Value::Datetime(d) => Self::String(d.to_string()),
+            toml::Value::Array(seq) => {
+                Self::Seq(share(seq.into_iter().map(Self::from).collect()))
+            }
+            toml::Value::Table(map) => Self::Map(share(
+                map.into_iter()
+                    .map(|(key, val)| (Self::String(key), Self::from(val)))
+                    .collect(),
+            )),
+        }
+    }
+}
+
+/// TOML has no null, so `Null` and `None` fail to convert, as do integers beyond the range of
+/// `i64` and maps with keys that aren't strings
+#[cfg(feature = "toml")]
+impl<T: UnstructuredDataTrait> std::convert::TryFrom<Unstructured<T>> for toml::Value {
+    type Error = CastError;
+
+    fn try_from(doc: Unstructured<T>) -> Result<Self, CastError> {
+        to_toml(doc)
+    }
+}
+
+#[cfg(feature = "toml")]
+impl<T: UnstructuredDataTrait> Unstructured<T> {
+    /// Convert to a `toml::Value`, see the `TryFrom` implementation. This saves spelling out
+    /// `TryFrom::try_from`, which `toml::Value::try_from` and `Unstructured::try_into` shadow.
+    ///
+    /// ```
+    /// use unstructured::Document;
+    ///
+    /// let doc: Document = serde_json::from_str(r#"{"a": [1, 2]}"#).unwrap();
+    /// assert_eq!(doc.into_toml_value().unwrap()["a"][1].as_integer(), Some(2));
+    /// assert!(Document::Null.into_toml_value().is_err());
+    /// ```
+    pub fn into_toml_value(self) -> Result<toml::Value, CastError> {
+        to_toml(self)
+    }
+}
+
+#[cfg(feature = "toml")]
+fn to_toml<T: UnstructuredDataTrait>(doc: Unstructured<T>) -> Result<toml::Value, CastError> {
+    Ok(match doc {
+        Unstructured::<T>::Bool(b) => toml::Value::Boolean(b),
+        Unstructured::<T>::Number(n) if n.is_float() => toml::Value::Float(f64::from(&n)),
+        Unstructured::<T>::Number(n) => match n.as_i64() {
+            Some(i) => toml::Value::Integer(i),
+            None => {
+                return Err(CastError {
+                    found: n.type_name(),
+                    target: "toml",
+                    reason: CastFailure::Overflow,
+                })
+            }
+        },
+        Unstructured::<T>::Char(c) => toml::Value::String(c.to_string()),
+        Unstructured::<T>::String(s) => toml::Value::String(s),
+        Unstructured::<T>::Bytes(b) => {
+            toml::Value::Array(b.into_iter().map(|b| toml::Value::Integer(b.into())).collect())
+        }
+        Unstructured::<T>::Option(Some(inner)) | Unstructured::<T>::Newtype(inner) => {
+            to_toml(*inner)?
+        }
+        Unstructured::<T>::Seq(seq) => {
+            toml::Value::Array(unshare(seq).into_iter().map(to_toml).collect::<Result<_, _>>()?)
+        }
+        Unstructured::<T>::Map(map) => toml::Value::Table(
+            unshare(map)
+                .into_iter()
+                .map(|(key, val)| match key {
+                    Unstructured::<T>::String(key) => Ok((key, to_toml(val)?)),
+                    key => Err(CastError::mismatch(&key, "toml key")),
+                })
+                .collect::<Result<_, _>>()?,
+        ),
+        #[cfg(feature = "chrono")]
+        Unstructured::<T>::DateTime(d) => match d.to_rfc3339().parse() {
+            Ok(d) => toml::Value::Datetime(d),
+            Err(_) => toml::Value::String(d.to_rfc3339()),
+        },
+        Unstructured::<T>::Err(e) => toml::Value::String(e.to_string()),
+        other => return Err(CastError::mismatch(&other, "toml")),
+    })
+}
+
+/// MessagePack extension values become a `Newtype` holding the type and data, the same as
+/// deserializing them with rmp-serde, and are converted back to extensions. Strings that aren't
+/// valid UTF-8 become `Bytes`.
+#[cfg(feature = "rmpv")]
+impl<T: UnstructuredDataTrait> From<rmpv::Value> for Unstructured<T> {
+    fn from(value: rmpv::Value) -> Self {
+        match value {
+            rmpv::Value::Nil => Self::Null,
+            rmpv::Value::Boolean(b) => Self::Bool(b),
+            rmpv::Value::Integer(n) => match (n.as_u64(), n.as_i64()) {
+                (Some(u), _) => Self::Number(Number::U64(u)),
+                (_, Some(i)) => Self::Number(Number::I64(i)),
+                _ => Self::Number(Number::F64(n.as_f64().unwrap_or(f64::NAN))),
+            },
+            rmpv::Value::F32(f) => Self::Number(Number::F32(f)),
+            rmpv::Value::F64(f) => Self::Number(Number::F64(f)),
+            rmpv::Value::String(s) if s.is_str() => Self::String(s.into_str().unwrap_or_default()),
+            rmpv::Value::String(s) => Self::Bytes(s.into_bytes()),
+            rmpv::Value::Binary(b) => Self::Bytes(b),
+            rmpv::Value::Array(seq) => {
+                Self::Seq(share(seq.into_iter().map(Self::from).collect()))
+            }
+            rmpv::Value::Map(map) => Self::Map(share(
+                map.into_iter()
+                    .map(|(key, val)| (Self::from(key), Self::from(val)))
+                    .collect(),
+            )),
+            rmpv::Value::Ext(kind, data) => Self::Newtype(Box::new(Self::Seq(share(vec![
+                Self::Number(Number::I8(kind)),
+                Self::Bytes(data),
+            ])))),
+        }
+    }
+}
+
+#[cfg(feature = "rmpv")]
+impl<T: UnstructuredDataTrait> From<Unstructured<T>> for rmpv::Value {
+    fn from(doc: Unstructured<T>) -> Self {
+        match doc {
+            Unstructured::<T>::Null
+            | Unstructured::<T>::Unassigned
+            | Unstructured::<T>::Option(None) => rmpv::Value::Nil,
+            Unstructured::<T>::Bool(b) => rmpv::Value::Boolean(b),
+            Unstructured::<T>::Number(Number::F32(f)) => rmpv::Value::F32(f),
+            Unstructured::<T>::Number(n) => match widen(&n) {
+                Widened::U64(u) => u.into(),
+                Widened::I64(i) => i.into(),
+                Widened::F64(f) => f.into(),
+            },
+            Unstructured::<T>::Char(c) => c.to_string().into(),
+            Unstructured::<T>::String(s) => s.into(),
+            Unstructured::<T>::Bytes(b) => rmpv::Value::Binary(b),
+            Unstructured::<T>::Option(Some(inner)) => Self::from(*inner),
+            Unstructured::<T>::Newtype(inner) => match ext_parts(&inner) {
+                Some((kind, data)) => rmpv::Value::Ext(kind, data.to_vec()),
+                None => Self::from(*inner),
+            },
+            Unstructured::<T>::Seq(seq) => {
+                rmpv::Value::Array(unshare(seq).into_iter().map(Self::from).collect())
+            }
+            Unstructured::<T>::Map(map) => rmpv::Value::Map(
+                unshare(map)
+                    .into_iter()
+                    .map(|(key, val)| (Self::from(key), Self::from(val)))
+                    .collect(),
+            ),
+            #[cfg(feature = "chrono")]
+            Unstructured::<T>::DateTime(d) => d.to_rfc3339().into(),
+            Unstructured::<T>::Err(e) => e.to_string().into(),
+            Unstructured::<T>::Other(..) => "other".into(),
+        }
+    }
+}
+
+/// The type and data of an extension value, converted from rmpv or read by rmp-serde
+#[cfg(feature = "rmpv")]
+fn ext_parts<T: UnstructuredDataTrait>(doc: &Unstructured<T>) -> Option<(i8, &[u8])> {
+    match doc {
+        Unstructured::<T>::Seq(seq) => match &seq[..] {
+            [Unstructured::<T>::Number(Number::I8(kind)), Unstructured::<T>::Bytes(data)] => {
+                Some((*kind, data))
+            }
+            _ => None,
+        },
+        _ => None,
+    }
+}
@@ -0,0 +1,516 @@
+use super::parser::{Ast, Comparator, Projection};
+use crate::*;
+use std::cmp::Ordering;
+use std::convert::TryFrom;
+
+type Output<T> = Result<Unstructured<T>, String>;
+
+/// A function argument, expression references are kept unevaluated
+enum Arg<'a, T: UnstructuredDataTrait> {
+    Value(Unstructured<T>),
+    Expression(&'a Ast),
+}
+
+impl Ast {
+    /// Evaluate the expression against an input, missing values evaluate to null. The depth is
+    /// how far into the expression this is, which compiling has already limited.
+    pub(super) fn eval<T: UnstructuredDataTrait>(
+        &self,
+        input: &Unstructured<T>,
+        depth: usize,
+    ) -> Output<T> {
+        nested(depth, 0)?;
+        let input = peel(input);
+        Ok(match self {
+            Ast::Current => input.clone(),
+            Ast::Literal(json) => json.clone().into(),
+            Ast::Field(name) => match input {
+                Unstructured::<T>::Map(map) => map
+                    .get(&Unstructured::<T>::from(name.as_str()))
+                    .map(|val| peel(val).clone())
+                    .unwrap_or(Unstructured::<T>::Null),
+                _ => Unstructured::<T>::Null,
+            },
+            Ast::Index(i) => match input {
+                Unstructured::<T>::Seq(seq) => {
+                    let i = if *i < 0 { *i + seq.len() as i64 } else { *i };
+                    usize::try_from(i)
+                        .ok()
+                        .and_then(|i| seq.get(i))
+                        .map(|val| peel(val).clone())
+                        .unwrap_or(Unstructured::<T>::Null)
+                }
+                _ => Unstructured::<T>::Null,
+            },
+            Ast::Sub(lhs, rhs) => rhs.eval(&lhs.eval(input, depth + 1)?, depth + 1)?,
+            Ast::Projection(projection, lhs, rhs) => {
+                let base = lhs.eval(input, depth + 1)?;
+                let elements: Vec<Unstructured<T>> = match (projection, &base) {
+                    (Projection::List, Unstructured::<T>::Seq(seq)) => seq.to_vec(),
+                    (Projection::Values, Unstructured::<T>::Map(map)) => {
+                        map.values().cloned().collect()
+                    }
+                    (Projection::Filter(cond), Unstructured::<T>::Seq(seq)) => {
+                        let mut kept = vec![];
+                        for val in seq.iter() {
+                            if truthy(&cond.eval(val, depth + 1)?) {
+                                kept.push(val.clone());
+                            }
+                        }
+                        kept
+                    }
+                    (Projection::Slice(start, stop, step), Unstructured::<T>::Seq(seq)) => {
+                        slice(seq, *start, *stop, *step)
+                    }
+                    _ => return Ok(Unstructured::<T>::Null),
+                };
+                let mut results = vec![];
+                for val in elements.iter() {
+                    let result = rhs.eval(val, depth + 1)?;
+                    if !result.is_null() {
+                        results.push(result);
+                    }
+                }
+                Unstructured::<T>::Seq(share(results))
+            }
+            Ast::Flatten(target) => match target.eval(input, depth + 1)? {
+                Unstructured::<T>::Seq(seq) => {
+                    let mut flat = vec![];
+                    for val in unshare(seq) {
+                        match peel(&val) {
+                            Unstructured::<T>::Seq(inner) => flat.extend(inner.iter().cloned()),
+                            val => flat.push(val.clone()),
+                        }
+                    }
+                    Unstructured::<T>::Seq(share(flat))
+                }
+                _ => Unstructured::<T>::Null,
+            },
+            Ast::MultiList(items) if !input.is_null() => Unstructured::<T>::Seq(share(
+                items
+                    .iter()
+                    .map(|item| item.eval(input, depth + 1))
+                    .collect::<Result<_, _>>()?,
+            )),
+            Ast::MultiHash(entries) if !input.is_null() => {
+                let mut map = Mapping::<T>::new();
+                for (key, val) in entries.iter() {
+                    map.insert(key.as_str().into(), val.eval(input, depth + 1)?);
+                }
+                Unstructured::<T>::Map(share(map))
+            }
+            Ast::MultiList(_) | Ast::MultiHash(_) => Unstructured::<T>::Null,
+            Ast::Function(name, args) => {
+                let args = args
+                    .iter()
+                    .map(|arg| match arg {
+                        Ast::ExpressionRef(ast) => Ok(Arg::Expression(ast)),
+                        _ => Ok(Arg::Value(arg.eval(input, depth + 1)?)),
+                    })
+                    .collect::<Result<Vec<_>, String>>()?;
+                call(name, args, depth + 1)?
+            }
+            Ast::ExpressionRef(_) => {
+                return Err("Expression references can only be passed to functions".to_string())
+            }
+            Ast::Pipe(lhs, rhs) => rhs.eval(&lhs.eval(input, depth + 1)?, depth + 1)?,
+            Ast::Or(lhs, rhs) => {
+                let lhs = lhs.eval(input, depth + 1)?;
+                if truthy(&lhs) {
+                    lhs
+                } else {
+                    rhs.eval(input, depth + 1)?
+                }
+            }
+            Ast::And(lhs, rhs) => {
+                let lhs = lhs.eval(input, depth + 1)?;
+                if truthy(&lhs) {
+                    rhs.eval(input, depth + 1)?
+                } else {
+                    lhs
+                }
+            }
+            Ast::Not(target) => {
+                Unstructured::<T>::Bool(!truthy(&target.eval(input, depth + 1)?))
+            }
+            Ast::Compare(lhs, op, rhs) => {
+                let (lhs, rhs) = (lhs.eval(input, depth + 1)?, rhs.eval(input, depth + 1)?);
+                match (op, &lhs, &rhs) {
+                    (Comparator::Eq, _, _) => Unstructured::<T>::Bool(lhs == rhs),
+                    (Comparator::Ne, _, _) => Unstructured::<T>::Bool(lhs != rhs),
+                    (_, Unstructured::<T>::Number(l), Unstructured::<T>::Number(r)) => {
+                        let ordering = l.cmp(r);
+                        Unstructured::<T>::Bool(match op {
+                            Comparator::Lt => ordering == Ordering::Less,
+                            Comparator::Le => ordering != Ordering::Greater,
+                            Comparator::Gt => ordering == Ordering::Greater,
+                            _ => ordering != Ordering::Less,
+                        })
+                    }
+                    // Ordering is only defined for numbers
+                    _ => Unstructured::<T>::Null,
+                }
+            }
+        })
+    }
+}
+
+fn call<T: UnstructuredDataTrait>(name: &str, mut args: Vec<Arg<T>>, depth: usize) -> Output<T> {
+    let arity = match name {
+        "abs" | "avg" | "ceil" | "floor" | "keys" | "length" | "max" | "min" | "reverse"
+        | "sort" | "sum" | "to_array" | "to_string" | "to_number" | "type" | "values" => 1,
+        "contains" | "ends_with" | "join" | "map" | "max_by" | "min_by" | "sort_by"
+        | "starts_with" => 2,
+        "merge" | "not_null" if !args.is_empty() => args.len(),
+        "merge" | "not_null" => 1,
+        _ => return Err(format!("Unknown function {}()", name)),
+    };
+    if args.len() != arity {
+        return Err(format!(
+            "{}() takes {} argument{}, got {}",
+            name,
+            arity,
+            if arity == 1 { "" } else { "s" },
+            args.len()
+        ));
+    }
+    // Functions taking an expression have it as their first or second argument
+    let expression = match (name, args.as_slice()) {
+        ("map", [Arg::Expression(ast), _]) => Some(*ast),
+        ("max_by" | "min_by" | "sort_by", [_, Arg::Expression(ast)]) => Some(*ast),
+        ("map" | "max_by" | "min_by" | "sort_by", _) => {
+            return Err(format!("{}() expects an expression reference", name))
+        }
+        _ => None,
+    };
+    let mut values = vec![];
+    for arg in args.drain(..) {
+        match arg {
+            Arg::Value(val) => values.push(val),
+            Arg::Expression(_) if expression.is_some() => {}
+            Arg::Expression(_) => {
+                return Err(format!("{}() does not take an expression reference", name))
+            }
+        }
+    }
+    let invalid = |expected: &str, val: &Unstructured<T>| {
+        Err(format!(
+            "{}() expects {}, got {}",
+            name,
+            expected,
+            type_of(val)
+        ))
+    };
+    let first = values.remove(0);
+    Ok(match (name, first) {
+        ("abs", Unstructured::<T>::Number(n)) => Unstructured::<T>::Number(if n.is_float() {
+            Number::from(f64::from(&n).abs())
+        } else if n.is_signed() {
+            match i128::from(&n).checked_abs() {
+                Some(abs) => int_number(abs),
+                None => return Err(format!("abs() of {} overflows", n)),
+            }
+        } else {
+            n
+        }),
+        ("avg", Unstructured::<T>::Seq(seq)) if seq.is_empty() => Unstructured::<T>::Null,
+        ("avg", Unstructured::<T>::Seq(seq)) => {
+            let total = numbers(name, &seq)?.into_iter().map(f64::from).sum::<f64>();
+            Unstructured::<T>::Number(Number::from(total / seq.len() as f64))
+        }
+        ("ceil", Unstructured::<T>::Number(n)) => Unstructured::<T>::Number(round(&n, f64::ceil)),
+        ("floor", Unstructured::<T>::Number(n)) => Unstructured::<T>::Number(round(&n, f64::floor)),
+        ("contains", Unstructured::<T>::Seq(seq)) => {
+            Unstructured::<T>::Bool(seq.contains(&values[0]))
+        }
+        ("contains", Unstructured::<T>::String(s)) => Unstructured::<T>::Bool(match &values[0] {
+            Unstructured::<T>::String(needle) => s.contains(needle.as_str()),
+            _ => false,
+        }),
+        ("ends_with" | "starts_with", Unstructured::<T>::String(s)) => match &values[0] {
+            Unstructured::<T>::String(affix) if name == "ends_with" => {
+                Unstructured::<T>::Bool(s.ends_with(affix.as_str()))
+            }
+            Unstructured::<T>::String(affix) => {
+                Unstructured::<T>::Bool(s.starts_with(affix.as_str()))
+            }
+            other => return invalid("a string", other),
+        },
+        ("join", Unstructured::<T>::String(sep)) => match &values[0] {
+            Unstructured::<T>::Seq(seq) => Unstructured::<T>::String(
+                strings(name, seq)?
+                    .into_iter()
+                    .collect::<Vec<_>>()
+                    .join(&sep),
+            ),
+            other => return invalid("an array of strings", other),
+        },
+        ("keys", Unstructured::<T>::Map(map)) => {
+            Unstructured::<T>::Seq(share(map.keys().cloned().collect()))
+        }
+        ("values", Unstructured::<T>::Map(map)) => {
+            Unstructured::<T>::Seq(share(map.values().cloned().collect()))
+        }
+        ("length", val @ Unstructured::<T>::String(_))
+        | ("length", val @ Unstructured::<T>::Seq(_))
+        | ("length", val @ Unstructured::<T>::Map(_)) => Unstructured::<T>::from(val.len() as u64),
+        ("map", Unstructured::<T>::Seq(seq)) => Unstructured::<T>::Seq(share(
+            seq.iter()
+                .map(|val| expression.unwrap().eval(val, depth))
+                .collect::<Result<_, _>>()?,
+        )),
+        ("max" | "min", Unstructured::<T>::Seq(seq)) => {
+            let keys = sort_keys(name, seq.to_vec())?;
+            let best = if name == "max" {
+                keys.into_iter().max_by(|l, r| l.cmp(r))
+            } else {
+                keys.into_iter().min_by(|l, r| l.cmp(r))
+            };
+            best.unwrap_or(Unstructured::<T>::Null)
+        }
+        ("max_by" | "min_by" | "sort_by", Unstructured::<T>::Seq(seq)) => {
+            let ast = expression.unwrap();
+            let keys = sort_keys(
+                name,
+                seq.iter()
+                    .map(|val| ast.eval(val, depth))
+                    .collect::<Result<_, _>>()?,
+            )?;
+            let mut pairs: Vec<_> = keys.into_iter().zip(seq.iter().cloned()).collect();
+            match name {
+                "max_by" => pairs
+                    .into_iter()
+                    .max_by(|l, r| l.0.cmp(&r.0))
+                    .map_or(Unstructured::<T>::Null, |(_, val)| val),
+                "min_by" => pairs
+                    .into_iter()
+                    .min_by(|l, r| l.0.cmp(&r.0))
+                    .map_or(Unstructured::<T>::Null, |(_, val)| val),
+                _ => {
+                    pairs.sort_by(|l, r| l.0.cmp(&r.0));
+                    Unstructured::<T>::Seq(share(pairs.into_iter().map(|(_, val)| val).collect()))
+                }
+            }
+        }
+        ("merge", first) => {
+            let mut merged = Mapping::<T>::new();
+            for val in std::iter::once(first).chain(values) {
+                match val {
+                    Unstructured::<T>::Map(map) => merged.extend(unshare(map)),
+                    other => return invalid("objects", &other),
+                }
+            }
+            Unstructured::<T>::Map(share(merged))
+        }
+        ("not_null", first) => std::iter::once(first)
+            .chain(values)
+            .find(|val| !val.is_null())
+            .unwrap_or(Unstructured::<T>::Null),
+        ("reverse", Unstructured::<T>::String(s)) => {
+            Unstructured::<T>::String(s.chars().rev().collect())
+        }
+        ("reverse", Unstructured::<T>::Seq(seq)) => {
+            let mut seq = unshare(seq);
+            seq.reverse();
+            Unstructured::<T>::Seq(share(seq))
+        }
+        ("sort", Unstructured::<T>::Seq(seq)) => {
+            let mut seq = sort_keys(name, unshare(seq))?;
+            seq.sort();
+            Unstructured::<T>::Seq(share(seq))
+        }
+        ("sum", Unstructured::<T>::Seq(seq)) => {
+            let numbers = numbers(name, &seq)?;
+            // Integers that don't fit an i128, or a total that overflows one, are summed as floats
+            let total = numbers.iter().try_fold(0i128, |total, n| {
+                let n = if n.is_float() {
+                    None
+                } else if n.is_signed() {
+                    Some(i128::from(*n))
+                } else {
+                    i128::try_from(u128::from(*n)).ok()
+                };
+                n.and_then(|n| total.checked_add(n))
+            });
+            Unstructured::<T>::Number(match total {
+                Some(total) => int_number(total),
+                None => Number::from(numbers.into_iter().map(f64::from).sum::<f64>()),
+            })
+        }
+        ("to_array", val @ Unstructured::<T>::Seq(_)) => val,
+        ("to_array", val) => Unstructured::<T>::Seq(share(vec![val])),
+        ("to_string", val @ Unstructured::<T>::String(_)) => val,
+        ("to_string", val) => Unstructured::<T>::String(
+            serde_json::to_string(&val).map_err(|e| format!("to_string() failed: {}", e))?,
+        ),
+        ("to_number", val @ Unstructured::<T>::Number(_)) => val,
+        ("to_number", Unstructured::<T>::String(s)) => {
+            match serde_json::from_str::<serde_json::Value>(s.trim()) {
+                Ok(val @ serde_json::Value::Number(_)) => val.into(),
+                _ => Unstructured::<T>::Null,
+            }
+        }
+        ("to_number", _) => Unstructured::<T>::Null,
+        ("type", val) => Unstructured::<T>::from(type_of(&val)),
+        (_, val) => {
+            let expected = match name {
+                "abs" | "ceil" | "floor" => "a number",
+                "avg" | "sum" => "an array of numbers",
+                "contains" => "an array or a string",
+                "ends_with" | "join" | "starts_with" => "a string",
+                "keys" | "values" => "an object",
+                "length" => "a string, array or object",
+                "max" | "min" | "sort" => "an array of numbers or strings",
+                "reverse" => "an array or a string",
+                _ => "an array",
+            };
+            return invalid(expected, &val);
+        }
+    })
+}
+
+/// `Option` and `Newtype` wrappers are transparent to expressions
+fn peel<T: UnstructuredDataTrait>(val: &Unstructured<T>) -> &Unstructured<T> {
+    match val {
+        Unstructured::<T>::Option(Some(inner)) | Unstructured::<T>::Newtype(inner) => peel(inner),
+        Unstructured::<T>::Option(None) | Unstructured::<T>::Unassigned => &Unstructured::<T>::Null,
+        _ => val,
+    }
+}
+
+/// False, null and empty strings, sequences and maps are false, everything else is true
+fn truthy<T: UnstructuredDataTrait>(val: &Unstructured<T>) -> bool {
+    match peel(val) {
+        Unstructured::<T>::Null | Unstructured::<T>::Bool(false) => false,
+        Unstructured::<T>::String(s) => !s.is_empty(),
+        Unstructured::<T>::Seq(seq) => !seq.is_empty(),
+        Unstructured::<T>::Map(map) => !map.is_empty(),
+        _ => true,
+    }
+}
+
+/// The type names JMESPath uses
+fn type_of<T: UnstructuredDataTrait>(val: &Unstructured<T>) -> &'static str {
+    match peel(val) {
+        Unstructured::<T>::Null => "null",
+        Unstructured::<T>::Bool(_) => "boolean",
+        Unstructured::<T>::Number(_) => "number",
+        Unstructured::<T>::String(_) | Unstructured::<T>::Char(_) => "string",
+        Unstructured::<T>::Seq(_) => "array",
+        Unstructured::<T>::Map(_) => "object",
+        other => other.type_name(),
+    }
+}
+
+/// Python style slicing, out of range bounds are clamped
+fn slice<T: UnstructuredDataTrait>(
+    seq: &[Unstructured<T>],
+    start: Option<i64>,
+    stop: Option<i64>,
+    step: Option<i64>,
+) -> Vec<Unstructured<T>> {
+    let len = seq.len() as i64;
+    let step = step.unwrap_or(1);
+    let bound = |i: Option<i64>, default: i64| match i {
+        None => default,
+        Some(i) if i < 0 && step < 0 => (i + len).max(-1),
+        Some(i) if i < 0 => (i + len).max(0),
+        Some(i) if step < 0 => i.min(len - 1),
+        Some(i) => i.min(len),
+    };
+    let mut out = vec![];
+    if step > 0 {
+        let (mut i, stop) = (bound(start, 0), bound(stop, len));
+        while i < stop {
+            out.push(seq[i as usize].clone());
+            match i.checked_add(step) {
+                Some(next) => i = next,
+                None => break,
+            }
+        }
+    } else {
+        let (mut i, stop) = (bound(start, len - 1), bound(stop, -1));
+        while i > stop {
+            out.push(seq[i as usize].clone());
+            match i.checked_add(step) {
+                Some(next) => i = next,
+                None => break,
+            }
+        }
+    }
+    out
+}
+
+fn numbers<'a, T: UnstructuredDataTrait>(
+    name: &str,
+    seq: &'a [Unstructured<T>],
+) -> Result<Vec<&'a Number>, String> {
+    seq.iter()
+        .map(|val| match peel(val) {
+            Unstructured::<T>::Number(n) => Ok(n),
+            other => Err(format!(
+                "{}() expects an array of numbers, got an array containing {}",
+                name,
+                type_of(other)
+            )),
+        })
+        .collect()
+}
+
+fn strings<'a, T: UnstructuredDataTrait>(
+    name: &str,
+    seq: &'a [Unstructured<T>],
+) -> Result<Vec<&'a str>, String> {
+    seq.iter()
+        .map(|val| match peel(val) {
+            Unstructured::<T>::String(s) => Ok(s.as_str()),
+            other => Err(format!(
+                "{}() expects an array of strings, got an array containing {}",
+                name,
+                type_of(other)
+            )),
+        })
+        .collect()
+}
+
+/// Check values can be ordered, they must be all numbers or all strings
+fn sort_keys<T: UnstructuredDataTrait>(
+    name: &str,
+    vals: Vec<Unstructured<T>>,
+) -> Result<Vec<Unstructured<T>>, String> {
+    let vals: Vec<_> = vals.iter().map(|val| peel(val).clone()).collect();
+    let all_numbers = vals.iter().all(|val| val.is_number());
+    let all_strings = vals
+        .iter()
+        .all(|val| matches!(val, Unstructured::<T>::String(_)));
+    if all_numbers || all_strings {
+        Ok(vals)
+    } else {
+        Err(format!(
+            "{}() expects all numbers or all strings, got {}",
+            name,
+            vals.iter().map(type_of).collect::<Vec<_>>().join(", ")
+        ))
+    }
+}
+
+/// Round a number, keeping integers as they are
+fn round(n: &Number, f: fn(f64) -> f64) -> Number {
+    if !n.is_float() {
+        return n.clone();
+    }
+    let rounded = f(f64::from(n));
+    if rounded.abs() < i64::MAX as f64 {
+        int_number(rounded as i128)
+    } else {
+        Number::from(rounded)
+    }
+}
+
+/// The smallest of u64, i64 and f64 that can hold an integer
+fn int_number(n: i128) -> Number {
+    u64::try_from(n)
+        .map(Number::from)
+        .or_else(|_| i64::try_from(n).map(Number::from))
+        .unwrap_or_else(|_| Number::from(n as f64))
+}
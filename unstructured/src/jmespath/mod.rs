@@ -0,0 +1,73 @@
+mod eval;
+mod parser;
+
+use crate::*;
+use parser::Ast;
+
+/// A parsed [JMESPath](https://jmespath.org/specification.html) expression, for evaluating the
+/// same expression against many documents. See [`Unstructured::jmespath`] for what is supported.
+///
+/// ```
+/// use unstructured::{Document, JmesPath};
+///
+/// let expression = JmesPath::compile("people[?age > `20`].name | sort(@)").unwrap();
+/// let doc: Document = serde_json::from_str(
+///     r#"{"people": [{"name": "b", "age": 30}, {"name": "c", "age": 10},
+///         {"name": "a", "age": 40}]}"#,
+/// )
+/// .unwrap();
+/// let names: Document = serde_json::from_str(r#"["a", "b"]"#).unwrap();
+/// assert_eq!(expression.search(&doc).unwrap(), names);
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct JmesPath {
+    ast: Ast,
+}
+
+impl JmesPath {
+    /// Parse an expression, failing if it is not valid
+    pub fn compile(expression: &str) -> Result<Self, Error> {
+        Ok(JmesPath {
+            ast: Ast::parse(expression).map_err(Error::Parse)?,
+        })
+    }
+
+    /// Evaluate the expression against a document
+    pub fn search<T: UnstructuredDataTrait>(
+        &self,
+        doc: &Unstructured<T>,
+    ) -> Result<Unstructured<T>, Error> {
+        self.ast.eval(doc, 0).map_err(Error::Select)
+    }
+}
+
+impl<T: UnstructuredDataTrait> Unstructured<T> {
+    /// Evaluate a JMESPath expression against the document. Anything that doesn't exist
+    /// evaluates to null, errors are only returned for invalid expressions and for functions
+    /// called with the wrong types.
+    ///
+    /// The whole of the JMESPath specification is supported: identifiers, sub-expressions,
+    /// index and slice expressions, list, object, filter and flatten projections, pipes,
+    /// multi-select lists and hashes, literals, raw strings, comparisons, `&&`, `||`, `!` and
+    /// the builtin functions. `Option` and `Newtype` wrappers are transparent to expressions.
+    ///
+    /// ```
+    /// use unstructured::Document;
+    ///
+    /// let doc: Document = serde_json::from_str(
+    ///     r#"{"locations": [
+    ///         {"name": "Seattle", "state": "WA"},
+    ///         {"name": "New York", "state": "NY"},
+    ///         {"name": "Bellevue", "state": "WA"}
+    ///     ]}"#,
+    /// )
+    /// .unwrap();
+    /// let names = doc.jmespath("locations[?state=='WA'].name").unwrap();
+    /// assert_eq!(names[0], Document::from("Seattle"));
+    /// assert_eq!(names[1], Document::from("Bellevue"));
+    /// assert_eq!(doc.jmespath("length(locations)").unwrap(), Document::from(3));
+    /// ```
+    pub fn jmespath(&self, expression: &str) -> Result<Self, Error> {
+        JmesPath::compile(expression)?.search(self)
+    }
+}
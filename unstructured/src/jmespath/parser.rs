@@ -0,0 +1,345 @@
+use crate::core::{nested, unescape, Nesting};
+use pest::iterators::Pair;
+use pest::Parser;
+use pest_derive::*;
+
+#[derive(Parser)]
+#[grammar = "jmespath/grammar/jmespath.pest"]
+struct JmesPathParser;
+
+/// A parsed JMESPath expression
+#[derive(Debug, Clone, PartialEq)]
+pub(super) enum Ast {
+    /// `@`
+    Current,
+    /// A JSON literal or a raw string
+    Literal(serde_json::Value),
+    Field(String),
+    /// `[index]`, negative indices count from the end
+    Index(i64),
+    /// Evaluate the right side against the result of the left side
+    Sub(Box<Ast>, Box<Ast>),
+    /// Evaluate the right side against every element the projection produces from the left
+    /// side, dropping null results
+    Projection(Projection, Box<Ast>, Box<Ast>),
+    /// `[]`, merging nested sequences one level deep
+    Flatten(Box<Ast>),
+    /// `[a, b]`
+    MultiList(Vec<Ast>),
+    /// `{a: a, b: b}`
+    MultiHash(Vec<(String, Ast)>),
+    /// A builtin function and its arguments
+    Function(String, Vec<Ast>),
+    /// `&expr`, an expression passed to a function unevaluated
+    ExpressionRef(Box<Ast>),
+    Pipe(Box<Ast>, Box<Ast>),
+    Or(Box<Ast>, Box<Ast>),
+    And(Box<Ast>, Box<Ast>),
+    Not(Box<Ast>),
+    Compare(Box<Ast>, Comparator, Box<Ast>),
+}
+
+/// How a projection produces the elements its right side is evaluated against
+#[derive(Debug, Clone, PartialEq)]
+pub(super) enum Projection {
+    /// `[*]` and `[]`, every element of a sequence
+    List,
+    /// `*`, every value of a map
+    Values,
+    /// `[?expr]`, the elements of a sequence the expression is true for
+    Filter(Box<Ast>),
+    /// `[start:stop:step]`
+    Slice(Option<i64>, Option<i64>, Option<i64>),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) enum Comparator {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+const NESTING: Nesting = Nesting {
+    quotes: &['"', '\'', '`'],
+    prefixes: &['!'],
+    if_blocks: false,
+};
+
+/// A link in a chain such as `a.b[*].c`, projections apply the links after them to every
+/// element they produce
+enum Link {
+    Plain(Ast),
+    Project(Projection),
+    Flatten,
+}
+
+impl Ast {
+    pub(super) fn parse(expression: &str) -> Result<Self, String> {
+        NESTING.check(expression)?;
+        let mut pairs =
+            JmesPathParser::parse(Rule::expression, expression).map_err(|e| e.to_string())?;
+        match pairs.next() {
+            Some(pair) => Ast::compile(pair, 0),
+            None => Err("Empty expression".to_string()),
+        }
+    }
+
+    /// Compile a pair nested `depth` levels into the expression. Each kind of pair is compiled
+    /// by its own function, which keeps the stack used by every level of nesting small.
+    fn compile(pair: Pair<Rule>, depth: usize) -> Result<Self, String> {
+        let pair = skip_wrappers(pair);
+        match pair.as_rule() {
+            Rule::pipe => fold(pair.into_inner(), depth, Ast::Pipe),
+            Rule::or_expr => fold(pair.into_inner(), depth, Ast::Or),
+            Rule::and_expr => fold(pair.into_inner(), depth, Ast::And),
+            Rule::comparison => Ast::comparison(pair, depth),
+            Rule::not_expr => {
+                let target = Ast::compile(next(&mut pair.into_inner())?, nested(depth, 1)?)?;
+                Ok(Ast::Not(Box::new(target)))
+            }
+            Rule::chain => Ast::chain(pair, depth),
+            Rule::multi_list => {
+                Ok(Ast::MultiList(compile_all(pair.into_inner(), nested(depth, 1)?)?))
+            }
+            Rule::multi_hash => Ast::multi_hash(pair, depth),
+            Rule::function => {
+                let mut inner = pair.into_inner();
+                let name = next(&mut inner)?.as_str().to_string();
+                Ok(Ast::Function(name, compile_all(inner, nested(depth, 1)?)?))
+            }
+            Rule::expression_ref => {
+                let target = Ast::compile(next(&mut pair.into_inner())?, nested(depth, 1)?)?;
+                Ok(Ast::ExpressionRef(Box::new(target)))
+            }
+            _ => Ast::leaf(pair),
+        }
+    }
+
+    fn comparison(pair: Pair<Rule>, depth: usize) -> Result<Self, String> {
+        let mut inner = pair.into_inner();
+        let lhs = next(&mut inner)?;
+        let op = match inner.next() {
+            Some(op) => op,
+            None => return Ast::compile(lhs, depth),
+        };
+        let depth = nested(depth, 1)?;
+        let lhs = Ast::compile(lhs, depth)?;
+        let op = match op.as_str() {
+            "==" => Comparator::Eq,
+            "!=" => Comparator::Ne,
+            "<" => Comparator::Lt,
+            "<=" => Comparator::Le,
+            ">" => Comparator::Gt,
+            ">=" => Comparator::Ge,
+            other => return Err(format!("Unknown comparator {}", other)),
+        };
+        let rhs = Ast::compile(next(&mut inner)?, depth)?;
+        Ok(Ast::Compare(Box::new(lhs), op, Box::new(rhs)))
+    }
+
+    fn chain(pair: Pair<Rule>, depth: usize) -> Result<Self, String> {
+        // Every link nests the chain a level, and a flatten nests it twice
+        let pairs: Vec<_> = pair.into_inner().collect();
+        let flattens = pairs.iter().filter(|p| p.as_rule() == Rule::flatten).count();
+        let depth = nested(depth, pairs.len() + flattens)?;
+        let mut inner = pairs.into_iter();
+        let first = next(&mut inner)?;
+        let mut links = vec![];
+        let base = match first.as_rule() {
+            Rule::values => {
+                links.push(Link::Project(Projection::Values));
+                Ast::Current
+            }
+            Rule::flatten | Rule::list_wildcard | Rule::filter | Rule::slice | Rule::index => {
+                links.push(Link::compile(first, depth)?);
+                Ast::Current
+            }
+            _ => Ast::compile(first, depth)?,
+        };
+        for link in inner {
+            links.push(Link::compile(link, depth)?);
+        }
+        Ok(build_chain(base, &links))
+    }
+
+    fn multi_hash(pair: Pair<Rule>, depth: usize) -> Result<Self, String> {
+        let depth = nested(depth, 1)?;
+        let mut entries = vec![];
+        for entry in pair.into_inner() {
+            let mut parts = entry.into_inner();
+            let key = match Ast::compile(next(&mut parts)?, depth)? {
+                Ast::Field(key) => key,
+                _ => return Err("Invalid key in multi-select hash".to_string()),
+            };
+            entries.push((key, Ast::compile(next(&mut parts)?, depth)?));
+        }
+        Ok(Ast::MultiHash(entries))
+    }
+
+    /// Compile a pair that doesn't contain any other expressions
+    fn leaf(pair: Pair<Rule>) -> Result<Self, String> {
+        let rule = pair.as_rule();
+        let source = pair.as_str();
+        let mut inner = pair.into_inner();
+        Ok(match rule {
+            Rule::current => Ast::Current,
+            Rule::unquoted => Ast::Field(source.to_string()),
+            Rule::quoted => Ast::Field(unescape(next(&mut inner)?.as_str())?),
+            Rule::literal => {
+                let json = next(&mut inner)?.as_str().replace("\\`", "`");
+                Ast::Literal(
+                    serde_json::from_str(json.trim())
+                        .map_err(|e| format!("Invalid literal `{}`: {}", json, e))?,
+                )
+            }
+            Rule::raw_string => {
+                let raw = next(&mut inner)?.as_str().replace("\\'", "'");
+                Ast::Literal(serde_json::Value::String(raw))
+            }
+            _ => return Err(format!("Unexpected {} in expression", source)),
+        })
+    }
+}
+
+impl Link {
+    fn compile(pair: Pair<Rule>, depth: usize) -> Result<Self, String> {
+        let pair = skip_wrappers(pair);
+        let rule = pair.as_rule();
+        let mut inner = pair.into_inner();
+        Ok(match rule {
+            Rule::dot => {
+                let target = next(&mut inner)?;
+                match target.as_rule() {
+                    Rule::values => Link::Project(Projection::Values),
+                    _ => Link::Plain(Ast::compile(target, depth)?),
+                }
+            }
+            Rule::flatten => Link::Flatten,
+            Rule::list_wildcard => Link::Project(Projection::List),
+            Rule::filter => {
+                let cond = Ast::compile(next(&mut inner)?, nested(depth, 1)?)?;
+                Link::Project(Projection::Filter(Box::new(cond)))
+            }
+            Rule::slice => {
+                let mut part = || -> Result<Option<i64>, String> {
+                    next(&mut inner)?
+                        .into_inner()
+                        .next()
+                        .map(|n| parse_int(n.as_str()))
+                        .transpose()
+                };
+                let start = part()?;
+                let stop = part()?;
+                let step = match inner.next() {
+                    Some(step) => step.into_inner().next().map(|n| parse_int(n.as_str())),
+                    None => None,
+                }
+                .transpose()?;
+                if step == Some(0) {
+                    return Err("Slice step cannot be 0".to_string());
+                }
+                Link::Project(Projection::Slice(start, stop, step))
+            }
+            Rule::index => Link::Plain(Ast::Index(parse_int(next(&mut inner)?.as_str())?)),
+            _ => return Err("Unexpected link in expression".to_string()),
+        })
+    }
+}
+
+/// Apply the links to the base left to right. A projection takes every following link up to
+/// the next flatten as its right side, the flatten then applies to the whole projection.
+fn build_chain(base: Ast, links: &[Link]) -> Ast {
+    let mut lhs = base;
+    let mut i = 0;
+    while i < links.len() {
+        let end = match links[i] {
+            Link::Plain(_) => i + 1,
+            _ => links[i + 1..]
+                .iter()
+                .position(|link| matches!(link, Link::Flatten))
+                .map_or(links.len(), |p| i + 1 + p),
+        };
+        lhs = match &links[i] {
+            Link::Plain(ast) if lhs == Ast::Current => ast.clone(),
+            Link::Plain(ast) => Ast::Sub(Box::new(lhs), Box::new(ast.clone())),
+            Link::Project(projection) => Ast::Projection(
+                projection.clone(),
+                Box::new(lhs),
+                Box::new(build_chain(Ast::Current, &links[i + 1..end])),
+            ),
+            Link::Flatten => Ast::Projection(
+                Projection::List,
+                Box::new(Ast::Flatten(Box::new(lhs))),
+                Box::new(build_chain(Ast::Current, &links[i + 1..end])),
+            ),
+        };
+        i = end;
+    }
+    lhs
+}
+
+/// Skip the pairs that only wrap a single operand, such as the operators a parenthesized
+/// expression is parsed through, so compiling only recurses where the expression nests
+fn skip_wrappers(mut pair: Pair<Rule>) -> Pair<Rule> {
+    loop {
+        let mut inner = pair.clone().into_inner();
+        let only = match (inner.next(), inner.next()) {
+            (Some(only), None) => only,
+            _ => return pair,
+        };
+        match (pair.as_rule(), only.as_rule()) {
+            (Rule::chain, Rule::values)
+            | (Rule::chain, Rule::flatten)
+            | (Rule::chain, Rule::list_wildcard)
+            | (Rule::chain, Rule::filter)
+            | (Rule::chain, Rule::slice)
+            | (Rule::chain, Rule::index) => return pair,
+            (Rule::pipe | Rule::or_expr | Rule::and_expr | Rule::comparison | Rule::paren, _)
+            | (Rule::chain, _) => pair = only,
+            _ => return pair,
+        }
+    }
+}
+
+fn next<'a>(pairs: &mut impl Iterator<Item = Pair<'a, Rule>>) -> Result<Pair<'a, Rule>, String> {
+    pairs
+        .next()
+        .ok_or_else(|| "Incomplete expression".to_string())
+}
+
+/// Compile every pair at the same depth, looping rather than collecting so that nested
+/// expressions use as little of the stack as possible
+fn compile_all<'a>(
+    pairs: impl Iterator<Item = Pair<'a, Rule>>,
+    depth: usize,
+) -> Result<Vec<Ast>, String> {
+    let mut compiled = vec![];
+    for pair in pairs {
+        compiled.push(Ast::compile(pair, depth)?);
+    }
+    Ok(compiled)
+}
+
+/// Combine a list of operands left to right, each one after the first nests the result a level
+fn fold<'a>(
+    pairs: impl Iterator<Item = Pair<'a, Rule>>,
+    depth: usize,
+    combine: impl Fn(Box<Ast>, Box<Ast>) -> Ast,
+) -> Result<Ast, String> {
+    let pairs: Vec<_> = pairs.collect();
+    let depth = nested(depth, pairs.len().saturating_sub(1))?;
+    let mut pairs = pairs.into_iter();
+    let mut lhs = Ast::compile(next(&mut pairs)?, depth)?;
+    for rhs in pairs {
+        lhs = combine(Box::new(lhs), Box::new(Ast::compile(rhs, depth)?));
+    }
+    Ok(lhs)
+}
+
+fn parse_int(s: &str) -> Result<i64, String> {
+    s.parse()
+        .map_err(|e| format!("Invalid number {} in expression: {}", s, e))
+}
@@ -25,16 +25,17 @@ In addition to many of the futures provided by the above libraries, unstructured
 - Filters to create new documents from an array of input documents: ```docs.filter("[0].path.to.key | [1].path.to.array[0:5]")```
 - Convenience methods for is_type(), as_type(), take_type()
 - Most of the From implementation for easy document creation
+- Building documents whose shape is only known at runtime: ```DocumentBuilder::new().key("a").value(1).build()?```
+- Event streams for selecting from inputs too large to hold in memory: ```Document::stream_events```
 
 # Example Usage
 
 The primary struct used in this repo is ```Document```. Document provides methods for easy type conversion and manipulation.
 
 ```
-use unstructured::{Document, Number};
-use std::collections::BTreeMap;
+use unstructured::{Document, Mapping, Number};
 
-let mut map = BTreeMap::new(); // Will be inferred as BTreeMap<Document, Document> though root element can be any supported type
+let mut map = Mapping::new(); // A BTreeMap<Document, Document>, though the root element can be any supported type
 map.insert("test".into(), 100u64.into()); // From<> is implement for most basic data types
 let doc: Document = map.into(); // Create a new Document where the root element is the map defined above
 assert_eq!(doc["test"], Document::Number(Number::U64(100)));
@@ -71,6 +72,10 @@ Selectors can be used to retrieve a reference to nested values, regardless of th
 
 - [JSON Pointer syntax](https://tools.ietf.org/html/rfc6901): ```doc.select("/path/to/key")```
 - A JQ inspired syntax: ```doc.select(".path.to.[\"key\"")```
+- Escaped keys, using JSON string escapes in quoted keys or `~0` and `~1` in pointers: ```doc.select(r#".["a.b\"c"]"#)``` ```doc.select("/a~1b")```
+- Recursive descent, matching a key at any depth: ```doc.select_all("..key")```
+- Wildcards, matching every map value or sequence element: ```doc.select_all(".users[*].email")```
+- Predicates, matching the elements for which an expression is true: ```doc.select_all(".items[?(@.price > 10)]")```
 
 ```
 use unstructured::Document;
@@ -86,8 +91,9 @@ In addition to selectors, filters can be used to create new documents from an ar
 
 - Document selection: ```"[0]", "[1]", "*"```
 - Path navigation: ```"[0].path.to.key" "[0] /path/to/key" r#" [0] .["path"].["to"].["key"] "#```
-- Index selection: ```"[0] .array.[0]"```
-- Sequence selection: ```"[0] .array.[0:0]" "[0] .array.[:]" "[0] .array.[:5]"```
+- Index selection, negative indices count from the end: ```"[0] .array.[0]" "[0] .array.[-1]"```
+- Sequence selection: ```"[0] .array.[0:0]" "[0] .array.[:]" "[0] .array.[:5]" "[0] .array.[-2:]"```
+- Sequence selection with a step: ```"[0] .array.[::2]" "[0] .array.[::-1]"```
 - Filtering multiple docs: ```"[0].key | [1].key"```
 - Merging docs: ```"*" "[0].key.to.merge | [1].add.this.key.too | [2].key.to.merge"```
 
@@ -101,6 +107,46 @@ let docs: Vec<Document> = vec![
 let result = Document::filter(&docs, "[0].some.nested.vals | [1].some.nested.vals").unwrap();
 assert_eq!(result["some"]["nested"]["vals"][4], Document::Number(Number::U64(5)));
 ```
+
+# Features
+
+Only serde is required for the core document types, everything else can be disabled with
+`default-features = false`.
+
+- **selector** (default): ```select```, ```select_mut``` and ```select_all```, pulls in pest
+- **filter** (default): ```Document::filter```, implies **selector**
+- **query**: ```Document::query``` and ```Query``` for running a subset of jq, with pipes,
+  ```map```, ```select```, arithmetic and string interpolation, pulls in pest
+- **jmespath**: ```Document::jmespath``` and ```JmesPath``` for evaluating
+  [JMESPath](https://jmespath.org) expressions, pulls in pest and serde_json
+- **cache**: ```DocCache``` for reusing parsed documents
+- **stream**: ```DocumentStream``` for parsing NDJSON and MessagePack byte streams
+- **transcode**: ```transcode``` for converting between JSON and MessagePack without building
+  a ```Document```, and ```Document::roundtrips_via``` for checking a conversion is lossless
+- **raw**: ```RawDocument``` for holding JSON or MessagePack that is only parsed when it is used
+- **hash**: ```Document::canonical_hash``` for a SHA-256 hash that is stable across processes
+  and versions, and ```ReplaceWith::Hash``` for redacting with a keyed digest, pulls in sha2
+  and hmac
+- **macros**: ```sel!``` for parsing a selector into a ```Path``` at compile time
+- **tracing**: ```Document::as_trace_value``` and ```record_fields``` for recording documents
+  as fields on tracing spans and events, deep values are written as JSON
+- **arbitrary**: ```arbitrary::Arbitrary``` for ```Document``` and ```Number```, and
+  ```Document::arbitrary_with_limits``` for generating documents of a bounded size to fuzz with
+- **generate**: ```Document::generate``` for filling templates with random data, pulls in rand
+- **chrono**: a ```DateTime``` variant for timestamps, serialized as RFC 3339 strings
+- **preserve_order**: keep map keys in insertion order by using ```IndexMap``` for ```Mapping```,
+  pulls in indexmap
+- **serde_json**: ```Document::from_json_str```, ```to_json_string``` and friends,
+  ```JsonFloatFormatter``` for choosing how floats are written as JSON and ```From```
+  conversions to and from ```serde_json::Value```, also enabled by **stream** and **transcode**
+- **serde_yaml**, **toml**, **ciborium**, **rmp-serde**: the same helpers for YAML, TOML, CBOR
+  and MessagePack, e.g. ```Document::from_yaml_str``` and ```to_msgpack_vec```, along with
+  conversions to and from ```serde_yaml::Value``` and ```toml::Value```
+- **rmpv**: conversions to and from ```rmpv::Value```
+- **shared**: stores ```Seq``` and ```Map``` contents in an ```Arc```, so cloning a document
+  is O(1) and contents are only copied when a clone mutates them. Contents are always held in
+  a ```Shared```, which has the same API with or without this feature.
+  ```Document::dedup_subtrees``` makes repeated sequences and maps share one ```Arc```
 */
 
 #[macro_use]
@@ -111,8 +157,59 @@ mod test;
 
 pub use number::*;
 pub use crate::core::*;
-
+pub use error::*;
+#[cfg(feature = "selector")]
+pub use selector::*;
+#[cfg(feature = "cache")]
+pub use cache::*;
+#[cfg(feature = "jmespath")]
+pub use jmespath::*;
+#[cfg(feature = "query")]
+pub use query::*;
+#[cfg(feature = "raw")]
+pub use raw::*;
+#[cfg(feature = "stream")]
+pub use stream::*;
+#[cfg(feature = "transcode")]
+pub use transcode::*;
+#[cfg(feature = "macros")]
+pub use unstructured_macros::sel;
+
+#[cfg(feature = "arbitrary")]
+mod fuzz;
+#[cfg(feature = "cache")]
+mod cache;
 mod selector;
+#[cfg(feature = "jmespath")]
+mod jmespath;
+#[cfg(feature = "query")]
+mod query;
 mod core;
+mod error;
+#[cfg(any(
+    feature = "serde_json",
+    feature = "serde_yaml",
+    feature = "toml",
+    feature = "ciborium",
+    feature = "rmp-serde"
+))]
+mod formats;
+#[cfg(feature = "generate")]
+mod generate;
+#[cfg(any(
+    feature = "serde_json",
+    feature = "serde_yaml",
+    feature = "toml",
+    feature = "rmpv"
+))]
+mod interop;
 mod macros;
 mod number;
+#[cfg(feature = "raw")]
+mod raw;
+#[cfg(feature = "stream")]
+mod stream;
+#[cfg(feature = "tracing")]
+mod trace;
+#[cfg(feature = "transcode")]
+mod transcode;
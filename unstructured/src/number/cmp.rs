@@ -2,25 +2,87 @@ use super::*;
 use std::cmp::Ordering;
 use std::hash::{Hash, Hasher};
 
+/// Total ordering for floats where NaN is equal to itself and greater than any other value
+fn cmp_float(lhs: f64, rhs: f64) -> Ordering {
+    match lhs.partial_cmp(&rhs) {
+        Some(ordering) => ordering,
+        None if lhs.is_nan() && rhs.is_nan() => Ordering::Equal,
+        None if lhs.is_nan() => Ordering::Greater,
+        None => Ordering::Less,
+    }
+}
+
+/// Hash floats consistently with `cmp_float`, all NaNs hash the same as do 0 and -0
+fn hash_float<H: Hasher>(v: f64, hasher: &mut H) {
+    let bits = if v.is_nan() {
+        f64::NAN.to_bits()
+    } else if v == 0.0 {
+        0
+    } else {
+        v.to_bits()
+    };
+    bits.hash(hasher);
+}
+
+/// The exact value of a number, used to compare and hash numbers of different types
+enum Value {
+    Neg(i128),
+    Pos(u128),
+    Float(f64),
+}
+
+impl Number {
+    fn value(&self) -> Value {
+        match *self {
+            Number::F32(f) => Value::Float(f as f64),
+            Number::F64(f) => Value::Float(f),
+            ref n if n.is_signed() && i128::from(n) < 0 => Value::Neg(i128::from(n)),
+            ref n => Value::Pos(u128::from(n)),
+        }
+    }
+
+    /// Compare both the value and the type, so `Number::U8(1)` is not strictly equal to
+    /// `Number::U64(1)` even though they are equal
+    pub fn strict_eq(&self, rhs: &Number) -> bool {
+        self.discriminant() == rhs.discriminant() && self == rhs
+    }
+}
+
+/// Compare an integer with a float exactly, NaN is greater than any integer
+fn cmp_int_float(int: &Value, f: f64) -> Ordering {
+    if f.is_nan() || f >= u128::MAX as f64 {
+        return Ordering::Less;
+    }
+    if f < i128::MIN as f64 {
+        return Ordering::Greater;
+    }
+    let whole = f.trunc();
+    let ordering = match (int, whole < 0.0) {
+        (Value::Neg(i), true) => i.cmp(&(whole as i128)),
+        (Value::Neg(_), false) => Ordering::Less,
+        (Value::Pos(_), true) => Ordering::Greater,
+        (Value::Pos(u), false) => u.cmp(&(whole as u128)),
+        (Value::Float(_), _) => unreachable!(),
+    };
+    ordering.then_with(|| cmp_float(whole, f))
+}
+
 impl Hash for Number {
+    /// Numbers that are equal hash the same regardless of their type
     fn hash<H>(&self, hasher: &mut H)
     where
         H: Hasher,
     {
-        self.discriminant().hash(hasher);
-        match *self {
-            Number::U8(v) => v.hash(hasher),
-            Number::U16(v) => v.hash(hasher),
-            Number::U32(v) => v.hash(hasher),
-            Number::U64(v) => v.hash(hasher),
-            Number::U128(v) => v.hash(hasher),
-            Number::I8(v) => v.hash(hasher),
-            Number::I16(v) => v.hash(hasher),
-            Number::I32(v) => v.hash(hasher),
-            Number::I64(v) => v.hash(hasher),
-            Number::I128(v) => v.hash(hasher),
-            Number::F32(v) => OrderedFloat(v).hash(hasher),
-            Number::F64(v) => OrderedFloat(v).hash(hasher),
+        match self.value() {
+            Value::Neg(i) => i.hash(hasher),
+            Value::Pos(u) => u.hash(hasher),
+            Value::Float(f) if f.fract() == 0.0 && f < 0.0 && f >= i128::MIN as f64 => {
+                (f as i128).hash(hasher)
+            }
+            Value::Float(f) if f.fract() == 0.0 && f >= 0.0 && f < u128::MAX as f64 => {
+                (f as u128).hash(hasher)
+            }
+            Value::Float(f) => hash_float(f, hasher),
         }
     }
 }
@@ -34,72 +96,30 @@ impl PartialOrd for Number {
 }
 
 impl Ord for Number {
+    /// Numbers are ordered by their value regardless of type, NaN is greater than any other
+    /// number and equal to itself
     fn cmp(&self, rhs: &Self) -> Ordering {
-        match (self, rhs) {
-            (Number::I128(i), n) => i.cmp(&i128::from(n)),
-            (Number::U128(i), n) => i.cmp(&u128::from(n)),
-            (Number::F64(i), n) => OrderedFloat(*i).cmp(&OrderedFloat(f64::from(n))),
-            (Number::I64(i), n) => i.cmp(&i64::from(n)),
-            (Number::U64(i), n) => i.cmp(&u64::from(n)),
-            (Number::F32(i), n) => OrderedFloat(*i).cmp(&OrderedFloat(f32::from(n))),
-            (Number::I32(i), n) => i.cmp(&i32::from(n)),
-            (Number::U32(i), n) => i.cmp(&u32::from(n)),
-            (Number::I16(i), n) => i.cmp(&i16::from(n)),
-            (Number::U16(i), n) => i.cmp(&u16::from(n)),
-            (Number::I8(i), n) => i.cmp(&i8::from(n)),
-            (Number::U8(i), n) => i.cmp(&u8::from(n)),
-        }
-    }
-}
-
-impl std::ops::Add<Number> for Number {
-    type Output = Number;
-
-    fn add(self, rhs: Number) -> Number {
-        match (self, rhs) {
-            (Number::I128(i), n) => (i + i128::from(n)).into(),
-            (Number::U128(i), n) => (i + u128::from(n)).into(),
-            (Number::F64(i), n) => (i + f64::from(n)).into(),
-            (Number::I64(i), n) => (i + i64::from(n)).into(),
-            (Number::U64(i), n) => (i + u64::from(n)).into(),
-            (Number::F32(i), n) => (i + f32::from(n)).into(),
-            (Number::I32(i), n) => (i + i32::from(n)).into(),
-            (Number::U32(i), n) => (i + u32::from(n)).into(),
-            (Number::I16(i), n) => (i + i16::from(n)).into(),
-            (Number::U16(i), n) => (i + u16::from(n)).into(),
-            (Number::I8(i), n) => (i + i8::from(n)).into(),
-            (Number::U8(i), n) => (i + u8::from(n)).into(),
+        match (self.value(), rhs.value()) {
+            (Value::Neg(l), Value::Neg(r)) => l.cmp(&r),
+            (Value::Pos(l), Value::Pos(r)) => l.cmp(&r),
+            (Value::Neg(_), Value::Pos(_)) => Ordering::Less,
+            (Value::Pos(_), Value::Neg(_)) => Ordering::Greater,
+            (Value::Float(l), Value::Float(r)) => cmp_float(l, r),
+            (int, Value::Float(f)) => cmp_int_float(&int, f),
+            (Value::Float(f), int) => cmp_int_float(&int, f).reverse(),
         }
     }
 }
 
 impl PartialEq<Number> for Number {
     fn eq(&self, rhs: &Number) -> bool {
-        match (self, rhs) {
-            (Number::I128(i), n) => i == &i128::from(n),
-            (Number::U128(i), n) => i == &u128::from(n),
-            (Number::F64(i), n) => OrderedFloat(*i) == OrderedFloat(f64::from(n)),
-            (Number::I64(i), n) => i == &i64::from(n),
-            (Number::U64(i), n) => i == &u64::from(n),
-            (Number::F32(i), n) => OrderedFloat(*i) == OrderedFloat(f32::from(n)),
-            (Number::I32(i), n) => i == &i32::from(n),
-            (Number::U32(i), n) => i == &u32::from(n),
-            (Number::I16(i), n) => i == &i16::from(n),
-            (Number::U16(i), n) => i == &u16::from(n),
-            (Number::I8(i), n) => i == &i8::from(n),
-            (Number::U8(i), n) => i == &u8::from(n),
-        }
+        self.cmp(rhs) == Ordering::Equal
     }
 }
 
 impl<T: UnstructuredDataTrait> PartialEq<Unstructured<T>> for Number {
     fn eq(&self, rhs: &Unstructured<T>) -> bool {
-        match (rhs, self) {
-            (Unstructured::<T>::Number(n1), n2) => n1 == n2,
-            (Unstructured::<T>::Option(Some(n1)), n2) => &**n1 == n2,
-            (Unstructured::<T>::Newtype(n1), n2) => &**n1 == n2,
-            _ => false,
-        }
+        rhs == self
     }
 }
 
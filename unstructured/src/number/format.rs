@@ -0,0 +1,128 @@
+use super::*;
+#[cfg(feature = "serde_json")]
+use std::io;
+
+/// How floats are written as text, see [`Number::format_with`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum FloatFormat {
+    /// The shortest digits that round-trip, without an exponent, e.g. `0.0000001`
+    #[default]
+    Shortest,
+    /// A fixed number of decimal places, e.g. `0.10` with two places
+    Fixed(usize),
+    /// Scientific notation with the shortest digits that round-trip, e.g. `1e-7`
+    Scientific,
+}
+
+impl FloatFormat {
+    fn write<F: fmt::Display + fmt::LowerExp>(self, f: F) -> String {
+        match self {
+            FloatFormat::Shortest => f.to_string(),
+            FloatFormat::Fixed(places) => format!("{:.*}", places, f),
+            FloatFormat::Scientific => format!("{:e}", f),
+        }
+    }
+}
+
+impl Number {
+    /// Format the number as text, writing floats in the given format. Integers are unaffected.
+    ///
+    /// ```
+    /// use unstructured::{FloatFormat, Number};
+    ///
+    /// let n = Number::F64(0.0000001);
+    /// assert_eq!(n.format_with(FloatFormat::Shortest), "0.0000001");
+    /// assert_eq!(n.format_with(FloatFormat::Scientific), "1e-7");
+    /// assert_eq!(Number::F32(2.5).format_with(FloatFormat::Fixed(2)), "2.50");
+    /// assert_eq!(Number::U8(3).format_with(FloatFormat::Fixed(2)), "3");
+    /// ```
+    pub fn format_with(&self, format: FloatFormat) -> String {
+        match self {
+            Number::F32(f) => format.write(*f),
+            Number::F64(f) => format.write(*f),
+            n => n.to_string(),
+        }
+    }
+}
+
+/// A `serde_json` formatter that writes floats in the given format, wrapping another formatter
+/// for everything else.
+///
+/// ```
+/// use unstructured::{Document, FloatFormat, JsonFloatFormatter};
+///
+/// let doc: Document = serde_json::from_str("[1e-7, 0.5, 2]").unwrap();
+/// let mut out = vec![];
+/// let formatter = JsonFloatFormatter::new(FloatFormat::Shortest);
+/// let mut ser = serde_json::Serializer::with_formatter(&mut out, formatter);
+/// serde::Serialize::serialize(&doc, &mut ser).unwrap();
+/// assert_eq!(out, b"[0.0000001,0.5,2]");
+/// ```
+#[cfg(feature = "serde_json")]
+#[derive(Debug, Clone)]
+pub struct JsonFloatFormatter<F = serde_json::ser::CompactFormatter> {
+    format: FloatFormat,
+    inner: F,
+}
+
+#[cfg(feature = "serde_json")]
+impl JsonFloatFormatter {
+    /// Write compact JSON with floats in the given format
+    pub fn new(format: FloatFormat) -> Self {
+        Self::with_formatter(format, serde_json::ser::CompactFormatter)
+    }
+}
+
+#[cfg(feature = "serde_json")]
+impl<'a> JsonFloatFormatter<serde_json::ser::PrettyFormatter<'a>> {
+    /// Write indented JSON with floats in the given format
+    pub fn pretty(format: FloatFormat) -> Self {
+        Self::with_formatter(format, serde_json::ser::PrettyFormatter::new())
+    }
+}
+
+#[cfg(feature = "serde_json")]
+impl<F> JsonFloatFormatter<F> {
+    /// Wrap any other formatter, only the way floats are written is changed
+    pub fn with_formatter(format: FloatFormat, inner: F) -> Self {
+        JsonFloatFormatter { format, inner }
+    }
+}
+
+/// Forward every method to the inner formatter other than the float writers
+#[cfg(feature = "serde_json")]
+macro_rules! forward_formatter {
+    ( $( $method:ident ( $( $arg:ident : $ty:ty ),* ) ),* $(,)? ) => {
+        $(
+            fn $method<W: ?Sized + io::Write>(&mut self, writer: &mut W $(, $arg: $ty )*)
+                -> io::Result<()>
+            {
+                self.inner.$method(writer $(, $arg )*)
+            }
+        )*
+    };
+}
+
+#[cfg(feature = "serde_json")]
+impl<F: serde_json::ser::Formatter> serde_json::ser::Formatter for JsonFloatFormatter<F> {
+    fn write_f32<W: ?Sized + io::Write>(&mut self, writer: &mut W, value: f32) -> io::Result<()> {
+        writer.write_all(self.format.write(value).as_bytes())
+    }
+
+    fn write_f64<W: ?Sized + io::Write>(&mut self, writer: &mut W, value: f64) -> io::Result<()> {
+        writer.write_all(self.format.write(value).as_bytes())
+    }
+
+    forward_formatter! {
+        begin_array(),
+        end_array(),
+        begin_array_value(first: bool),
+        end_array_value(),
+        begin_object(),
+        end_object(),
+        begin_object_key(first: bool),
+        end_object_key(),
+        begin_object_value(),
+        end_object_value(),
+    }
+}
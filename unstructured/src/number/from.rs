@@ -5,7 +5,7 @@ macro_rules! from_imp {
         $(
             impl From<&$ty> for Number {
                 fn from(n: &$ty) -> Self {
-                    Number::$v(n.to_owned())
+                    Number::$v(n.to_owned().into())
                 }
             }
 
@@ -30,7 +30,7 @@ macro_rules! from_imp {
 
             impl From<$ty> for Number {
                 fn from(n: $ty) -> Self {
-                    Number::$v(n as $ty)
+                    Number::$v(n.into())
                 }
             }
 
@@ -1,5 +1,5 @@
 use crate::*;
-use ordered_float::OrderedFloat;
+use std::convert::TryFrom;
 use std::fmt;
 use serde::{Deserialize, Serialize};
 use serde::{
@@ -9,9 +9,13 @@ use serde::{
 
 mod cmp;
 mod de;
+mod format;
 mod from;
+mod ops;
 mod ser;
 
+pub use format::*;
+
 #[derive(Clone, Debug)]
 pub enum Number {
     U8(u8),
@@ -66,7 +70,7 @@ impl Number {
     }
 
     #[allow(clippy::cast_lossless)]
-    pub(crate) fn unexpected(&self) -> serde::de::Unexpected {
+    pub(crate) fn unexpected(&self) -> serde::de::Unexpected<'_> {
         match *self {
             Number::U8(n) => serde::de::Unexpected::Unsigned(n as u64),
             Number::U16(n) => serde::de::Unexpected::Unsigned(n as u64),
@@ -96,4 +100,123 @@ impl Number {
     pub fn is_float(&self) -> bool {
         matches!(self, Number::F32(_) | Number::F64(_))
     }
+
+    /// Returns true if the value is any signed or unsigned integer
+    pub fn is_integer(&self) -> bool {
+        !self.is_float()
+    }
+
+    /// The value as an `i64` if it is an integer within range. Unlike `i64::from`, this never
+    /// truncates, floats return None even if they are whole numbers.
+    ///
+    /// ```
+    /// use unstructured::Number;
+    ///
+    /// assert_eq!(Number::U64(42).as_i64(), Some(42));
+    /// assert_eq!(Number::U64(u64::MAX).as_i64(), None);
+    /// assert_eq!(Number::F64(1.0).as_i64(), None);
+    /// ```
+    pub fn as_i64(&self) -> Option<i64> {
+        match self {
+            n if n.is_signed() => i64::try_from(i128::from(n)).ok(),
+            n if n.is_unsigned() => i64::try_from(u128::from(n)).ok(),
+            _ => None,
+        }
+    }
+
+    /// The value as a `u64` if it is a non-negative integer within range, floats return None
+    pub fn as_u64(&self) -> Option<u64> {
+        match self {
+            n if n.is_signed() => u64::try_from(i128::from(n)).ok(),
+            n if n.is_unsigned() => u64::try_from(u128::from(n)).ok(),
+            _ => None,
+        }
+    }
+
+    /// The value as an `f64` if it can be represented exactly, integers beyond 2^53 that would
+    /// be rounded return None
+    ///
+    /// ```
+    /// use unstructured::Number;
+    ///
+    /// assert_eq!(Number::F32(0.5).as_f64(), Some(0.5));
+    /// assert_eq!(Number::U64(1 << 53).as_f64(), Some(9007199254740992.0));
+    /// assert_eq!(Number::U64((1 << 53) + 1).as_f64(), None);
+    /// ```
+    pub fn as_f64(&self) -> Option<f64> {
+        let f = f64::from(self);
+        match self {
+            Number::F32(_) | Number::F64(_) => Some(f),
+            n if n.is_signed() => Some(f).filter(|f| {
+                *f >= i128::MIN as f64 && *f < i128::MAX as f64 && *f as i128 == i128::from(n)
+            }),
+            n => Some(f).filter(|f| *f < u128::MAX as f64 && *f as u128 == u128::from(n)),
+        }
+    }
+
+    /// Convert to the smallest type that holds the same value. Whole floats become integers,
+    /// integers use the smallest unsigned type or, if negative, the smallest signed type, and
+    /// other floats are stored as `f32` where that is exact.
+    ///
+    /// ```
+    /// use unstructured::Number;
+    ///
+    /// assert_eq!(Number::I64(200).canonical().type_name(), "u8");
+    /// assert_eq!(Number::F64(-2.0).canonical().type_name(), "i8");
+    /// assert_eq!(Number::F64(0.5).canonical().type_name(), "f32");
+    /// assert_eq!(Number::F64(0.1).canonical().type_name(), "f64");
+    /// ```
+    pub fn canonical(&self) -> Number {
+        if self.is_float() {
+            let f = f64::from(self);
+            if f.fract() == 0.0 && f >= i128::MIN as f64 && f < u128::MAX as f64 {
+                return if f < 0.0 {
+                    Number::from(f as i128).canonical()
+                } else {
+                    Number::from(f as u128).canonical()
+                };
+            }
+            let narrow = f as f32;
+            return if f64::from(narrow) == f || f.is_nan() {
+                Number::F32(narrow)
+            } else {
+                Number::F64(f)
+            };
+        }
+        if self.is_signed() && i128::from(self) < 0 {
+            let v = i128::from(self);
+            i8::try_from(v)
+                .map(Number::I8)
+                .or_else(|_| i16::try_from(v).map(Number::I16))
+                .or_else(|_| i32::try_from(v).map(Number::I32))
+                .or_else(|_| i64::try_from(v).map(Number::I64))
+                .unwrap_or_else(|_| Number::from(v))
+        } else {
+            let v = u128::from(self);
+            u8::try_from(v)
+                .map(Number::U8)
+                .or_else(|_| u16::try_from(v).map(Number::U16))
+                .or_else(|_| u32::try_from(v).map(Number::U32))
+                .or_else(|_| u64::try_from(v).map(Number::U64))
+                .unwrap_or_else(|_| Number::from(v))
+        }
+    }
+
+    /// The name of the primitive type held, e.g. `"u8"` or `"f64"`
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Number::U8(_) => "u8",
+            Number::U16(_) => "u16",
+            Number::U32(_) => "u32",
+            Number::U64(_) => "u64",
+            Number::U128(_) => "u128",
+            Number::I8(_) => "i8",
+            Number::I16(_) => "i16",
+            Number::I32(_) => "i32",
+            Number::I64(_) => "i64",
+            Number::I128(_) => "i128",
+            Number::F32(_) => "f32",
+            Number::F64(_) => "f64",
+        }
+    }
 }
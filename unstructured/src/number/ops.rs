@@ -0,0 +1,339 @@
+use super::*;
+use std::convert::TryFrom;
+
+/// The type arithmetic is done in, see [`Kind::promote`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Kind {
+    Unsigned(u32),
+    Signed(u32),
+    F32,
+    F64,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Op {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Rem,
+}
+
+impl Number {
+    fn kind(&self) -> Kind {
+        match self {
+            Number::U8(_) => Kind::Unsigned(8),
+            Number::U16(_) => Kind::Unsigned(16),
+            Number::U32(_) => Kind::Unsigned(32),
+            Number::U64(_) => Kind::Unsigned(64),
+            Number::U128(_) => Kind::Unsigned(128),
+            Number::I8(_) => Kind::Signed(8),
+            Number::I16(_) => Kind::Signed(16),
+            Number::I32(_) => Kind::Signed(32),
+            Number::I64(_) => Kind::Signed(64),
+            Number::I128(_) => Kind::Signed(128),
+            Number::F32(_) => Kind::F32,
+            Number::F64(_) => Kind::F64,
+        }
+    }
+}
+
+impl Kind {
+    /// The narrowest type that can hold every value of both types. Mixing signed and unsigned
+    /// integers gives a signed type wide enough for the unsigned one and mixing floats with
+    /// integers wider than 16 bits gives `f64`.
+    fn promote(self, rhs: Kind) -> Kind {
+        match (self, rhs) {
+            (Kind::F64, _) | (_, Kind::F64) => Kind::F64,
+            (Kind::F32, Kind::F32) => Kind::F32,
+            (Kind::F32, Kind::Unsigned(bits) | Kind::Signed(bits))
+            | (Kind::Unsigned(bits) | Kind::Signed(bits), Kind::F32) => {
+                if bits <= 16 {
+                    Kind::F32
+                } else {
+                    Kind::F64
+                }
+            }
+            (Kind::Unsigned(l), Kind::Unsigned(r)) => Kind::Unsigned(l.max(r)),
+            (Kind::Signed(l), Kind::Signed(r)) => Kind::Signed(l.max(r)),
+            (Kind::Signed(s), Kind::Unsigned(u)) | (Kind::Unsigned(u), Kind::Signed(s)) => {
+                Kind::Signed(s.max((u * 2).min(128)))
+            }
+        }
+    }
+
+    fn number_from_u128(self, v: u128) -> Option<Number> {
+        match self {
+            Kind::Unsigned(8) => u8::try_from(v).ok().map(Number::U8),
+            Kind::Unsigned(16) => u16::try_from(v).ok().map(Number::U16),
+            Kind::Unsigned(32) => u32::try_from(v).ok().map(Number::U32),
+            Kind::Unsigned(64) => u64::try_from(v).ok().map(Number::U64),
+            Kind::Unsigned(_) => Some(Number::from(v)),
+            _ => i128::try_from(v).ok().and_then(|v| self.number_from_i128(v)),
+        }
+    }
+
+    fn number_from_i128(self, v: i128) -> Option<Number> {
+        match self {
+            Kind::Signed(8) => i8::try_from(v).ok().map(Number::I8),
+            Kind::Signed(16) => i16::try_from(v).ok().map(Number::I16),
+            Kind::Signed(32) => i32::try_from(v).ok().map(Number::I32),
+            Kind::Signed(64) => i64::try_from(v).ok().map(Number::I64),
+            Kind::Signed(_) => Some(Number::from(v)),
+            Kind::Unsigned(_) => u128::try_from(v).ok().and_then(|v| self.number_from_u128(v)),
+            Kind::F32 => Some(Number::F32(v as f32)),
+            Kind::F64 => Some(Number::F64(v as f64)),
+        }
+    }
+
+    fn max(self) -> Number {
+        match self {
+            Kind::Unsigned(bits) => self.number_from_u128(u128::MAX >> (128 - bits)),
+            Kind::Signed(bits) => self.number_from_i128(i128::MAX >> (128 - bits)),
+            Kind::F32 => Some(Number::F32(f32::MAX)),
+            Kind::F64 => Some(Number::F64(f64::MAX)),
+        }
+        .unwrap()
+    }
+
+    fn min(self) -> Number {
+        match self {
+            Kind::Unsigned(_) => self.number_from_u128(0),
+            Kind::Signed(bits) => self.number_from_i128(i128::MIN >> (128 - bits)),
+            Kind::F32 => Some(Number::F32(f32::MIN)),
+            Kind::F64 => Some(Number::F64(f64::MIN)),
+        }
+        .unwrap()
+    }
+}
+
+impl Op {
+    fn unsigned(self, l: u128, r: u128) -> Option<u128> {
+        match self {
+            Op::Add => l.checked_add(r),
+            Op::Sub => l.checked_sub(r),
+            Op::Mul => l.checked_mul(r),
+            Op::Div => l.checked_div(r),
+            Op::Rem => l.checked_rem(r),
+        }
+    }
+
+    fn signed(self, l: i128, r: i128) -> Option<i128> {
+        match self {
+            Op::Add => l.checked_add(r),
+            Op::Sub => l.checked_sub(r),
+            Op::Mul => l.checked_mul(r),
+            Op::Div => l.checked_div(r),
+            Op::Rem => l.checked_rem(r),
+        }
+    }
+
+    fn float(self, l: f64, r: f64) -> f64 {
+        match self {
+            Op::Add => l + r,
+            Op::Sub => l - r,
+            Op::Mul => l * r,
+            Op::Div => l / r,
+            Op::Rem => l % r,
+        }
+    }
+
+    /// Apply the operation in the given type, or None if the result doesn't fit in it. Float
+    /// operations on finite numbers fail if the result isn't finite.
+    fn apply(self, kind: Kind, l: &Number, r: &Number) -> Option<Number> {
+        let finite = f64::from(l).is_finite() && f64::from(r).is_finite();
+        match kind {
+            Kind::F32 => {
+                let result = self.float(f32::from(l) as f64, f32::from(r) as f64) as f32;
+                Some(Number::F32(result)).filter(|_| result.is_finite() || !finite)
+            }
+            Kind::F64 => {
+                let result = self.float(f64::from(l), f64::from(r));
+                Some(Number::F64(result)).filter(|_| result.is_finite() || !finite)
+            }
+            Kind::Unsigned(_) if l.is_signed() || r.is_signed() => None,
+            Kind::Unsigned(_) => kind.number_from_u128(self.unsigned(u128::from(l), u128::from(r))?),
+            Kind::Signed(_) => kind.number_from_i128(self.signed(to_i128(l)?, to_i128(r)?)?),
+        }
+    }
+
+    /// Apply the operation in the promoted type of both numbers
+    fn checked(self, l: &Number, r: &Number) -> Option<Number> {
+        self.apply(l.kind().promote(r.kind()), l, r)
+    }
+
+    /// Apply the operation, clamping the result to the range of the promoted type. Integer
+    /// division by zero has no value to clamp to and gives the `f64` result instead.
+    fn saturating(self, l: &Number, r: &Number) -> Number {
+        if let Some(result) = self.checked(l, r) {
+            return result;
+        }
+        let kind = l.kind().promote(r.kind());
+        let approx = self.float(f64::from(l), f64::from(r));
+        match kind {
+            Kind::F32 if approx.is_nan() => Number::F32(f32::NAN),
+            Kind::F64 if approx.is_nan() => Number::F64(f64::NAN),
+            Kind::Unsigned(_) | Kind::Signed(_) if approx.is_nan() || approx.is_infinite() => {
+                Number::F64(approx)
+            }
+            _ if approx > 0.0 => kind.max(),
+            _ => kind.min(),
+        }
+    }
+
+    /// Apply the operation, widening the result to a 128 bit integer and then to `f64` if it
+    /// doesn't fit in the promoted type. Unsigned results below zero become signed. Integer
+    /// division by zero doesn't fit any integer, so it gives the `f64` result.
+    fn widening(self, l: &Number, r: &Number) -> Number {
+        if let Some(result) = self.checked(l, r) {
+            return result;
+        }
+        let kind = l.kind().promote(r.kind());
+        let wide = match kind {
+            Kind::Unsigned(_) => self
+                .apply(Kind::Unsigned(128), l, r)
+                .or_else(|| self.apply(Kind::Signed(128), l, r)),
+            Kind::Signed(_) => self.apply(Kind::Signed(128), l, r),
+            Kind::F32 => self.apply(Kind::F64, l, r),
+            Kind::F64 => None,
+        };
+        wide.unwrap_or_else(|| Number::F64(self.float(f64::from(l), f64::from(r))))
+    }
+}
+
+fn to_i128(n: &Number) -> Option<i128> {
+    if n.is_signed() {
+        Some(i128::from(n))
+    } else {
+        i128::try_from(u128::from(n)).ok()
+    }
+}
+
+macro_rules! impl_number_op {
+    ( $(
+        $trait:ident $method:ident $assign:ident $assign_method:ident $op:ident $checked:ident
+        $( $saturating:ident )?
+    ),* ) => {
+        $(
+            impl std::ops::$trait<Number> for Number {
+                type Output = Number;
+
+                /// Numbers of different types are promoted to a type that holds both, if the
+                /// result doesn't fit it is widened further rather than overflowing. Integers
+                /// divided by zero give the `f64` result, infinity or NaN.
+                fn $method(self, rhs: Number) -> Number {
+                    Op::$op.widening(&self, &rhs)
+                }
+            }
+
+            impl std::ops::$assign<Number> for Number {
+                fn $assign_method(&mut self, rhs: Number) {
+                    *self = Op::$op.widening(self, &rhs);
+                }
+            }
+
+            impl Number {
+                /// Returns None if the result doesn't fit in the promoted type of both numbers,
+                /// a float result isn't finite or an integer is divided by zero
+                pub fn $checked(&self, rhs: &Number) -> Option<Number> {
+                    Op::$op.checked(self, rhs)
+                }
+
+                $(
+                    /// Clamps the result to the range of the promoted type of both numbers.
+                    /// Integers divided by zero give the `f64` result, infinity or NaN.
+                    pub fn $saturating(&self, rhs: &Number) -> Number {
+                        Op::$op.saturating(self, rhs)
+                    }
+                )?
+            }
+        )*
+    };
+}
+
+impl_number_op! {
+    Add add AddAssign add_assign Add checked_add saturating_add,
+    Sub sub SubAssign sub_assign Sub checked_sub saturating_sub,
+    Mul mul MulAssign mul_assign Mul checked_mul saturating_mul,
+    Div div DivAssign div_assign Div checked_div saturating_div,
+    Rem rem RemAssign rem_assign Rem checked_rem
+}
+
+impl std::ops::Neg for Number {
+    type Output = Number;
+
+    /// Unsigned integers become a signed type wide enough to hold the result
+    fn neg(self) -> Number {
+        let kind = match self {
+            Number::F32(n) => return Number::F32(-n),
+            Number::F64(n) => return Number::F64(-n),
+            _ => match self.kind() {
+                Kind::Unsigned(bits) => Kind::Signed((bits * 2).min(128)),
+                kind => kind,
+            },
+        };
+        let zero = Number::I8(0);
+        Op::Sub.apply(kind, &zero, &self).unwrap_or_else(|| Op::Sub.widening(&zero, &self))
+    }
+}
+
+impl Number {
+    /// The absolute value, widened to a larger type if it doesn't fit, e.g. `i8::MIN`
+    pub fn abs(&self) -> Number {
+        match self {
+            Number::F32(n) => Number::F32(n.abs()),
+            Number::F64(n) => Number::F64(n.abs()),
+            n if n.is_signed() && i128::from(n) < 0 => -n.clone(),
+            n => n.clone(),
+        }
+    }
+
+    /// Raise to a power in the same type, widening to a 128 bit integer and then to `f64` if
+    /// the result doesn't fit
+    ///
+    /// ```
+    /// use unstructured::Number;
+    ///
+    /// assert_eq!(Number::U8(3).pow(4), Number::U8(81));
+    /// assert_eq!(Number::U8(2).pow(10), Number::from(1024u128));
+    /// assert_eq!(Number::F32(1.5).pow(2), Number::F32(2.25));
+    /// ```
+    pub fn pow(&self, exp: u32) -> Number {
+        let kind = self.kind();
+        let result = match kind {
+            Kind::F32 => return Number::F32(f32::from(self).powf(exp as f32)),
+            Kind::F64 => return Number::F64(f64::from(self).powf(exp as f64)),
+            Kind::Unsigned(_) => u128::from(self)
+                .checked_pow(exp)
+                .map(|v| kind.number_from_u128(v).unwrap_or_else(|| Number::from(v))),
+            Kind::Signed(_) => i128::from(self)
+                .checked_pow(exp)
+                .map(|v| kind.number_from_i128(v).unwrap_or_else(|| Number::from(v))),
+        };
+        result.unwrap_or_else(|| Number::F64(f64::from(self).powf(exp as f64)))
+    }
+
+    /// The smaller of two numbers compared by value, unlike [`Ord::min`] NaN is ignored
+    /// unless both are NaN
+    pub fn min(self, other: Number) -> Number {
+        match (self.is_nan(), other.is_nan()) {
+            (false, true) => self,
+            (true, false) => other,
+            _ => std::cmp::min(self, other),
+        }
+    }
+
+    /// The larger of two numbers compared by value, unlike [`Ord::max`] NaN is ignored
+    /// unless both are NaN
+    pub fn max(self, other: Number) -> Number {
+        match (self.is_nan(), other.is_nan()) {
+            (false, true) => self,
+            (true, false) => other,
+            _ => std::cmp::max(self, other),
+        }
+    }
+
+    fn is_nan(&self) -> bool {
+        self.is_float() && f64::from(self).is_nan()
+    }
+}
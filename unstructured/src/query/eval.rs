@@ -0,0 +1,848 @@
+use super::parser::{Ast, BinaryOp, FormatPart, Literal};
+use crate::*;
+use std::cmp::Ordering;
+use std::convert::TryFrom;
+use std::fmt::Write;
+
+type Outputs<T> = Result<Vec<Unstructured<T>>, String>;
+
+impl Ast {
+    /// Run the expression against an input, returning every value it outputs. The depth is how
+    /// far into the query this is, which compiling has already limited.
+    pub(super) fn eval<T: UnstructuredDataTrait>(
+        &self,
+        input: &Unstructured<T>,
+        depth: usize,
+    ) -> Outputs<T> {
+        nested(depth, 0)?;
+        let input = peel(input);
+        Ok(match self {
+            Ast::Identity => vec![input.clone()],
+            Ast::Recurse => input
+                .walk()
+                .map(|(_, val)| val)
+                .filter(|val| !is_wrapper(val))
+                .cloned()
+                .collect(),
+            Ast::Literal(literal) => vec![literal.to_value()],
+            Ast::Format(parts) => {
+                let mut results = vec![String::new()];
+                for part in parts.iter() {
+                    match part {
+                        FormatPart::Literal(s) => results.iter_mut().for_each(|r| r.push_str(s)),
+                        FormatPart::Interpolation(ast) => {
+                            let vals = ast.eval(input, depth + 1)?;
+                            results = results
+                                .iter()
+                                .flat_map(|r| vals.iter().map(move |v| r.clone() + &to_text(v)))
+                                .collect();
+                        }
+                    }
+                }
+                results.into_iter().map(Unstructured::<T>::String).collect()
+            }
+            Ast::Field(target, name) => {
+                let key = Unstructured::<T>::from(name.as_str());
+                target
+                    .eval(input, depth + 1)?
+                    .iter()
+                    .map(|val| index(val, &key))
+                    .collect::<Result<_, _>>()?
+            }
+            Ast::Index(target, key) => {
+                let keys = key.eval(input, depth + 1)?;
+                let mut out = vec![];
+                for val in target.eval(input, depth + 1)? {
+                    for key in keys.iter() {
+                        out.push(index(&val, key)?);
+                    }
+                }
+                out
+            }
+            Ast::Slice(target, from, to) => {
+                let bound = |ast: &Option<Box<Ast>>| match ast {
+                    Some(ast) => ast.eval(input, depth + 1),
+                    None => Ok(vec![Unstructured::<T>::Null]),
+                };
+                let (froms, tos) = (bound(from)?, bound(to)?);
+                let mut out = vec![];
+                for val in target.eval(input, depth + 1)? {
+                    for to in tos.iter() {
+                        for from in froms.iter() {
+                            out.push(slice(&val, from, to)?);
+                        }
+                    }
+                }
+                out
+            }
+            Ast::Iterate(target) => {
+                let mut out = vec![];
+                for val in target.eval(input, depth + 1)? {
+                    out.extend(iterate(&val)?);
+                }
+                out
+            }
+            Ast::Try(inner) => inner.eval(input, depth + 1).unwrap_or_default(),
+            Ast::Pipe(lhs, rhs) => {
+                let mut out = vec![];
+                for val in lhs.eval(input, depth + 1)? {
+                    out.extend(rhs.eval(&val, depth + 1)?);
+                }
+                out
+            }
+            Ast::Comma(lhs, rhs) => {
+                let mut out = lhs.eval(input, depth + 1)?;
+                out.extend(rhs.eval(input, depth + 1)?);
+                out
+            }
+            Ast::Alternative(lhs, rhs) => {
+                let found: Vec<_> = lhs
+                    .eval(input, depth + 1)
+                    .unwrap_or_default()
+                    .into_iter()
+                    .filter(truthy)
+                    .collect();
+                if found.is_empty() {
+                    rhs.eval(input, depth + 1)?
+                } else {
+                    found
+                }
+            }
+            Ast::And(lhs, rhs) | Ast::Or(lhs, rhs) => {
+                let short_circuit = matches!(self, Ast::Or(..));
+                let mut out = vec![];
+                for val in lhs.eval(input, depth + 1)? {
+                    if truthy(&val) == short_circuit {
+                        out.push(short_circuit.into());
+                        continue;
+                    }
+                    for val in rhs.eval(input, depth + 1)? {
+                        out.push(truthy(&val).into());
+                    }
+                }
+                out
+            }
+            Ast::Binary(lhs, op, rhs) => {
+                let lhs = lhs.eval(input, depth + 1)?;
+                let mut out = vec![];
+                for r in rhs.eval(input, depth + 1)? {
+                    for l in lhs.iter() {
+                        out.push(binary(*op, l.clone(), r.clone())?);
+                    }
+                }
+                out
+            }
+            Ast::Negate(inner) => inner
+                .eval(input, depth + 1)?
+                .into_iter()
+                .map(|val| match val {
+                    Unstructured::<T>::Number(_) => binary(BinaryOp::Sub, 0u64.into(), val),
+                    other => Err(format!("{} cannot be negated", describe(&other))),
+                })
+                .collect::<Result<_, _>>()?,
+            Ast::Array(inner) => vec![Unstructured::<T>::Seq(share(match inner {
+                Some(inner) => inner.eval(input, depth + 1)?,
+                None => vec![],
+            }))],
+            Ast::Object(entries) => {
+                let mut maps = vec![Mapping::<T>::new()];
+                for (key, val) in entries.iter() {
+                    let keys = key.eval(input, depth + 1)?;
+                    let mut next = vec![];
+                    for key in keys.iter() {
+                        if !matches!(key, Unstructured::<T>::String(_)) {
+                            return Err(format!(
+                                "Object keys must be strings, not {}",
+                                jq_type(key)
+                            ));
+                        }
+                        let vals = match val {
+                            Some(val) => val.eval(input, depth + 1)?,
+                            None => vec![index(input, key)?],
+                        };
+                        for map in maps.iter() {
+                            for val in vals.iter() {
+                                let mut map = map.clone();
+                                map.insert(key.clone(), val.clone());
+                                next.push(map);
+                            }
+                        }
+                    }
+                    maps = next;
+                }
+                maps.into_iter()
+                    .map(|map| Unstructured::<T>::Map(share(map)))
+                    .collect()
+            }
+            Ast::If(branches, otherwise) => {
+                eval_if(branches, otherwise.as_deref(), input, depth + 1)?
+            }
+            Ast::Call(name, args) => call(name, args, input, depth + 1)?,
+        })
+    }
+}
+
+impl Literal {
+    fn to_value<T: UnstructuredDataTrait>(&self) -> Unstructured<T> {
+        match self {
+            Literal::Null => Unstructured::<T>::Null,
+            Literal::Bool(b) => (*b).into(),
+            Literal::Number(n) => n.clone().into(),
+            Literal::String(s) => s.as_str().into(),
+        }
+    }
+}
+
+fn eval_if<T: UnstructuredDataTrait>(
+    branches: &[(Ast, Ast)],
+    otherwise: Option<&Ast>,
+    input: &Unstructured<T>,
+    depth: usize,
+) -> Outputs<T> {
+    let ((cond, then), rest) = match branches.split_first() {
+        Some(split) => split,
+        None => {
+            return otherwise.map_or_else(|| Ok(vec![input.clone()]), |ast| ast.eval(input, depth))
+        }
+    };
+    let mut out = vec![];
+    for val in cond.eval(input, depth)? {
+        if truthy(&val) {
+            out.extend(then.eval(input, depth)?);
+        } else {
+            out.extend(eval_if(rest, otherwise, input, depth + 1)?);
+        }
+    }
+    Ok(out)
+}
+
+fn call<T: UnstructuredDataTrait>(
+    name: &str,
+    args: &[Ast],
+    input: &Unstructured<T>,
+    depth: usize,
+) -> Outputs<T> {
+    let one = |val: Unstructured<T>| Ok(vec![val]);
+    match (name, args) {
+        ("empty", []) => Ok(vec![]),
+        ("not", []) => one((!truthy(input)).into()),
+        ("type", []) => one(jq_type(input).into()),
+        ("length", []) => one(match input {
+            Unstructured::<T>::Null => 0u64.into(),
+            Unstructured::<T>::Number(n) if f64::from(n) < 0.0 => {
+                binary(BinaryOp::Sub, 0u64.into(), input.clone())?
+            }
+            Unstructured::<T>::Number(_) => input.clone(),
+            Unstructured::<T>::String(s) => s.chars().count().into(),
+            Unstructured::<T>::Seq(_) | Unstructured::<T>::Map(_) => input.len().into(),
+            other => return Err(format!("{} has no length", describe(other))),
+        }),
+        ("keys", []) | ("keys_unsorted", []) => {
+            let mut keys: Vec<_> = match input {
+                Unstructured::<T>::Map(map) => map.keys().cloned().collect(),
+                Unstructured::<T>::Seq(seq) => {
+                    (0..seq.len()).map(Unstructured::<T>::from).collect()
+                }
+                other => return Err(format!("{} has no keys", describe(other))),
+            };
+            if name == "keys" {
+                keys.sort_by(compare);
+            }
+            one(Unstructured::<T>::Seq(share(keys)))
+        }
+        ("has", [key]) => key
+            .eval(input, depth)?
+            .iter()
+            .map(|key| match (input, key) {
+                (Unstructured::<T>::Map(map), Unstructured::<T>::String(_)) => {
+                    Ok(map.contains_key(key).into())
+                }
+                (Unstructured::<T>::Seq(seq), Unstructured::<T>::Number(_)) => {
+                    Ok(matches!(key.as_usize(), Some(i) if i < seq.len()).into())
+                }
+                _ => Err(format!(
+                    "Cannot check whether {} has {}",
+                    jq_type(input),
+                    describe(key)
+                )),
+            })
+            .collect(),
+        ("map", [f]) => {
+            let mut out = vec![];
+            for val in iterate(input)? {
+                out.extend(f.eval(&val, depth)?);
+            }
+            one(Unstructured::<T>::Seq(share(out)))
+        }
+        ("select", [f]) => Ok(f
+            .eval(input, depth)?
+            .iter()
+            .filter(|val| truthy(val))
+            .map(|_| input.clone())
+            .collect()),
+        ("recurse", []) => Ast::Recurse.eval(input, depth),
+        ("to_entries", []) => match input {
+            Unstructured::<T>::Map(map) => one(Unstructured::<T>::Seq(share(
+                map.iter()
+                    .map(|(key, val)| {
+                        let mut entry = Mapping::<T>::new();
+                        entry.insert("key".into(), key.clone());
+                        entry.insert("value".into(), val.clone());
+                        Unstructured::<T>::Map(share(entry))
+                    })
+                    .collect(),
+            ))),
+            other => Err(format!("{} has no entries", describe(other))),
+        },
+        ("from_entries", []) => {
+            let mut map = Mapping::<T>::new();
+            for entry in iterate(input)? {
+                let field = |names: &[&str]| {
+                    names
+                        .iter()
+                        .map(|name| &entry[*name])
+                        .find(|val| !val.is_null())
+                        .cloned()
+                        .unwrap_or(Unstructured::<T>::Null)
+                };
+                let key = match field(&["key", "k", "name", "Name", "Key", "K"]) {
+                    key @ Unstructured::<T>::String(_) => key,
+                    Unstructured::<T>::Null => return Err("Entries must have a key".to_string()),
+                    key => to_text(&key).into(),
+                };
+                map.insert(key, field(&["value", "v", "Value", "V"]));
+            }
+            one(Unstructured::<T>::Map(share(map)))
+        }
+        ("with_entries", [f]) => {
+            let entries = call("to_entries", &[], input, depth)?;
+            let mut out = vec![];
+            for entries in entries.iter() {
+                for mapped in call("map", std::slice::from_ref(f), entries, depth)? {
+                    out.extend(call("from_entries", &[], &mapped, depth)?);
+                }
+            }
+            Ok(out)
+        }
+        ("add", []) => {
+            let sum = iterate(input)?
+                .into_iter()
+                .try_fold(Unstructured::<T>::Null, |sum, val| {
+                    binary(BinaryOp::Add, sum, val)
+                })?;
+            one(sum)
+        }
+        ("any", []) => one(iterate(input)?.iter().any(truthy).into()),
+        ("all", []) => one(iterate(input)?.iter().all(truthy).into()),
+        ("tostring", []) => one(to_text(input).into()),
+        ("tonumber", []) => match input {
+            Unstructured::<T>::Number(_) => one(input.clone()),
+            Unstructured::<T>::String(s) => s
+                .trim()
+                .parse::<u64>()
+                .map(Number::from)
+                .or_else(|_| s.trim().parse::<i64>().map(Number::from))
+                .or_else(|_| s.trim().parse::<f64>().map(Number::from))
+                .map(|n| vec![n.into()])
+                .map_err(|_| format!("Cannot parse {:?} as a number", s)),
+            other => Err(format!("{} cannot be parsed as a number", describe(other))),
+        },
+        ("ascii_downcase", []) | ("ascii_upcase", []) => match input {
+            Unstructured::<T>::String(s) if name == "ascii_downcase" => {
+                one(s.to_ascii_lowercase().into())
+            }
+            Unstructured::<T>::String(s) => one(s.to_ascii_uppercase().into()),
+            other => Err(format!("{} cannot be case converted", describe(other))),
+        },
+        ("sort", []) => {
+            let mut vals = sequence(input, name)?;
+            vals.sort_by(compare);
+            one(Unstructured::<T>::Seq(share(vals)))
+        }
+        ("sort_by", [f]) => {
+            let mut keyed = sequence(input, name)?
+                .into_iter()
+                .map(|val| Ok((Unstructured::<T>::Seq(share(f.eval(&val, depth)?)), val)))
+                .collect::<Result<Vec<_>, String>>()?;
+            keyed.sort_by(|(a, _), (b, _)| compare(a, b));
+            one(Unstructured::<T>::Seq(share(
+                keyed.into_iter().map(|(_, val)| val).collect(),
+            )))
+        }
+        ("unique", []) => {
+            let mut vals = sequence(input, name)?;
+            vals.sort_by(compare);
+            vals.dedup_by(|a, b| compare(a, b) == Ordering::Equal);
+            one(Unstructured::<T>::Seq(share(vals)))
+        }
+        ("min", []) => one(sequence(input, name)?
+            .into_iter()
+            .min_by(compare)
+            .unwrap_or(Unstructured::<T>::Null)),
+        ("max", []) => one(sequence(input, name)?
+            .into_iter()
+            .max_by(compare)
+            .unwrap_or(Unstructured::<T>::Null)),
+        ("reverse", []) => match input {
+            Unstructured::<T>::String(s) => one(s.chars().rev().collect::<String>().into()),
+            Unstructured::<T>::Null => one(Unstructured::<T>::Seq(Default::default())),
+            _ => {
+                let mut vals = sequence(input, name)?;
+                vals.reverse();
+                one(Unstructured::<T>::Seq(share(vals)))
+            }
+        },
+        ("first", []) => index(input, &0u64.into()).map(|val| vec![val]),
+        ("last", []) => index(input, &(-1i64).into()).map(|val| vec![val]),
+        ("first", [f]) => Ok(f.eval(input, depth)?.into_iter().take(1).collect()),
+        ("join", [sep]) => sep
+            .eval(input, depth)?
+            .iter()
+            .map(|sep| {
+                let sep = match sep {
+                    Unstructured::<T>::String(sep) => sep,
+                    other => return Err(format!("Cannot join with {}", describe(other))),
+                };
+                let parts = iterate(input)?
+                    .iter()
+                    .map(|val| match val {
+                        Unstructured::<T>::Null => Ok(String::new()),
+                        Unstructured::<T>::String(s) => Ok(s.clone()),
+                        Unstructured::<T>::Number(_) | Unstructured::<T>::Bool(_) => {
+                            Ok(to_text(val))
+                        }
+                        other => Err(format!("Cannot join {}", describe(other))),
+                    })
+                    .collect::<Result<Vec<_>, String>>()?;
+                Ok(parts.join(sep).into())
+            })
+            .collect(),
+        ("split", [sep]) | ("startswith", [sep]) | ("endswith", [sep]) => sep
+            .eval(input, depth)?
+            .iter()
+            .map(|sep| match (input, sep) {
+                (Unstructured::<T>::String(s), Unstructured::<T>::String(sep)) => Ok(match name {
+                    "split" => Unstructured::<T>::Seq(share(
+                        s.split(sep.as_str()).map(Unstructured::<T>::from).collect(),
+                    )),
+                    "startswith" => s.starts_with(sep.as_str()).into(),
+                    _ => s.ends_with(sep.as_str()).into(),
+                }),
+                _ => Err(format!("{} requires string inputs", name)),
+            })
+            .collect(),
+        ("range", [to]) => {
+            range(&Ast::Literal(Literal::Number(0u64.into())), to, input, depth)
+        }
+        ("range", [from, to]) => range(from, to, input, depth),
+        ("floor", []) => match input {
+            Unstructured::<T>::Number(n) if n.is_float() => one(f64::from(n).floor().into()),
+            Unstructured::<T>::Number(_) => one(input.clone()),
+            other => Err(format!("{} cannot be floored", describe(other))),
+        },
+        _ => Err(format!("Unknown function {}/{}", name, args.len())),
+    }
+}
+
+/// The most numbers one call of `range` produces, since they are all held in memory at once
+const MAX_RANGE: usize = 1 << 20;
+
+fn range<T: UnstructuredDataTrait>(
+    from: &Ast,
+    to: &Ast,
+    input: &Unstructured<T>,
+    depth: usize,
+) -> Outputs<T> {
+    let mut out = vec![];
+    for to in to.eval(input, depth)? {
+        for from in from.eval(input, depth)? {
+            let (from, to) = match (&from, &to) {
+                (Unstructured::<T>::Number(from), Unstructured::<T>::Number(to)) => {
+                    (i128::from(from), i128::from(to))
+                }
+                _ => return Err("Range bounds must be numbers".to_string()),
+            };
+            if to.saturating_sub(from) > (MAX_RANGE - out.len()) as i128 {
+                return Err(format!("range produces more than {} numbers", MAX_RANGE));
+            }
+            out.extend((from..to).map(int_number).map(Unstructured::<T>::Number));
+        }
+    }
+    Ok(out)
+}
+
+/// `Option` and `Newtype` wrappers are transparent to queries
+fn peel<T: UnstructuredDataTrait>(val: &Unstructured<T>) -> &Unstructured<T> {
+    match val {
+        Unstructured::<T>::Option(Some(inner)) | Unstructured::<T>::Newtype(inner) => peel(inner),
+        Unstructured::<T>::Option(None) => &Unstructured::<T>::Null,
+        _ => val,
+    }
+}
+
+fn is_wrapper<T: UnstructuredDataTrait>(val: &Unstructured<T>) -> bool {
+    matches!(
+        val,
+        Unstructured::<T>::Option(Some(_)) | Unstructured::<T>::Newtype(_)
+    )
+}
+
+fn truthy<T: UnstructuredDataTrait>(val: &Unstructured<T>) -> bool {
+    !matches!(
+        peel(val),
+        Unstructured::<T>::Null | Unstructured::<T>::Unassigned | Unstructured::<T>::Bool(false)
+    )
+}
+
+/// The type names jq uses
+fn jq_type<T: UnstructuredDataTrait>(val: &Unstructured<T>) -> &'static str {
+    match peel(val) {
+        Unstructured::<T>::Null | Unstructured::<T>::Unassigned => "null",
+        Unstructured::<T>::Bool(_) => "boolean",
+        Unstructured::<T>::Number(_) => "number",
+        Unstructured::<T>::String(_) | Unstructured::<T>::Char(_) => "string",
+        Unstructured::<T>::Seq(_) => "array",
+        Unstructured::<T>::Map(_) => "object",
+        other => other.type_name(),
+    }
+}
+
+/// A value and its type for error messages, e.g. `number (1)`
+fn describe<T: UnstructuredDataTrait>(val: &Unstructured<T>) -> String {
+    format!("{} ({})", jq_type(val), to_json(val))
+}
+
+fn index<T: UnstructuredDataTrait>(
+    val: &Unstructured<T>,
+    key: &Unstructured<T>,
+) -> Result<Unstructured<T>, String> {
+    let (val, key) = (peel(val), peel(key));
+    match (val, key) {
+        (Unstructured::<T>::Null, Unstructured::<T>::String(_) | Unstructured::<T>::Number(_)) => {
+            Ok(Unstructured::<T>::Null)
+        }
+        (Unstructured::<T>::Map(map), Unstructured::<T>::String(_)) => {
+            Ok(map.get(key).cloned().unwrap_or(Unstructured::<T>::Null))
+        }
+        (Unstructured::<T>::Seq(seq), Unstructured::<T>::Number(n)) => {
+            let i = i128::from(n);
+            let i = if i < 0 { i + seq.len() as i128 } else { i };
+            Ok(usize::try_from(i)
+                .ok()
+                .and_then(|i| seq.get(i))
+                .cloned()
+                .unwrap_or(Unstructured::<T>::Null))
+        }
+        _ => Err(format!(
+            "Cannot index {} with {}",
+            jq_type(val),
+            describe(key)
+        )),
+    }
+}
+
+fn slice<T: UnstructuredDataTrait>(
+    val: &Unstructured<T>,
+    from: &Unstructured<T>,
+    to: &Unstructured<T>,
+) -> Result<Unstructured<T>, String> {
+    let bound = |bound: &Unstructured<T>, len: usize, default: usize| match peel(bound) {
+        Unstructured::<T>::Null => Ok(default),
+        Unstructured::<T>::Number(n) => {
+            let i = f64::from(n).floor() as i128;
+            let i = if i < 0 { i + len as i128 } else { i };
+            Ok(i.clamp(0, len as i128) as usize)
+        }
+        other => Err(format!(
+            "Slice bounds must be numbers, not {}",
+            describe(other)
+        )),
+    };
+    match peel(val) {
+        Unstructured::<T>::Null => Ok(Unstructured::<T>::Null),
+        Unstructured::<T>::Seq(seq) => {
+            let (from, to) = (bound(from, seq.len(), 0)?, bound(to, seq.len(), seq.len())?);
+            Ok(Unstructured::<T>::Seq(share(
+                seq[from..to.max(from)].to_vec(),
+            )))
+        }
+        Unstructured::<T>::String(s) => {
+            let len = s.chars().count();
+            let (from, to) = (bound(from, len, 0)?, bound(to, len, len)?);
+            Ok(s.chars()
+                .skip(from)
+                .take(to.saturating_sub(from))
+                .collect::<String>()
+                .into())
+        }
+        other => Err(format!("Cannot slice {}", describe(other))),
+    }
+}
+
+fn iterate<T: UnstructuredDataTrait>(val: &Unstructured<T>) -> Outputs<T> {
+    match peel(val) {
+        val @ (Unstructured::<T>::Seq(_) | Unstructured::<T>::Map(_)) => {
+            Ok(val.iter().cloned().collect())
+        }
+        other => Err(format!("Cannot iterate over {}", describe(other))),
+    }
+}
+
+/// The elements of a sequence, for functions that only accept sequences
+fn sequence<T: UnstructuredDataTrait>(val: &Unstructured<T>, function: &str) -> Outputs<T> {
+    match peel(val) {
+        Unstructured::<T>::Seq(seq) => Ok(seq.to_vec()),
+        other => Err(format!(
+            "{} cannot be used with {}",
+            function,
+            describe(other)
+        )),
+    }
+}
+
+fn binary<T: UnstructuredDataTrait>(
+    op: BinaryOp,
+    lhs: Unstructured<T>,
+    rhs: Unstructured<T>,
+) -> Result<Unstructured<T>, String> {
+    let ordering = || compare(&lhs, &rhs);
+    let result = match (op, peel(&lhs), peel(&rhs)) {
+        (BinaryOp::Eq, ..) => ordering().is_eq().into(),
+        (BinaryOp::Ne, ..) => ordering().is_ne().into(),
+        (BinaryOp::Lt, ..) => ordering().is_lt().into(),
+        (BinaryOp::Le, ..) => ordering().is_le().into(),
+        (BinaryOp::Gt, ..) => ordering().is_gt().into(),
+        (BinaryOp::Ge, ..) => ordering().is_ge().into(),
+        (_, Unstructured::<T>::Number(l), Unstructured::<T>::Number(r)) => {
+            Unstructured::<T>::Number(arithmetic(op, l, r)?)
+        }
+        (BinaryOp::Add, Unstructured::<T>::Null, other)
+        | (BinaryOp::Add, other, Unstructured::<T>::Null) => other.clone(),
+        (BinaryOp::Add, Unstructured::<T>::String(l), Unstructured::<T>::String(r)) => {
+            (l.clone() + r).into()
+        }
+        (BinaryOp::Add, Unstructured::<T>::Seq(l), Unstructured::<T>::Seq(r)) => {
+            Unstructured::<T>::Seq(share(l.iter().chain(r.iter()).cloned().collect()))
+        }
+        (BinaryOp::Add, Unstructured::<T>::Map(l), Unstructured::<T>::Map(r)) => {
+            let mut map = Mapping::<T>::clone(l);
+            map.extend(r.iter().map(|(key, val)| (key.clone(), val.clone())));
+            Unstructured::<T>::Map(share(map))
+        }
+        (BinaryOp::Sub, Unstructured::<T>::Seq(l), Unstructured::<T>::Seq(r)) => {
+            Unstructured::<T>::Seq(share(
+                l.iter()
+                    .filter(|val| !r.iter().any(|other| compare(val, other).is_eq()))
+                    .cloned()
+                    .collect(),
+            ))
+        }
+        (BinaryOp::Mul, Unstructured::<T>::Map(_), Unstructured::<T>::Map(_)) => {
+            let mut merged = lhs.clone();
+            deep_merge(&mut merged, rhs.clone());
+            merged
+        }
+        (BinaryOp::Div, Unstructured::<T>::String(l), Unstructured::<T>::String(r)) => {
+            Unstructured::<T>::Seq(share(
+                l.split(r.as_str()).map(Unstructured::<T>::from).collect(),
+            ))
+        }
+        (_, l, r) => {
+            let verb = match op {
+                BinaryOp::Add => "added",
+                BinaryOp::Sub => "subtracted",
+                BinaryOp::Mul => "multiplied",
+                BinaryOp::Div => "divided",
+                _ => "divided (remainder)",
+            };
+            return Err(format!(
+                "{} and {} cannot be {}",
+                describe(l),
+                describe(r),
+                verb
+            ));
+        }
+    };
+    Ok(result)
+}
+
+/// Integer arithmetic is exact where possible, anything else is done with floats
+fn arithmetic(op: BinaryOp, lhs: &Number, rhs: &Number) -> Result<Number, String> {
+    if !lhs.is_float() && !rhs.is_float() {
+        let (l, r) = (i128::from(lhs), i128::from(rhs));
+        if matches!(op, BinaryOp::Div | BinaryOp::Rem) && r == 0 {
+            return Err(format!(
+                "{} and {} cannot be divided because the divisor is zero",
+                l, r
+            ));
+        }
+        let exact = match op {
+            BinaryOp::Add => l.checked_add(r),
+            BinaryOp::Sub => l.checked_sub(r),
+            BinaryOp::Mul => l.checked_mul(r),
+            BinaryOp::Div if l % r == 0 => Some(l / r),
+            BinaryOp::Rem => Some(l % r),
+            _ => None,
+        };
+        if let Some(exact) = exact {
+            return Ok(int_number(exact));
+        }
+    }
+    let (l, r) = (f64::from(lhs), f64::from(rhs));
+    if matches!(op, BinaryOp::Div | BinaryOp::Rem) && r == 0.0 {
+        return Err(format!(
+            "{} and {} cannot be divided because the divisor is zero",
+            l, r
+        ));
+    }
+    Ok(Number::from(match op {
+        BinaryOp::Add => l + r,
+        BinaryOp::Sub => l - r,
+        BinaryOp::Mul => l * r,
+        BinaryOp::Div => l / r,
+        _ => l % r,
+    }))
+}
+
+/// The smallest of u64, i64 and f64 that can hold an integer
+fn int_number(n: i128) -> Number {
+    u64::try_from(n)
+        .map(Number::from)
+        .or_else(|_| i64::try_from(n).map(Number::from))
+        .unwrap_or_else(|_| Number::from(n as f64))
+}
+
+/// Merge maps recursively, anything else in `rhs` replaces what is in `lhs`
+fn deep_merge<T: UnstructuredDataTrait>(lhs: &mut Unstructured<T>, rhs: Unstructured<T>) {
+    match (lhs, rhs) {
+        (Unstructured::<T>::Map(l), Unstructured::<T>::Map(r)) => {
+            let l = make_mut(l);
+            for (key, val) in unshare(r).into_iter() {
+                match l.get_mut(&key) {
+                    Some(existing) => deep_merge(existing, val),
+                    None => {
+                        l.insert(key, val);
+                    }
+                }
+            }
+        }
+        (lhs, rhs) => *lhs = rhs,
+    }
+}
+
+/// Order values the way jq does: null, false, true, numbers, strings, arrays then objects.
+/// Numbers are compared by value regardless of their type.
+fn compare<T: UnstructuredDataTrait>(lhs: &Unstructured<T>, rhs: &Unstructured<T>) -> Ordering {
+    let rank = |val: &Unstructured<T>| match val {
+        Unstructured::<T>::Null | Unstructured::<T>::Unassigned => 0,
+        Unstructured::<T>::Bool(false) => 1,
+        Unstructured::<T>::Bool(true) => 2,
+        Unstructured::<T>::Number(_) => 3,
+        Unstructured::<T>::String(_) => 4,
+        Unstructured::<T>::Seq(_) => 5,
+        Unstructured::<T>::Map(_) => 6,
+        _ => 7,
+    };
+    let (lhs, rhs) = (peel(lhs), peel(rhs));
+    match (lhs, rhs) {
+        (Unstructured::<T>::Number(l), Unstructured::<T>::Number(r)) => {
+            if l.is_float() || r.is_float() {
+                f64::from(l).total_cmp(&f64::from(r))
+            } else {
+                i128::from(l).cmp(&i128::from(r))
+            }
+        }
+        (Unstructured::<T>::Seq(l), Unstructured::<T>::Seq(r)) => l
+            .iter()
+            .zip(r.iter())
+            .map(|(l, r)| compare(l, r))
+            .find(|ordering| ordering.is_ne())
+            .unwrap_or_else(|| l.len().cmp(&r.len())),
+        (Unstructured::<T>::Map(l), Unstructured::<T>::Map(r)) => {
+            let sorted_keys = |map: &Mapping<T>| {
+                let mut keys: Vec<_> = map.keys().cloned().collect();
+                keys.sort_by(compare);
+                Unstructured::<T>::Seq(share(keys))
+            };
+            let (l_keys, r_keys) = (sorted_keys(l), sorted_keys(r));
+            compare(&l_keys, &r_keys).then_with(|| {
+                l_keys
+                    .iter()
+                    .map(|key| match (l.get(key), r.get(key)) {
+                        (Some(l), Some(r)) => compare(l, r),
+                        (l, r) => l.is_some().cmp(&r.is_some()),
+                    })
+                    .find(|ordering| ordering.is_ne())
+                    .unwrap_or(Ordering::Equal)
+            })
+        }
+        _ if rank(lhs) == rank(rhs) => lhs.cmp(rhs),
+        _ => rank(lhs).cmp(&rank(rhs)),
+    }
+}
+
+/// Strings as they are, anything else as JSON
+fn to_text<T: UnstructuredDataTrait>(val: &Unstructured<T>) -> String {
+    match peel(val) {
+        Unstructured::<T>::String(s) => s.clone(),
+        val => to_json(val),
+    }
+}
+
+fn to_json<T: UnstructuredDataTrait>(val: &Unstructured<T>) -> String {
+    let mut json = String::new();
+    write_json(&mut json, val);
+    json
+}
+
+fn write_json<T: UnstructuredDataTrait>(out: &mut String, val: &Unstructured<T>) {
+    match peel(val) {
+        Unstructured::<T>::Null | Unstructured::<T>::Unassigned => out.push_str("null"),
+        Unstructured::<T>::Bool(b) => out.push_str(if *b { "true" } else { "false" }),
+        Unstructured::<T>::Number(n) => {
+            let _ = write!(out, "{}", n);
+        }
+        Unstructured::<T>::String(s) => write_json_string(out, s),
+        Unstructured::<T>::Seq(seq) => {
+            out.push('[');
+            for (i, val) in seq.iter().enumerate() {
+                if i > 0 {
+                    out.push(',');
+                }
+                write_json(out, val);
+            }
+            out.push(']');
+        }
+        Unstructured::<T>::Map(map) => {
+            out.push('{');
+            for (i, (key, val)) in map.iter().enumerate() {
+                if i > 0 {
+                    out.push(',');
+                }
+                write_json_string(out, &to_text(key));
+                out.push(':');
+                write_json(out, val);
+            }
+            out.push('}');
+        }
+        other => write_json_string(out, &other.to_string()),
+    }
+}
+
+fn write_json_string(out: &mut String, s: &str) {
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => {
+                let _ = write!(out, "\\u{:04x}", c as u32);
+            }
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+}
@@ -0,0 +1,81 @@
+mod eval;
+mod parser;
+
+use crate::*;
+use parser::Ast;
+
+/// A parsed query in a subset of the [jq](https://jqlang.github.io/jq/manual/) language, for
+/// running the same query against many documents. See [`Unstructured::query`] for what is
+/// supported.
+///
+/// ```
+/// use unstructured::{Document, Query};
+///
+/// let query = Query::compile(".items | map(.price * .count) | add").unwrap();
+/// let doc: Document =
+///     serde_json::from_str(r#"{"items": [{"price": 2, "count": 3}, {"price": 5, "count": 1}]}"#)
+///         .unwrap();
+/// assert_eq!(query.run(&doc).unwrap(), vec![Document::from(11)]);
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct Query {
+    ast: Ast,
+}
+
+impl Query {
+    /// Parse a query, failing if it is not valid
+    pub fn compile(query: &str) -> Result<Self, Error> {
+        Ok(Query {
+            ast: Ast::parse(query).map_err(Error::Parse)?,
+        })
+    }
+
+    /// Run the query against a document, returning every value it outputs
+    pub fn run<T: UnstructuredDataTrait>(
+        &self,
+        doc: &Unstructured<T>,
+    ) -> Result<Vec<Unstructured<T>>, Error> {
+        self.ast.eval(doc, 0).map_err(Error::Select)
+    }
+}
+
+impl<T: UnstructuredDataTrait> Unstructured<T> {
+    /// Run a jq query against the document, returning every value it outputs. A query that
+    /// fails returns the error of the first expression that failed.
+    ///
+    /// The supported subset of jq is:
+    ///
+    /// - Paths: `.`, `.key`, `."key"`, `.[expr]`, `.[from:to]`, `.[]`, `..` and `?` to ignore
+    ///   errors
+    /// - Operators: `|`, `,`, `//`, `and`, `or`, `==`, `!=`, `<`, `<=`, `>`, `>=`, `+`, `-`,
+    ///   `*`, `/` and `%`, with parentheses for grouping
+    /// - Literals: numbers, `"strings"` with `\(expr)` interpolation, `true`, `false`, `null`,
+    ///   `[arrays]` and `{objects}`, including `{key}` and `{(expr): value}`
+    /// - `if ... then ... elif ... else ... end`
+    /// - Functions: `empty`, `not`, `type`, `length`, `keys`, `keys_unsorted`, `has(key)`,
+    ///   `map(f)`, `select(f)`, `recurse`, `to_entries`, `from_entries`, `with_entries(f)`,
+    ///   `add`, `any`, `all`, `tostring`, `tonumber`, `ascii_downcase`, `ascii_upcase`, `sort`,
+    ///   `sort_by(f)`, `unique`, `min`, `max`, `reverse`, `first`, `last`, `first(f)`,
+    ///   `join(sep)`, `split(sep)`, `startswith(s)`, `endswith(s)`, `range(n)`,
+    ///   `range(from; to)` and `floor`
+    ///
+    /// `range` fails rather than produce more than 1048576 numbers.
+    ///
+    /// Variables, `reduce`, user defined functions, regular expressions and assignment are not
+    /// supported. `Option` and `Newtype` wrappers are transparent to queries.
+    ///
+    /// ```
+    /// use unstructured::Document;
+    ///
+    /// let doc: Document = serde_json::from_str(
+    ///     r#"{"users": [{"name": "a", "age": 30}, {"name": "b", "age": 17}]}"#,
+    /// )
+    /// .unwrap();
+    /// let adults = doc.query(r#".users[] | select(.age >= 18) | "\(.name) is \(.age)""#);
+    /// assert_eq!(adults.unwrap(), vec![Document::from("a is 30")]);
+    /// assert_eq!(doc.query(".users | length").unwrap(), vec![Document::from(2)]);
+    /// ```
+    pub fn query(&self, query: &str) -> Result<Vec<Self>, Error> {
+        Query::compile(query)?.run(self)
+    }
+}
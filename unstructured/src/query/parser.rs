@@ -0,0 +1,357 @@
+use crate::*;
+use pest::iterators::Pair;
+use pest::Parser;
+use pest_derive::*;
+
+#[derive(Parser)]
+#[grammar = "query/grammar/query.pest"]
+struct QueryParser;
+
+const NESTING: Nesting = Nesting {
+    quotes: &['"'],
+    prefixes: &['-'],
+    if_blocks: true,
+};
+
+/// A parsed query expression
+#[derive(Debug, Clone, PartialEq)]
+pub(super) enum Ast {
+    /// `.`
+    Identity,
+    /// `..`, the input and all of its descendants
+    Recurse,
+    Literal(Literal),
+    /// A string with `\(...)` interpolations
+    Format(Vec<FormatPart>),
+    /// `.key` or `.["key"]` applied to the output of an expression
+    Field(Box<Ast>, String),
+    /// `[index]`, where the index is evaluated against the original input
+    Index(Box<Ast>, Box<Ast>),
+    /// `[from:to]`, either bound may be missing
+    Slice(Box<Ast>, Option<Box<Ast>>, Option<Box<Ast>>),
+    /// `[]`
+    Iterate(Box<Ast>),
+    /// `?`, drops errors
+    Try(Box<Ast>),
+    Pipe(Box<Ast>, Box<Ast>),
+    Comma(Box<Ast>, Box<Ast>),
+    /// `//`
+    Alternative(Box<Ast>, Box<Ast>),
+    And(Box<Ast>, Box<Ast>),
+    Or(Box<Ast>, Box<Ast>),
+    Binary(Box<Ast>, BinaryOp, Box<Ast>),
+    Negate(Box<Ast>),
+    /// `[...]`, collecting every output into a sequence
+    Array(Option<Box<Ast>>),
+    /// `{...}`, a value of None is the `{key}` shorthand for `{key: .key}`
+    Object(Vec<(Ast, Option<Ast>)>),
+    /// `if ... then ... elif ... else ... end`
+    If(Vec<(Ast, Ast)>, Option<Box<Ast>>),
+    /// A builtin function and its arguments
+    Call(String, Vec<Ast>),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub(super) enum Literal {
+    Null,
+    Bool(bool),
+    Number(Number),
+    String(String),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub(super) enum FormatPart {
+    Literal(String),
+    Interpolation(Ast),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) enum BinaryOp {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Rem,
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+impl Ast {
+    pub(super) fn parse(query: &str) -> Result<Self, String> {
+        NESTING.check(query)?;
+        let mut pairs = QueryParser::parse(Rule::query, query).map_err(|e| e.to_string())?;
+        match pairs.next() {
+            Some(pair) => Ast::compile(pair, 0),
+            None => Err("Empty query".to_string()),
+        }
+    }
+
+    /// Compile a pair nested `depth` levels into the query. Each kind of pair is compiled by its
+    /// own function, which keeps the stack used by every level of nesting small.
+    fn compile(pair: Pair<Rule>, depth: usize) -> Result<Self, String> {
+        let pair = skip_wrappers(pair);
+        let inner = || {
+            pair.clone().into_inner().filter(|p| {
+                !matches!(
+                    p.as_rule(),
+                    Rule::kw_and | Rule::kw_or | Rule::kw_if | Rule::kw_elif
+                )
+            })
+        };
+        match pair.as_rule() {
+            // `.key` on its own is a field suffix applied to the input
+            Rule::field => Ast::Identity.apply_suffix(pair, depth),
+            Rule::pipe | Rule::entry_value => fold(inner(), depth, Ast::Pipe),
+            Rule::comma => fold(inner(), depth, Ast::Comma),
+            Rule::alternative => fold(inner(), depth, Ast::Alternative),
+            Rule::or_expr => fold(inner(), depth, Ast::Or),
+            Rule::and_expr => fold(inner(), depth, Ast::And),
+            Rule::comparison | Rule::additive | Rule::multiplicative => {
+                fold_operators(inner(), depth)
+            }
+            Rule::postfix => Ast::postfix(pair, depth),
+            Rule::negate => {
+                let target = Ast::compile(next(&mut inner())?, nested(depth, 1)?)?;
+                Ok(Ast::Negate(Box::new(target)))
+            }
+            Rule::string => Ast::string(pair, depth),
+            Rule::array => Ok(Ast::Array(match inner().next() {
+                Some(pipe) => Some(Box::new(Ast::compile(pipe, nested(depth, 1)?)?)),
+                None => None,
+            })),
+            Rule::object => Ast::object(pair, depth),
+            Rule::if_expr => Ast::if_expr(inner(), depth),
+            Rule::call => Ast::call(pair, depth),
+            Rule::recurse => Ok(Ast::Recurse),
+            Rule::identity => Ok(Ast::Identity),
+            Rule::number => Ok(Ast::Literal(Literal::Number(parse_number(pair.as_str())?))),
+            _ => Err(format!("Unexpected {} in query", pair.as_str())),
+        }
+    }
+
+    fn postfix(pair: Pair<Rule>, depth: usize) -> Result<Self, String> {
+        // Every suffix nests the term a level
+        let pairs: Vec<_> = pair.into_inner().collect();
+        let depth = nested(depth, pairs.len() - 1)?;
+        let mut inner = pairs.into_iter();
+        let mut ast = Ast::compile(next(&mut inner)?, depth)?;
+        for suffix in inner {
+            ast = ast.apply_suffix(suffix, depth)?;
+        }
+        Ok(ast)
+    }
+
+    fn string(pair: Pair<Rule>, depth: usize) -> Result<Self, String> {
+        let depth = nested(depth, 1)?;
+        let mut parts = vec![];
+        for part in pair.into_inner() {
+            parts.push(match part.as_rule() {
+                Rule::chars => FormatPart::Literal(unescape(part.as_str())?),
+                _ => {
+                    let ast = Ast::compile(next(&mut part.into_inner())?, depth)?;
+                    FormatPart::Interpolation(ast)
+                }
+            });
+        }
+        Ok(match parts.as_slice() {
+            [] => Ast::Literal(Literal::String(String::new())),
+            [FormatPart::Literal(s)] => Ast::Literal(Literal::String(s.clone())),
+            _ => Ast::Format(parts),
+        })
+    }
+
+    fn object(pair: Pair<Rule>, depth: usize) -> Result<Self, String> {
+        let depth = nested(depth, 1)?;
+        let mut entries = vec![];
+        for entry in pair.into_inner() {
+            let mut parts = entry.into_inner();
+            let key = next(&mut parts)?;
+            let key = match key.as_rule() {
+                Rule::key_ident => Ast::Literal(Literal::String(key.as_str().into())),
+                _ => Ast::compile(key, depth)?,
+            };
+            let val = match parts.next() {
+                Some(val) => Some(Ast::compile(val, depth)?),
+                None => None,
+            };
+            entries.push((key, val));
+        }
+        Ok(Ast::Object(entries))
+    }
+
+    fn if_expr<'a>(
+        inner: impl Iterator<Item = Pair<'a, Rule>>,
+        depth: usize,
+    ) -> Result<Self, String> {
+        // Every branch after the first is evaluated a level deeper
+        let parts: Vec<_> = inner.collect();
+        let depth = nested(depth, parts.len())?;
+        let mut inner = parts.into_iter();
+        let mut branches = vec![];
+        let cond = Ast::compile(next(&mut inner)?, depth)?;
+        branches.push((cond, Ast::compile(next(&mut inner)?, depth)?));
+        let mut otherwise = None;
+        for part in inner {
+            if part.as_rule() == Rule::elif {
+                let mut elif = part.into_inner().skip(1);
+                let cond = Ast::compile(next(&mut elif)?, depth)?;
+                branches.push((cond, Ast::compile(next(&mut elif)?, depth)?));
+            } else {
+                otherwise = Some(Box::new(Ast::compile(part, depth)?));
+            }
+        }
+        Ok(Ast::If(branches, otherwise))
+    }
+
+    fn call(pair: Pair<Rule>, depth: usize) -> Result<Self, String> {
+        let mut inner = pair.into_inner();
+        let name = next(&mut inner)?.as_str();
+        let depth = nested(depth, 1)?;
+        let mut args = vec![];
+        for arg in inner {
+            args.push(Ast::compile(arg, depth)?);
+        }
+        Ok(match (name, args.is_empty()) {
+            ("null", true) => Ast::Literal(Literal::Null),
+            ("true", true) => Ast::Literal(Literal::Bool(true)),
+            ("false", true) => Ast::Literal(Literal::Bool(false)),
+            _ => Ast::Call(name.to_string(), args),
+        })
+    }
+
+    fn apply_suffix(self, suffix: Pair<Rule>, depth: usize) -> Result<Self, String> {
+        let rule = suffix.as_rule();
+        let mut inner = suffix.into_inner();
+        Ok(match rule {
+            Rule::field => {
+                let name = next(&mut inner)?;
+                let name = match name.as_rule() {
+                    Rule::ident => name.as_str().to_string(),
+                    _ => match Ast::compile(name, depth)? {
+                        Ast::Literal(Literal::String(s)) => s,
+                        _ => return Err("Field names can't be interpolated".to_string()),
+                    },
+                };
+                Ast::Field(Box::new(self), name)
+            }
+            Rule::iterate => Ast::Iterate(Box::new(self)),
+            Rule::subscript => {
+                let from = bound(next(&mut inner)?, depth)?;
+                match inner.next() {
+                    // Slice bounds are optional, but an index isn't
+                    Some(_) => {
+                        let to = bound(next(&mut inner)?, depth)?;
+                        Ast::Slice(Box::new(self), from, to)
+                    }
+                    None => match from {
+                        Some(index) => Ast::Index(Box::new(self), index),
+                        None => return Err("Expected an index or slice".to_string()),
+                    },
+                }
+            }
+            Rule::try_op => Ast::Try(Box::new(self)),
+            _ => return Err("Unexpected suffix in query".to_string()),
+        })
+    }
+}
+
+/// Compile the bound of a slice, or the index of a subscript without a colon
+fn bound(pair: Pair<Rule>, depth: usize) -> Result<Option<Box<Ast>>, String> {
+    Ok(match pair.into_inner().next() {
+        Some(pipe) => Some(Box::new(Ast::compile(pipe, depth)?)),
+        None => None,
+    })
+}
+
+/// Skip the pairs that only wrap a single operand, such as the operators a parenthesized
+/// expression is parsed through, so compiling only recurses where the query nests
+fn skip_wrappers(mut pair: Pair<Rule>) -> Pair<Rule> {
+    while matches!(
+        pair.as_rule(),
+        Rule::pipe
+            | Rule::entry_value
+            | Rule::comma
+            | Rule::alternative
+            | Rule::or_expr
+            | Rule::and_expr
+            | Rule::comparison
+            | Rule::additive
+            | Rule::multiplicative
+            | Rule::postfix
+            | Rule::paren
+    ) {
+        let mut inner = pair.clone().into_inner();
+        match (inner.next(), inner.next()) {
+            (Some(only), None) => pair = only,
+            _ => break,
+        }
+    }
+    pair
+}
+
+fn next<'a>(pairs: &mut impl Iterator<Item = Pair<'a, Rule>>) -> Result<Pair<'a, Rule>, String> {
+    pairs.next().ok_or_else(|| "Incomplete query".to_string())
+}
+
+/// Combine a list of operands left to right, each one after the first nests the result a level
+fn fold<'a>(
+    pairs: impl Iterator<Item = Pair<'a, Rule>>,
+    depth: usize,
+    combine: impl Fn(Box<Ast>, Box<Ast>) -> Ast,
+) -> Result<Ast, String> {
+    let pairs: Vec<_> = pairs.collect();
+    let depth = nested(depth, pairs.len().saturating_sub(1))?;
+    let mut pairs = pairs.into_iter();
+    let mut lhs = Ast::compile(next(&mut pairs)?, depth)?;
+    for rhs in pairs {
+        lhs = combine(Box::new(lhs), Box::new(Ast::compile(rhs, depth)?));
+    }
+    Ok(lhs)
+}
+
+/// Combine operands separated by operators left to right, nesting like [`fold`]
+fn fold_operators<'a>(
+    pairs: impl Iterator<Item = Pair<'a, Rule>>,
+    depth: usize,
+) -> Result<Ast, String> {
+    let pairs: Vec<_> = pairs.collect();
+    let depth = nested(depth, pairs.len() / 2)?;
+    let mut pairs = pairs.into_iter();
+    let mut lhs = Ast::compile(next(&mut pairs)?, depth)?;
+    while let Some(op) = pairs.next() {
+        let op = match op.as_str() {
+            "+" => BinaryOp::Add,
+            "-" => BinaryOp::Sub,
+            "*" => BinaryOp::Mul,
+            "/" => BinaryOp::Div,
+            "%" => BinaryOp::Rem,
+            "==" => BinaryOp::Eq,
+            "!=" => BinaryOp::Ne,
+            "<" => BinaryOp::Lt,
+            "<=" => BinaryOp::Le,
+            ">" => BinaryOp::Gt,
+            ">=" => BinaryOp::Ge,
+            other => return Err(format!("Unknown operator {}", other)),
+        };
+        let rhs = Ast::compile(next(&mut pairs)?, depth)?;
+        lhs = Ast::Binary(Box::new(lhs), op, Box::new(rhs));
+    }
+    Ok(lhs)
+}
+
+fn parse_number(s: &str) -> Result<Number, String> {
+    let number = if s.contains(['.', 'e', 'E']) {
+        s.parse::<f64>()
+            .map(Number::from)
+            .map_err(|e| e.to_string())
+    } else {
+        s.parse::<u64>()
+            .map(Number::from)
+            .map_err(|e| e.to_string())
+    };
+    number.map_err(|e| format!("Invalid number {} in query: {}", s, e))
+}
@@ -0,0 +1,133 @@
+use crate::*;
+use serde::de::{Deserialize, Deserializer};
+use serde::ser::{Error as _, Serialize, Serializer};
+use serde_json::value::RawValue;
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fmt;
+use std::sync::OnceLock;
+
+/// The format of the bytes held by a [`RawDocument`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FormatHint {
+    Json,
+    Msgpack,
+}
+
+#[derive(Debug)]
+pub enum RawError {
+    Json(serde_json::Error),
+    Msgpack(rmp_serde::decode::Error),
+}
+
+impl fmt::Display for RawError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            RawError::Json(e) => write!(f, "Invalid raw JSON: {}", e),
+            RawError::Msgpack(e) => write!(f, "Invalid raw MessagePack: {}", e),
+        }
+    }
+}
+
+impl Error for RawError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            RawError::Json(e) => Some(e),
+            RawError::Msgpack(e) => Some(e),
+        }
+    }
+}
+
+/// Serialized bytes that are only parsed into a [`Document`] the first time they are used, like
+/// `serde_json::value::RawValue`. This saves parsing the parts of an input that are never read.
+///
+/// ```
+/// use unstructured::{FormatHint, RawDocument};
+///
+/// let input = r#"{"id": 7, "payload": {"huge": [1, 2, 3]}}"#;
+/// let fields = RawDocument::json_fields(input).unwrap();
+/// assert_eq!(fields["payload"].bytes(), br#"{"huge": [1, 2, 3]}"#);
+/// assert_eq!(*fields["id"].get().unwrap(), 7);
+///
+/// let packed = rmp_serde::to_vec(&[1, 2]).unwrap();
+/// let raw = RawDocument::new(packed, FormatHint::Msgpack);
+/// assert_eq!(raw.get().unwrap()[1], 2);
+/// ```
+#[derive(Debug, Clone)]
+pub struct RawDocument {
+    bytes: Vec<u8>,
+    format: FormatHint,
+    parsed: OnceLock<Document>,
+}
+
+impl RawDocument {
+    pub fn new<B: Into<Vec<u8>>>(bytes: B, format: FormatHint) -> Self {
+        RawDocument {
+            bytes: bytes.into(),
+            format,
+            parsed: OnceLock::new(),
+        }
+    }
+
+    /// Split a JSON object into its fields, without parsing their values
+    pub fn json_fields(json: &str) -> Result<BTreeMap<String, RawDocument>, RawError> {
+        let fields: BTreeMap<String, &RawValue> =
+            serde_json::from_str(json).map_err(RawError::Json)?;
+        Ok(fields
+            .into_iter()
+            .map(|(key, val)| (key, RawDocument::new(val.get(), FormatHint::Json)))
+            .collect())
+    }
+
+    /// The unparsed bytes
+    pub fn bytes(&self) -> &[u8] {
+        &self.bytes
+    }
+
+    pub fn format(&self) -> FormatHint {
+        self.format
+    }
+
+    /// Whether the bytes have been parsed yet
+    pub fn is_parsed(&self) -> bool {
+        self.parsed.get().is_some()
+    }
+
+    /// The parsed document, parsing it on the first call
+    pub fn get(&self) -> Result<&Document, RawError> {
+        if let Some(doc) = self.parsed.get() {
+            return Ok(doc);
+        }
+        let doc = self.parse()?;
+        Ok(self.parsed.get_or_init(|| doc))
+    }
+
+    /// Take the parsed document, parsing it if that hasn't been done yet
+    pub fn into_document(self) -> Result<Document, RawError> {
+        match self.parsed.into_inner() {
+            Some(doc) => Ok(doc),
+            None => RawDocument::new(self.bytes, self.format).parse(),
+        }
+    }
+
+    fn parse(&self) -> Result<Document, RawError> {
+        match self.format {
+            FormatHint::Json => serde_json::from_slice(&self.bytes).map_err(RawError::Json),
+            FormatHint::Msgpack => rmp_serde::from_slice(&self.bytes).map_err(RawError::Msgpack),
+        }
+    }
+}
+
+/// Only JSON deserializers can leave a value unparsed, other formats will fail
+impl<'de> Deserialize<'de> for RawDocument {
+    fn deserialize<D: Deserializer<'de>>(d: D) -> Result<Self, D::Error> {
+        let raw = Box::<RawValue>::deserialize(d)?;
+        Ok(RawDocument::new(raw.get(), FormatHint::Json))
+    }
+}
+
+impl Serialize for RawDocument {
+    fn serialize<S: Serializer>(&self, s: S) -> Result<S::Ok, S::Error> {
+        self.get().map_err(S::Error::custom)?.serialize(s)
+    }
+}
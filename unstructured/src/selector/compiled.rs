@@ -0,0 +1,627 @@
+use super::parser::{parse_key, Rule, SelectorParser};
+use super::predicate::Expr;
+use crate::core::index::Index;
+use crate::core::Nesting;
+use crate::*;
+use pest::Parser;
+use std::collections::HashSet;
+use std::fmt;
+
+/// How predicates nest, checked before a selector is parsed
+pub(super) const NESTING: Nesting = Nesting {
+    quotes: &['"'],
+    prefixes: &['!'],
+    if_blocks: false,
+};
+
+/// A single step of a compiled selector
+#[derive(Debug, Clone, PartialEq)]
+pub(super) enum Step {
+    /// A map key or sequence index, e.g. `.key`, `["key"]` or `[0]`
+    Child(Segment),
+    /// A negative sequence index counting back from the end, e.g. `[-1]`
+    FromEnd(i64),
+    /// Every map value or sequence element, `.*` or `[*]`
+    Wildcard,
+    /// The current values and all of their descendants, the `..` in `..key`
+    Recursive,
+    /// The map values or sequence elements matching a predicate, along with its source
+    Predicate(Expr, String),
+}
+
+impl Step {
+    fn apply<'a, T: UnstructuredDataTrait>(
+        &self,
+        nodes: Vec<&'a Unstructured<T>>,
+    ) -> Vec<&'a Unstructured<T>> {
+        let nodes = nodes.into_iter();
+        match self {
+            Step::Child(segment) => nodes.filter_map(|node| segment.child(node)).collect(),
+            Step::FromEnd(i) => {
+                let index = Unstructured::<T>::from(*i);
+                nodes.filter_map(|node| index.index_into(node)).collect()
+            }
+            Step::Wildcard => nodes.flat_map(|node| node.iter()).collect(),
+            Step::Recursive => nodes.flat_map(|node| node.nodes()).collect(),
+            Step::Predicate(expr, _) => nodes
+                .flat_map(|node| node.iter())
+                .filter(|node| expr.matches(*node))
+                .collect(),
+        }
+    }
+
+    /// The same as [`Step::apply`], keeping the path each node was reached by. A node shared by
+    /// several parts of the document is matched once for every path leading to it.
+    fn apply_paths<'a, T: UnstructuredDataTrait>(
+        &self,
+        nodes: Vec<(Path, &'a Unstructured<T>)>,
+    ) -> Vec<(Path, &'a Unstructured<T>)> {
+        let mut matched = vec![];
+        for (path, node) in nodes {
+            match self {
+                Step::Child(segment) => {
+                    if let Some(child) = segment.child(node) {
+                        matched.push((path.join(segment.clone()), child));
+                    }
+                }
+                Step::FromEnd(i) => {
+                    let index = Unstructured::<T>::from(*i);
+                    let segment = match node {
+                        Unstructured::<T>::Seq(seq) => {
+                            index.seq_position(seq.len()).map(Segment::Index)
+                        }
+                        _ => Some(Segment::from_key(&index)),
+                    };
+                    if let (Some(segment), Some(child)) = (segment, index.index_into(node)) {
+                        matched.push((path.join(segment), child));
+                    }
+                }
+                Step::Wildcard => matched.extend(children(&path, node)),
+                Step::Recursive => {
+                    for (relative, descendant) in node.walk() {
+                        let mut full = path.clone();
+                        for segment in relative.segments() {
+                            full.push(segment.clone());
+                        }
+                        matched.push((full, descendant));
+                    }
+                }
+                Step::Predicate(expr, _) => matched.extend(
+                    children(&path, node)
+                        .into_iter()
+                        .filter(|(_, child)| expr.matches(*child)),
+                ),
+            }
+        }
+        matched
+    }
+
+    /// The same as [`Step::apply_paths`], matching each value at each path only once. A second
+    /// recursive descent from values nested in one another, as in `..a..b`, reaches the values
+    /// below the innermost one more than once.
+    fn apply_unique<'a, T: UnstructuredDataTrait>(
+        &self,
+        nodes: Vec<(Path, &'a Unstructured<T>)>,
+    ) -> Vec<(Path, &'a Unstructured<T>)> {
+        let mut matched = self.apply_paths(nodes);
+        if *self == Step::Recursive {
+            // A wrapper and its contents share a path, so both are kept
+            let mut seen = HashSet::new();
+            matched.retain(|(path, node)| seen.insert((path.clone(), *node as *const _)));
+        }
+        matched
+    }
+}
+
+/// The elements of a sequence or the values of a map along with their paths
+fn children<'a, T: UnstructuredDataTrait>(
+    path: &Path,
+    node: &'a Unstructured<T>,
+) -> Vec<(Path, &'a Unstructured<T>)> {
+    match node {
+        Unstructured::<T>::Seq(seq) => seq
+            .iter()
+            .enumerate()
+            .map(|(i, child)| (path.join(i), child))
+            .collect(),
+        Unstructured::<T>::Map(map) => map
+            .iter()
+            .map(|(key, child)| (path.join(Segment::from_key(key)), child))
+            .collect(),
+        _ => vec![],
+    }
+}
+
+/// Find the first value reached by following `steps` from `node`, searching depth-first
+fn first<'a, T: UnstructuredDataTrait>(
+    steps: &[Step],
+    node: &'a Unstructured<T>,
+) -> Option<&'a Unstructured<T>> {
+    let (step, rest) = match steps.split_first() {
+        Some(split) => split,
+        None => return Some(node),
+    };
+    match step {
+        Step::Child(segment) => first(rest, segment.child(node)?),
+        Step::FromEnd(i) => first(rest, Unstructured::<T>::from(*i).index_into(node)?),
+        Step::Wildcard => node.iter().find_map(|child| first(rest, child)),
+        Step::Recursive => node.nodes().find_map(|descendant| first(rest, descendant)),
+        Step::Predicate(expr, _) => node
+            .iter()
+            .filter(|child| expr.matches(*child))
+            .find_map(|child| first(rest, child)),
+    }
+}
+
+/// The same as [`first`], only mutable
+fn first_mut<'a, T: UnstructuredDataTrait>(
+    steps: &[Step],
+    node: &'a mut Unstructured<T>,
+) -> Option<&'a mut Unstructured<T>> {
+    let (step, rest) = match steps.split_first() {
+        Some(split) => split,
+        None => return Some(node),
+    };
+    match step {
+        Step::Child(segment) => first_mut(rest, segment.child_mut(node)?),
+        Step::FromEnd(i) => first_mut(rest, Unstructured::<T>::from(*i).index_into_mut(node)?),
+        Step::Wildcard => node.iter_mut().find_map(|child| first_mut(rest, child)),
+        Step::Recursive => {
+            let mut pending = vec![node];
+            while let Some(node) = pending.pop() {
+                // Check immutably first, the borrow can't be released if the search fails
+                if first(rest, node).is_some() {
+                    return first_mut(rest, node);
+                }
+                pending.extend(children_mut(node));
+            }
+            None
+        }
+        Step::Predicate(expr, _) => node
+            .iter_mut()
+            .filter(|child| expr.matches(&**child))
+            .find_map(|child| first_mut(rest, child)),
+    }
+}
+
+/// Remove and return the first value reached by following `steps` from `node`, searching
+/// depth-first. There must be at least one step.
+fn take_first<T: UnstructuredDataTrait>(
+    steps: &[Step],
+    node: &mut Unstructured<T>,
+) -> Option<Unstructured<T>> {
+    let (step, rest) = steps.split_first()?;
+    if rest.is_empty() {
+        return match (step, node) {
+            (Step::Child(segment), node) => segment.remove_from(node),
+            (Step::FromEnd(i), Unstructured::<T>::Seq(seq)) => {
+                let index = seq.len().checked_sub(i.unsigned_abs() as usize)?;
+                Some(make_mut(seq).remove(index))
+            }
+            (Step::Wildcard, Unstructured::<T>::Seq(seq)) if !seq.is_empty() => {
+                Some(make_mut(seq).remove(0))
+            }
+            (Step::Wildcard, Unstructured::<T>::Map(map)) => {
+                make_mut(map).pop_front().map(|(_, v)| v)
+            }
+            (Step::Predicate(expr, _), Unstructured::<T>::Seq(seq)) => {
+                let index = seq.iter().position(|child| expr.matches(child))?;
+                Some(make_mut(seq).remove(index))
+            }
+            (Step::Predicate(expr, _), Unstructured::<T>::Map(map)) => {
+                let key = map
+                    .iter()
+                    .find(|(_, child)| expr.matches(*child))
+                    .map(|(key, _)| key.clone())?;
+                make_mut(map).remove(&key)
+            }
+            _ => None,
+        };
+    }
+    match step {
+        Step::Child(segment) => take_first(rest, segment.child_mut(node)?),
+        Step::FromEnd(i) => take_first(rest, Unstructured::<T>::from(*i).index_into_mut(node)?),
+        Step::Wildcard => node.iter_mut().find_map(|child| take_first(rest, child)),
+        Step::Recursive => {
+            let mut pending = vec![node];
+            while let Some(node) = pending.pop() {
+                if let Some(taken) = take_first(rest, node) {
+                    return Some(taken);
+                }
+                pending.extend(children_mut(node));
+            }
+            None
+        }
+        Step::Predicate(expr, _) => node
+            .iter_mut()
+            .filter(|child| expr.matches(&**child))
+            .find_map(|child| take_first(rest, child)),
+    }
+}
+
+/// The values inside `node` in reverse order, so a stack of them is searched depth-first in the
+/// same order as [`Unstructured::walk`], including the contents of wrappers
+fn children_mut<T: UnstructuredDataTrait>(
+    node: &mut Unstructured<T>,
+) -> Vec<&mut Unstructured<T>> {
+    match node {
+        Unstructured::<T>::Option(Some(inner)) | Unstructured::<T>::Newtype(inner) => {
+            vec![&mut **inner]
+        }
+        node => {
+            let mut children: Vec<_> = node.iter_mut().collect();
+            children.reverse();
+            children
+        }
+    }
+}
+
+impl fmt::Display for Step {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Step::Child(Segment::Key(k)) if is_ident(k) => write!(f, ".{}", k),
+            Step::Child(Segment::Key(k)) => write!(f, "[{:?}]", k),
+            Step::Child(Segment::Index(i)) => write!(f, "[{}]", i),
+            Step::FromEnd(i) => write!(f, "[{}]", i),
+            Step::Wildcard => f.write_str("[*]"),
+            Step::Recursive => f.write_str(".."),
+            Step::Predicate(_, source) => write!(f, "[?({})]", source),
+        }
+    }
+}
+
+fn is_ident(key: &str) -> bool {
+    key.starts_with(|c: char| c.is_ascii_alphabetic())
+        && key.chars().all(|c| c.is_ascii_alphanumeric())
+}
+
+/// Version of the selector syntax to parse with. Selectors stored outside of the program (e.g. in
+/// configuration files) can be pinned to a version so their meaning never changes when the
+/// syntax is extended.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SelectorVersion {
+    /// The original syntax of keys, indices and JSON pointers. In this version `..` is treated
+    /// the same as `.` and negative indices are rejected.
+    V1,
+    /// Adds recursive descent, wildcards, predicates, negative indices and brackets without a
+    /// leading `.`
+    #[default]
+    V2,
+}
+
+impl SelectorVersion {
+    /// The most recent version, used by [`CompiledSelector::compile`]
+    pub const LATEST: SelectorVersion = SelectorVersion::V2;
+
+    fn rule(self) -> Rule {
+        match self {
+            SelectorVersion::V1 => Rule::selector_v1,
+            SelectorVersion::V2 => Rule::selector,
+        }
+    }
+}
+
+/// A selector that has been parsed once so it can be applied to many documents without
+/// parsing it again. The syntax is the same as [`Unstructured::select_all`].
+///
+/// ```
+/// use unstructured::{Document, Selector};
+///
+/// let selector = Selector::compile(".users[*].name").unwrap();
+/// let mut doc: Document =
+///     serde_json::from_str(r#"{"users": [{"id": 1}, {"name": "b"}, {"name": "c"}]}"#).unwrap();
+/// assert_eq!(selector.apply(&doc), Some(&Document::from("b")));
+/// *selector.apply_mut(&mut doc).unwrap() = "d".into();
+/// assert_eq!(doc["users"][1]["name"], "d");
+/// ```
+///
+/// A trace of how the selector resolved can be retrieved with
+/// [`explain`](CompiledSelector::explain).
+///
+/// ```
+/// use unstructured::{CompiledSelector, Document};
+///
+/// let selector = CompiledSelector::compile(".users[*].name").unwrap();
+/// let doc: Document = serde_json::from_str(r#"{"users": [{"name": "a"}, {"id": 2}]}"#).unwrap();
+/// let trace = selector.explain(&doc);
+/// assert_eq!(trace.steps.len(), 3);
+/// assert_eq!(trace.steps[2].matched, 1);
+/// println!("{}", trace);
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct CompiledSelector {
+    pub(super) steps: Vec<Step>,
+}
+
+impl CompiledSelector {
+    /// Parse a selector using the latest syntax, failing if it is not valid
+    pub fn compile(sel: &str) -> Result<Self, Error> {
+        Self::compile_with(sel, SelectorVersion::LATEST)
+    }
+
+    /// Parse a selector using a specific version of the syntax, failing if it is not valid
+    /// in that version
+    pub fn compile_with(sel: &str, version: SelectorVersion) -> Result<Self, Error> {
+        NESTING.check(sel).map_err(Error::Select)?;
+        Self::compile_steps(sel, version).map_err(Error::Parse)
+    }
+
+    fn compile_steps(sel: &str, version: SelectorVersion) -> Result<Self, String> {
+        let selection = SelectorParser::parse(version.rule(), sel).map_err(|e| e.to_string())?;
+        let mut steps = vec![];
+        for pair in selection {
+            steps.push(match pair.as_rule() {
+                Rule::index if pair.as_str().starts_with('-') => Step::FromEnd(
+                    pair.as_str()
+                        .parse::<i64>()
+                        .map_err(|e| format!("Parse failure: {}!", e))?,
+                ),
+                Rule::index | Rule::index_v1 => Step::Child(Segment::Index(
+                    pair.as_str()
+                        .parse::<usize>()
+                        .map_err(|e| format!("Parse failure: {}!", e))?,
+                )),
+                // Escape sequences in keys were only resolved from V2
+                Rule::chars if version == SelectorVersion::V1 => {
+                    Step::Child(Segment::Key(pair.as_str().to_string()))
+                }
+                Rule::chars | Rule::ident | Rule::pointer_key => {
+                    Step::Child(Segment::Key(parse_key(&pair)?.into_owned()))
+                }
+                Rule::wildcard => Step::Wildcard,
+                Rule::recursive => Step::Recursive,
+                Rule::predicate => {
+                    let source = pair.as_str().to_string();
+                    Step::Predicate(Expr::compile(pair, 0)?, source)
+                }
+                Rule::EOI => break,
+                _ => return Err(format!("Invalid selector {}", pair)),
+            });
+        }
+        Ok(CompiledSelector { steps })
+    }
+
+    /// Return the first value in the document matching the selector
+    pub fn apply<'a, T: UnstructuredDataTrait>(
+        &self,
+        doc: &'a Unstructured<T>,
+    ) -> Option<&'a Unstructured<T>> {
+        first(&self.steps, doc)
+    }
+
+    /// Return a mutable reference to the first value in the document matching the selector
+    pub fn apply_mut<'a, T: UnstructuredDataTrait>(
+        &self,
+        doc: &'a mut Unstructured<T>,
+    ) -> Option<&'a mut Unstructured<T>> {
+        first_mut(&self.steps, doc)
+    }
+
+    /// Remove the first value in the document matching the selector and return it. Selecting
+    /// the root takes the whole document, leaving it `Unassigned`.
+    pub fn take<T: UnstructuredDataTrait>(
+        &self,
+        doc: &mut Unstructured<T>,
+    ) -> Option<Unstructured<T>> {
+        if self.steps.is_empty() {
+            return Some(doc.take());
+        }
+        take_first(&self.steps, doc)
+    }
+
+    /// Return every value in the document matching the selector
+    pub fn select_all<'a, T: UnstructuredDataTrait>(
+        &self,
+        doc: &'a Unstructured<T>,
+    ) -> Vec<&'a Unstructured<T>> {
+        // Only descending again from what one descent matched can reach a value twice, otherwise
+        // there is no need to build the paths that tell the matches apart
+        if self.descents() < 2 {
+            return self
+                .steps
+                .iter()
+                .fold(vec![doc], |nodes, step| step.apply(nodes));
+        }
+        self.steps
+            .iter()
+            .fold(vec![(Path::default(), doc)], |nodes, step| step.apply_unique(nodes))
+            .into_iter()
+            .map(|(_, node)| node)
+            .collect()
+    }
+
+    fn descents(&self) -> usize {
+        self.steps.iter().filter(|step| **step == Step::Recursive).count()
+    }
+
+    /// Return the path of every value in the document matching the selector. Unlike comparing
+    /// the values [`select_all`](Self::select_all) returns, this tells apart the places a
+    /// shared value appears in.
+    pub(crate) fn select_paths<T: UnstructuredDataTrait>(
+        &self,
+        doc: &Unstructured<T>,
+    ) -> Vec<Path> {
+        self.steps
+            .iter()
+            .fold(vec![(Path::default(), doc)], |nodes, step| step.apply_unique(nodes))
+            .into_iter()
+            .map(|(path, _)| path)
+            .collect()
+    }
+
+    /// Apply the selector to the document one step at a time, recording what each step matched.
+    /// Evaluation stops after the first step that matches nothing.
+    pub fn explain<T: UnstructuredDataTrait>(&self, doc: &Unstructured<T>) -> Explanation {
+        let mut nodes = vec![(Path::default(), doc)];
+        let mut steps = vec![];
+        for step in self.steps.iter() {
+            let input = nodes.iter().map(|(_, node)| node.type_name()).collect();
+            nodes = step.apply_unique(nodes);
+            steps.push(TraceStep {
+                step: step.to_string(),
+                input,
+                matched: nodes.len(),
+            });
+            if nodes.is_empty() {
+                break;
+            }
+        }
+        Explanation {
+            steps,
+            total: self.steps.len(),
+        }
+    }
+}
+
+/// Shorthand for [`CompiledSelector`]
+pub type Selector = CompiledSelector;
+
+impl fmt::Display for CompiledSelector {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.steps.is_empty() {
+            return f.write_str(".");
+        }
+        for step in self.steps.iter() {
+            step.fmt(f)?;
+        }
+        Ok(())
+    }
+}
+
+/// The result of a single selector step, see [`CompiledSelector::explain`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TraceStep {
+    /// The step as written in selector syntax
+    pub step: String,
+    /// The type of each value the step was applied to
+    pub input: Vec<&'static str>,
+    /// The number of values the step matched
+    pub matched: usize,
+}
+
+/// A step by step trace of a selector applied to a document, see [`CompiledSelector::explain`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Explanation {
+    /// The steps that were evaluated, in order
+    pub steps: Vec<TraceStep>,
+    total: usize,
+}
+
+impl Explanation {
+    /// Return the step where resolution stopped because nothing matched, if any
+    pub fn stopped_at(&self) -> Option<&TraceStep> {
+        self.steps.last().filter(|step| step.matched == 0)
+    }
+}
+
+impl fmt::Display for Explanation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (i, step) in self.steps.iter().enumerate() {
+            writeln!(
+                f,
+                "{}. {} applied to [{}] matched {}",
+                i + 1,
+                step.step,
+                step.input.join(", "),
+                step.matched
+            )?;
+        }
+        if self.stopped_at().is_some() {
+            writeln!(
+                f,
+                "Stopped after step {} of {}, nothing matched",
+                self.steps.len(),
+                self.total
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Error returned by [`Unstructured::clone_at`], [`Unstructured::extract`] and
+/// [`Unstructured::redact`] when a selector is not valid
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExtractError {
+    /// The selector that failed to parse
+    pub selector: String,
+    pub message: String,
+    /// Whether the selector parsed but couldn't be applied, which makes this an
+    /// [`Error::Select`] rather than an [`Error::Parse`]
+    pub(crate) unusable: bool,
+}
+
+impl ExtractError {
+    pub(super) fn compile(sel: &str) -> Result<CompiledSelector, Self> {
+        CompiledSelector::compile(sel).map_err(|e| ExtractError::new(sel, e))
+    }
+
+    pub(super) fn new(sel: &str, e: Error) -> Self {
+        ExtractError {
+            selector: sel.to_string(),
+            message: e.to_string(),
+            unusable: matches!(e, Error::Select(_)),
+        }
+    }
+}
+
+impl fmt::Display for ExtractError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Invalid selector {}: {}", self.selector, self.message)
+    }
+}
+
+impl std::error::Error for ExtractError {}
+
+impl<T: UnstructuredDataTrait> Unstructured<T> {
+    /// Clone the first value matching the selector, or return None if nothing matched. Only
+    /// the selected subtree is cloned. Any selector accepted by [`CompiledSelector`] can be
+    /// used, including wildcards and predicates.
+    ///
+    /// ```
+    /// use unstructured::Document;
+    ///
+    /// let doc: Document = serde_json::from_str(r#"{"a": [{"id": 1}, {"id": 2}]}"#).unwrap();
+    /// assert_eq!(doc.clone_at(".a[?(@.id > 1)]").unwrap().unwrap()["id"], 2);
+    /// assert_eq!(doc.clone_at(".b").unwrap(), None);
+    /// assert!(doc.clone_at(".a[").is_err());
+    /// ```
+    pub fn clone_at(&self, sel: &str) -> Result<Option<Self>, ExtractError> {
+        Ok(ExtractError::compile(sel)?.apply(self).cloned())
+    }
+
+    /// Clone every value matching any of the selectors into a new document, placing each at
+    /// the same path it has in this one. Sequences are padded with `Null` up to the index of a
+    /// selected element. This is what [`filter`](Unstructured::filter) does, for a single
+    /// document. If nothing matches the result is an empty map.
+    ///
+    /// ```
+    /// use unstructured::Document;
+    ///
+    /// let doc: Document =
+    ///     serde_json::from_str(r#"{"a": {"b": 1, "c": 2}, "d": [3, 4], "e": 5}"#).unwrap();
+    /// let expected: Document = serde_json::from_str(r#"{"a": {"c": 2}, "e": 5}"#).unwrap();
+    /// assert_eq!(doc.extract(&[".a.c", ".e"]).unwrap(), expected);
+    /// let err = doc.extract(&[".a", "[?("]).unwrap_err();
+    /// assert_eq!(err.selector, "[?(");
+    /// ```
+    pub fn extract(&self, selectors: &[&str]) -> Result<Self, ExtractError> {
+        let selectors = selectors
+            .iter()
+            .map(|sel| ExtractError::compile(sel))
+            .collect::<Result<Vec<_>, _>>()?;
+        let matched: std::collections::HashSet<Path> = selectors
+            .iter()
+            .flat_map(|sel| sel.select_paths(self))
+            .collect();
+        let mut result = Self::Unassigned;
+        for (path, node) in self.walk() {
+            if matched.contains(&path) {
+                result.set_at(&path, node.clone());
+            }
+        }
+        if let Self::Unassigned = result {
+            result = Self::Map(Default::default());
+        }
+        Ok(result)
+    }
+}
@@ -0,0 +1,134 @@
+use super::compiled::Step;
+use crate::*;
+
+/// Where the next value of a stream of events is
+enum Position {
+    Seq(usize),
+    Map(Option<Segment>),
+    Wrapper,
+}
+
+/// Applies a selector to a stream of [`DocumentEvent`]s, only building the values that match it.
+/// Made with [`CompiledSelector::event_filter`].
+///
+/// ```
+/// use unstructured::{CompiledSelector, Document};
+///
+/// let input = r#"{"users": [{"email": "a@x"}, {"email": "b@x", "admin": {"email": "c@x"}}]}"#;
+/// let mut filter = CompiledSelector::compile("..email").unwrap().event_filter().unwrap();
+/// let mut emails = vec![];
+/// let mut de = serde_json::Deserializer::from_str(input);
+/// Document::stream_events(&mut de, |event| {
+///     if let Some(email) = filter.push(event).unwrap() {
+///         emails.push(email);
+///     }
+/// })
+/// .unwrap();
+/// assert_eq!(emails, vec!["a@x", "b@x", "c@x"]);
+/// ```
+pub struct EventFilter<T: UnstructuredDataTrait> {
+    steps: Vec<Step>,
+    position: Vec<Position>,
+    builders: Vec<EventBuilder<T>>,
+}
+
+impl<T: UnstructuredDataTrait> EventFilter<T> {
+    /// Add the next event, returning a value once all of its events have been pushed. Values
+    /// nested inside another match are returned before the value holding them.
+    pub fn push(&mut self, event: DocumentEvent<T>) -> Result<Option<Unstructured<T>>, Error> {
+        let starts_value = !matches!(
+            event,
+            DocumentEvent::Key(_) | DocumentEvent::EndMap | DocumentEvent::EndSeq
+        );
+        if starts_value
+            && !matches!(self.position.last(), Some(Position::Wrapper))
+            && path_matches(&self.steps, &self.path())
+        {
+            self.builders.push(EventBuilder::new());
+        }
+
+        let mut found = None;
+        let mut i = 0;
+        while i < self.builders.len() {
+            if let Some(doc) = self.builders[i].push(event.clone())? {
+                self.builders.remove(i);
+                found = Some(doc);
+            } else {
+                i += 1;
+            }
+        }
+
+        match event {
+            DocumentEvent::StartMap => self.position.push(Position::Map(None)),
+            DocumentEvent::StartSeq => self.position.push(Position::Seq(0)),
+            DocumentEvent::WrapSome | DocumentEvent::WrapNewtype => {
+                self.position.push(Position::Wrapper)
+            }
+            DocumentEvent::Key(key) => match self.position.last_mut() {
+                Some(Position::Map(pending)) => *pending = Some(Segment::from_key(&key)),
+                _ => return Err(Error::Build(format!("Unexpected map key {}", key))),
+            },
+            DocumentEvent::Value(_) => self.finish_value(),
+            DocumentEvent::EndMap | DocumentEvent::EndSeq => {
+                self.position.pop();
+                self.finish_value();
+            }
+        }
+        Ok(found)
+    }
+
+    fn path(&self) -> Vec<Segment> {
+        self.position
+            .iter()
+            .filter_map(|position| match position {
+                Position::Seq(i) => Some(Segment::Index(*i)),
+                Position::Map(key) => key.clone(),
+                Position::Wrapper => None,
+            })
+            .collect()
+    }
+
+    fn finish_value(&mut self) {
+        while let Some(Position::Wrapper) = self.position.last() {
+            self.position.pop();
+        }
+        match self.position.last_mut() {
+            Some(Position::Seq(i)) => *i += 1,
+            Some(Position::Map(key)) => *key = None,
+            _ => {}
+        }
+    }
+}
+
+fn path_matches(steps: &[Step], path: &[Segment]) -> bool {
+    match steps.split_first() {
+        None => path.is_empty(),
+        Some((Step::Child(segment), rest)) => {
+            path.first() == Some(segment) && path_matches(rest, &path[1..])
+        }
+        Some((Step::Wildcard, rest)) => !path.is_empty() && path_matches(rest, &path[1..]),
+        Some((Step::Recursive, rest)) => {
+            (0..=path.len()).any(|i| path_matches(rest, &path[i..]))
+        }
+        Some(_) => false,
+    }
+}
+
+impl CompiledSelector {
+    /// A filter applying this selector to a stream of events, see [`EventFilter`]. Negative
+    /// indices and predicates need the values around them, so they can't be used.
+    pub fn event_filter<T: UnstructuredDataTrait>(&self) -> Result<EventFilter<T>, Error> {
+        let unsupported = |step: &&Step| matches!(step, Step::FromEnd(_) | Step::Predicate(..));
+        match self.steps.iter().find(unsupported) {
+            Some(step) => Err(Error::Select(format!(
+                "{} can't be applied to a stream of events",
+                step
+            ))),
+            None => Ok(EventFilter {
+                steps: self.steps.clone(),
+                position: vec![],
+                builders: vec![],
+            }),
+        }
+    }
+}
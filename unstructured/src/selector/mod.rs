@@ -1,2 +1,21 @@
 #[cfg(feature = "selector")]
+mod compiled;
+#[cfg(feature = "selector")]
+mod events;
+#[cfg(feature = "selector")]
 mod parser;
+#[cfg(feature = "selector")]
+mod predicate;
+#[cfg(feature = "selector")]
+mod redact;
+#[cfg(feature = "selector")]
+mod view;
+
+#[cfg(feature = "selector")]
+pub use compiled::*;
+#[cfg(feature = "selector")]
+pub use events::*;
+#[cfg(feature = "selector")]
+pub use redact::*;
+#[cfg(feature = "selector")]
+pub use view::*;
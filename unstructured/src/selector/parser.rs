@@ -1,7 +1,10 @@
+use super::compiled::NESTING;
+use super::CompiledSelector;
 use crate::*;
+use pest::iterators::{Pair, Pairs};
 use pest::Parser;
 use pest_derive::*;
-use std::collections::BTreeMap;
+use std::borrow::Cow;
 
 // #[cfg(test)]
 // mod test {
@@ -58,25 +61,24 @@ use std::collections::BTreeMap;
 
 macro_rules! parse_array_index {
     ($pair:ident, $name:ident) => {
-        $name[$pair
-            .as_str()
-            .parse::<usize>()
-            .map_err(|e| format!("Parse failure: {}!", e))?]
+        $name[parse_index::<T>($pair.as_str())?]
     };
 }
 
+#[cfg(feature = "filter")]
 macro_rules! parse_char_string {
     ($pair: ident) => {
-        String::from($pair.as_str())
+        parse_key(&$pair)?.into_owned()
     };
 }
 
 macro_rules! parse_char {
     ($pair:ident, $name:ident) => {
-        $name[$pair.as_str()]
+        $name[parse_key(&$pair)?.as_ref()]
     };
 }
 
+#[cfg(feature = "filter")]
 macro_rules! parse_ident_string {
     ($pair: ident) => {
         String::from($pair.as_str())
@@ -89,30 +91,20 @@ macro_rules! parse_ident {
     };
 }
 
+#[cfg(feature = "filter")]
 macro_rules! parse_range {
     ($pair:ident, $name:ident) => {
         match $name {
             Unstructured::<T>::Seq(s) => {
-                let mut range: Vec<usize> = $pair
-                    .as_str()
-                    .split(":")
-                    .map(|v| v.parse::<usize>().unwrap_or(0))
-                    .collect();
-                if range[1] > s.len() || range[1] == 0 {
-                    range[1] = s.len();
-                }
-                if range[0] >= s.len() {
-                    Unstructured::<T>::Seq(vec![])
-                } else {
-                    let res = Vec::from(&s[range[0]..range[1]]);
-                    Unstructured::<T>::Seq(res)
-                }
+                let indices = range_indices($pair.as_str(), s.len())?;
+                Unstructured::<T>::Seq(share(indices.into_iter().map(|i| s[i].clone()).collect()))
             }
             _ => return Err(format!("Cannot take range on non-sequence value!")),
         }
     };
 }
 
+#[cfg(feature = "filter")]
 macro_rules! parse_doc_index {
     ($pair:ident) => {
         $pair
@@ -124,20 +116,61 @@ macro_rules! parse_doc_index {
 
 #[derive(Parser)]
 #[grammar = "selector/grammar/selector.pest"]
-struct SelectorParser;
+pub(super) struct SelectorParser;
 
 impl<T: UnstructuredDataTrait> Unstructured<T> {
-    pub fn select<'a>(&'a self, sel: &str) -> Result<&'a Unstructured<T>, String>
+    pub fn select<'a>(&'a self, sel: &str) -> Result<&'a Unstructured<T>, Error>
+    where
+        T: Clone,
+    {
+        NESTING.check(sel).map_err(Error::Select)?;
+        let selection = SelectorParser::parse(Rule::selector, sel)
+            .map_err(|e| Error::Parse(e.to_string()))?;
+        self.select_pairs(selection).map_err(Error::Select)
+    }
+
+    /// Select a value like [`select`](Unstructured::select), returning a selector that fails as
+    /// an `Err` document so it can be checked later with [`ok`](Unstructured::ok). Selecting
+    /// from an `Err` gives the same `Err`.
+    ///
+    /// ```
+    /// use unstructured::Document;
+    ///
+    /// let doc: Document = serde_json::from_str(r#"{"a": [1, 2]}"#).unwrap();
+    /// assert_eq!(doc.select_or_err(".a[1]"), 2);
+    /// let failed = doc.select_or_err(".a[");
+    /// assert!(failed.select_or_err(".b").ok().is_err());
+    /// ```
+    pub fn select_or_err(&self, sel: &str) -> Self {
+        match self.select(sel) {
+            Ok(selected) => selected.clone(),
+            Err(e) => Self::error_or_null(e.to_string()),
+        }
+    }
+
+    pub fn select_mut<'a>(&'a mut self, sel: &str) -> Result<&'a mut Unstructured<T>, Error>
+    where
+        T: Clone,
+    {
+        NESTING.check(sel).map_err(Error::Select)?;
+        let selection = SelectorParser::parse(Rule::selector, sel)
+            .map_err(|e| Error::Parse(e.to_string()))?;
+        self.select_pairs_mut(selection).map_err(Error::Select)
+    }
+
+    fn select_pairs(&self, selection: Pairs<Rule>) -> Result<&Unstructured<T>, String>
     where
         T: Clone,
     {
-        let selection = SelectorParser::parse(Rule::selector, sel).map_err(|e| e.to_string())?;
         let mut result = self;
         for selector in selection {
             match selector.as_rule() {
                 Rule::index => result = &parse_array_index!(selector, result),
-                Rule::chars => result = &parse_char!(selector, result),
+                Rule::chars | Rule::pointer_key => result = &parse_char!(selector, result),
                 Rule::ident => result = &parse_ident!(selector, result),
+                Rule::recursive | Rule::wildcard | Rule::predicate => {
+                    return Err(multiple_values_error(&selector))
+                }
                 Rule::EOI => return Ok(result),
                 _ => return Err(format!("Invalid selector {}", selector)),
             };
@@ -145,17 +178,21 @@ impl<T: UnstructuredDataTrait> Unstructured<T> {
         Ok(result)
     }
 
-    pub fn select_mut<'a>(&'a mut self, sel: &str) -> Result<&'a mut Unstructured<T>, String>
+    fn select_pairs_mut(&mut self, selection: Pairs<Rule>) -> Result<&mut Unstructured<T>, String>
     where
         T: Clone,
     {
-        let selection = SelectorParser::parse(Rule::selector, sel).map_err(|e| e.to_string())?;
         let mut result = self;
         for selector in selection {
             match selector.as_rule() {
                 Rule::index => result = &mut parse_array_index!(selector, result),
-                Rule::chars => result = &mut parse_char!(selector, result),
+                Rule::chars | Rule::pointer_key => {
+                    result = &mut parse_char!(selector, result)
+                }
                 Rule::ident => result = &mut parse_ident!(selector, result),
+                Rule::recursive | Rule::wildcard | Rule::predicate => {
+                    return Err(multiple_values_error(&selector))
+                }
                 Rule::EOI => return Ok(result),
                 _ => return Err(format!("Invalid selector {}", selector)),
             };
@@ -163,84 +200,280 @@ impl<T: UnstructuredDataTrait> Unstructured<T> {
         Ok(result)
     }
 
-    pub fn filter(docs: &[Unstructured<T>], sel: &str) -> Result<Unstructured<T>, String>
+    /// Select every value matching the selector. Unlike [`select`](Unstructured::select), this
+    /// supports recursive descent (`..key`) which matches the key at any depth and wildcards
+    /// (`.*` or `[*]`) which match every map value or sequence element. Predicates such as
+    /// `[?(@.price > 10 && @.active)]` match the elements for which the expression is true,
+    /// where `@` refers to the element. Values that do not exist are left out of the results
+    /// rather than returned as `Null`.
+    ///
+    /// ```
+    /// use unstructured::Document;
+    ///
+    /// let doc: Document =
+    ///     serde_json::from_str(r#"{"id": 1, "items": [{"id": 2}, {"sub": {"id": 3}}]}"#).unwrap();
+    /// let ids = doc.select_all("..id").unwrap();
+    /// assert_eq!(ids, vec![&Document::from(1u64), &2u64.into(), &3u64.into()]);
+    /// assert_eq!(doc.select_all(".items..id").unwrap().len(), 2);
+    /// assert_eq!(doc.select_all(".items[*].id").unwrap(), vec![&Document::from(2u64)]);
+    /// ```
+    pub fn select_all<'a>(&'a self, sel: &str) -> Result<Vec<&'a Unstructured<T>>, Error> {
+        Ok(CompiledSelector::compile(sel)?.select_all(self))
+    }
+
+    /// Select a value and return an owned copy of it, cloning only the selected value rather
+    /// than the document
+    pub fn select_cloned(&self, sel: &str) -> Result<Unstructured<T>, Error> {
+        self.select(sel).cloned()
+    }
+
+    /// Remove the first value matching the selector from the document and return it by value,
+    /// avoiding a clone of the selected subtree. Returns None if nothing matched.
+    ///
+    /// ```
+    /// use unstructured::Document;
+    ///
+    /// let mut doc: Document =
+    ///     serde_json::from_str(r#"{"a": {"big": [1, 2, 3]}, "b": 1}"#).unwrap();
+    /// let big = doc.select_take(".a.big").unwrap().unwrap();
+    /// assert_eq!(big[2], 3);
+    /// assert_eq!(doc, serde_json::from_str::<Document>(r#"{"a": {}, "b": 1}"#).unwrap());
+    /// ```
+    pub fn select_take(&mut self, sel: &str) -> Result<Option<Unstructured<T>>, Error> {
+        Ok(CompiledSelector::compile(sel)?.take(self))
+    }
+
+    #[cfg(feature = "filter")]
+    pub fn filter(docs: &[Unstructured<T>], sel: &str) -> Result<Unstructured<T>, Error>
     where
         T: Clone,
     {
-        let mut result = Unstructured::<T>::Map(BTreeMap::new());
-        if !docs.is_empty() {
-            let mut current_owned = None;
-            let mut current = &docs[0];
-            let mut key_path = vec![];
-            let selection =
-                SelectorParser::parse(Rule::selector_filter, sel).map_err(|e| e.to_string())?;
-            for selector in selection {
-                match selector.as_rule() {
-                    Rule::doc_index => {
-                        let index = parse_doc_index!(selector);
-                        if index >= docs.len() {
-                            return Err(format!("Document index of {} is out of bounds", index));
-                        } else {
-                            current = &docs[index];
-                        }
+        let mut result = Unstructured::<T>::Map(Default::default());
+        Self::filter_into(docs, sel, &mut result)?;
+        Ok(result)
+    }
+
+    /// The same as [`filter`](Unstructured::filter), but the results are merged into an existing
+    /// document rather than a new one, the same as [`merge`](Unstructured::merge). This avoids
+    /// building a new result on every call when filtering repeatedly.
+    ///
+    /// ```
+    /// use unstructured::Document;
+    ///
+    /// let docs: Vec<Document> = vec![
+    ///     serde_json::from_str(r#"{"a": 1}"#).unwrap(),
+    ///     serde_json::from_str(r#"{"b": 2}"#).unwrap(),
+    /// ];
+    /// let mut result = Document::Map(Default::default());
+    /// Document::filter_into(&docs, "[0].a", &mut result).unwrap();
+    /// Document::filter_into(&docs, "[1].b", &mut result).unwrap();
+    /// assert_eq!(result, serde_json::from_str::<Document>(r#"{"a": 1, "b": 2}"#).unwrap());
+    /// ```
+    #[cfg(feature = "filter")]
+    pub fn filter_into(
+        docs: &[Unstructured<T>],
+        sel: &str,
+        result: &mut Unstructured<T>,
+    ) -> Result<(), Error>
+    where
+        T: Clone,
+    {
+        let observation = Observation::start(Operation::Filter);
+        let filtered = Self::filter_into_imp(docs, sel, result);
+        observation.end(filtered.as_ref().ok().map(|_| &*result));
+        filtered
+    }
+
+    #[cfg(feature = "filter")]
+    fn filter_into_imp(
+        docs: &[Unstructured<T>],
+        sel: &str,
+        result: &mut Unstructured<T>,
+    ) -> Result<(), Error>
+    where
+        T: Clone,
+    {
+        if docs.is_empty() {
+            return Ok(());
+        }
+        let selection = SelectorParser::parse(Rule::selector_filter, sel)
+            .map_err(|e| Error::Parse(e.to_string()))?;
+        Self::filter_pairs(docs, selection, result).map_err(Error::Filter)
+    }
+
+    #[cfg(feature = "filter")]
+    fn filter_pairs(
+        docs: &[Unstructured<T>],
+        selection: Pairs<Rule>,
+        result: &mut Unstructured<T>,
+    ) -> Result<(), String>
+    where
+        T: Clone,
+    {
+        let mut current_owned = None;
+        let mut current = &docs[0];
+        let mut key_path = vec![];
+        for selector in selection {
+            match selector.as_rule() {
+                Rule::doc_index => {
+                    let index = parse_doc_index!(selector);
+                    if index >= docs.len() {
+                        return Err(format!("Document index of {} is out of bounds", index));
+                    } else {
+                        current = &docs[index];
                     }
-                    Rule::doc_wildcard => {
-                        for doc in docs.iter() {
-                            result = result + doc.clone();
-                        }
+                }
+                Rule::doc_wildcard => {
+                    for doc in docs.iter() {
+                        result.merge_imp(doc.clone());
                     }
-                    Rule::index => current = &parse_array_index!(selector, current),
-                    Rule::chars => {
-                        current = &parse_char!(selector, current);
-                        if current != &Unstructured::<T>::Null {
-                            key_path.push(parse_char_string!(selector));
-                        }
+                }
+                Rule::index => current = &parse_array_index!(selector, current),
+                Rule::chars | Rule::pointer_key => {
+                    current = &parse_char!(selector, current);
+                    if !is_absent(current) {
+                        key_path.push(parse_char_string!(selector));
                     }
-                    Rule::ident => {
-                        current = &parse_ident!(selector, current);
-                        if current != &Unstructured::<T>::Null {
-                            key_path.push(parse_ident_string!(selector));
-                        }
+                }
+                Rule::ident => {
+                    current = &parse_ident!(selector, current);
+                    if !is_absent(current) {
+                        key_path.push(parse_ident_string!(selector));
                     }
-                    Rule::range => current_owned = Some(parse_range!(selector, current)),
-                    Rule::EOI | Rule::pipe => {
-                        if !key_path.is_empty() {
-                            let mut tree = Unstructured::<T>::Map(BTreeMap::default());
-                            let mut pos = &mut tree;
-                            for (i, path) in key_path.iter().enumerate() {
-                                let mut new_doc = Unstructured::<T>::Map(BTreeMap::default());
-                                if i == key_path.len() - 1 {
-                                    new_doc = new_doc
-                                        + match current_owned {
-                                            Some(s) => s,
-                                            None => current.clone(),
-                                        };
-                                    current_owned = None;
-                                    current = &docs[0];
-                                }
-                                pos[&path] = new_doc;
-                                pos = &mut pos[&path];
-                            }
-                            if tree != Unstructured::<T>::Null {
-                                result = result + tree;
-                            }
-                            key_path.clear();
-                        } else {
-                            let temp = match current_owned {
-                                Some(s) => s,
-                                None => current.clone(),
-                            };
-                            if temp != Unstructured::<T>::Null {
-                                result = result + temp;
+                }
+                Rule::range => current_owned = Some(parse_range!(selector, current)),
+                Rule::EOI | Rule::pipe => {
+                    if !key_path.is_empty() {
+                        let mut tree = Unstructured::<T>::Map(Default::default());
+                        let mut pos = &mut tree;
+                        for (i, path) in key_path.iter().enumerate() {
+                            let mut new_doc = Unstructured::<T>::Map(Default::default());
+                            if i == key_path.len() - 1 {
+                                new_doc.merge_imp(match current_owned {
+                                    Some(s) => s,
+                                    None => current.clone(),
+                                });
+                                current_owned = None;
+                                current = &docs[0];
                             }
-                            current_owned = None;
-                            current = &docs[0];
+                            pos[&path] = new_doc;
+                            pos = &mut pos[&path];
                         }
+                        if tree != Unstructured::<T>::Null {
+                            result.merge_imp(tree);
+                        }
+                        key_path.clear();
+                    } else {
+                        let temp = match current_owned {
+                            Some(s) => s,
+                            None => current.clone(),
+                        };
+                        if !is_absent(&temp) {
+                            result.merge_imp(temp);
+                        }
+                        current_owned = None;
+                        current = &docs[0];
                     }
-                    _ => return Err(format!("Invalid selector {}", selector)),
                 }
+                _ => return Err(format!("Invalid selector {}", selector)),
             }
         }
-        Ok(result)
+        Ok(())
+    }
+}
+
+/// Missing values, and nulls, are left out of filter results
+#[cfg(feature = "filter")]
+fn is_absent<T: UnstructuredDataTrait>(doc: &Unstructured<T>) -> bool {
+    matches!(doc, Unstructured::<T>::Null | Unstructured::<T>::Unassigned)
+}
+
+/// Get the key named by a selector pair, resolving any escape sequences
+pub(super) fn parse_key<'a>(pair: &Pair<'a, Rule>) -> Result<Cow<'a, str>, String> {
+    let key = pair.as_str();
+    match pair.as_rule() {
+        Rule::chars if key.contains('\\') => unescape(key).map(Cow::Owned),
+        Rule::pointer_key if key.contains('~') => unescape_pointer(key).map(Cow::Owned),
+        _ => Ok(Cow::Borrowed(key)),
+    }
+}
+
+fn multiple_values_error(selector: &pest::iterators::Pair<Rule>) -> String {
+    format!(
+        "Selector {} may match multiple values, use select_all instead",
+        selector.as_str()
+    )
+}
+
+/// Parse a sequence index, negative indices are kept signed so they count back from the end
+fn parse_index<T: UnstructuredDataTrait>(index: &str) -> Result<Unstructured<T>, String> {
+    let parsed = if index.starts_with('-') {
+        index.parse::<i64>().map(Unstructured::<T>::from)
+    } else {
+        index.parse::<usize>().map(Unstructured::<T>::from)
+    };
+    parsed.map_err(|e| format!("Parse failure: {}!", e))
+}
+
+#[cfg(feature = "filter")]
+/// Resolve a `start:end:step` range against the length of a sequence, returning the selected
+/// positions in order. Negative bounds count back from the end and a negative step walks the
+/// sequence in reverse. With a positive step, a missing or zero end selects through the end.
+fn range_indices(range: &str, len: usize) -> Result<Vec<usize>, String> {
+    let mut parts = range.split(':').map(|part| {
+        if part.is_empty() {
+            Ok(None)
+        } else {
+            part.parse::<i64>()
+                .map(Some)
+                .map_err(|e| format!("Parse failure: {}!", e))
+        }
+    });
+    let start = parts.next().unwrap_or(Ok(None))?;
+    let end = parts.next().unwrap_or(Ok(None))?;
+    let step = parts.next().unwrap_or(Ok(None))?.unwrap_or(1);
+    let len = len as i64;
+    if step == 0 {
+        return Err(format!("Range step cannot be zero in [{}]", range));
+    }
+    let mut indices = vec![];
+    if step > 0 {
+        let resolve = |bound: i64| {
+            if bound < 0 {
+                (len + bound).max(0)
+            } else {
+                bound.min(len)
+            }
+        };
+        let mut i = start.map_or(0, resolve);
+        let end = match end {
+            None | Some(0) => len,
+            Some(end) => resolve(end),
+        };
+        while i < end {
+            indices.push(i as usize);
+            i = match i.checked_add(step) {
+                Some(next) => next,
+                None => break,
+            };
+        }
+    } else {
+        let resolve = |bound: i64| {
+            if bound < 0 {
+                (len + bound).max(-1)
+            } else {
+                bound.min(len - 1)
+            }
+        };
+        let mut i = start.map_or(len - 1, resolve);
+        let end = end.map_or(-1, resolve);
+        while i > end {
+            indices.push(i as usize);
+            i = match i.checked_add(step) {
+                Some(next) => next,
+                None => break,
+            };
+        }
     }
+    Ok(indices)
 }
@@ -0,0 +1,199 @@
+use super::parser::{parse_key, Rule};
+use crate::core::nested;
+use crate::*;
+use pest::iterators::Pair;
+use std::borrow::Cow;
+use std::cmp::Ordering;
+
+/// A parsed `[?(...)]` predicate expression
+#[derive(Debug, Clone, PartialEq)]
+pub(super) enum Expr {
+    Or(Vec<Expr>),
+    And(Vec<Expr>),
+    Not(Box<Expr>),
+    /// A lone operand is true if it exists and is not null or false
+    Test(Operand),
+    Compare(Operand, Comparator, Operand),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub(super) enum Operand {
+    /// A path relative to the value being tested, `@` in the expression
+    Current(Vec<Segment>),
+    Null,
+    Bool(bool),
+    Number(Number),
+    String(String),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) enum Comparator {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+impl Expr {
+    /// Compile a predicate nested `depth` levels into the selector. Every group and negation
+    /// nests a level.
+    pub(super) fn compile(pair: Pair<Rule>, depth: usize) -> Result<Self, String> {
+        match pair.as_rule() {
+            Rule::predicate => Ok(Expr::Or(Expr::compile_all(pair, nested(depth, 1)?)?)),
+            Rule::and_expr => Ok(Expr::And(Expr::compile_all(pair, depth)?)),
+            Rule::negation => match pair.into_inner().next() {
+                Some(inner) => Ok(Expr::Not(Box::new(Expr::compile(inner, nested(depth, 1)?)?))),
+                None => Err("Expected expression after '!'".to_string()),
+            },
+            Rule::comparison => {
+                let mut inner = pair.into_inner();
+                let lhs = match inner.next() {
+                    Some(lhs) => Operand::compile(lhs)?,
+                    None => return Err("Expected operand in predicate".to_string()),
+                };
+                match (inner.next(), inner.next()) {
+                    (Some(op), Some(rhs)) => Ok(Expr::Compare(
+                        lhs,
+                        Comparator::compile(op.as_str())?,
+                        Operand::compile(rhs)?,
+                    )),
+                    _ => Ok(Expr::Test(lhs)),
+                }
+            }
+            _ => Err(format!("Invalid predicate {}", pair.as_str())),
+        }
+    }
+
+    fn compile_all(pair: Pair<Rule>, depth: usize) -> Result<Vec<Self>, String> {
+        let mut exprs = vec![];
+        for inner in pair.into_inner() {
+            exprs.push(Expr::compile(inner, depth)?);
+        }
+        Ok(exprs)
+    }
+
+    /// Evaluate the expression against a single value, which is `@` in the expression
+    pub(super) fn matches<T: UnstructuredDataTrait>(&self, current: &Unstructured<T>) -> bool {
+        match self {
+            Expr::Or(exprs) => exprs.iter().any(|e| e.matches(current)),
+            Expr::And(exprs) => exprs.iter().all(|e| e.matches(current)),
+            Expr::Not(expr) => !expr.matches(current),
+            Expr::Test(operand) => operand.resolve(current).is_some_and(|v| truthy(&v)),
+            Expr::Compare(lhs, op, rhs) => {
+                match (lhs.resolve(current), rhs.resolve(current)) {
+                    (Some(lhs), Some(rhs)) => op.apply(compare(&lhs, &rhs)),
+                    _ => *op == Comparator::Ne,
+                }
+            }
+        }
+    }
+}
+
+impl Operand {
+    fn compile(pair: Pair<Rule>) -> Result<Self, String> {
+        Ok(match pair.as_rule() {
+            Rule::current => Operand::Current(
+                pair.into_inner()
+                    .map(|segment| match segment.as_rule() {
+                        Rule::index => segment
+                            .as_str()
+                            .parse::<usize>()
+                            .map(Segment::Index)
+                            .map_err(|e| format!("Parse failure: {}!", e)),
+                        _ => parse_key(&segment).map(|key| Segment::Key(key.into_owned())),
+                    })
+                    .collect::<Result<_, _>>()?,
+            ),
+            Rule::chars => Operand::String(unescape(pair.as_str())?),
+            Rule::number => Operand::Number(parse_number(pair.as_str())?),
+            Rule::boolean => Operand::Bool(pair.as_str() == "true"),
+            Rule::null => Operand::Null,
+            _ => return Err(format!("Invalid operand {}", pair.as_str())),
+        })
+    }
+
+    /// Resolve the operand to a value, or None if it refers to a path that doesn't exist
+    fn resolve<'a, T: UnstructuredDataTrait>(
+        &self,
+        current: &'a Unstructured<T>,
+    ) -> Option<Cow<'a, Unstructured<T>>> {
+        Some(match self {
+            Operand::Current(path) => Cow::Borrowed(
+                path.iter()
+                    .try_fold(current, |node, segment| segment.child(node))?,
+            ),
+            Operand::Null => Cow::Owned(Unstructured::<T>::Null),
+            Operand::Bool(b) => Cow::Owned((*b).into()),
+            Operand::Number(n) => Cow::Owned(n.clone().into()),
+            Operand::String(s) => Cow::Owned(s.as_str().into()),
+        })
+    }
+}
+
+impl Comparator {
+    fn compile(op: &str) -> Result<Self, String> {
+        Ok(match op {
+            "==" => Comparator::Eq,
+            "!=" => Comparator::Ne,
+            "<" => Comparator::Lt,
+            "<=" => Comparator::Le,
+            ">" => Comparator::Gt,
+            ">=" => Comparator::Ge,
+            other => return Err(format!("Unknown comparison operator {}", other)),
+        })
+    }
+
+    fn apply(self, ordering: Option<Ordering>) -> bool {
+        match self {
+            Comparator::Eq => ordering == Some(Ordering::Equal),
+            Comparator::Ne => ordering != Some(Ordering::Equal),
+            Comparator::Lt => ordering == Some(Ordering::Less),
+            Comparator::Le => matches!(ordering, Some(Ordering::Less | Ordering::Equal)),
+            Comparator::Gt => ordering == Some(Ordering::Greater),
+            Comparator::Ge => matches!(ordering, Some(Ordering::Greater | Ordering::Equal)),
+        }
+    }
+}
+
+fn parse_number(s: &str) -> Result<Number, String> {
+    let number = if s.contains(['.', 'e', 'E']) {
+        s.parse::<f64>().map(Number::from).map_err(|e| e.to_string())
+    } else if s.starts_with('-') {
+        s.parse::<i64>().map(Number::from).map_err(|e| e.to_string())
+    } else {
+        s.parse::<u64>().map(Number::from).map_err(|e| e.to_string())
+    };
+    number.map_err(|e| format!("Invalid number {} in predicate: {}", s, e))
+}
+
+fn truthy<T: UnstructuredDataTrait>(val: &Unstructured<T>) -> bool {
+    !matches!(
+        val,
+        Unstructured::<T>::Null | Unstructured::<T>::Unassigned | Unstructured::<T>::Bool(false)
+    )
+}
+
+/// Numbers are compared by value regardless of their type, other values only compare with
+/// values of the same type
+fn compare<T: UnstructuredDataTrait>(
+    lhs: &Unstructured<T>,
+    rhs: &Unstructured<T>,
+) -> Option<Ordering> {
+    match (lhs, rhs) {
+        (Unstructured::<T>::Number(l), Unstructured::<T>::Number(r)) => {
+            if l.is_float() || r.is_float() {
+                f64::from(l).partial_cmp(&f64::from(r))
+            } else if l.is_signed() || r.is_signed() {
+                Some(i128::from(l).cmp(&i128::from(r)))
+            } else {
+                Some(u128::from(l).cmp(&u128::from(r)))
+            }
+        }
+        (Unstructured::<T>::String(l), Unstructured::<T>::String(r)) => Some(l.cmp(r)),
+        (Unstructured::<T>::Bool(l), Unstructured::<T>::Bool(r)) => Some(l.cmp(r)),
+        (l, r) if l == r => Some(Ordering::Equal),
+        _ => None,
+    }
+}
@@ -0,0 +1,105 @@
+use super::compiled::ExtractError;
+use crate::*;
+use std::collections::HashSet;
+
+/// What [`Unstructured::redact`] replaces matched values with
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ReplaceWith {
+    /// A fixed string such as `"***"`
+    Placeholder(String),
+    /// A hex HMAC-SHA256 of the value's [canonical hash](Unstructured::canonical_hash)
+    /// encoding under the given secret key, so equal values can still be correlated by anyone
+    /// holding the same key without the values being guessable from their digests.
+    #[cfg(feature = "hash")]
+    Hash(Vec<u8>),
+    /// Remove the value from its map or sequence
+    Remove,
+}
+
+impl<T: UnstructuredDataTrait> Unstructured<T> {
+    /// Replace every value matching any of the selectors, returning how many were replaced. A
+    /// value inside another matched value is replaced along with it and isn't counted again.
+    /// Nothing is changed if a selector is not valid.
+    ///
+    /// ```
+    /// use unstructured::{Document, ReplaceWith};
+    ///
+    /// let mut doc: Document = serde_json::from_str(
+    ///     r#"{"user": "a", "auth": {"password": "p", "token": "t"}, "keys": ["k1", "k2"]}"#,
+    /// )
+    /// .unwrap();
+    /// let stars = ReplaceWith::Placeholder("***".into());
+    /// assert_eq!(doc.redact(&["..password", ".auth.token"], stars).unwrap(), 2);
+    /// assert_eq!(doc["auth"]["password"], "***");
+    /// assert_eq!(doc.redact(&[".auth", ".auth.token"], ReplaceWith::Remove).unwrap(), 1);
+    /// doc.redact(&[".keys[0]"], ReplaceWith::Remove).unwrap();
+    /// assert_eq!(doc["keys"], Document::from(["k2"]));
+    /// ```
+    pub fn redact(&mut self, selectors: &[&str], with: ReplaceWith) -> Result<usize, ExtractError> {
+        let selectors = selectors
+            .iter()
+            .map(|sel| ExtractError::compile(sel))
+            .collect::<Result<Vec<_>, _>>()?;
+        let matched: HashSet<Path> = selectors
+            .iter()
+            .flat_map(|sel| sel.select_paths(self))
+            .collect();
+        // Parents come before their children and a subtree is walked in one go, so anything
+        // under the last kept path is part of a value that is already replaced as a whole. The
+        // contents of a wrapper have the same path as the wrapper and are skipped the same way.
+        let mut paths: Vec<Path> = vec![];
+        for (path, _) in self.walk() {
+            if !matched.contains(&path) {
+                continue;
+            }
+            let nested = paths
+                .last()
+                .is_some_and(|kept| path.segments().starts_with(kept.segments()));
+            if !nested {
+                paths.push(path);
+            }
+        }
+
+        let mut redacted = 0;
+        if with == ReplaceWith::Remove {
+            // Later elements of a sequence are removed first so earlier indices stay valid
+            for path in paths.iter().rev() {
+                let removed = if path.is_empty() {
+                    Some(self.take())
+                } else {
+                    self.remove_at(path)
+                };
+                redacted += removed.is_some() as usize;
+            }
+            return Ok(redacted);
+        }
+        for path in paths.iter() {
+            if let Some(node) = self.get_at_mut(path) {
+                *node = match &with {
+                    ReplaceWith::Placeholder(placeholder) => placeholder.as_str().into(),
+                    #[cfg(feature = "hash")]
+                    ReplaceWith::Hash(key) => digest(key, node).into(),
+                    ReplaceWith::Remove => unreachable!(),
+                };
+                redacted += 1;
+            }
+        }
+        Ok(redacted)
+    }
+}
+
+/// Hex HMAC-SHA256 of the value's canonical encoding
+#[cfg(feature = "hash")]
+fn digest<T: UnstructuredDataTrait>(key: &[u8], val: &Unstructured<T>) -> String {
+    use hmac::{Hmac, Mac};
+
+    let mut mac =
+        Hmac::<sha2::Sha256>::new_from_slice(key).expect("HMAC accepts keys of any length");
+    val.encode_canonical(&mut mac);
+    mac.finalize()
+        .into_bytes()
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
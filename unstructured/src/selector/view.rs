@@ -0,0 +1,82 @@
+use super::compiled::ExtractError;
+use crate::de::DeserializerError;
+use crate::ser::{Serializer, SerializerError};
+use crate::*;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::error::Error;
+use std::fmt;
+
+/// Error returned by [`Unstructured::view_as`] and [`Unstructured::splice`]
+#[derive(Debug)]
+pub enum ViewError {
+    Selector(ExtractError),
+    Deserialize(DeserializerError),
+    Serialize(SerializerError),
+}
+
+impl fmt::Display for ViewError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ViewError::Selector(e) => e.fmt(f),
+            ViewError::Deserialize(e) => write!(f, "Selected value has the wrong shape: {}", e),
+            ViewError::Serialize(e) => write!(f, "Value could not be serialized: {}", e),
+        }
+    }
+}
+
+impl Error for ViewError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            ViewError::Selector(e) => Some(e),
+            ViewError::Deserialize(e) => Some(e),
+            ViewError::Serialize(e) => Some(e),
+        }
+    }
+}
+
+impl<T: UnstructuredDataTrait> Unstructured<T> {
+    /// Select a value and deserialize it into `Q`, cloning only the selected value. A missing
+    /// key selects `Unassigned`, which can be read into an `Option`.
+    ///
+    /// ```
+    /// use unstructured::Document;
+    ///
+    /// #[derive(serde::Deserialize)]
+    /// struct Server {
+    ///     host: String,
+    ///     port: u16,
+    /// }
+    ///
+    /// let doc: Document =
+    ///     serde_json::from_str(r#"{"servers": [{"host": "a", "port": 80}]}"#).unwrap();
+    /// let server: Server = doc.view_as(".servers[0]").unwrap();
+    /// assert_eq!((server.host.as_str(), server.port), ("a", 80));
+    /// assert_eq!(doc.view_as::<Option<u16>>(".timeout").unwrap(), None);
+    /// ```
+    pub fn view_as<Q: DeserializeOwned>(&self, sel: &str) -> Result<Q, ViewError> {
+        let selected = self
+            .select(sel)
+            .map_err(|e| ViewError::Selector(ExtractError::new(sel, e)))?;
+        Q::deserialize(selected.clone()).map_err(ViewError::Deserialize)
+    }
+
+    /// Serialize a value and store it at the selector, replacing what was there. Missing maps
+    /// and sequence elements along the way are created.
+    ///
+    /// ```
+    /// use unstructured::Document;
+    ///
+    /// let mut doc: Document = serde_json::from_str(r#"{"servers": []}"#).unwrap();
+    /// doc.splice(".servers[0].ports", vec![80, 443]).unwrap();
+    /// assert_eq!(doc["servers"][0]["ports"][1], 443);
+    /// ```
+    pub fn splice<Q: Serialize>(&mut self, sel: &str, value: Q) -> Result<(), ViewError> {
+        let value = value.serialize(Serializer::new()).map_err(ViewError::Serialize)?;
+        let target = self
+            .select_mut(sel)
+            .map_err(|e| ViewError::Selector(ExtractError::new(sel, e)))?;
+        *target = value;
+        Ok(())
+    }
+}
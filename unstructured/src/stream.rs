@@ -0,0 +1,230 @@
+use crate::*;
+use futures_core::Stream;
+use std::error::Error;
+use std::fmt;
+use std::marker::PhantomData;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+/// How documents are delimited within a byte stream
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Framing {
+    /// Newline delimited JSON, blank lines are skipped
+    Ndjson,
+    /// MessagePack documents, each prefixed by a big-endian u32 byte length
+    LengthPrefixedMsgpack,
+}
+
+#[derive(Debug)]
+pub enum StreamError<E> {
+    Source(E),
+    Json(serde_json::Error),
+    Msgpack(rmp_serde::decode::Error),
+    Truncated(usize),
+    /// A frame was longer than the stream's `max_frame_len`, the stream ends after this error
+    FrameTooLarge(usize),
+}
+
+impl<E: fmt::Display> fmt::Display for StreamError<E> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            StreamError::Source(e) => write!(f, "Source stream error: {}", e),
+            StreamError::Json(e) => write!(f, "Invalid JSON document: {}", e),
+            StreamError::Msgpack(e) => write!(f, "Invalid MessagePack document: {}", e),
+            StreamError::Truncated(len) => {
+                write!(f, "Stream ended with {} bytes of an incomplete frame", len)
+            }
+            StreamError::FrameTooLarge(len) => {
+                write!(f, "Frame of at least {} bytes exceeds the maximum frame length", len)
+            }
+        }
+    }
+}
+
+impl<E: Error + 'static> Error for StreamError<E> {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            StreamError::Source(e) => Some(e),
+            StreamError::Json(e) => Some(e),
+            StreamError::Msgpack(e) => Some(e),
+            StreamError::Truncated(_) | StreamError::FrameTooLarge(_) => None,
+        }
+    }
+}
+
+/// The default for [`DocumentStream::max_frame_len`], 16 MiB
+pub const DEFAULT_MAX_FRAME_LEN: usize = 16 * 1024 * 1024;
+
+/// Adapts a stream of byte chunks into a stream of documents.
+///
+/// Chunks are only pulled from the inner stream once all complete frames already buffered have
+/// been yielded, so a slow consumer applies backpressure all the way to the source. Frames
+/// longer than [`max_frame_len`](DocumentStream::max_frame_len) end the stream with
+/// [`StreamError::FrameTooLarge`] rather than being buffered.
+///
+/// ```
+/// use futures::{executor::block_on, stream, StreamExt};
+/// use unstructured::DocumentStream;
+///
+/// let chunks = stream::iter(vec![
+///     Ok::<_, std::io::Error>(b"{\"a\": 1}\n{\"a\"".to_vec()),
+///     Ok(b": 2}\n".to_vec()),
+/// ]);
+/// let docs: Vec<_> = block_on(DocumentStream::ndjson(chunks).collect());
+/// assert_eq!(docs.len(), 2);
+/// assert_eq!(docs[1].as_ref().unwrap()["a"], 2);
+/// ```
+pub struct DocumentStream<S, T: UnstructuredDataTrait = UnstructuredType> {
+    inner: S,
+    framing: Framing,
+    buffer: Vec<u8>,
+    /// Where the next frame starts in the buffer, everything before it has been yielded
+    start: usize,
+    /// How far the buffer has been searched for the end of the next newline delimited frame
+    scanned: usize,
+    max_frame_len: usize,
+    done: bool,
+    _type: PhantomData<T>,
+}
+
+impl<S> DocumentStream<S> {
+    pub fn ndjson(inner: S) -> Self {
+        Self::new(inner, Framing::Ndjson)
+    }
+
+    pub fn msgpack(inner: S) -> Self {
+        Self::new(inner, Framing::LengthPrefixedMsgpack)
+    }
+}
+
+impl<S, T: UnstructuredDataTrait> DocumentStream<S, T> {
+    pub fn new(inner: S, framing: Framing) -> Self {
+        DocumentStream {
+            inner,
+            framing,
+            buffer: vec![],
+            start: 0,
+            scanned: 0,
+            max_frame_len: DEFAULT_MAX_FRAME_LEN,
+            done: false,
+            _type: PhantomData,
+        }
+    }
+
+    /// Set the longest frame that will be buffered, in bytes. For MessagePack this is checked
+    /// against the length prefix, so an oversized frame is rejected before it is read.
+    pub fn max_frame_len(mut self, max_frame_len: usize) -> Self {
+        self.max_frame_len = max_frame_len;
+        self
+    }
+
+    pub fn into_inner(self) -> S {
+        self.inner
+    }
+
+    fn next_frame<E>(&mut self) -> Option<Result<Unstructured<T>, StreamError<E>>> {
+        match self.framing {
+            Framing::Ndjson => loop {
+                // Only the bytes received since the last search are searched for a newline
+                let end = match self.buffer[self.scanned..].iter().position(|b| *b == b'\n') {
+                    Some(newline) => self.scanned + newline + 1,
+                    None => {
+                        self.scanned = self.buffer.len();
+                        let pending = self.buffer.len() - self.start;
+                        if pending > self.max_frame_len {
+                            return Some(Err(self.too_large(pending)));
+                        }
+                        if !self.done || pending == 0 {
+                            return None;
+                        }
+                        self.buffer.len()
+                    }
+                };
+                let line = self.start..end;
+                self.start = end;
+                self.scanned = end;
+                if line.len() > self.max_frame_len {
+                    return Some(Err(self.too_large(line.len())));
+                }
+                let line = &self.buffer[line];
+                if line.iter().all(|b| b.is_ascii_whitespace()) {
+                    continue;
+                }
+                return Some(serde_json::from_slice(line).map_err(StreamError::Json));
+            },
+            Framing::LengthPrefixedMsgpack => {
+                let pending = self.buffer.len() - self.start;
+                if pending >= 4 {
+                    let mut prefix = [0u8; 4];
+                    prefix.copy_from_slice(&self.buffer[self.start..self.start + 4]);
+                    let len = u32::from_be_bytes(prefix) as usize;
+                    if len > self.max_frame_len {
+                        return Some(Err(self.too_large(len)));
+                    }
+                    if pending - 4 >= len {
+                        let frame = self.start + 4..self.start + 4 + len;
+                        self.start = frame.end;
+                        let frame = &self.buffer[frame];
+                        return Some(rmp_serde::from_slice(frame).map_err(StreamError::Msgpack));
+                    }
+                }
+                if self.done && pending > 0 {
+                    self.start = self.buffer.len();
+                    return Some(Err(StreamError::Truncated(pending)));
+                }
+                None
+            }
+        }
+    }
+
+    /// Drop the frames that have been yielded from the buffer. This is done once before every
+    /// chunk is added rather than after every frame, so the rest of the buffer isn't moved
+    /// again for each frame in it.
+    fn compact(&mut self) {
+        self.buffer.drain(..self.start);
+        self.scanned = self.scanned.saturating_sub(self.start);
+        self.start = 0;
+    }
+
+    /// Give up on the stream after a frame that is too long, there is no way to find where
+    /// the next one starts without buffering this one
+    fn too_large<E>(&mut self, len: usize) -> StreamError<E> {
+        self.buffer = vec![];
+        self.start = 0;
+        self.scanned = 0;
+        self.done = true;
+        StreamError::FrameTooLarge(len)
+    }
+}
+
+impl<S, B, E, T> Stream for DocumentStream<S, T>
+where
+    S: Stream<Item = Result<B, E>> + Unpin,
+    B: AsRef<[u8]>,
+    T: UnstructuredDataTrait,
+{
+    type Item = Result<Unstructured<T>, StreamError<E>>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+        loop {
+            if let Some(item) = this.next_frame() {
+                return Poll::Ready(Some(item));
+            }
+            if this.done {
+                return Poll::Ready(None);
+            }
+            match Pin::new(&mut this.inner).poll_next(cx) {
+                Poll::Ready(Some(Ok(chunk))) => {
+                    this.compact();
+                    this.buffer.extend_from_slice(chunk.as_ref());
+                }
+                Poll::Ready(Some(Err(e))) => return Poll::Ready(Some(Err(StreamError::Source(e)))),
+                Poll::Ready(None) => this.done = true,
+                Poll::Pending => return Poll::Pending,
+            }
+        }
+    }
+}
+
+impl<S: Unpin, T: UnstructuredDataTrait> Unpin for DocumentStream<S, T> {}
@@ -36,7 +36,7 @@ fn deserialize_inside_deserialize_impl() {
         }
     }
 
-    let input = Document::Map(
+    let input = Document::Map(share(
         vec![
             (
                 Document::String("kind".to_owned()),
@@ -46,11 +46,11 @@ fn deserialize_inside_deserialize_impl() {
         ]
         .into_iter()
         .collect(),
-    );
+    ));
     let event = Event::deserialize(input).expect("could not deserialize ADDED event");
     assert_eq!(event, Event::Added(5));
 
-    let input = Document::Map(
+    let input = Document::Map(share(
         vec![
             (
                 Document::String("kind".to_owned()),
@@ -60,11 +60,11 @@ fn deserialize_inside_deserialize_impl() {
         ]
         .into_iter()
         .collect(),
-    );
+    ));
     let event = Event::deserialize(input).expect("could not deserialize ERROR event");
     assert_eq!(event, Event::Error(5));
 
-    let input = Document::Map(
+    let input = Document::Map(share(
         vec![
             (
                 Document::String("kind".to_owned()),
@@ -74,6 +74,6 @@ fn deserialize_inside_deserialize_impl() {
         ]
         .into_iter()
         .collect(),
-    );
+    ));
     let _ = Event::deserialize(input).expect_err("expected deserializing bad ADDED event to fail");
 }
@@ -0,0 +1,56 @@
+use crate::*;
+use tracing::field::{display, Empty};
+use tracing::{Span, Value};
+
+impl<T: UnstructuredDataTrait> Unstructured<T> {
+    /// A value for recording this document as a field on a tracing span or event. Booleans,
+    /// numbers and strings are recorded as themselves, `Null` records nothing and anything else
+    /// is recorded as a JSON string
+    ///
+    /// ```
+    /// use unstructured::Document;
+    ///
+    /// let doc: Document = serde_json::from_str(r#"{"user": "x", "ids": [1, 2]}"#).unwrap();
+    /// tracing::info!(user = doc["user"].as_trace_value(), ids = doc["ids"].as_trace_value());
+    /// ```
+    pub fn as_trace_value(&self) -> Box<dyn Value + '_> {
+        match self {
+            Self::Null | Self::Unassigned | Self::Option(None) => Box::new(Empty),
+            Self::Bool(b) => Box::new(*b),
+            Self::String(s) => Box::new(s.as_str()),
+            Self::Char(c) => Box::new(display(*c)),
+            Self::Number(n) => match (n.as_i64(), n.as_u64()) {
+                (Some(i), _) => Box::new(i),
+                (_, Some(u)) => Box::new(u),
+                _ if n.is_float() => Box::new(f64::from(n)),
+                _ => Box::new(display(n.to_string())),
+            },
+            Self::Newtype(v) | Self::Option(Some(v)) => v.as_trace_value(),
+            doc => {
+                let json = serde_json::to_string(doc).unwrap_or_else(|_| doc.to_string());
+                Box::new(display(json))
+            }
+        }
+    }
+
+    /// Record the entries of a map onto `span`, one field per string key. Only keys the span
+    /// declared are recorded, e.g. with `field = tracing::field::Empty`, as tracing fields are
+    /// fixed when the span is created
+    ///
+    /// ```
+    /// use unstructured::Document;
+    ///
+    /// let doc: Document = serde_json::from_str(r#"{"user": "x", "extra": 1}"#).unwrap();
+    /// let span = tracing::info_span!("request", user = tracing::field::Empty);
+    /// doc.record_fields(&span);
+    /// ```
+    pub fn record_fields(&self, span: &Span) {
+        if let Self::Map(map) = self {
+            for (key, val) in map.iter() {
+                if let Self::String(key) = key {
+                    span.record(key.as_str(), val.as_trace_value());
+                }
+            }
+        }
+    }
+}
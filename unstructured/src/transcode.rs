@@ -0,0 +1,240 @@
+use crate::*;
+use std::error::Error;
+use std::fmt;
+use std::io::{Read, Write};
+
+/// A serialization format supported by [`transcode`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Json,
+    Msgpack,
+}
+
+impl fmt::Display for Format {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Format::Json => f.write_str("JSON"),
+            Format::Msgpack => f.write_str("MessagePack"),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum TranscodeError {
+    Json(serde_json::Error),
+    MsgpackDecode(rmp_serde::decode::Error),
+    MsgpackEncode(rmp_serde::encode::Error),
+}
+
+impl fmt::Display for TranscodeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TranscodeError::Json(e) => write!(f, "JSON transcoding failed: {}", e),
+            TranscodeError::MsgpackDecode(e) => write!(f, "Invalid MessagePack input: {}", e),
+            TranscodeError::MsgpackEncode(e) => write!(f, "MessagePack output failed: {}", e),
+        }
+    }
+}
+
+impl Error for TranscodeError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            TranscodeError::Json(e) => Some(e),
+            TranscodeError::MsgpackDecode(e) => Some(e),
+            TranscodeError::MsgpackEncode(e) => Some(e),
+        }
+    }
+}
+
+impl From<serde_json::Error> for TranscodeError {
+    fn from(e: serde_json::Error) -> Self {
+        TranscodeError::Json(e)
+    }
+}
+
+impl From<rmp_serde::decode::Error> for TranscodeError {
+    fn from(e: rmp_serde::decode::Error) -> Self {
+        TranscodeError::MsgpackDecode(e)
+    }
+}
+
+impl From<rmp_serde::encode::Error> for TranscodeError {
+    fn from(e: rmp_serde::encode::Error) -> Self {
+        TranscodeError::MsgpackEncode(e)
+    }
+}
+
+/// Convert a single value from one format to another without building a [`Document`] in
+/// between, values are streamed from the reader to the writer as they are parsed. Use this
+/// when no filtering or selection is needed.
+///
+/// ```
+/// use unstructured::{transcode, Format};
+///
+/// let mut packed = vec![];
+/// transcode(&br#"{"a": [1, 2]}"#[..], Format::Json, &mut packed, Format::Msgpack).unwrap();
+/// let mut json = vec![];
+/// transcode(&packed[..], Format::Msgpack, &mut json, Format::Json).unwrap();
+/// assert_eq!(json, br#"{"a":[1,2]}"#);
+/// ```
+///
+/// [`Document`]: crate::Document
+pub fn transcode<R: Read, W: Write>(
+    reader: R,
+    from: Format,
+    writer: W,
+    to: Format,
+) -> Result<(), TranscodeError> {
+    match from {
+        Format::Json => {
+            let mut de = serde_json::Deserializer::from_reader(reader);
+            serialize_to(&mut de, writer, to)?;
+            de.end()?;
+        }
+        Format::Msgpack => {
+            let mut de = rmp_serde::Deserializer::new(reader);
+            serialize_to(&mut de, writer, to)?;
+        }
+    }
+    Ok(())
+}
+
+fn serialize_to<'de, D, W>(de: D, writer: W, to: Format) -> Result<(), TranscodeError>
+where
+    D: serde::Deserializer<'de>,
+    W: Write,
+{
+    match to {
+        Format::Json => {
+            let mut ser = serde_json::Serializer::new(writer);
+            serde_transcode::transcode(de, &mut ser)?;
+        }
+        Format::Msgpack => {
+            let mut ser = rmp_serde::Serializer::new(writer).with_struct_map();
+            serde_transcode::transcode(de, &mut ser)?;
+        }
+    }
+    Ok(())
+}
+
+/// The differences found by [`Unstructured::roundtrips_via`]
+#[derive(Debug)]
+pub struct RoundTripDiff {
+    pub format: Format,
+    /// Paths of the values that were changed, dropped or added by the round trip
+    pub paths: Vec<Path>,
+    /// Set if the document could not be written or read back at all
+    pub error: Option<TranscodeError>,
+}
+
+impl fmt::Display for RoundTripDiff {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match &self.error {
+            Some(e) => write!(f, "Round trip through {} failed: {}", self.format, e),
+            None => {
+                let paths: Vec<String> = self
+                    .paths
+                    .iter()
+                    .map(|path| match path.is_empty() {
+                        true => "/".to_string(),
+                        false => path.to_string(),
+                    })
+                    .collect();
+                write!(f, "Round trip through {} changed {}", self.format, paths.join(", "))
+            }
+        }
+    }
+}
+
+impl Error for RoundTripDiff {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        self.error.as_ref().map(|e| e as &(dyn Error + 'static))
+    }
+}
+
+impl<T: UnstructuredDataTrait> Unstructured<T> {
+    /// Write the document in the given format, read it back and compare the result with the
+    /// original, so that a conversion can be refused rather than silently losing data. Numbers
+    /// are compared by value, so widening a `u8` to a `u64` is not a difference, but a `char`
+    /// read back as a string, bytes read back as a sequence or a map key converted to a string
+    /// all are.
+    ///
+    /// ```
+    /// use unstructured::{Document, Format};
+    ///
+    /// let doc: Document = serde_json::from_str(r#"{"a": [1, 2.5, "x"]}"#).unwrap();
+    /// assert!(doc.roundtrips_via(Format::Json).is_ok());
+    ///
+    /// let mut doc = doc;
+    /// doc["a"][2] = Document::Char('x');
+    /// doc["b"] = Document::Bytes(vec![1, 2]);
+    /// let diff = doc.roundtrips_via(Format::Json).unwrap_err();
+    /// assert_eq!(diff.to_string(), "Round trip through JSON changed /a/2, /b");
+    /// ```
+    pub fn roundtrips_via(&self, format: Format) -> Result<(), RoundTripDiff> {
+        let failed = |error| RoundTripDiff {
+            format,
+            paths: vec![],
+            error: Some(error),
+        };
+        let parsed: Self = match format {
+            Format::Json => serde_json::to_vec(self)
+                .and_then(|bytes| serde_json::from_slice(&bytes))
+                .map_err(|e| failed(e.into()))?,
+            Format::Msgpack => {
+                let bytes = rmp_serde::to_vec_named(self).map_err(|e| failed(e.into()))?;
+                rmp_serde::from_slice(&bytes).map_err(|e| failed(e.into()))?
+            }
+        };
+        let mut paths = vec![];
+        diff(self, &parsed, &mut Path::new(), &mut paths);
+        if paths.is_empty() {
+            Ok(())
+        } else {
+            Err(RoundTripDiff {
+                format,
+                paths,
+                error: None,
+            })
+        }
+    }
+}
+
+/// Collect the paths where two documents differ
+fn diff<T: UnstructuredDataTrait>(
+    original: &Unstructured<T>,
+    parsed: &Unstructured<T>,
+    path: &mut Path,
+    paths: &mut Vec<Path>,
+) {
+    match (original, parsed) {
+        (Unstructured::<T>::Map(l), Unstructured::<T>::Map(r)) => {
+            for (key, val) in l.iter() {
+                path.push(Segment::from_key(key));
+                match r.get(key) {
+                    Some(other) => diff(val, other, path, paths),
+                    None => paths.push(path.clone()),
+                }
+                path.pop();
+            }
+            for key in r.keys().filter(|key| !l.contains_key(*key)) {
+                let added = path.join(Segment::from_key(key));
+                if !paths.contains(&added) {
+                    paths.push(added);
+                }
+            }
+        }
+        (Unstructured::<T>::Seq(l), Unstructured::<T>::Seq(r)) => {
+            for i in 0..l.len().max(r.len()) {
+                path.push(i);
+                match (l.get(i), r.get(i)) {
+                    (Some(val), Some(other)) => diff(val, other, path, paths),
+                    _ => paths.push(path.clone()),
+                }
+                path.pop();
+            }
+        }
+        (l, r) if l != r => paths.push(path.clone()),
+        _ => {}
+    }
+}
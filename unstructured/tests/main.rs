@@ -1,11 +1,15 @@
+// Seq and Map contents are converted with `.into()` so the tests also build with the shared
+// feature, where they are reference counted
+#![allow(clippy::useless_conversion)]
+
 use serde::{Deserialize, Serialize};
 use unstructured::*;
 
 #[test]
 fn numeric_indexing_test() {
-    let doc = Document::Seq(vec![1u64.into(), 2u64.into(), 3u64.into()]);
+    let doc = Document::Seq(vec![1u64.into(), 2u64.into(), 3u64.into()].into());
     assert_eq!(doc[1], Document::Number(Number::U64(2)));
-    assert_eq!(doc[100][9999], Document::Null);
+    assert_eq!(doc[100][9999], Document::Unassigned);
 }
 
 const MERGE1: &str = r#"{
@@ -78,8 +82,8 @@ fn map_indexing_test() {
     let doc: Document = map.into();
     println!("{}", doc["test"] == Document::Number(Number::U64(100)));
     assert_eq!(doc["test"], Document::Number(Number::U64(100)));
-    assert_eq!(doc["test-not-exist"], Document::Null);
-    assert_eq!(doc[100][9999], Document::Null);
+    assert_eq!(doc["test-not-exist"], Document::Unassigned);
+    assert_eq!(doc[100][9999], Document::Unassigned);
 }
 
 #[test]
@@ -87,8 +91,8 @@ fn index_dynamic_mod_test() {
     let mut doc = Document::default();
     doc["test"] = 100u64.into();
     assert_eq!(doc["test"], 100u64);
-    assert_eq!(doc["test-not-exist"], Document::Null);
-    assert_eq!(doc[100][9999], Document::Null);
+    assert_eq!(doc["test-not-exist"], Document::Unassigned);
+    assert_eq!(doc[100][9999], Document::Unassigned);
 }
 
 #[test]
@@ -108,9 +112,10 @@ fn de_smoke_test() {
                 ),
             ]
             .into_iter()
-            .collect(),
+            .collect::<Mapping<_>>()
+            .into(),
         ),
-    ]))));
+    ].into()))));
 
     // assert that the Document: remains unchanged through deserialization
     let document_de = Document::deserialize(document.clone()).unwrap();
@@ -141,11 +146,12 @@ fn ser_smoke_test() {
             ),
             (
                 Document::String("c".into()),
-                Document::Seq(vec![Document::Bool(true), Document::Bool(false)]),
+                Document::Seq(vec![Document::Bool(true), Document::Bool(false)].into()),
             ),
         ]
         .into_iter()
-        .collect(),
+        .collect::<Mapping<_>>()
+        .into(),
     );
 
     let document = Document::new(&f).unwrap();
@@ -163,7 +169,8 @@ fn deserialize_into_enum() {
     let document = Document::String("Bar".into());
     assert_eq!(Foo::deserialize(document).unwrap(), Foo::Bar);
 
-    let document = Document::Map(vec![("Baz".into(), 1u8.into())].into_iter().collect());
+    let document =
+        Document::Map(vec![("Baz".into(), 1u8.into())].into_iter().collect::<Mapping<_>>().into());
     assert_eq!(Foo::deserialize(document).unwrap(), Foo::Baz(1));
 }
 
@@ -214,8 +221,3678 @@ fn deserialize_newtype2() {
     let input = Document::Map(
         vec![(Document::String("foo".to_owned()), 5i32.into())]
             .into_iter()
-            .collect(),
+            .collect::<Mapping<_>>()
+            .into(),
     );
     let b = Bar::deserialize(input).unwrap();
     assert_eq!(b, Bar { foo: Foo(5) });
 }
+
+#[cfg(feature = "cache")]
+#[test]
+fn doc_cache_test() {
+    let mut cache = DocCache::new(2);
+    let mut parses = 0;
+    for payload in [&br#"{"a": 1}"#[..], br#"{"a": 1}"#, br#"{"b": 2}"#].iter() {
+        cache
+            .get_or_parse(payload, |src| {
+                parses += 1;
+                serde_json::from_slice::<Document>(src)
+            })
+            .unwrap();
+    }
+    assert_eq!(parses, 2);
+    assert_eq!(cache.len(), 2);
+
+    // Touch the first payload so the second becomes least recently used
+    assert_eq!(cache.get(br#"{"a": 1}"#).unwrap()["a"], 1);
+    cache.insert(b"[]", Document::Seq(Default::default()));
+    assert_eq!(cache.len(), 2);
+    assert!(cache.contains(br#"{"a": 1}"#));
+    assert!(!cache.contains(br#"{"b": 2}"#));
+
+    // Replacing, removing and re-parsing keep the order entries were used in
+    cache.insert(br#"{"a": 1}"#, Document::from(2));
+    cache.insert(b"null", Document::Null);
+    assert!(!cache.contains(b"[]"));
+    assert_eq!(cache.get(br#"{"a": 1}"#), Some(&Document::from(2)));
+    assert_eq!(cache.remove(b"null"), Some(Document::Null));
+    assert_eq!(cache.len(), 1);
+    let failed = cache.get_or_parse(b"{", |src| serde_json::from_slice::<Document>(src));
+    assert!(failed.is_err());
+    assert_eq!(cache.len(), 1);
+    for i in 0..100u64 {
+        let payload = i.to_string();
+        let doc = cache.get_or_parse(payload.as_bytes(), |src| serde_json::from_slice(src));
+        assert_eq!(*doc.unwrap(), i);
+        assert!(cache.len() <= 2);
+    }
+    assert!(cache.contains(b"98") && cache.contains(b"99"));
+}
+
+#[test]
+fn merge_resolve_test() {
+    let mut doc: Document = serde_json::from_str(MERGE1).unwrap();
+    let mut conflicts = vec![];
+    doc.merge_resolve(serde_json::from_str(MERGE2).unwrap(), |path, existing, incoming| {
+        conflicts.push(path.clone());
+        if existing.is_number() || incoming.is_number() {
+            existing.clone()
+        } else {
+            incoming
+        }
+    });
+    // Conflicts are reported in key order, which is insertion order with preserve_order
+    #[cfg(not(feature = "preserve_order"))]
+    assert_eq!(
+        conflicts,
+        vec![
+            Path::from(vec!["other".into(), "array".into()]),
+            Path::from(vec!["other".into(), "key1".into()]),
+            Path::from(vec!["overwrite-me".into()]),
+        ]
+    );
+    #[cfg(feature = "preserve_order")]
+    assert_eq!(
+        conflicts,
+        vec![
+            Path::from(vec!["other".into(), "key1".into()]),
+            Path::from(vec!["other".into(), "array".into()]),
+            Path::from(vec!["overwrite-me".into()]),
+        ]
+    );
+    assert_eq!(doc["other"]["key1"], "val1-appended");
+    assert_eq!(doc["other"]["array"][0], 4);
+    assert_eq!(doc["overwrite-me"], "something");
+    assert_eq!(doc["some-new"], "val-appended");
+}
+
+#[cfg(feature = "stream")]
+#[test]
+fn document_stream_test() {
+    use futures::{executor::block_on, stream, StreamExt};
+
+    let lines = stream::iter(vec![
+        Ok::<_, std::io::Error>(&b"{\"a\": 1}\n\n{\"a\": "[..]),
+        Ok(&b"2}\r\n{\"a\": 3}"[..]),
+    ]);
+    let docs: Vec<_> = block_on(DocumentStream::ndjson(lines).collect());
+    let docs: Vec<Document> = docs.into_iter().map(Result::unwrap).collect();
+    assert_eq!(docs.len(), 3);
+    assert_eq!(docs[2]["a"], 3);
+
+    let mut bytes = vec![];
+    for doc in docs.iter() {
+        let encoded = rmp_serde::to_vec(doc).unwrap();
+        bytes.extend_from_slice(&(encoded.len() as u32).to_be_bytes());
+        bytes.extend_from_slice(&encoded);
+    }
+    bytes.extend_from_slice(&[0, 0]);
+    let chunks = stream::iter(bytes.chunks(3).map(|c| Ok::<_, std::io::Error>(c.to_vec())));
+    let decoded: Vec<_> = block_on(DocumentStream::msgpack(chunks).collect());
+    assert_eq!(decoded.len(), 4);
+    for (doc, expected) in decoded.iter().zip(docs.iter()) {
+        assert_eq!(doc.as_ref().unwrap(), expected);
+    }
+    assert!(matches!(decoded[3], Err(StreamError::Truncated(2))));
+
+    // Oversized frames end the stream instead of being buffered
+    let hostile = stream::iter(vec![Ok::<_, std::io::Error>(vec![0xff, 0xff, 0xff, 0xff, 0])]);
+    let decoded: Vec<_> = block_on(DocumentStream::msgpack(hostile).max_frame_len(1024).collect());
+    assert_eq!(decoded.len(), 1);
+    assert!(matches!(decoded[0], Err(StreamError::FrameTooLarge(0xffff_ffff))));
+    let endless = stream::iter((0..4).map(|_| Ok::<_, std::io::Error>(vec![b'1'; 64])));
+    let decoded: Vec<_> = block_on(DocumentStream::ndjson(endless).max_frame_len(100).collect());
+    assert_eq!(decoded.len(), 1);
+    assert!(matches!(decoded[0], Err(StreamError::FrameTooLarge(128))));
+
+    // Many frames in one chunk and one frame over many chunks are both read in linear time
+    let many = "{\"a\": 1}\n".repeat(200_000).into_bytes();
+    let chunks = stream::iter(vec![Ok::<_, std::io::Error>(many)]);
+    assert_eq!(block_on(DocumentStream::ndjson(chunks).count()), 200_000);
+    let long = format!("[{}0]\n[1]", "0,".repeat(100_000)).into_bytes();
+    let chunks = stream::iter(long.chunks(1).map(|c| Ok::<_, std::io::Error>(c.to_vec())));
+    let decoded: Vec<_> = block_on(DocumentStream::ndjson(chunks).collect());
+    assert_eq!(decoded.len(), 2);
+    assert_eq!(decoded[0].as_ref().unwrap().iter().count(), 100_001);
+    assert_eq!(decoded[1].as_ref().unwrap()[0], 1);
+}
+
+#[test]
+fn merge_checked_test() {
+    let mut doc: Document = serde_json::from_str(MERGE1).unwrap();
+    let mut expected = doc.clone();
+    expected.merge(serde_json::from_str(MERGE2).unwrap());
+
+    let conflicts = doc.merge_checked(serde_json::from_str(MERGE2).unwrap());
+    assert_eq!(doc, expected);
+    assert_eq!(conflicts.len(), 2);
+    assert_eq!(conflicts[0].path, Path::from(vec!["other".into(), "key1".into()]));
+    assert_eq!(conflicts[0].old, "val1");
+    assert_eq!(conflicts[0].new, "val1-appended");
+    assert_eq!(conflicts[1].path, Path::from(vec!["overwrite-me".into()]));
+    assert_eq!(conflicts[1].new, 10);
+}
+
+#[test]
+fn iteration_test() {
+    let mut doc: Document = serde_json::from_str(MERGE1).unwrap();
+    assert_eq!(doc.iter().count(), 3);
+    assert_eq!(doc["other"]["array"].iter().count(), 3);
+    assert_eq!(doc["some"].iter().count(), 0);
+
+    for val in doc["other"]["array"].iter_mut() {
+        *val = (val.clone().cast::<u64>().unwrap() * 10).into();
+    }
+    let values: Vec<Document> = doc["other"]["array"].take().into_iter().collect();
+    assert_eq!(values, vec![Document::from(10u64), 20u64.into(), 30u64.into()]);
+
+    let paths: Vec<Path> = doc.walk().map(|(path, _)| path).collect();
+    assert_eq!(paths[0], Path::new());
+    #[cfg(not(feature = "preserve_order"))]
+    assert_eq!(paths[1], Path::from(vec!["other".into()]));
+    #[cfg(not(feature = "preserve_order"))]
+    assert_eq!(paths[2], Path::from(vec!["other".into(), "array".into()]));
+    #[cfg(feature = "preserve_order")]
+    assert_eq!(paths[1..3], [Path::from(vec!["some".into()]), Path::from(vec!["other".into()])]);
+    assert_eq!(paths.len(), 7);
+}
+
+#[test]
+fn merge_strategy_test() {
+    let base: Document = serde_json::from_str(
+        r#"{"containers": [
+            {"name": "app", "image": "app:1", "ports": [80]},
+            {"name": "sidecar", "image": "proxy:1"},
+            {"image": "anonymous:1"}
+        ]}"#,
+    )
+    .unwrap();
+    let patch: Document = serde_json::from_str(
+        r#"{"containers": [
+            {"name": "app", "image": "app:2", "ports": [443]},
+            {"name": "new", "image": "new:1"},
+            {"image": "anonymous:2"}
+        ]}"#,
+    )
+    .unwrap();
+
+    let mut doc = base.clone();
+    doc.merge_with(patch.clone(), MergeStrategy::SeqMergeBy("name"));
+    let containers = &doc["containers"];
+    assert_eq!(containers.iter().count(), 5);
+    assert_eq!(containers[0]["image"], "app:2");
+    assert_eq!(containers[0]["ports"], Document::Seq(vec![80u64.into(), 443u64.into()].into()));
+    assert_eq!(containers[1]["image"], "proxy:1");
+    assert_eq!(containers[3]["name"], "new");
+    assert_eq!(containers[4]["image"], "anonymous:2");
+
+    // Elements are matched by key without a search per element, and later elements in the
+    // other sequence merge into ones it added
+    let items = |range: std::ops::Range<u64>, field: &str| -> Document {
+        let items = range.map(|i| {
+            let mut item = Document::empty_map();
+            item["id"] = i.into();
+            item[field] = i.into();
+            item
+        });
+        Document::Seq(items.collect::<Vec<_>>().into())
+    };
+    let mut many = items(0..100_000, "old");
+    let mut more = items(50_000..150_000, "new");
+    more.merge(items(149_999..150_000, "again"));
+    many.merge_with(more, MergeStrategy::SeqMergeBy("id"));
+    assert_eq!(many.iter().count(), 150_000);
+    assert_eq!(many[75_000]["old"], 75_000);
+    assert_eq!(many[75_000]["new"], 75_000);
+    assert_eq!(many[149_999]["again"], 149_999);
+
+    let mut doc = base.clone();
+    doc.merge_with(patch.clone(), MergeStrategy::Replace);
+    assert_eq!(doc, patch);
+
+    let mut doc = base.clone();
+    let mut expected = base;
+    doc.merge_with(patch.clone(), MergeStrategy::Append);
+    expected.merge(patch);
+    assert_eq!(doc, expected);
+}
+
+#[test]
+fn typed_path_test() {
+    let pointer = Path::from_pointer("/a~1b/0/c~0d").unwrap();
+    assert_eq!(
+        pointer,
+        Path::from(vec!["a/b".into(), 0.into(), "c~d".into()])
+    );
+    assert_eq!(pointer.to_string(), "/a~1b/0/c~0d");
+    assert_eq!(Path::from_pointer("/01").unwrap(), Path::from(vec!["01".into()]));
+    assert_eq!(Path::from_pointer("").unwrap(), Path::new());
+    assert!(Path::from_pointer("a/b").is_err());
+    assert!(Path::from_pointer("/a~2").is_err());
+
+    let jq = Path::from_jq(r#".a.b[1]["c.d\n"].[2]"#).unwrap();
+    assert_eq!(
+        jq,
+        Path::from(vec!["a".into(), "b".into(), 1.into(), "c.d\n".into(), 2.into()])
+    );
+    assert_eq!(Path::from_jq(".").unwrap(), Path::new());
+    assert!(Path::from_jq(".a[x]").is_err());
+    assert!(Path::from_jq(r#".["a]"#).is_err());
+    assert!(Path::from_jq(".a..b").is_err());
+
+    let mut doc: Document = serde_json::from_str(r#"{"a": [{"b": 1}], "m": {"0": "zero"}}"#).unwrap();
+    assert_eq!(*doc.get_at(&"/a/0/b".parse().unwrap()).unwrap(), 1);
+    assert_eq!(*doc.get_at(&".m[0]".parse().unwrap()).unwrap(), "zero");
+    assert!(doc.get_at(&".a[1]".parse().unwrap()).is_none());
+
+    doc.set_at(&".a[2].c".parse().unwrap(), "new");
+    assert_eq!(doc["a"][1], Document::Null);
+    assert_eq!(doc["a"][2]["c"], "new");
+    *doc.get_at_mut(&".a[0].b".parse().unwrap()).unwrap() = Document::Bool(true);
+    assert_eq!(doc["a"][0]["b"], true);
+
+    assert_eq!(doc.remove_at(&"/a/1".parse().unwrap()), Some(Document::Null));
+    assert_eq!(doc["a"][1]["c"], "new");
+    assert_eq!(doc.remove_at(&"/m/0".parse().unwrap()), Some("zero".into()));
+    assert_eq!(doc.remove_at(&"/nope".parse().unwrap()), None);
+    assert_eq!(doc.remove_at(&Path::new()), None);
+}
+
+#[test]
+fn recursive_descent_test() {
+    let doc: Document = serde_json::from_str(
+        r#"{"id": 1, "items": [{"id": 2, "name": "a"}, {"sub": {"id": 3}}], "other": {"x": {"id": 4}}}"#,
+    )
+    .unwrap();
+    let ids = doc.select_all("..id").unwrap();
+    assert_eq!(ids.len(), 4);
+    assert_eq!(*ids[0], 1);
+    assert_eq!(*ids[3], 4);
+    assert_eq!(doc.select_all(".items..id").unwrap().len(), 2);
+    assert_eq!(doc.select_all(r#"..["name"]"#).unwrap(), vec![&Document::from("a")]);
+    assert_eq!(*doc.select_all(".items.[1]..id").unwrap()[0], 3);
+    assert!(doc.select_all("..missing").unwrap().is_empty());
+    assert_eq!(doc.select_all(".other.x.id").unwrap().len(), 1);
+    assert!(doc.select("..id").is_err());
+
+    // Values reached by overlapping descents are matched once
+    let nested: Document = serde_json::from_str(r#"{"a": {"a": {"b": 1}}, "b": 2}"#).unwrap();
+    assert_eq!(nested.select_all("..a..b").unwrap(), vec![&Document::from(1)]);
+    assert_eq!(nested.select_all("....b").unwrap().len(), 2);
+    let explained = CompiledSelector::compile("..a..b").unwrap().explain(&nested);
+    assert_eq!(explained.steps[3].matched, 1);
+    let mut redacted = nested.clone();
+    assert_eq!(redacted.redact(&["..a..b"], ReplaceWith::Remove).unwrap(), 1);
+    assert_eq!(redacted.select_all("..b").unwrap(), vec![&Document::from(2)]);
+
+    // Descending doesn't recurse, so it is safe for documents of any depth
+    let mut deep = Document::empty_map();
+    deep["z"] = 1.into();
+    for _ in 0..100_000 {
+        let mut parent = Document::empty_map();
+        parent["a"] = deep;
+        deep = parent;
+    }
+    let selector = CompiledSelector::compile("..z").unwrap();
+    assert_eq!(selector.select_all(&deep), vec![&Document::from(1)]);
+    assert_eq!(selector.apply(&deep), Some(&Document::from(1)));
+    *selector.apply_mut(&mut deep).unwrap() = 2.into();
+    assert_eq!(selector.take(&mut deep), Some(Document::from(2)));
+    assert_eq!(selector.apply(&deep), None);
+}
+
+#[test]
+fn strategic_merge_test() {
+    let base: Document = serde_json::from_str(
+        r#"{"spec": {
+            "replicas": 1,
+            "selector": {"app": "web", "tier": "front"},
+            "containers": [
+                {"name": "app", "image": "app:1", "args": ["--a"], "env": [{"name": "A", "value": "1"}]},
+                {"name": "sidecar", "image": "proxy:1"}
+            ],
+            "volumes": [{"name": "data"}, {"name": "tmp"}],
+            "tolerations": ["a", "b"]
+        }}"#,
+    )
+    .unwrap();
+    let patch: Document = serde_json::from_str(
+        r#"{"spec": {
+            "replicas": null,
+            "selector": {"$patch": "replace", "app": "api"},
+            "containers": [
+                {"name": "app", "image": "app:2", "args": ["--b"], "env": [{"name": "B", "value": "2"}]},
+                {"name": "sidecar", "$patch": "delete"},
+                {"name": "new", "image": "new:1", "ports": null}
+            ],
+            "volumes": [{"name": "cache"}, {"$patch": "replace"}],
+            "tolerations": ["c"]
+        }}"#,
+    )
+    .unwrap();
+
+    let keys = [("containers", "name"), ("env", "name"), ("volumes", "name")];
+    let mut doc = base;
+    doc.merge_with(patch, MergeStrategy::Strategic(&keys));
+    let spec = &doc["spec"];
+    assert_eq!(spec.get_at(&"/replicas".parse().unwrap()), None);
+    assert_eq!(spec["selector"], serde_json::from_str::<Document>(r#"{"app": "api"}"#).unwrap());
+    assert_eq!(spec["containers"].iter().count(), 2);
+    assert_eq!(spec["containers"][0]["image"], "app:2");
+    assert_eq!(spec["containers"][0]["args"], Document::Seq(vec!["--b".into()].into()));
+    assert_eq!(spec["containers"][0]["env"].iter().count(), 2);
+    assert_eq!(spec["containers"][1]["name"], "new");
+    assert!(spec.select_all(".containers.[1].ports").unwrap().is_empty());
+    assert_eq!(spec["volumes"], serde_json::from_str::<Document>(r#"[{"name": "cache"}]"#).unwrap());
+    assert_eq!(spec["tolerations"], Document::Seq(vec!["c".into()].into()));
+    assert!(doc.select_all(r#"..["$patch"]"#).unwrap().is_empty());
+}
+
+#[test]
+fn merge_depth_limit_test() {
+    let mut deep = Document::Null;
+    let path: Path = (0..64).map(|_| Segment::from("a")).collect::<Vec<_>>().into();
+    deep.set_at(&path, 1u64);
+
+    let mut doc: Document = serde_json::from_str(r#"{"a": {"b": 1}}"#).unwrap();
+    let original = doc.clone();
+    let err = doc
+        .try_merge_with(deep.clone(), MergeStrategy::Append, 32)
+        .unwrap_err();
+    assert!(matches!(&err, Error::Merge(e) if e.max_depth == 32));
+    assert_eq!(err.path().unwrap().len(), 33);
+    assert!(err.to_string().contains("maximum merge depth of 32"));
+    assert_eq!(doc, original);
+
+    let keys = [("items", "id")];
+    assert!(doc
+        .try_merge_with(deep, MergeStrategy::Strategic(&keys), 64)
+        .is_ok());
+    assert_eq!(doc["a"]["b"], 1);
+    assert_eq!(doc.get_at(&path), Some(&Document::from(1u64)));
+}
+
+#[test]
+fn selector_wildcard_test() {
+    let doc: Document = serde_json::from_str(
+        r#"{"users": [
+            {"name": "a", "email": "a@example.com"},
+            {"name": "b"},
+            {"name": "c", "email": "c@example.com"}
+        ], "groups": {"x": {"size": 1}, "y": {"size": 2}}}"#,
+    )
+    .unwrap();
+    let emails = doc.select_all(".users[*].email").unwrap();
+    assert_eq!(emails, vec![&Document::from("a@example.com"), &"c@example.com".into()]);
+    assert_eq!(doc.select_all(".users.[*].name").unwrap().len(), 3);
+    assert_eq!(doc.select_all(".users.*.name").unwrap().len(), 3);
+    assert_eq!(
+        doc.select_all(".groups.*.size").unwrap(),
+        vec![&Document::from(1u64), &2u64.into()]
+    );
+    assert_eq!(doc.select_all(".*").unwrap().len(), 2);
+    assert_eq!(doc.select_all("..*").unwrap().len(), 2 + 3 + 5 + 2 + 2);
+    assert_eq!(*doc.select_all(".users[2][\"name\"]").unwrap()[0], "c");
+    assert_eq!(*doc.select(".users[1].name").unwrap(), "b");
+    assert!(doc.select(".users[*].name").is_err());
+}
+
+#[test]
+fn selector_predicate_test() {
+    let doc: Document = serde_json::from_str(
+        r#"{"items": [
+            {"name": "a", "price": 5, "tags": {"sale": true}},
+            {"name": "b", "price": 10.5, "active": true},
+            {"name": "c", "price": 20, "active": false},
+            {"name": "d \"quoted\"", "price": -1, "active": null}
+        ]}"#,
+    )
+    .unwrap();
+    let names = |sel: &str| -> Vec<String> {
+        doc.select_all(sel)
+            .unwrap()
+            .into_iter()
+            .map(|v| v.clone().unwrap::<String>())
+            .collect()
+    };
+    assert_eq!(names(".items[?(@.price > 10)].name"), vec!["b", "c"]);
+    assert_eq!(names(".items.[?(@.price <= 10)].name"), vec!["a", "d \"quoted\""]);
+    assert_eq!(names(".items[?(@.active == true)].name"), vec!["b"]);
+    assert_eq!(names(".items[?(@.active)].name"), vec!["b"]);
+    assert_eq!(names(".items[?(!@.active)].name"), vec!["a", "c", "d \"quoted\""]);
+    assert_eq!(names(".items[?(@.active == null)].name"), vec!["d \"quoted\""]);
+    assert_eq!(names(".items[?(@.tags[\"sale\"])].name"), vec!["a"]);
+    let quoted = doc.select_all(r#".items[?(@.name == "d \"quoted\"")].price"#).unwrap();
+    assert_eq!(quoted, vec![&Document::from(-1i64)]);
+    assert_eq!(
+        names(".items[?(@.price >= 5 && @.price < 20 || @.name == \"c\")].name"),
+        vec!["a", "b", "c"]
+    );
+    assert_eq!(
+        names(".items[?(@.price > -2.5e0 && (@.active == false || @.price < 0))].name"),
+        vec!["c", "d \"quoted\""]
+    );
+    assert!(doc.select_all(".items[?(@.price >)]").is_err());
+    assert!(doc.select(".items[?(@.price > 10)]").is_err());
+}
+
+#[test]
+fn typed_iteration_test() {
+    let doc: Document = serde_json::from_str(r#"{"map": {"a": 1, "b": 2}, "seq": [1, 2, 3]}"#).unwrap();
+    let entries: Vec<(Document, Document)> = doc["map"].clone().into_map_iter().collect();
+    assert_eq!(entries[1], ("b".into(), 2u64.into()));
+    assert_eq!(doc["seq"].clone().into_seq_iter().count(), 3);
+    assert_eq!(doc["seq"].clone().into_map_iter().count(), 0);
+    assert_eq!(doc["map"].clone().into_seq_iter().count(), 0);
+
+    let mut doc = doc;
+    let drained: Vec<Document> = doc["seq"].drain_seq().collect();
+    assert_eq!(drained.len(), 3);
+    assert_eq!(doc["seq"], Document::Seq(Default::default()));
+    assert_eq!(doc["map"].drain_map().count(), 2);
+    assert_eq!(doc["map"], Document::Map(Default::default()));
+    assert_eq!(doc["map"].drain_seq().count(), 0);
+    assert_eq!(doc["map"], Document::Map(Default::default()));
+}
+
+#[test]
+fn negative_index_test() {
+    let mut doc: Document = serde_json::from_str(r#"{"items": [1, 2, 3, 4]}"#).unwrap();
+    assert_eq!(doc["items"][-1], 4);
+    assert_eq!(doc["items"][-4], 1);
+    assert_eq!(doc["items"][-5], Document::Unassigned);
+    assert_eq!(Document::from(-1i64).as_usize(), None);
+    assert_eq!(Document::from(200u8).cast::<i8>(), None);
+
+    assert_eq!(*doc.select(".items.[-2]").unwrap(), 3);
+    assert_eq!(*doc.select("/items/-1").unwrap(), 4);
+    assert_eq!(doc.select_all(".items[-1]").unwrap(), vec![&Document::from(4u64)]);
+    assert!(doc.select_all(".items[-9]").unwrap().is_empty());
+
+    let docs = vec![doc.clone()];
+    let last_two = Document::filter(&docs, "[0].items.[-2:]").unwrap();
+    assert_eq!(last_two["items"], Document::Seq(vec![3u64.into(), 4u64.into()].into()));
+    let middle = Document::filter(&docs, "[0].items.[1:-1]").unwrap();
+    assert_eq!(middle["items"], Document::Seq(vec![2u64.into(), 3u64.into()].into()));
+    let all = Document::filter(&docs, "[0].items.[-10:]").unwrap();
+    assert_eq!(all["items"].iter().count(), 4);
+
+    doc["items"][-1] = 40u64.into();
+    assert_eq!(doc["items"][3], 40);
+    doc["items"][-10] = 0u64.into();
+    assert_eq!(doc["items"][0], 0);
+    assert_eq!(doc["items"].iter().count(), 5);
+}
+
+#[cfg(feature = "macros")]
+#[test]
+fn sel_macro_test() {
+    use unstructured::sel;
+
+    let path = sel!(".path.to[3].key");
+    assert_eq!(path, Path::parse(".path.to[3].key").unwrap());
+    assert_eq!(sel!("/a~1b/0/c~0d"), Path::from_pointer("/a~1b/0/c~0d").unwrap());
+    assert_eq!(sel!(r#".["quoted \"key\""].[0]"#), Path::from(vec!["quoted \"key\"".into(), 0.into()]));
+    assert_eq!(sel!("."), Path::new());
+    assert_eq!(sel!(""), Path::new());
+
+    let mut doc = Document::Null;
+    doc.set_at(&sel!(".users[1].name"), "Bob");
+    assert_eq!(doc.get_at(&sel!("/users/1/name")), Some(&Document::from("Bob")));
+}
+
+#[test]
+fn range_step_test() {
+    let docs: Vec<Document> = vec![serde_json::from_str(r#"{"a": [0, 1, 2, 3, 4, 5]}"#).unwrap()];
+    let range = |sel: &str| -> Vec<u64> {
+        Document::filter(&docs, sel).unwrap()["a"]
+            .clone()
+            .into_seq_iter()
+            .map(|v| v.unwrap::<u64>())
+            .collect()
+    };
+    assert_eq!(range("[0].a.[::2]"), vec![0, 2, 4]);
+    assert_eq!(range("[0].a.[1::2]"), vec![1, 3, 5]);
+    assert_eq!(range("[0].a.[1:5:3]"), vec![1, 4]);
+    assert_eq!(range("[0].a.[::-1]"), vec![5, 4, 3, 2, 1, 0]);
+    assert_eq!(range("[0].a.[-2::-2]"), vec![4, 2, 0]);
+    assert_eq!(range("[0].a.[4:1:-1]"), vec![4, 3, 2]);
+    assert_eq!(range("[0].a.[10:-10:-4]"), vec![5, 1]);
+    assert_eq!(range("[0].a.[2:4:]"), vec![2, 3]);
+    assert_eq!(range("[0].a.[1:3]"), vec![1, 2]);
+    assert!(Document::filter(&docs, "[0].a.[::0]").is_err());
+}
+
+#[test]
+fn selector_explain_test() {
+    let doc: Document = serde_json::from_str(
+        r#"{"users": [{"name": "a", "email": "a@example.com"}, {"name": "b"}]}"#,
+    )
+    .unwrap();
+
+    let selector = CompiledSelector::compile(".users[*].email").unwrap();
+    assert_eq!(selector.to_string(), ".users[*].email");
+    assert_eq!(selector.select_all(&doc), vec![&Document::from("a@example.com")]);
+    let trace = selector.explain(&doc);
+    assert_eq!(trace.steps.len(), 3);
+    assert_eq!(trace.steps[0].input, vec!["map"]);
+    assert_eq!(trace.steps[1].step, "[*]");
+    assert_eq!(trace.steps[2].input, vec!["map", "map"]);
+    assert_eq!(trace.steps[2].matched, 1);
+    assert!(trace.stopped_at().is_none());
+
+    let selector = CompiledSelector::compile(".users[0].missing.name").unwrap();
+    let trace = selector.explain(&doc);
+    assert_eq!(trace.steps.len(), 3);
+    assert_eq!(trace.stopped_at().unwrap().step, ".missing");
+    assert!(trace.to_string().contains("Stopped after step 3 of 4"));
+
+    let selector = CompiledSelector::compile(r#"..["first name"][?(@ > 1)][-1]"#).unwrap();
+    assert_eq!(selector.to_string(), r#"..["first name"][?(@ > 1)][-1]"#);
+    assert!(CompiledSelector::compile(".users[").is_err());
+}
+
+#[test]
+fn compiled_selector_test() {
+    let mut doc: Document = serde_json::from_str(
+        r#"{"orders": [
+            {"id": 1, "items": [{"sku": "a", "qty": 1}]},
+            {"id": 2, "items": [{"sku": "b", "qty": 5}, {"sku": "c", "qty": 7}]}
+        ]}"#,
+    )
+    .unwrap();
+
+    let selector = Selector::compile(".orders[*].items[?(@.qty > 2)].sku").unwrap();
+    assert_eq!(selector.apply(&doc), Some(&Document::from("b")));
+    assert_eq!(selector.select_all(&doc).len(), 2);
+    *selector.apply_mut(&mut doc).unwrap() = "B".into();
+    assert_eq!(doc["orders"][1]["items"][0]["sku"], "B");
+
+    let last = Selector::compile(".orders[-1].id").unwrap();
+    assert_eq!(last.apply(&doc), Some(&Document::from(2u64)));
+    *last.apply_mut(&mut doc).unwrap() = 20u64.into();
+    assert_eq!(doc["orders"][1]["id"], 20);
+
+    let deep = Selector::compile("..qty").unwrap();
+    assert_eq!(deep.apply(&doc), Some(&Document::from(1u64)));
+    *deep.apply_mut(&mut doc).unwrap() = 10u64.into();
+    assert_eq!(doc["orders"][0]["items"][0]["qty"], 10);
+
+    let missing = Selector::compile(".orders[*].missing").unwrap();
+    assert_eq!(missing.apply(&doc), None);
+    assert!(missing.apply_mut(&mut doc).is_none());
+    assert_eq!(Selector::compile(".").unwrap().apply(&doc), Some(&doc));
+}
+
+/// Selectors pinned to a syntax version must keep resolving to exactly these results
+const SELECTOR_CORPUS_DOC: &str = r#"{"a": {"b": [10, 20, {"c": "x"}], "d e": 1}, "b": 2}"#;
+const SELECTOR_CORPUS: &[(SelectorVersion, &str, Option<&str>)] = &[
+    (SelectorVersion::V1, ".", Some(r#"[{"a":{"b":[10,20,{"c":"x"}],"d e":1},"b":2}]"#)),
+    (SelectorVersion::V1, "", None),
+    (SelectorVersion::V1, ".b", Some("[2]")),
+    (SelectorVersion::V1, ".a.b.[1]", Some("[20]")),
+    (SelectorVersion::V1, ".a.b.[2].c", Some(r#"["x"]"#)),
+    (SelectorVersion::V1, r#".["a"].["d e"]"#, Some("[1]")),
+    (SelectorVersion::V1, ".a..b.[0]", Some("[10]")),
+    (SelectorVersion::V1, "/a/b/1", Some("[20]")),
+    (SelectorVersion::V1, "/a/b/2/c", Some(r#"["x"]"#)),
+    (SelectorVersion::V1, ".a.missing", Some("[]")),
+    (SelectorVersion::V1, ".a.b[0]", None),
+    (SelectorVersion::V1, ".a.b.[-1]", None),
+    (SelectorVersion::V1, ".a.*", None),
+    (SelectorVersion::V1, ".a.b.[?(@ > 10)]", None),
+    (SelectorVersion::V2, ".a..b.[0]", Some("[10]")),
+    (SelectorVersion::V2, "..b", Some(r#"[2,[10,20,{"c":"x"}]]"#)),
+    (SelectorVersion::V2, ".a.b[-1].c", Some(r#"["x"]"#)),
+    (SelectorVersion::V2, ".a.*", Some(r#"[[10,20,{"c":"x"}],1]"#)),
+    (SelectorVersion::V2, ".a.b[?(@ > 10)]", Some("[20]")),
+];
+
+#[test]
+fn selector_compat_corpus_test() {
+    let doc: Document = serde_json::from_str(SELECTOR_CORPUS_DOC).unwrap();
+    for (version, sel, expected) in SELECTOR_CORPUS {
+        let result = CompiledSelector::compile_with(sel, *version)
+            .map(|selector| serde_json::to_string(&selector.select_all(&doc)).unwrap());
+        assert_eq!(result.ok().as_deref(), *expected, "{:?} {}", version, sel);
+    }
+    assert_eq!(SelectorVersion::default(), SelectorVersion::LATEST);
+}
+
+#[test]
+fn select_take_test() {
+    let mut doc: Document = serde_json::from_str(
+        r#"{"config": {"big": [1, 2, 3], "keep": true}, "items": [{"id": 1}, {"id": 2}]}"#,
+    )
+    .unwrap();
+
+    assert_eq!(doc.select_cloned(".config.big[1]").unwrap(), 2);
+    assert_eq!(doc.select_cloned(".config.missing").unwrap(), Document::Unassigned);
+
+    let big = doc.select_take(".config.big").unwrap().unwrap();
+    assert_eq!(big, Document::Seq(vec![1u64.into(), 2u64.into(), 3u64.into()].into()));
+    assert_eq!(doc["config"], serde_json::from_str::<Document>(r#"{"keep": true}"#).unwrap());
+    assert_eq!(doc.select_take(".config.big").unwrap(), None);
+
+    let last = doc.select_take(".items[-1]").unwrap().unwrap();
+    assert_eq!(last["id"], 2);
+    assert_eq!(doc.select_take("..id").unwrap(), Some(Document::from(1u64)));
+    assert_eq!(doc["items"][0], Document::Map(Default::default()));
+    assert!(doc.select_take(".items[").is_err());
+
+    let all = doc.select_take(".").unwrap().unwrap();
+    assert_eq!(all["config"]["keep"], true);
+    assert_eq!(doc, Document::Unassigned);
+}
+
+#[test]
+fn path_of_test() {
+    let doc: Document = serde_json::from_str(r#"{"a": {"b": [1, {"c": 1}]}, "d": 1}"#).unwrap();
+    let c = doc.select(".a.b.[1].c").unwrap();
+    assert_eq!(
+        doc.path_of(c),
+        Some(Path::from(vec!["a".into(), "b".into(), 1.into(), "c".into()]))
+    );
+    assert_eq!(doc.path_of(&doc["d"]).unwrap().to_string(), "/d");
+    assert_eq!(doc.path_of(&doc), Some(Path::new()));
+    // Equal values at other locations are not matched
+    assert_eq!(doc.path_of(&Document::from(1u64)), None);
+    let other = doc.clone();
+    #[cfg(not(feature = "shared"))]
+    assert_eq!(doc.path_of(&other["d"]), None);
+    #[cfg(feature = "shared")]
+    assert_eq!(doc.path_of(&other["d"]).unwrap().to_string(), "/d");
+}
+
+#[test]
+fn try_cast_test() {
+    assert_eq!(Document::from(12u64).try_cast::<i8>(), Ok(12));
+    assert_eq!(Document::from(2.0f64).try_cast::<u32>(), Ok(2));
+
+    let overflow = Document::from(-1i64).try_cast::<u64>().unwrap_err();
+    assert_eq!(overflow.found, "i64");
+    assert_eq!(overflow.target, "u64");
+    assert_eq!(overflow.reason, CastFailure::Overflow);
+    assert_eq!(
+        Document::from(1e40f64).try_cast::<f32>().unwrap_err().reason,
+        CastFailure::Overflow
+    );
+
+    let precision = Document::from(1.5f64).try_cast::<i32>().unwrap_err();
+    assert_eq!(precision.reason, CastFailure::Precision);
+    assert_eq!(
+        Document::from(9_007_199_254_740_993u64).try_cast::<f64>().unwrap_err().reason,
+        CastFailure::Precision
+    );
+
+    let parse = Document::from("12a").try_cast::<u8>().unwrap_err();
+    assert_eq!(parse.found, "string");
+    assert!(matches!(parse.reason, CastFailure::Parse(_)));
+    assert_eq!(
+        parse.to_string(),
+        "Cannot cast string to u8: failed to parse, invalid digit found in string"
+    );
+
+    let mismatch = Document::Null.try_cast::<String>().unwrap_err();
+    assert_eq!((mismatch.found, mismatch.target), ("null", "string"));
+    assert_eq!(mismatch.reason, CastFailure::Mismatch);
+    let seq = Document::from(true).try_cast::<Sequence<UnstructuredType>>();
+    assert_eq!(seq.unwrap_err().target, "seq");
+    assert_eq!(Document::Option(Some(Box::new(5u8.into()))).try_cast::<u16>(), Ok(5));
+
+    // Types relying on the default try_cast are reported by their own name
+    #[derive(Debug, PartialEq)]
+    struct Port(u16);
+
+    impl DocumentConvertible<UnstructuredType> for Port {
+        const NAME: &'static str = "port";
+
+        fn is(val: &Document) -> bool {
+            val.is::<u16>()
+        }
+
+        fn into_unstructured(self) -> Document {
+            self.0.into()
+        }
+
+        fn into_native(val: Document) -> Option<Self> {
+            val.cast().map(Port)
+        }
+
+        fn castable(val: &Document) -> bool {
+            val.clone().cast::<u16>().is_some_and(|port| port > 0)
+        }
+
+        fn cast(val: Document) -> Option<Self> {
+            val.cast().filter(|port| *port > 0).map(Port)
+        }
+    }
+
+    assert_eq!(Document::from(8080).try_cast::<Port>(), Ok(Port(8080)));
+    let port = Document::from(0).try_cast::<Port>().unwrap_err();
+    assert_eq!((port.found, port.target), ("i32", "port"));
+    assert_eq!(port.to_string(), "Cannot cast i32 to port: incompatible type");
+
+    // cast keeps its existing behaviour
+    assert_eq!(Document::from(300u64).cast::<u8>(), None);
+    assert_eq!(Document::from("7").cast::<u8>(), Some(7));
+}
+
+#[test]
+#[cfg(feature = "filter")]
+fn filter_into_test() {
+    let docs: Vec<Document> = vec![
+        serde_json::from_str(r#"{"level": "info", "tags": ["a"]}"#).unwrap(),
+        serde_json::from_str(r#"{"level": "warn", "tags": ["b"], "code": 7}"#).unwrap(),
+    ];
+
+    let mut target: Document = serde_json::from_str(r#"{"existing": true}"#).unwrap();
+    Document::filter_into(&docs, "[0].level | [1].code", &mut target).unwrap();
+    assert_eq!(target["existing"], true);
+    assert_eq!(target["level"], "info");
+    assert_eq!(target["code"], 7);
+
+    // Later results overwrite earlier values and sequences are appended
+    Document::filter_into(&docs, "[1].level | [0].tags | [1].tags", &mut target).unwrap();
+    assert_eq!(target["level"], "warn");
+    assert_eq!(target["tags"], Document::Seq(vec!["a".into(), "b".into()].into()));
+
+    let mut fresh = Document::Map(Default::default());
+    Document::filter_into(&docs, "[0].level", &mut fresh).unwrap();
+    assert_eq!(fresh, Document::filter(&docs, "[0].level").unwrap());
+    assert!(Document::filter_into(&docs, "[5].level", &mut fresh).is_err());
+}
+
+#[test]
+fn cast_containers_test() {
+    let numbers: Document = serde_json::from_str(r#"[1, 2.0, "3", 4]"#).unwrap();
+    assert_eq!(numbers.clone().cast_seq::<i32>().unwrap(), vec![1, 2, 3, 4]);
+    assert_eq!(numbers.clone().cast_seq::<f64>().unwrap(), vec![1.0, 2.0, 3.0, 4.0]);
+
+    let mixed: Document = serde_json::from_str(r#"[1, 2, 300]"#).unwrap();
+    let (index, err) = mixed.cast_seq::<u8>().unwrap_err();
+    assert_eq!(index, 2);
+    assert_eq!(err.reason, CastFailure::Overflow);
+    let (index, err) = Document::from("x").cast_seq::<u8>().unwrap_err();
+    assert_eq!((index, err.found, err.target), (0, "string", "seq"));
+
+    let names: Document = serde_json::from_str(r#"{"a": "x", "b": "y"}"#).unwrap();
+    let map = names.cast_map::<String, String>().unwrap();
+    assert_eq!(map.get("b").map(String::as_str), Some("y"));
+
+    let scores: Document = serde_json::from_str(r#"{"1": 10, "2": "high"}"#).unwrap();
+    let (key, _) = scores.clone().cast_map::<u8, String>().unwrap_err();
+    assert_eq!(key, "1");
+    let (key, err) = scores.cast_map::<u8, u8>().unwrap_err();
+    assert_eq!(key, "2");
+    assert!(matches!(err.reason, CastFailure::Parse(_)));
+    let (key, _) = Document::Null.cast_map::<String, u8>().unwrap_err();
+    assert_eq!(key, Document::Unassigned);
+}
+
+#[test]
+fn selector_escape_test() {
+    let doc: Document = serde_json::from_str(
+        r#"{"weird\".key": 1, "a.b": {"[0]": 2}, "tab\there": 3, "a/b": {"m~n": 4}, "x y": 5}"#,
+    )
+    .unwrap();
+
+    assert_eq!(*doc.select(r#".["weird\".key"]"#).unwrap(), 1);
+    assert_eq!(*doc.select(r#".["a.b"].["[0]"]"#).unwrap(), 2);
+    assert_eq!(*doc.select(r#"["a.b"]["[0]"]"#).unwrap(), 2);
+    assert_eq!(*doc.select(r#".["tab\there"]"#).unwrap(), 3);
+    assert_eq!(*doc.select(r#".["tab\u0009here"]"#).unwrap(), 3);
+    assert_eq!(*doc.select(r#".["x y"]"#).unwrap(), 5);
+
+    assert_eq!(*doc.select("/a~1b/m~0n").unwrap(), 4);
+    assert_eq!(*doc.select("/a.b/[0]").unwrap(), 2);
+    assert_eq!(*doc.select("/x y").unwrap(), 5);
+    assert!(doc.select("/a~2b").is_err());
+
+    let mut copy = doc.clone();
+    *copy.select_mut("/a~1b/m~0n").unwrap() = 40u64.into();
+    assert_eq!(copy["a/b"]["m~n"], 40);
+    assert_eq!(doc.select_all(r#"..["m~n"]"#).unwrap(), vec![&Document::from(4u64)]);
+    assert_eq!(doc.select_all(r#"[?(@["[0]"] == 2)]"#).unwrap().len(), 1);
+
+    // Selectors pinned to the original syntax keep escapes as written
+    let v1 = CompiledSelector::compile_with(r#".["tab\there"]"#, SelectorVersion::V1).unwrap();
+    assert_eq!(v1.apply(&doc), None);
+}
+
+#[test]
+fn coerce_test() {
+    let mut doc: Document = serde_json::from_str(
+        r#"{
+            "id": "17",
+            "enabled": "true",
+            "ratio": 2,
+            "label": 5,
+            "ports": ["80", 443, "http"],
+            "limits": {"cpu": "1.5", "mem": 512},
+            "parent": null,
+            "extra": "untouched"
+        }"#,
+    )
+    .unwrap();
+    let schema = Schema::Struct(
+        vec![
+            ("id".to_string(), Schema::Integer),
+            ("enabled".to_string(), Schema::Bool),
+            ("ratio".to_string(), Schema::Float),
+            ("label".to_string(), Schema::String),
+            ("ports".to_string(), Schema::Seq(Box::new(Schema::Integer))),
+            ("limits".to_string(), Schema::Map(Box::new(Schema::Float))),
+            ("parent".to_string(), Schema::Optional(Box::new(Schema::Integer))),
+            ("missing".to_string(), Schema::Bool),
+        ]
+        .into_iter()
+        .collect(),
+    );
+
+    let errors = doc.coerce(&schema).unwrap_err();
+    assert_eq!(doc["id"], Document::Number(Number::U64(17)));
+    assert_eq!(doc["enabled"], true);
+    assert_eq!(doc["ratio"], Document::Number(Number::F64(2.0)));
+    assert_eq!(doc["label"], "5");
+    assert_eq!(doc["ports"][0], 80);
+    assert_eq!(doc["limits"]["cpu"], Document::Number(Number::F64(1.5)));
+    assert_eq!(doc["parent"], Document::Null);
+    assert_eq!(doc["extra"], "untouched");
+
+    assert_eq!(errors.len(), 1);
+    assert_eq!(errors[0].path.to_string(), "/ports/2");
+    assert_eq!((errors[0].expected, errors[0].found), ("integer", "string"));
+    assert_eq!(doc["ports"][2], "http");
+
+    // Lossy conversions are refused
+    let mut lossy: Document = serde_json::from_str(r#"[1.5, 2, -3]"#).unwrap();
+    let errors = lossy.coerce(&Schema::Seq(Box::new(Schema::Integer))).unwrap_err();
+    assert_eq!(errors.len(), 1);
+    assert_eq!(lossy[2], Document::Number(Number::I64(-3)));
+    let mut flag = Document::from(2u64);
+    assert!(flag.coerce(&Schema::Bool).is_err());
+    let mut seq = Document::from("x");
+    let errors = seq.coerce(&Schema::Seq(Box::new(Schema::Any))).unwrap_err();
+    assert_eq!(errors[0].to_string(), "Cannot coerce string to seq");
+
+    #[allow(deprecated)]
+    let errors = Document::from("x").coerce_to_schema(&Schema::Bool).unwrap_err();
+    assert_eq!(errors[0].to_string(), "Cannot coerce string to bool");
+}
+
+#[test]
+fn pointer_mutation_test() {
+    let mut doc: Document = serde_json::from_str(r#"{"a": {"b": [1, 2]}, "c/d": 3}"#).unwrap();
+    assert_eq!(doc.pointer("/a/b/1"), Some(&Document::from(2u64)));
+    assert_eq!(doc.pointer("/c~1d"), Some(&Document::from(3u64)));
+    assert_eq!(doc.pointer(""), Some(&doc));
+    assert_eq!(doc.pointer("/missing"), None);
+    assert_eq!(doc.pointer("no-slash"), None);
+    *doc.pointer_mut("/a/b/0").unwrap() = 10u64.into();
+    assert_eq!(doc["a"]["b"][0], 10);
+
+    *doc.pointer_or_insert("/a/b/-").unwrap() = 3u64.into();
+    assert_eq!(doc["a"]["b"], Document::Seq(vec![10u64.into(), 2u64.into(), 3u64.into()].into()));
+    *doc.pointer_or_insert("/x/y/2").unwrap() = true.into();
+    assert_eq!(doc["x"]["y"], Document::Seq(vec![Document::Null, Document::Null, true.into()].into()));
+    *doc.pointer_or_insert("/x/-/z").unwrap() = "map key".into();
+    assert_eq!(doc["x"]["-"]["z"], "map key");
+    assert!(doc.pointer_or_insert("bad").is_err());
+
+    assert_eq!(doc.pointer_remove("/a/b/0"), Some(10u64.into()));
+    assert_eq!(doc["a"]["b"].iter().count(), 2);
+    assert_eq!(doc.pointer_remove("/a/b/-"), None);
+    assert_eq!(doc.pointer_remove("/c~1d"), Some(3u64.into()));
+    assert_eq!(doc.pointer("/c~1d"), None);
+}
+
+#[test]
+fn flatten_test() {
+    let doc: Document = serde_json::from_str(
+        r#"{"a": {"b/c": [1, {"d": null}], "e": []}, "f": "x", "g": [[true]], "h": {}}"#,
+    )
+    .unwrap();
+    let flat = doc.flatten();
+    let expected: Document = serde_json::from_str(
+        r#"{"/a/b~1c/0": 1, "/a/b~1c/1/d": null, "/a/e": [], "/f": "x", "/g/0/0": true, "/h": {}}"#,
+    )
+    .unwrap();
+    assert_eq!(flat, expected);
+    assert_eq!(flat.unflatten().unwrap(), doc);
+
+    let long: Document = serde_json::from_str(r#"{"s": [0,1,2,3,4,5,6,7,8,9,10,11]}"#).unwrap();
+    assert_eq!(long.flatten().unflatten().unwrap(), long);
+
+    let scalar = Document::from(5u64);
+    assert_eq!(scalar.flatten()[""], 5);
+    assert_eq!(scalar.flatten().unflatten().unwrap(), scalar);
+    assert!(Document::from("x").unflatten().is_err());
+    assert!(serde_json::from_str::<Document>(r#"{"no-slash": 1}"#).unwrap().unflatten().is_err());
+}
+
+#[test]
+fn canonicalize_test() {
+    let mut json: Document =
+        serde_json::from_str(r#"{"n": [300, -1, 2.0, 0.25], "s": ["b", "a"], "big": 1e300}"#)
+            .unwrap();
+    let mut yaml: Document =
+        serde_yaml::from_str("{n: [300, -1, 2, 0.25], s: [b, a], big: 1.0e+300}").unwrap();
+    json.canonicalize(false);
+    yaml.canonicalize(false);
+    assert_eq!(json, yaml);
+    assert_eq!(json["n"][0], Document::Number(Number::U16(300)));
+    assert_eq!(json["n"][1], Document::Number(Number::I8(-1)));
+    assert_eq!(json["n"][2], Document::Number(Number::U8(2)));
+    assert_eq!(json["n"][3], Document::Number(Number::F32(0.25)));
+    assert_eq!(json["big"], Document::Number(Number::F64(1e300)));
+    assert_eq!(json["s"][0], "b");
+
+    json.canonicalize(true);
+    assert_eq!(json["s"], Document::Seq(vec!["a".into(), "b".into()].into()));
+    let mut nums: Document = serde_json::from_str("[3, 1.0, 2]").unwrap();
+    nums.canonicalize(true);
+    assert_eq!(nums, Document::Seq(vec![1u8.into(), 2u8.into(), 3u8.into()].into()));
+
+    let mut wrapped = Document::Newtype(Box::new(Document::Option(Some(Box::new(
+        Document::Number(Number::U64(7)),
+    )))));
+    wrapped.canonicalize(false);
+    assert_eq!(wrapped, Document::Number(Number::U8(7)));
+    let mut none = Document::Option(None);
+    none.canonicalize(false);
+    assert_eq!(none, Document::Null);
+
+    let mut keys = Document::Map(
+        vec![(Document::Number(Number::I64(1)), Document::from("x"))]
+            .into_iter()
+            .collect::<Mapping<_>>()
+            .into(),
+    );
+    keys.canonicalize(false);
+    assert_eq!(keys.iter().count(), 1);
+    assert!(matches!(&keys, Document::Map(m) if m.contains_key(&Document::Number(Number::U8(1)))));
+}
+
+#[test]
+fn semantic_number_eq_test() {
+    use std::collections::hash_map::DefaultHasher;
+    use std::hash::{Hash, Hasher};
+    fn hash(doc: &Document) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        doc.hash(&mut hasher);
+        hasher.finish()
+    }
+
+    let pairs = vec![
+        (Number::U64(5), Number::I64(5)),
+        (Number::U8(5), Number::F64(5.0)),
+        (Number::I8(-3), Number::F32(-3.0)),
+        (Number::from(u128::MAX), Number::from(u128::MAX)),
+        (Number::F64(0.0), Number::F64(-0.0)),
+    ];
+    for (l, r) in pairs {
+        let (l, r) = (Document::Number(l), Document::Number(r));
+        assert_eq!(l, r);
+        assert_eq!(r, l);
+        assert_eq!(hash(&l), hash(&r), "{:?} {:?}", l, r);
+    }
+
+    // Values are never truncated to the narrower type
+    assert_ne!(Number::U8(44), Number::U16(300));
+    assert_ne!(Number::U16(300), Number::U8(44));
+    assert_ne!(Number::I64(2), Number::F64(2.5));
+    assert_ne!(Number::F64(2.5), Number::I64(2));
+    assert_ne!(Number::I8(-1), Number::U8(255));
+
+    let mut ordered = vec![
+        Number::F64(f64::NAN),
+        Number::U16(300),
+        Number::F32(0.5),
+        Number::I8(-1),
+        Number::F64(-1.5),
+        Number::U8(2),
+        Number::from(i128::MIN),
+        Number::F64(f64::INFINITY),
+        Number::U64(u64::MAX),
+    ];
+    ordered.sort();
+    let names: Vec<String> = ordered.iter().map(|n| n.to_string()).collect();
+    assert_eq!(
+        names,
+        vec![
+            i128::MIN.to_string(),
+            "-1.5".to_string(),
+            "-1".to_string(),
+            "0.5".to_string(),
+            "2".to_string(),
+            "300".to_string(),
+            u64::MAX.to_string(),
+            "inf".to_string(),
+            "NaN".to_string(),
+        ]
+    );
+
+    let mut keys = std::collections::BTreeMap::new();
+    keys.insert(Document::Number(Number::U64(1)), "a");
+    keys.insert(Document::Number(Number::I32(1)), "b");
+    assert_eq!(keys.len(), 1);
+
+    let strict = Document::Number(Number::U64(1));
+    assert!(strict.strict_eq(&Document::Number(Number::U64(1))));
+    assert!(!strict.strict_eq(&Document::Number(Number::I64(1))));
+    assert!(!Document::Seq(vec![strict.clone()].into())
+        .strict_eq(&Document::Seq(vec![Document::Number(Number::F64(1.0))].into())));
+}
+
+#[test]
+fn document_arithmetic_test() {
+    let a = Document::from(7u64);
+    let b = Document::from(2u64);
+    assert_eq!(a.clone() + b.clone(), 9);
+    assert_eq!(a.clone() - b.clone(), 5);
+    assert_eq!(a.clone() * b.clone(), 14);
+    assert_eq!(a.clone() / b.clone(), 3);
+    assert_eq!(Document::from(7.0f64) / b.clone(), 3.5);
+    assert_eq!(a.clone() % b.clone(), 1);
+
+    assert_eq!(Document::from("foo") + Document::from("bar"), "foobar");
+    assert!((Document::from("foo") - Document::from("bar")).ok().is_err());
+    assert!((a.clone() * Document::from("x")).ok().is_err());
+
+    // Containers are still merged
+    let seq = Document::Seq(vec![a.clone()].into()) + Document::Seq(vec![b.clone()].into());
+    assert_eq!(seq, Document::Seq(vec![a.clone(), b.clone()].into()));
+    let map: Document = serde_json::from_str(r#"{"a": 1}"#).unwrap();
+    let other: Document = serde_json::from_str(r#"{"b": 2}"#).unwrap();
+    assert_eq!((map + other)["b"], 2);
+
+    let mut doc: Document = serde_json::from_str(r#"{"price": 2.5, "qty": 4}"#).unwrap();
+    doc["total"] = doc["price"].clone() * doc["qty"].clone();
+    assert_eq!(doc["total"], 10.0);
+}
+
+#[test]
+fn adversarial_selector_test() {
+    let doc: Document = serde_json::from_str(r#"{"a": [1, 2, 3], "b": {"c": null}}"#).unwrap();
+    let docs = vec![doc.clone()];
+    let selectors = [
+        "[0].a.[1:3:9223372036854775807]",
+        "[0].a.[::-9223372036854775808]",
+        "[0].a.[-9223372036854775808:]",
+        "[0].a.[99999999999999999999]",
+        ".a[-9223372036854775808]",
+        ".a[18446744073709551615]",
+        "/a/-9223372036854775808",
+        ".[\"\\uD800\"]",
+        ".[\"\\u12\"]",
+        "[?(@ == )]",
+        "..",
+        "[[[[",
+        "",
+    ];
+    for sel in selectors.iter() {
+        let _ = doc.select(sel);
+        let _ = doc.select_all(sel);
+        let _ = Document::filter(&docs, sel);
+        let _ = Document::filter(&[], sel);
+        let _ = Path::parse(sel);
+        let _ = doc.clone().select_mut(sel);
+    }
+
+    // Deeply nested predicates are rejected rather than overflowing the stack
+    let nest = |open: &str, inner: &str, close: &str, n: usize| {
+        format!("{}{}{}", open.repeat(n), inner, close.repeat(n))
+    };
+    let shallow = format!("[?{}]", nest("(", "@ == 1", ")", 120));
+    assert_eq!(doc["a"].select_all(&shallow).unwrap(), vec![&Document::from(1)]);
+    let odd = format!("[?({})]", nest("!", "@ == 1", "", 121));
+    assert_eq!(doc["a"].select_all(&odd).unwrap().len(), 2);
+    let deep = [
+        format!("[?{}]", nest("(", "@", ")", 1000)),
+        format!("[?({})]", nest("!", "@", "", 5000)),
+        format!("[?{}]", nest("(!", "@", ")", 100)),
+        format!(".a{}", nest("[?(", "@", ")]", 100)),
+    ];
+    for sel in deep.iter() {
+        assert!(matches!(doc.select_all(sel), Err(Error::Select(msg)) if msg.contains("128")));
+        assert!(matches!(doc.select(sel), Err(Error::Select(_))));
+        assert!(matches!(doc.clone().select_mut(sel), Err(Error::Select(_))));
+        assert!(matches!(CompiledSelector::compile(sel), Err(Error::Select(_))));
+    }
+
+    // Unicode escapes must be four hex digits, and a high surrogate must be followed by a low one
+    let key = |sel: &str| Path::parse(sel).map(|path| path.to_string());
+    assert_eq!(key(r#".["\u0041\uD83D\uDE00"]"#).unwrap(), "/A\u{1F600}");
+    assert!(key(r#".["\uD800\u0041"]"#).is_err());
+    assert!(key(r#".["\uD800\uD800"]"#).is_err());
+    assert!(key(r#".["\u+041"]"#).is_err());
+    assert!(key(r#".["\u-041"]"#).is_err());
+    assert_eq!(
+        Document::filter(&docs, "[0].a.[1:3:9223372036854775807]").unwrap()["a"],
+        Document::Seq(vec![2u64.into()].into())
+    );
+}
+
+#[test]
+fn checked_number_arithmetic_test() {
+    // Mixed types are promoted rather than cast to the type of the left hand side
+    assert!((Number::U8(200) + Number::U16(1000)).strict_eq(&Number::U16(1200)));
+    assert!((Number::U8(1) + Number::U64(u64::MAX - 1)).strict_eq(&Number::U64(u64::MAX)));
+    assert!((Number::U8(5) - Number::I8(10)).strict_eq(&Number::I16(-5)));
+    assert!((Number::I32(3) * Number::F64(0.5)).strict_eq(&Number::F64(1.5)));
+    assert!((Number::F32(1.5) + Number::U8(1)).strict_eq(&Number::F32(2.5)));
+
+    // Overflowing results widen instead of wrapping or panicking
+    assert!((Number::U8(200) + Number::U8(100)).strict_eq(&Number::from(300u128)));
+    assert!((Number::U8(3) - Number::U8(5)).strict_eq(&Number::from(-2i128)));
+    assert!((Number::I64(i64::MAX) * Number::I64(4)).strict_eq(&Number::from(i64::MAX as i128 * 4)));
+    assert_eq!(Number::from(u128::MAX) + Number::U8(1), Number::F64(u128::MAX as f64 + 1.0));
+
+    assert_eq!(Number::U8(200).checked_add(&Number::U8(55)), Some(Number::U8(255)));
+    assert_eq!(Number::U8(200).checked_add(&Number::U8(56)), None);
+    assert_eq!(Number::U8(1).checked_sub(&Number::U8(2)), None);
+    assert_eq!(Number::I8(1).checked_sub(&Number::U8(2)), Some(Number::I16(-1)));
+    assert_eq!(Number::I64(i64::MIN).checked_div(&Number::I64(-1)), None);
+    assert_eq!(Number::U32(7).checked_div(&Number::U32(0)), None);
+    assert_eq!(Number::U32(7).checked_div(&Number::U32(2)), Some(Number::U32(3)));
+    assert_eq!(Number::F64(f64::MAX).checked_mul(&Number::F64(2.0)), None);
+    assert_eq!(Number::F64(1.0).checked_div(&Number::F64(0.0)), None);
+    assert!(Number::F64(f64::INFINITY).checked_add(&Number::F64(1.0)).is_some());
+
+    assert!(Number::U8(200).saturating_add(&Number::U8(100)).strict_eq(&Number::U8(255)));
+    assert!(Number::U8(1).saturating_sub(&Number::U8(2)).strict_eq(&Number::U8(0)));
+    assert!(Number::I8(-100).saturating_mul(&Number::I8(2)).strict_eq(&Number::I8(-128)));
+    assert!(Number::I8(-128).saturating_div(&Number::I8(-1)).strict_eq(&Number::I8(127)));
+    assert_eq!(Number::F32(f32::MAX).saturating_mul(&Number::F32(2.0)), Number::F32(f32::MAX));
+
+    // Integer division by zero gives the float result instead of panicking
+    assert!((Number::U8(1) / Number::U8(0)).strict_eq(&Number::F64(f64::INFINITY)));
+}
+
+#[test]
+fn adversarial_document_test() {
+    let json = r#"[null, [], {}, {"": {"~1": [[[]]]}}, [-1, 1e308, 18446744073709551615]]"#;
+    let docs: Vec<Document> = serde_json::from_str(json).unwrap();
+    let huge = Path::new().join("a").join(usize::MAX);
+    for doc in docs.iter() {
+        let mut doc = doc.clone();
+        let _ = doc.flatten().unflatten();
+        let _ = doc.clone().try_cast::<u8>();
+        let _ = doc.clone().cast_seq::<String>();
+        let _ = doc.coerce(&Schema::Seq(Box::new(Schema::Integer)));
+        let _ = String::deserialize(doc.clone());
+        let _ = doc.pointer_remove("/0");
+        doc.canonicalize(true);
+        doc.set_at(&huge, 1u64);
+        doc[-5i64][usize::MAX][u128::MAX]["key"] = true.into();
+        assert!(doc.pointer_or_insert("/a/18446744073709551615").is_err());
+        assert!(doc.pointer_or_insert("/x/-/-").is_ok());
+    }
+
+    // Deserializer errors are returned rather than panicking
+    assert!(String::deserialize(Document::from(5u64)).is_err());
+    assert!(<Vec<u8>>::deserialize(Document::from(-1i64)).is_err());
+
+    // So is dividing any kind of integer by zero
+    let nan = |n: Number| n.is_float() && f64::from(&n).is_nan();
+    let zeros = [Number::U8(0), Number::I64(0), Number::U128(0), Number::I128(0)];
+    for zero in zeros.iter() {
+        let big = Number::from(u128::MAX);
+        assert!((big.clone() / zero.clone()).strict_eq(&Number::F64(f64::INFINITY)));
+        assert!((Number::I8(-1) / zero.clone()).strict_eq(&Number::F64(f64::NEG_INFINITY)));
+        assert!(nan(zero.clone() / zero.clone()));
+        assert!(nan(big.clone() % zero.clone()));
+        let mut n = Number::I32(7);
+        n /= zero.clone();
+        assert!(n.strict_eq(&Number::F64(f64::INFINITY)));
+        n %= zero.clone();
+        assert!(nan(n));
+        assert!(big.saturating_div(zero).strict_eq(&Number::F64(f64::INFINITY)));
+        assert!(nan(zero.saturating_div(zero)));
+    }
+}
+
+#[test]
+fn number_operator_suite_test() {
+    assert!((Number::U8(7) % Number::U8(3)).strict_eq(&Number::U8(1)));
+    assert!((Number::I16(-7) % Number::U8(3)).strict_eq(&Number::I16(-1)));
+    assert!((Number::F64(7.5) % Number::U8(2)).strict_eq(&Number::F64(1.5)));
+    assert_eq!(Number::I8(i8::MIN).checked_rem(&Number::I8(-1)), Some(Number::I8(0)));
+    assert_eq!(Number::U8(1).checked_rem(&Number::U8(0)), None);
+
+    assert!((-Number::U8(200)).strict_eq(&Number::I16(-200)));
+    assert!((-Number::I32(5)).strict_eq(&Number::I32(-5)));
+    assert!((-Number::I8(i8::MIN)).strict_eq(&Number::from(128i128)));
+    assert!((-Number::from(u128::MAX)).strict_eq(&Number::F64(-(u128::MAX as f64))));
+    assert!((-Number::F32(0.5)).strict_eq(&Number::F32(-0.5)));
+
+    let mut n = Number::U8(250);
+    n += Number::U8(10);
+    assert!(n.strict_eq(&Number::from(260u128)));
+    n -= Number::U64(60);
+    n *= Number::U8(2);
+    n /= Number::U8(3);
+    n %= Number::U8(6);
+    assert_eq!(n, Number::U8(1));
+
+    assert!(Number::I8(i8::MIN).abs().strict_eq(&Number::from(128i128)));
+    assert!(Number::I64(-3).abs().strict_eq(&Number::I64(3)));
+    assert!(Number::F64(-2.5).abs().strict_eq(&Number::F64(2.5)));
+    assert!(Number::U16(3).abs().strict_eq(&Number::U16(3)));
+
+    assert!(Number::I8(-2).pow(3).strict_eq(&Number::I8(-8)));
+    assert!(Number::U64(10).pow(30).strict_eq(&Number::from(10u128.pow(30))));
+    assert!(Number::U64(10).pow(40).strict_eq(&Number::F64(1e40)));
+    assert!(Number::U8(0).pow(0).strict_eq(&Number::U8(1)));
+
+    assert!(Number::U8(3).min(Number::F64(2.5)).strict_eq(&Number::F64(2.5)));
+    assert!(Number::I8(-1).max(Number::U64(0)).strict_eq(&Number::U64(0)));
+    assert_eq!(Number::F64(f64::NAN).max(Number::U8(1)), Number::U8(1));
+    assert_eq!(Number::F32(f32::NAN).min(Number::U8(1)), Number::U8(1));
+}
+
+static DEFAULT_DOCS: [Document; 4] = [
+    Document::const_unit(),
+    Document::const_bool(true),
+    Document::const_i32(-5),
+    Document::const_f64(0.5),
+];
+
+#[test]
+fn const_constructors_test() {
+    const RETRIES: Document = Document::const_u8(3);
+    assert_eq!(RETRIES, 3u8);
+    assert_eq!(DEFAULT_DOCS[0], Document::Null);
+    assert_eq!(DEFAULT_DOCS[1], true);
+    assert_eq!(DEFAULT_DOCS[2], -5);
+    assert_eq!(DEFAULT_DOCS[3], 0.5);
+    assert_eq!(Document::const_char('x'), 'x');
+}
+
+#[test]
+fn document_size_test() {
+    use std::mem::size_of;
+
+    #[cfg(target_pointer_width = "64")]
+    assert_eq!(size_of::<Number>(), 32);
+    #[cfg(all(target_pointer_width = "64", not(feature = "preserve_order")))]
+    assert_eq!(size_of::<Document>(), 32);
+
+    // 128 bit values are stored inline, so they can be matched on directly
+    let n = Number::from(u128::MAX);
+    assert!(matches!(n, Number::U128(u128::MAX)));
+    assert_eq!(u128::from(&n), u128::MAX);
+    assert_eq!(Document::from(i128::MIN).cast::<i128>(), Some(i128::MIN));
+    assert_eq!(n.to_string(), u128::MAX.to_string());
+}
+
+#[test]
+fn lossless_number_accessors_test() {
+    assert_eq!(Number::I8(-5).as_i64(), Some(-5));
+    assert_eq!(Number::from(i128::MIN).as_i64(), None);
+    assert_eq!(Number::from(u128::MAX).as_u64(), None);
+    assert_eq!(Number::I32(-1).as_u64(), None);
+    assert_eq!(Number::U8(200).as_u64(), Some(200));
+    assert_eq!(Number::F64(3.0).as_u64(), None);
+
+    assert_eq!(Number::I64(-(1 << 53)).as_f64(), Some(-9007199254740992.0));
+    assert_eq!(Number::I64(i64::MIN).as_f64(), Some(i64::MIN as f64));
+    assert_eq!(Number::I64(i64::MAX).as_f64(), None);
+    assert_eq!(Number::from(u128::MAX).as_f64(), None);
+    assert_eq!(Number::from(1u128 << 127).as_f64(), Some(2f64.powi(127)));
+    assert!(Number::F64(f64::NAN).as_f64().unwrap().is_nan());
+
+    assert!(Number::U16(1).is_integer());
+    assert!(Number::I128(0.into()).is_integer());
+    assert!(!Number::F32(1.0).is_integer());
+}
+
+#[cfg(feature = "generate")]
+#[test]
+fn generate_test() {
+    use rand::SeedableRng;
+
+    let template: Document = serde_json::from_str(
+        r#"{"id": "$uuid", "n": "$int(-3..=3)", "f": "$float(0..0.5)", "tags": ["$word", "$bool"],
+            "name": "$name", "price": "$$5", "fixed": 1}"#,
+    )
+    .unwrap();
+    let mut rng = rand::rngs::StdRng::seed_from_u64(7);
+    for _ in 0..20 {
+        let doc = template.generate_with(&mut rng).unwrap();
+        let id = doc["id"].clone().unwrap::<String>();
+        assert_eq!((id.len(), &id[14..15]), (36, "4"));
+        let n = doc["n"].clone().cast::<i64>().unwrap();
+        assert!((-3..=3).contains(&n));
+        let f = doc["f"].clone().cast::<f64>().unwrap();
+        assert!((0.0..0.5).contains(&f));
+        assert!(doc["tags"][0].is::<String>() && doc["tags"][1].is::<bool>());
+        assert_eq!(doc["name"].clone().unwrap::<String>().split(' ').count(), 2);
+        assert_eq!(doc["price"], "$5");
+        assert_eq!(doc["fixed"], 1);
+    }
+    assert!(Document::from("$nope").generate().is_err());
+    assert!(Document::from("$int(5..5)").generate().is_err());
+    assert!(Document::from("$int(a..b)").generate().is_err());
+
+    let schema = Schema::Struct(
+        vec![
+            ("id".to_string(), Schema::Integer),
+            ("tags".to_string(), Schema::Seq(Box::new(Schema::String))),
+            ("score".to_string(), Schema::Optional(Box::new(Schema::Float))),
+        ]
+        .into_iter()
+        .collect(),
+    );
+    for _ in 0..20 {
+        let mut doc: Document = schema.generate_with(&mut rng);
+        assert!(doc.coerce(&schema).is_ok());
+        assert!(doc["id"].is_number());
+    }
+}
+
+#[cfg(feature = "chrono")]
+#[test]
+fn datetime_test() {
+    use chrono::{DateTime, FixedOffset, TimeZone, Utc};
+
+    let utc = Utc.with_ymd_and_hms(2020, 1, 2, 3, 4, 5).unwrap();
+    let doc = Document::from(utc);
+    assert_eq!(doc.type_name(), "datetime");
+    assert_eq!(doc.to_string(), "2020-01-02T03:04:05+00:00");
+    assert_eq!(serde_json::to_string(&doc).unwrap(), r#""2020-01-02T03:04:05+00:00""#);
+
+    // Comparison is by instant regardless of offset
+    let later: Document = DateTime::parse_from_rfc3339("2020-01-02T04:00:00+01:00").unwrap().into();
+    assert!(later < doc);
+    let same: Document = DateTime::parse_from_rfc3339("2020-01-02T05:04:05+02:00").unwrap().into();
+    assert_eq!(same, doc);
+
+    let parsed = Document::from("2020-01-02T03:04:05Z").cast::<DateTime<FixedOffset>>();
+    assert_eq!(parsed, Some(utc.into()));
+    let err = Document::from("yesterday").try_cast::<DateTime<FixedOffset>>().unwrap_err();
+    assert_eq!(err.target, "datetime");
+    let native: DateTime<Utc> = doc.clone().try_into().unwrap();
+    assert_eq!(native, utc);
+
+    let toml: Document = serde_json::from_str(
+        r#"{"at": {"$__toml_private_datetime": "1979-05-27T07:32:00Z"},
+            "local": {"$__toml_private_datetime": "1979-05-27T07:32:00"}}"#,
+    )
+    .unwrap();
+    assert!(toml["at"].is::<DateTime<FixedOffset>>());
+    assert_eq!(toml["local"], "1979-05-27T07:32:00");
+}
+
+#[cfg(feature = "transcode")]
+#[test]
+fn transcode_test() {
+    let json = r#"{"name":"x","vals":[1,-2,3.5,null,true],"nested":{"k":"v"}}"#;
+    let mut packed = vec![];
+    transcode(json.as_bytes(), Format::Json, &mut packed, Format::Msgpack).unwrap();
+    let doc: Document = rmp_serde::from_slice(&packed).unwrap();
+    assert_eq!(doc, serde_json::from_str::<Document>(json).unwrap());
+
+    let mut out = vec![];
+    transcode(&packed[..], Format::Msgpack, &mut out, Format::Json).unwrap();
+    assert_eq!(String::from_utf8(out).unwrap(), json);
+
+    let err = transcode(&b"{\"a\": }"[..], Format::Json, vec![], Format::Json).unwrap_err();
+    assert!(err.to_string().starts_with("JSON transcoding failed"));
+    assert!(transcode(&b"{} {}"[..], Format::Json, vec![], Format::Json).is_err());
+    assert!(transcode(&b"\xc1"[..], Format::Msgpack, vec![], Format::Json).is_err());
+}
+
+#[cfg(feature = "preserve_order")]
+#[test]
+fn preserve_order_test() {
+    let json = r#"{"zebra":1,"apple":{"y":true,"b":null},"mango":[3,2]}"#;
+    let mut doc: Document = serde_json::from_str(json).unwrap();
+    assert_eq!(serde_json::to_string(&doc).unwrap(), json);
+
+    doc["banana"] = 4u64.into();
+    doc.pointer_remove("/apple/y");
+    assert_eq!(
+        serde_json::to_string(&doc).unwrap(),
+        r#"{"zebra":1,"apple":{"b":null},"mango":[3,2],"banana":4}"#
+    );
+
+    // Equality, ordering and hashing ignore key order
+    let reordered: Document =
+        serde_json::from_str(r#"{"banana":4,"mango":[3,2],"apple":{"b":null},"zebra":1}"#).unwrap();
+    assert_eq!(doc, reordered);
+    assert_eq!(doc.cmp(&reordered), std::cmp::Ordering::Equal);
+    let set: std::collections::HashSet<Document> = vec![doc, reordered].into_iter().collect();
+    assert_eq!(set.len(), 1);
+}
+
+// Mapping has the same API with or without preserve_order, so this compiles either way
+#[test]
+fn mapping_api_test() {
+    let mut map: Mapping<UnstructuredType> = ["a", "b", "c"]
+        .iter()
+        .map(|k| (Document::from(*k), Document::from(k.len())))
+        .collect();
+    assert_eq!(*map.get_or_insert_with("d".into(), || 2.into()), 2);
+    assert_eq!(*map.get_or_insert_with("a".into(), || 5.into()), 1);
+    assert_eq!(map.remove(&"b".into()), Some(Document::from(1)));
+    let keys: Vec<_> = map.keys().cloned().collect();
+    assert_eq!(keys, vec!["a", "c", "d"]);
+    assert_eq!(map.values().next_back(), Some(&Document::from(2)));
+    assert_eq!(map.iter().len(), 3);
+
+    map.retain(|_, v| *v == 1);
+    let doc = Document::from(map.clone());
+    assert_eq!(doc, Document::Map(map.into()));
+    assert_eq!(doc["c"], 1);
+}
+
+#[test]
+fn float_format_test() {
+    let doc: Document = serde_json::from_str("[1e-7, 0.125, 1e21, -2.5, 7]").unwrap();
+    let formatted = |format| match &doc {
+        Document::Seq(seq) => seq
+            .iter()
+            .map(|v| match v {
+                Document::Number(n) => n.format_with(format),
+                _ => unreachable!(),
+            })
+            .collect::<Vec<_>>(),
+        _ => unreachable!(),
+    };
+    assert_eq!(
+        formatted(FloatFormat::Shortest),
+        ["0.0000001", "0.125", "1000000000000000000000", "-2.5", "7"]
+    );
+    assert_eq!(
+        formatted(FloatFormat::Fixed(2)),
+        ["0.00", "0.12", "1000000000000000000000.00", "-2.50", "7"]
+    );
+    assert_eq!(formatted(FloatFormat::Scientific), ["1e-7", "1.25e-1", "1e21", "-2.5e0", "7"]);
+    assert_eq!(FloatFormat::default(), FloatFormat::Shortest);
+
+    #[cfg(feature = "serde_json")]
+    {
+        let to_json = |format| {
+            let mut out = vec![];
+            let mut ser =
+                serde_json::Serializer::with_formatter(&mut out, JsonFloatFormatter::new(format));
+            doc.serialize(&mut ser).unwrap();
+            String::from_utf8(out).unwrap()
+        };
+        assert_eq!(to_json(FloatFormat::Scientific), "[1e-7,1.25e-1,1e21,-2.5e0,7]");
+        assert_eq!(to_json(FloatFormat::Fixed(1)), "[0.0,0.1,1000000000000000000000.0,-2.5,7]");
+        let reparsed: Document = serde_json::from_str(&to_json(FloatFormat::Shortest)).unwrap();
+        assert_eq!(reparsed, doc);
+
+        let mut out = vec![];
+        let formatter = JsonFloatFormatter::pretty(FloatFormat::Fixed(3));
+        let mut ser = serde_json::Serializer::with_formatter(&mut out, formatter);
+        Document::Seq(vec![0.5f64.into()].into()).serialize(&mut ser).unwrap();
+        assert_eq!(String::from_utf8(out).unwrap(), "[\n  0.500\n]");
+    }
+}
+
+#[test]
+fn scalar_comparison_matrix_test() {
+    let text = String::from("value");
+    let text_ref = &text;
+    let bytes = vec![1u8, 2, 3];
+    let wrap = |doc: Document| {
+        vec![
+            doc.clone(),
+            Document::Option(Some(Box::new(doc.clone()))),
+            Document::Newtype(Box::new(doc.clone())),
+            Document::Newtype(Box::new(Document::Option(Some(Box::new(doc))))),
+        ]
+    };
+
+    for doc in wrap("value".into()) {
+        assert_eq!(doc, "value");
+        assert_eq!("value", doc);
+        assert_eq!(doc, text);
+        assert_eq!(text, doc);
+        assert_eq!(doc, text_ref);
+        assert_eq!(text_ref, doc);
+        assert_ne!(doc, "other");
+        assert_ne!(doc, 5u8);
+        assert_ne!(doc, true);
+    }
+    for doc in wrap(Document::Bytes(bytes.clone())) {
+        assert_eq!(doc, bytes);
+        assert_eq!(bytes, doc);
+        assert_eq!(doc, &bytes[..]);
+        assert_eq!(&bytes[..], doc);
+        assert_ne!(doc, &b"other"[..]);
+        assert_ne!(doc, "value");
+    }
+    for doc in wrap(true.into()) {
+        assert_eq!(doc, true);
+        assert_eq!(true, doc);
+        assert_ne!(doc, false);
+    }
+    for doc in wrap('c'.into()) {
+        assert_eq!(doc, 'c');
+        assert_eq!('c', doc);
+        assert_ne!(doc, "c");
+    }
+    for doc in wrap(7u8.into()) {
+        assert_eq!(doc, 7u64);
+        assert_eq!(7i32, doc);
+        assert_eq!(doc, 7.0f64);
+        assert_eq!(doc, Number::U8(7));
+        assert_eq!(Number::I16(7), doc);
+        assert_ne!(doc, 8u8);
+        assert_ne!(doc, "7");
+    }
+    assert_ne!(Document::Option(None), "value");
+    assert_ne!(Document::Null, false);
+
+    // Comparing two documents still requires the same wrappers on both sides
+    assert_ne!(Document::Option(Some(Box::new("value".into()))), Document::from("value"));
+}
+
+#[test]
+fn shared_clone_test() {
+    let json = r#"{"a": [1, 2], "b": {"c": true}, "d": [{"e": 1}]}"#;
+    let doc: Document = serde_json::from_str(json).unwrap();
+    let mut copy = doc.clone();
+    copy["a"][0] = 10u64.into();
+    copy.pointer_remove("/d/0/e");
+    copy["a"].merge(Document::from(3u64));
+    for val in copy["d"].iter_mut() {
+        *val = Document::Null;
+    }
+    assert_eq!(doc, serde_json::from_str::<Document>(json).unwrap());
+    assert_eq!(copy["a"], serde_json::from_str::<Document>("[10, 2, 3]").unwrap());
+    assert_eq!(copy["d"][0], Document::Null);
+
+    // Untouched contents stay shared between the copies
+    #[cfg(feature = "shared")]
+    match (&doc["b"], &copy["b"]) {
+        (Document::Map(l), Document::Map(r)) => assert!(Shared::ptr_eq(l, r)),
+        _ => unreachable!(),
+    }
+
+    let workers: Vec<_> = (0..4u64)
+        .map(|i| {
+            let mut doc = doc.clone();
+            std::thread::spawn(move || {
+                doc["a"][1] = i.into();
+                doc
+            })
+        })
+        .collect();
+    for (i, worker) in workers.into_iter().enumerate() {
+        assert_eq!(worker.join().unwrap()["a"][1], i as u64);
+    }
+    assert_eq!(doc["a"][1], 2);
+}
+
+#[test]
+fn sentinel_documents_test() {
+    assert_eq!(Document::null(), Document::Null);
+    assert_eq!(Document::empty_seq(), Document::Seq(Default::default()));
+    assert_eq!(Document::empty_map(), Document::Map(Default::default()));
+    assert!(std::ptr::eq(Document::empty_seq_ref(), Document::empty_seq_ref()));
+    assert!(std::ptr::eq(Document::empty_map_ref(), Document::empty_map_ref()));
+    assert_eq!(Document::empty_map_ref(), &Document::empty_map());
+    assert_eq!(NULL, Document::Null);
+    assert_eq!(UNASSIGNED, Document::Unassigned);
+
+    let lookup = |doc: &Document| -> &'static Document {
+        match doc {
+            Document::Map(map) if map.is_empty() => Document::empty_map_ref(),
+            Document::Seq(seq) if seq.is_empty() => Document::empty_seq_ref(),
+            _ => &NULL,
+        }
+    };
+    assert_eq!(lookup(&Document::empty_map()), &Document::empty_map());
+    assert_eq!(lookup(&Document::empty_seq()).iter().count(), 0);
+    assert!(lookup(&true.into()).is_null());
+    assert!(std::thread::spawn(|| Document::empty_seq_ref().is::<Sequence<UnstructuredType>>())
+        .join()
+        .unwrap());
+}
+
+#[test]
+fn extract_test() {
+    let doc: Document = serde_json::from_str(
+        r#"{"users": [{"name": "a", "age": 30}, {"name": "b", "age": 20}], "meta": {"v": 1}}"#,
+    )
+    .unwrap();
+    assert_eq!(doc.clone_at(".users[1].name").unwrap().unwrap(), "b");
+    assert_eq!(doc.clone_at("..age").unwrap().unwrap(), 30);
+    assert_eq!(doc.clone_at(".users[5]").unwrap(), None);
+    assert_eq!(doc.clone_at(".").unwrap().unwrap(), doc);
+
+    let names = doc.extract(&[".users[*].name", ".meta"]).unwrap();
+    let expected: Document =
+        serde_json::from_str(r#"{"users": [{"name": "a"}, {"name": "b"}], "meta": {"v": 1}}"#)
+            .unwrap();
+    assert_eq!(names, expected);
+
+    // Selected sequence elements keep their index
+    let young = doc.extract(&[".users[?(@.age < 25)]"]).unwrap();
+    assert_eq!(young["users"][0], Document::Null);
+    assert_eq!(young["users"][1]["name"], "b");
+
+    assert_eq!(doc.extract(&[]).unwrap(), Document::empty_map());
+    assert_eq!(doc.extract(&[".missing"]).unwrap(), Document::empty_map());
+    assert_eq!(doc.extract(&["."]).unwrap(), doc);
+    let seq: Document = serde_json::from_str("[1, 2, 3]").unwrap();
+    let padded: Document = serde_json::from_str("[null, 2]").unwrap();
+    assert_eq!(seq.extract(&["[1]"]).unwrap(), padded);
+
+    let err = doc.extract(&[".meta", ".users[?(@.age >)]"]).unwrap_err();
+    assert_eq!(err.selector, ".users[?(@.age >)]");
+    assert!(err.to_string().starts_with("Invalid selector .users[?(@.age >)]: "));
+}
+
+#[cfg(feature = "transcode")]
+#[test]
+fn roundtrip_verify_test() {
+    let doc: Document =
+        serde_json::from_str(r#"{"a": {"b": [1, -2, 3.5, null, true]}, "s": "x"}"#).unwrap();
+    assert!(doc.roundtrips_via(Format::Json).is_ok());
+    assert!(doc.roundtrips_via(Format::Msgpack).is_ok());
+
+    let mut lossy = doc.clone();
+    lossy["a"]["b"][1] = Document::Char('c');
+    lossy["opt"] = Document::Option(Some(Box::new(1u8.into())));
+    lossy["bytes"] = Document::Bytes(vec![1, 2]);
+    let diff = lossy.roundtrips_via(Format::Json).unwrap_err();
+    assert_eq!(diff.format, Format::Json);
+    assert!(diff.error.is_none());
+    let mut paths: Vec<String> = diff.paths.iter().map(|p| p.to_string()).collect();
+    paths.sort();
+    // Options are written as the inner value and read back unwrapped
+    assert_eq!(paths, ["/a/b/1", "/bytes", "/opt"]);
+
+    // MessagePack keeps bytes but not chars
+    let diff = lossy.roundtrips_via(Format::Msgpack).unwrap_err();
+    assert!(diff.paths.contains(&Path::parse("/a/b/1").unwrap()));
+    assert!(!diff.paths.contains(&Path::parse("/bytes").unwrap()));
+
+    // Non-string keys are converted to strings by JSON
+    let mut keys = Document::empty_map();
+    keys[Document::Bool(true)] = 1u8.into();
+    let diff = keys.roundtrips_via(Format::Json).unwrap_err();
+    assert_eq!(diff.to_string(), "Round trip through JSON changed /true");
+
+    // Keys that can't be written as JSON fail outright
+    let mut keys = Document::empty_map();
+    keys[Document::empty_seq()] = 1u8.into();
+    let diff = keys.roundtrips_via(Format::Json).unwrap_err();
+    assert!(diff.error.is_some());
+    assert!(diff.to_string().starts_with("Round trip through JSON failed: "));
+}
+
+#[cfg(all(
+    feature = "serde_json",
+    feature = "serde_yaml",
+    feature = "toml",
+    feature = "ciborium",
+    feature = "rmp-serde"
+))]
+#[test]
+fn format_helpers_test() {
+    let doc = Document::from_json_str(r#"{"name": "x", "ports": [80, 443], "tls": {"on": true}}"#)
+        .unwrap();
+    assert_eq!(Document::from_json_reader(doc.to_json_string().unwrap().as_bytes()).unwrap(), doc);
+    assert_eq!(Document::from_json_str(&doc.to_json_pretty().unwrap()).unwrap(), doc);
+    let mut out = vec![];
+    doc.to_json_writer(&mut out).unwrap();
+    assert_eq!(String::from_utf8(out).unwrap(), doc.to_json_string().unwrap());
+    assert!(Document::from_json_str("{").is_err());
+
+    let yaml = doc.to_yaml_string().unwrap();
+    assert_eq!(Document::from_yaml_str(&yaml).unwrap(), doc);
+    assert_eq!(Document::from_yaml_reader(yaml.as_bytes()).unwrap(), doc);
+
+    assert_eq!(Document::from_toml_str(&doc.to_toml_string().unwrap()).unwrap(), doc);
+    assert_eq!(Document::from_toml_str(&doc.to_toml_pretty().unwrap()).unwrap(), doc);
+    assert!(Document::from(1u8).to_toml_string().is_err());
+
+    let cbor = doc.to_cbor_vec().unwrap();
+    assert_eq!(Document::from_cbor_reader(&cbor[..]).unwrap(), doc);
+
+    let packed = doc.to_msgpack_vec().unwrap();
+    assert_eq!(Document::from_msgpack_slice(&packed).unwrap(), doc);
+    assert_eq!(Document::from_msgpack_reader(&packed[..]).unwrap(), doc);
+    let mut out = vec![];
+    doc.to_msgpack_writer(&mut out).unwrap();
+    assert_eq!(out, packed);
+}
+
+#[test]
+fn serializer_options_test() {
+    #[derive(Serialize)]
+    enum Mode {
+        Fast,
+    }
+
+    struct Pairs(Vec<(&'static str, u8)>);
+
+    impl Serialize for Pairs {
+        fn serialize<S: serde::Serializer>(&self, s: S) -> Result<S::Ok, S::Error> {
+            s.collect_map(self.0.iter().cloned())
+        }
+    }
+
+    let pairs = || Pairs(vec![("b", 1), ("a", 2), ("b", 3)]);
+    let last = Document::new(pairs()).unwrap();
+    assert_eq!(last["b"], 3u8);
+    let first = SerializerOptions {
+        dedup_policy: DedupPolicy::FirstWins,
+        ..Default::default()
+    };
+    assert_eq!(Document::new_with(pairs(), first).unwrap()["b"], 1u8);
+    let error = SerializerOptions {
+        dedup_policy: DedupPolicy::Error,
+        ..Default::default()
+    };
+    assert!(Document::new_with(pairs(), error).is_err());
+
+    let sorted = SerializerOptions {
+        sort_keys: true,
+        ..Default::default()
+    };
+    let doc = Document::new_with(pairs(), sorted).unwrap();
+    let keys: Vec<_> = doc.into_map_iter().map(|(key, _)| key).collect();
+    assert_eq!(keys, vec![Document::from("a"), Document::from("b")]);
+    assert_eq!(serde_json::to_string(&last.with_options(sorted)).unwrap(), r#"{"a":2,"b":3}"#);
+
+    assert_eq!(Document::new(Mode::Fast).unwrap(), "Fast");
+    assert_eq!(Document::new(()).unwrap(), Document::Null);
+    let units = SerializerOptions {
+        unit_as_null: false,
+        ..Default::default()
+    };
+    assert_eq!(Document::new_with(Mode::Fast, units).unwrap(), "Fast");
+    assert_eq!(Document::new_with((), units).unwrap(), Document::Unassigned);
+
+    let base64 = SerializerOptions {
+        bytes_as_base64: true,
+        ..Default::default()
+    };
+    let bytes = Document::Bytes(b"any carnal pleas".to_vec());
+    assert_eq!(Document::new_with(&bytes, base64).unwrap(), "YW55IGNhcm5hbCBwbGVhcw==");
+    let nested = Document::Seq(vec![Document::Bytes(b"ab".to_vec())].into());
+    assert_eq!(serde_json::to_string(&nested.with_options(base64)).unwrap(), r#"["YWI="]"#);
+}
+
+#[test]
+fn deserializer_transcode_test() {
+    let yaml = "name: x\nports: [80, 443]\n";
+    let doc = Document::from_deserializer(serde_yaml::Deserializer::from_str(yaml)).unwrap();
+    assert_eq!(doc["ports"][0], 80u64);
+
+    let mut out = vec![];
+    doc.transcode_to(&mut serde_json::Serializer::new(&mut out)).unwrap();
+    let mut de = serde_json::Deserializer::from_slice(&out);
+    assert_eq!(Document::from_deserializer(&mut de).unwrap(), doc);
+
+    let mut bad = serde_json::Deserializer::from_str("{");
+    assert!(Document::from_deserializer(&mut bad).is_err());
+}
+
+#[cfg(feature = "tracing")]
+#[test]
+fn tracing_value_test() {
+    use std::sync::{Arc, Mutex};
+    use tracing::field::{Field, Visit};
+    use tracing::span::{Attributes, Id, Record};
+    use tracing::{Event, Metadata};
+
+    type Recorded = Arc<Mutex<Vec<String>>>;
+
+    struct Recorder(Recorded);
+
+    impl Visit for Recorder {
+        fn record_i64(&mut self, field: &Field, value: i64) {
+            self.0.lock().unwrap().push(format!("{}: i64 {}", field, value));
+        }
+
+        fn record_u64(&mut self, field: &Field, value: u64) {
+            self.0.lock().unwrap().push(format!("{}: u64 {}", field, value));
+        }
+
+        fn record_str(&mut self, field: &Field, value: &str) {
+            self.0.lock().unwrap().push(format!("{}: str {}", field, value));
+        }
+
+        fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+            self.0.lock().unwrap().push(format!("{}: {:?}", field, value));
+        }
+    }
+
+    struct Subscriber(Recorded);
+
+    impl tracing::Subscriber for Subscriber {
+        fn enabled(&self, _: &Metadata<'_>) -> bool {
+            true
+        }
+
+        fn new_span(&self, span: &Attributes<'_>) -> Id {
+            span.record(&mut Recorder(self.0.clone()));
+            Id::from_u64(1)
+        }
+
+        fn record(&self, _: &Id, values: &Record<'_>) {
+            values.record(&mut Recorder(self.0.clone()));
+        }
+
+        fn record_follows_from(&self, _: &Id, _: &Id) {}
+
+        fn event(&self, event: &Event<'_>) {
+            event.record(&mut Recorder(self.0.clone()));
+        }
+
+        fn enter(&self, _: &Id) {}
+
+        fn exit(&self, _: &Id) {}
+    }
+
+    let recorded = Recorded::default();
+    let doc: Document =
+        serde_json::from_str(r#"{"user": "x", "count": -2, "tags": {"a": [1]}, "gone": null}"#)
+            .unwrap();
+    tracing::subscriber::with_default(Subscriber(recorded.clone()), || {
+        let big = Document::from(u64::MAX);
+        tracing::info!(big = big.as_trace_value(), tags = doc["tags"].as_trace_value());
+        let span = tracing::info_span!(
+            "request",
+            user = tracing::field::Empty,
+            count = tracing::field::Empty,
+            gone = tracing::field::Empty
+        );
+        doc.record_fields(&span);
+    });
+    let mut recorded = recorded.lock().unwrap().clone();
+    recorded.sort();
+    assert_eq!(
+        recorded,
+        vec![
+            format!("big: u64 {}", u64::MAX),
+            "count: i64 -2".to_string(),
+            r#"tags: {"a":[1]}"#.to_string(),
+            "user: str x".to_string(),
+        ]
+    );
+}
+
+#[test]
+fn observability_hooks_test() {
+    use std::sync::Mutex;
+
+    static ENDED: Mutex<Vec<(Operation, OperationStats)>> = Mutex::new(vec![]);
+
+    struct Hooks;
+
+    impl ObservabilityHooks for Hooks {
+        fn on_end(&self, op: Operation, stats: &OperationStats) {
+            ENDED.lock().unwrap().push((op, *stats));
+        }
+    }
+
+    assert!(set_hooks(Hooks).is_ok());
+    assert!(set_hooks(Hooks).is_err());
+
+    let mut de = serde_json::Deserializer::from_str(r#"{"marker": ["filtered", 2]}"#);
+    let doc = Document::from_deserializer(&mut de).unwrap();
+    let doc_size = doc.approximate_size();
+    let empty = Document::from_deserializer(&mut serde_json::Deserializer::from_str("{}")).unwrap();
+    let mut merged = doc.clone();
+    merged.merge(empty);
+    let mut checked: Document = serde_json::from_str("[0, 1, 2, 3, 4, 5, 6, 7, 8, 9]").unwrap();
+    assert!(checked.merge_checked(Document::from(10)).is_empty());
+    let mut resolved: Document = serde_json::from_str(r#"{"resolved": [1]}"#).unwrap();
+    let other: Document = serde_json::from_str(r#"{"resolved": [2, 3, 4, 5, 6]}"#).unwrap();
+    resolved.merge_resolve(other, |_, _, incoming| incoming);
+    let filtered = Document::filter(&[doc], "[0].marker").unwrap();
+    assert!(Document::filter(&[filtered], "[9]").is_err());
+
+    let ended = ENDED.lock().unwrap();
+    let find = |op, nodes, success| {
+        ended.iter().any(|(o, s)| *o == op && s.nodes == nodes && s.success == success)
+    };
+    assert!(find(Operation::Parse, 4, true));
+    assert!(find(Operation::Parse, 1, true));
+    assert!(find(Operation::Merge, 4, true));
+    assert!(find(Operation::Merge, 12, true));
+    assert!(find(Operation::Merge, 7, true));
+    assert!(find(Operation::Filter, 4, true));
+    assert!(find(Operation::Filter, 0, false));
+    assert!(ended.iter().all(|(_, s)| s.success || s.bytes == 0));
+    // Sizes are reported the same way as approximate_size
+    assert!(ended.iter().any(|(o, s)| *o == Operation::Parse && s.bytes == doc_size));
+}
+
+#[test]
+fn document_events_test() {
+    let doc: Document =
+        serde_json::from_str(r#"{"a": [1, {"b": null}], "c": {"d": "x"}, "e": []}"#).unwrap();
+    let events: Vec<_> = doc.clone().into_events().collect();
+    assert_eq!(events.len(), 18);
+    assert_eq!(Document::from_events(events.clone()).unwrap(), doc);
+
+    let mut streamed = vec![];
+    let mut de = serde_json::Deserializer::from_str(r#"{"a": [1, {"b": null}]}"#);
+    Document::stream_events(&mut de, |event| streamed.push(event)).unwrap();
+    assert_eq!(streamed[0], DocumentEvent::StartMap);
+    assert_eq!(streamed[5], DocumentEvent::Key("b".into()));
+    assert_eq!(Document::from_events(streamed).unwrap()["a"][0], 1);
+
+    let wrapped = Document::Option(Some(Box::new(Document::Newtype(Box::new(1u8.into())))));
+    let events: Vec<_> = wrapped.clone().into_events().collect();
+    assert_eq!(events[0], DocumentEvent::WrapSome);
+    assert_eq!(Document::from_events(events).unwrap(), wrapped);
+
+    assert!(Document::from_events(vec![DocumentEvent::StartSeq]).is_err());
+    assert!(Document::from_events(vec![DocumentEvent::EndMap]).is_err());
+    assert!(Document::from_events(vec![DocumentEvent::Key("a".into())]).is_err());
+
+    let matches = |sel: &str| {
+        let mut filter = CompiledSelector::compile(sel).unwrap().event_filter().unwrap();
+        doc.clone()
+            .into_events()
+            .filter_map(|event| filter.push(event).unwrap())
+            .collect::<Vec<Document>>()
+    };
+    assert_eq!(matches(".a[1].b"), vec![Document::Null]);
+    assert_eq!(matches(".c"), vec![doc["c"].clone()]);
+    assert_eq!(matches(".a[*]").len(), 2);
+    assert_eq!(matches("..d"), vec!["x"]);
+    assert_eq!(matches("."), vec![doc.clone()]);
+    assert!(matches(".missing").is_empty());
+    let from_end = CompiledSelector::compile(".a[-1]").unwrap();
+    assert!(from_end.event_filter::<UnstructuredType>().is_err());
+}
+
+#[cfg(feature = "raw")]
+#[test]
+fn raw_document_test() {
+    #[derive(Deserialize, Serialize)]
+    struct Envelope {
+        id: u32,
+        payload: RawDocument,
+    }
+
+    let input = r#"{"id": 1, "payload": {"items": [1, 2], "skipped": "x"}}"#;
+    let envelope: Envelope = serde_json::from_str(input).unwrap();
+    assert_eq!(envelope.payload.format(), FormatHint::Json);
+    assert!(!envelope.payload.is_parsed());
+    assert_eq!(envelope.payload.get().unwrap()["items"][1], 2);
+    assert!(envelope.payload.is_parsed());
+    let out = serde_json::to_string(&envelope).unwrap();
+    assert_eq!(out, r#"{"id":1,"payload":{"items":[1,2],"skipped":"x"}}"#);
+    assert_eq!(envelope.payload.into_document().unwrap()["skipped"], "x");
+
+    let fields = RawDocument::json_fields(r#"{"ok": true, "bad": [1}"#);
+    assert!(matches!(fields, Err(RawError::Json(_))));
+    let fields = RawDocument::json_fields(r#"{"ok": true, "big": [1, 2]}"#).unwrap();
+    assert_eq!(*fields["ok"].get().unwrap(), true);
+    assert!(!fields["big"].is_parsed());
+
+    let broken = RawDocument::new(vec![0xc1], FormatHint::Msgpack);
+    assert!(broken.get().unwrap_err().to_string().starts_with("Invalid raw MessagePack"));
+    assert!(serde_json::to_string(&broken).is_err());
+    assert!(rmp_serde::from_slice::<RawDocument>(&rmp_serde::to_vec(&1).unwrap()).is_err());
+}
+
+#[cfg(feature = "serde_json")]
+#[test]
+fn serde_json_value_conversion_test() {
+    let json = serde_json::json!({"a": [1, -2, 1.5, null], "b": {"c": "x"}, "d": true});
+    let doc = Document::from(json.clone());
+    assert_eq!(doc, serde_json::from_value::<Document>(json.clone()).unwrap());
+    assert_eq!(doc["a"][1], Document::Number(Number::I64(-2)));
+    assert_eq!(serde_json::Value::from(doc), json);
+
+    let mut map = Mapping::new();
+    map.insert(1u8.into(), Document::Option(Some(Box::new('c'.into()))));
+    map.insert("nan".into(), f64::NAN.into());
+    map.insert("bytes".into(), Document::Bytes(vec![1, 2]));
+    map.insert("big".into(), (u64::MAX as u128 + 1).into());
+    let value = serde_json::Value::from(Document::from(map));
+    assert_eq!(value["1"], "c");
+    assert_eq!(value["nan"], serde_json::Value::Null);
+    assert_eq!(value["bytes"], serde_json::json!([1, 2]));
+    assert_eq!(value["big"], 18446744073709551616.0);
+}
+
+#[cfg(all(feature = "serde_yaml", feature = "toml", feature = "rmpv"))]
+#[test]
+fn format_value_conversion_test() {
+    let yaml: serde_yaml::Value = serde_yaml::from_str("a: [1, -2, 1.5]\n3: ~\n").unwrap();
+    let doc = Document::from(yaml.clone());
+    assert_eq!(doc, serde_yaml::from_value::<Document>(yaml.clone()).unwrap());
+    assert_eq!(doc[3], Document::Null);
+    assert_eq!(serde_yaml::Value::from(doc), yaml);
+
+    let toml: toml::Value = toml::from_str("a = [1, 2]\nat = 1979-05-27T07:32:00+01:00").unwrap();
+    let doc = Document::from(toml.clone());
+    assert_eq!(doc["a"][1], Document::Number(Number::I64(2)));
+    #[cfg(feature = "chrono")]
+    assert!(matches!(doc["at"], Document::DateTime(_)));
+    #[cfg(not(feature = "chrono"))]
+    assert_eq!(doc["at"], "1979-05-27T07:32:00+01:00");
+    assert_eq!(std::convert::TryFrom::try_from(doc), Ok(toml));
+    let err = Document::from(vec![Document::Null]).into_toml_value().unwrap_err();
+    assert_eq!(err.reason, CastFailure::Mismatch);
+    let err = Document::from(u64::MAX).into_toml_value().unwrap_err();
+    assert_eq!(err.reason, CastFailure::Overflow);
+
+    let packed = rmpv::Value::Map(vec![
+        (1.into(), rmpv::Value::F32(0.5)),
+        ("bin".into(), rmpv::Value::Binary(vec![1])),
+        ("ext".into(), rmpv::Value::Ext(5, vec![1, 2])),
+    ]);
+    let doc = Document::from(packed.clone());
+    let mut bytes = vec![];
+    rmpv::encode::write_value(&mut bytes, &packed).unwrap();
+    assert_eq!(doc, rmp_serde::from_slice::<Document>(&bytes).unwrap());
+    assert_eq!(rmpv::Value::from(doc), packed);
+}
+
+#[test]
+fn try_from_native_test() {
+    use std::collections::BTreeMap;
+    use std::convert::{TryFrom, TryInto};
+
+    let doc: Document =
+        serde_json::from_str(r#"{"port": 8080, "name": "x", "ids": [1, 2], "opt": null}"#)
+            .unwrap();
+    assert_eq!(u16::try_from(&doc["port"]), Ok(8080));
+    assert_eq!(String::try_from(doc["name"].clone()), Ok("x".to_string()));
+    assert_eq!(Vec::<u32>::try_from(&doc["ids"]), Ok(vec![1, 2]));
+    assert_eq!(Option::<u8>::try_from(&doc["opt"]), Ok(None));
+    assert_eq!(Option::<u8>::try_from(&doc["ids"][0]), Ok(Some(1)));
+    let port: Result<i64, _> = (&doc["port"]).try_into();
+    assert_eq!(port, Ok(8080));
+
+    let err = u8::try_from(&doc["port"]).unwrap_err();
+    assert_eq!(err.reason, CastFailure::Overflow);
+    let err = bool::try_from(&doc["name"]).unwrap_err();
+    assert_eq!(err.to_string(), "Cannot cast string to bool: incompatible type");
+    assert!(Vec::<u8>::try_from(&doc["name"]).is_err());
+
+    let map: Document = serde_json::from_str(r#"{"a": 1, "b": 2}"#).unwrap();
+    let native = BTreeMap::<String, f64>::try_from(&map).unwrap();
+    assert_eq!(native["b"], 2.0);
+    assert_eq!(BTreeMap::<String, u8>::try_from(map).unwrap().len(), 2);
+    assert!(BTreeMap::<String, u8>::try_from(&doc).is_err());
+    let nested: Document = serde_json::from_str("[[1], [2, 3]]").unwrap();
+    assert_eq!(Vec::<Vec<u8>>::try_from(nested), Ok(vec![vec![1], vec![2, 3]]));
+}
+
+#[test]
+fn from_collections_test() {
+    use std::collections::HashMap;
+
+    let tuple = Document::from(("a", 1, true));
+    assert_eq!(tuple, serde_json::from_str::<Document>(r#"["a", 1, true]"#).unwrap());
+    let array = Document::from([1u8, 2, 3]);
+    assert_eq!(array[2], 3);
+    let nested = Document::from([("x", 1), ("y", 2)]);
+    assert_eq!(nested[1][0], "y");
+
+    let mut native = HashMap::new();
+    native.insert("port", 8080);
+    native.insert("workers", 4);
+    let map = Document::from(native);
+    assert_eq!(map["port"], 8080);
+    assert_eq!(map["workers"], 4);
+
+    let collected: Document = vec![("k", Document::from("v"))].into_iter().collect();
+    assert_eq!(collected["k"], "v");
+
+    assert_eq!(Document::from(Some(5u32)), Document::Option(Some(Box::new(5u32.into()))));
+    assert_eq!(Document::from(None::<&str>), Document::Option(None));
+    assert_eq!(Document::from(Some("x")), Document::Option(Some(Box::new("x".into()))));
+}
+
+#[test]
+fn accessor_test() {
+    let mut doc: Document =
+        serde_json::from_str(r#"{"a": null, "list": [1, 2, 3], "name": "héllo", "m": {}}"#)
+            .unwrap();
+    assert_eq!(doc.get("a"), Some(&Document::Null));
+    assert_eq!(doc.get("missing"), None);
+    assert!(doc.contains_key("a"));
+    assert!(!doc.contains_key("missing"));
+    assert_eq!(doc["list"].get(1), Some(&Document::from(2)));
+    assert_eq!(doc["list"].get(-1), Some(&Document::from(3)));
+    assert_eq!(doc["list"].get(5), None);
+    assert!(!doc["name"].contains_key(0));
+
+    *doc.get_mut("a").unwrap() = "set".into();
+    assert_eq!(doc["a"], "set");
+    assert!(doc.get_mut("missing").is_none());
+    assert!(!doc.contains_key("missing"));
+
+    assert!(doc.contains(&"/list/2".parse().unwrap()));
+    assert!(!doc.contains(&"/list/3".parse().unwrap()));
+
+    assert_eq!(doc.len(), 4);
+    assert_eq!(doc["list"].len(), 3);
+    assert_eq!(doc["name"].len(), 5);
+    assert!(doc["m"].is_empty());
+    assert!(Document::Null.is_empty());
+    assert!(!doc.is_empty());
+}
+
+#[test]
+fn sequence_helpers_test() {
+    let mut doc = Document::Unassigned;
+    doc.push(1);
+    doc.push(2);
+    doc.insert(0, "zero");
+    doc.insert(3, 3);
+    assert_eq!(doc, Document::from(("zero", 1, 2, 3)));
+    assert_eq!(doc.pop(), Some(3.into()));
+    assert_eq!(doc.remove(0), Some("zero".into()));
+    assert_eq!(doc.remove(5), None);
+    doc.push(3);
+    doc.push(4);
+    doc.retain(|v| matches!(v.as_usize(), Some(n) if n % 2 == 0));
+    assert_eq!(doc, Document::from([2, 4]));
+
+    let mut list = Document::Null;
+    list.insert(0, true);
+    assert_eq!(list, Document::from([true]));
+    assert_eq!(Document::Null.pop(), None);
+    let mut text = Document::from("abc");
+    assert_eq!(text.remove(0), None);
+    text.retain(|_| false);
+    assert_eq!(text, "abc");
+    assert!(!text.push(1));
+    assert!(!text.insert(0, 1));
+    assert_eq!(text, "abc");
+    let mut empty = Document::Null;
+    assert!(!empty.insert(1, 1));
+    assert!(empty.is_null());
+    assert!(!doc.insert(3, 0));
+    assert_eq!(doc, Document::from([2, 4]));
+}
+
+#[test]
+fn map_helpers_test() {
+    let mut doc: Document =
+        serde_json::from_str(r#"{"a": 1, "b": {"x": 1}, "secret": "s", "n": null}"#).unwrap();
+    let mut keys: Vec<Document> = doc.keys().cloned().collect();
+    keys.sort();
+    assert_eq!(keys, vec!["a", "b", "n", "secret"]);
+    assert_eq!(doc.values().filter(|v| v.is_null()).count(), 1);
+    for val in doc.values_mut() {
+        if val.is_number() {
+            *val = 2.into();
+        }
+    }
+    assert_eq!(doc["a"], 2);
+
+    assert_eq!(doc.remove_key("secret"), Some("s".into()));
+    assert_eq!(doc.remove_key("secret"), None);
+    doc.retain_entries(|_, val| !val.is_null());
+    assert!(!doc.contains_key("n"));
+
+    let other: Document = serde_json::from_str(r#"{"b": {"y": 2}, "c": 3}"#).unwrap();
+    doc.merge_keys(other);
+    assert_eq!(doc["b"], serde_json::from_str::<Document>(r#"{"y": 2}"#).unwrap());
+    assert_eq!(doc["c"], 3);
+
+    let mut seq = Document::from([1, 2]);
+    assert_eq!(seq.keys().count(), 0);
+    assert_eq!(seq.values().count(), 0);
+    assert_eq!(seq.values_mut().count(), 0);
+    assert_eq!(seq.remove_key(0), None);
+    seq.retain_entries(|_, _| false);
+    seq.merge_keys(doc);
+    assert_eq!(seq, Document::from([1, 2]));
+}
+
+#[test]
+fn transform_test() {
+    let mut doc: Document = serde_json::from_str(
+        r#"{"Users": [{"Name": "a", "Token": "t1"}, {"Name": "b", "Token": "t2"}], "Count": "2"}"#,
+    )
+    .unwrap();
+
+    let mut visited = vec![];
+    doc.transform(|path, val| {
+        visited.push(path.to_string());
+        match (path.last(), val) {
+            (Some(Segment::Key(k)), _) if k == "Token" => "***".into(),
+            (_, Document::String(s)) => s.parse::<u64>().map(Document::from).unwrap_or(s.into()),
+            (_, val) => val,
+        }
+    });
+    assert_eq!(doc["Users"][1]["Token"], "***");
+    assert_eq!(doc["Users"][0]["Name"], "a");
+    assert_eq!(doc["Count"], 2u64);
+    assert_eq!(visited.len(), 9);
+    assert_eq!(visited.last().map(String::as_str), Some(""));
+    let position = |path: &str| visited.iter().position(|p| p == path);
+    assert!(position("/Users/0/Name") < position("/Users/0"));
+
+    let mut maps = vec![];
+    doc.transform_keys(|path, key| {
+        maps.push(path.to_string());
+        match key {
+            Document::String(s) => s.to_lowercase().into(),
+            key => key,
+        }
+    });
+    assert_eq!(doc["users"][0]["token"], "***");
+    assert_eq!(doc["count"], 2u64);
+    assert!(!doc.contains_key("Users"));
+    maps.sort();
+    assert_eq!(maps, vec!["", "", "/Users/0", "/Users/0", "/Users/1", "/Users/1"]);
+
+    let mut colliding: Document = serde_json::from_str(r#"{"a": 1, "b": 2}"#).unwrap();
+    colliding.transform_keys(|_, _| "k".into());
+    assert_eq!(colliding.len(), 1);
+}
+
+#[test]
+fn redact_test() {
+    let input = r#"{
+        "user": "a",
+        "auth": {"password": "p", "token": "t"},
+        "sessions": [{"token": "s1"}, {"token": "s2"}],
+        "ids": [1, 2, 3, 4]
+    }"#;
+    let mut doc: Document = serde_json::from_str(input).unwrap();
+    let stars = ReplaceWith::Placeholder("***".into());
+    assert_eq!(doc.redact(&["..token", ".auth"], stars).unwrap(), 3);
+    assert_eq!(doc["auth"], "***");
+    assert_eq!(doc["sessions"][1]["token"], "***");
+    assert_eq!(doc["user"], "a");
+
+    let mut doc: Document = serde_json::from_str(input).unwrap();
+    let removed = doc.redact(&[".ids[?(@ > 1)]", ".auth.password"], ReplaceWith::Remove);
+    assert_eq!(removed.unwrap(), 4);
+    assert_eq!(doc["ids"], Document::from([1]));
+    assert!(!doc["auth"].contains_key("password"));
+
+    // A value inside another matched value is only counted once, whatever the mode
+    let nested = [".auth", ".auth.token", ".sessions"];
+    let mut doc: Document = serde_json::from_str(input).unwrap();
+    assert_eq!(doc.redact(&nested, ReplaceWith::Remove).unwrap(), 2);
+    let mut doc: Document = serde_json::from_str(input).unwrap();
+    let stars = ReplaceWith::Placeholder("***".into());
+    assert_eq!(doc.redact(&nested, stars).unwrap(), 2);
+
+    let err = doc.redact(&[".user", "[?("], ReplaceWith::Remove).unwrap_err();
+    assert_eq!(err.selector, "[?(");
+    assert_eq!(doc["user"], "a");
+
+    // Only the matched place is redacted when its contents are shared with another
+    let creds: Document = serde_json::from_str(r#"{"password": "p"}"#).unwrap();
+    let mut doc = Document::Map(Mapping::new().into());
+    doc["live"] = creds.clone();
+    doc["backup"] = creds;
+    #[cfg(feature = "shared")]
+    match (&doc["live"], &doc["backup"]) {
+        (Document::Map(l), Document::Map(r)) => assert!(Shared::ptr_eq(l, r)),
+        _ => unreachable!(),
+    }
+    let extracted = doc.extract(&[".live.password"]).unwrap();
+    assert_eq!(extracted.to_string(), "{live => {password => p}}");
+    let stars = ReplaceWith::Placeholder("***".into());
+    assert_eq!(doc.redact(&[".live.password"], stars).unwrap(), 1);
+    assert_eq!(doc["live"]["password"], "***");
+    assert_eq!(doc["backup"]["password"], "p");
+}
+
+#[test]
+fn find_test() {
+    let doc: Document = serde_json::from_str(
+        r#"{"db": {"password": "x", "hosts": ["a", "b"]}, "users": [{"password": "y"}]}"#,
+    )
+    .unwrap();
+    let found = doc.find_key("password");
+    let paths: Vec<String> = found.iter().map(|(path, _)| path.to_string()).collect();
+    assert_eq!(paths, vec!["/db/password", "/users/0/password"]);
+    assert_eq!(*found[1].1, "y");
+    assert!(doc.find_key("missing").is_empty());
+
+    // Values wrapped in an Option or Newtype are found once
+    #[derive(Serialize)]
+    struct Token(String);
+
+    #[derive(Serialize)]
+    struct Account {
+        password: Option<String>,
+        token: Token,
+    }
+
+    let account = Account {
+        password: Some("z".into()),
+        token: Token("t".into()),
+    };
+    let wrapped = Document::new(&account).unwrap();
+    let found = wrapped.find_key("password");
+    assert_eq!(found.len(), 1);
+    assert!(matches!(found[0].1, Document::Option(Some(_))));
+    assert_eq!(wrapped.find_key("token").len(), 1);
+
+    let strings = doc.find_all(|_, val| matches!(val, Document::String(_)));
+    assert_eq!(strings.len(), 4);
+    let indexed = doc.find_all(|key, _| key == Some(&Segment::Index(1)));
+    assert_eq!(indexed.len(), 1);
+    assert_eq!(*indexed[0].1, "b");
+    let root = doc.find_all(|key, _| key.is_none());
+    assert_eq!(root[0].0, Path::new());
+}
+
+#[test]
+fn coerce_strings_test() {
+    // Every field of a CSV row is a string
+    let mut row: Document = serde_json::from_str(
+        r#"{"id": "7", "active": "1", "admin": "false", "score": "", "tags": ["1", "2"]}"#,
+    )
+    .unwrap();
+    let schema = Schema::Struct(
+        vec![
+            ("id".to_string(), Schema::Integer),
+            ("active".to_string(), Schema::Bool),
+            ("admin".to_string(), Schema::Bool),
+            ("score".to_string(), Schema::Optional(Box::new(Schema::Float))),
+            ("tags".to_string(), Schema::Seq(Box::new(Schema::Integer))),
+        ]
+        .into_iter()
+        .collect(),
+    );
+    assert!(row.coerce(&schema).is_ok());
+    assert_eq!(row["id"], 7);
+    assert_eq!(row["active"], true);
+    assert_eq!(row["admin"], false);
+    assert_eq!(row["score"], Document::Null);
+    assert_eq!(row["tags"][1], 2);
+
+    let mut flag = Document::from("yes");
+    let errors = flag.coerce(&Schema::Bool).unwrap_err();
+    assert_eq!(errors[0].to_string(), "Cannot coerce string to bool");
+}
+
+#[cfg(feature = "chrono")]
+#[test]
+fn coerce_datetime_test() {
+    let mut doc: Document =
+        serde_json::from_str(r#"["2024-05-01T12:00:00+02:00", "yesterday"]"#).unwrap();
+    let errors = doc.coerce(&Schema::Seq(Box::new(Schema::DateTime))).unwrap_err();
+    let expected = chrono::DateTime::parse_from_rfc3339("2024-05-01T10:00:00Z").unwrap();
+    assert_eq!(doc[0], Document::DateTime(expected));
+    assert_eq!(errors[0].path.to_string(), "/1");
+    assert_eq!(errors[0].expected, "datetime");
+}
+
+#[cfg(feature = "query")]
+#[test]
+fn query_test() {
+    let doc: Document = serde_json::from_str(
+        r#"{
+            "users": [
+                {"name": "Ann", "age": 31, "roles": ["admin", "dev"]},
+                {"name": "Bob", "age": 17, "roles": []},
+                {"name": "Cy", "age": 45, "roles": ["dev"], "email": null}
+            ],
+            "limits": {"cpu": 2, "mem": 512}
+        }"#,
+    )
+    .unwrap();
+    let query = |q: &str| doc.query(q).unwrap();
+    let json = |s: &str| serde_json::from_str::<Document>(s).unwrap();
+
+    assert_eq!(query(".users[0].name"), vec!["Ann"]);
+    assert_eq!(query(r#".users[-1]["name"]"#), vec!["Cy"]);
+    assert_eq!(query(".users[].name"), vec!["Ann", "Bob", "Cy"]);
+    assert_eq!(query(".users[1:].name?"), Vec::<Document>::new());
+    assert_eq!(query("[.users[1:][] | .name]"), vec![json(r#"["Bob", "Cy"]"#)]);
+    assert_eq!(query(".missing.deeper"), vec![Document::Null]);
+    assert_eq!(query(".users | map(.age) | add"), vec![Document::from(93)]);
+    assert_eq!(
+        query("[.users[] | select(.age > 18 and (.roles | length) > 0) | .name]"),
+        vec![json(r#"["Ann", "Cy"]"#)]
+    );
+    assert_eq!(
+        query(r#".users[0] | "\(.name) has \(.roles | length) roles""#),
+        vec!["Ann has 2 roles"]
+    );
+    assert_eq!(query(".limits | keys"), vec![json(r#"["cpu", "mem"]"#)]);
+    assert_eq!(query(".limits | to_entries | map(.value) | add"), vec![Document::from(514)]);
+    assert_eq!(
+        query(".limits | with_entries({key: (.key | ascii_upcase), value})"),
+        vec![json(r#"{"CPU": 2, "MEM": 512}"#)]
+    );
+    assert_eq!(
+        query("{total: (.users | length), names: [.users[].name | ascii_downcase]}"),
+        vec![json(r#"{"total": 3, "names": ["ann", "bob", "cy"]}"#)]
+    );
+    assert_eq!(query(".users[2].email // \"none\""), vec!["none"]);
+    assert_eq!(query("1 + 2 * 3 - 4 / 2"), vec![Document::from(5)]);
+    assert_eq!(query("7 / 2"), vec![Document::from(3.5)]);
+    assert_eq!(query("-(.limits.cpu) % 2"), vec![Document::from(0)]);
+    assert_eq!(query("(1, 2) * 10"), vec![Document::from(10), Document::from(20)]);
+    assert_eq!(
+        query(r#"[.users[].age | if . < 18 then "minor" elif . < 40 then "adult" else "old" end]"#),
+        vec![json(r#"["adult", "minor", "old"]"#)]
+    );
+    assert_eq!(query("[.users[].age] | sort | reverse | first"), vec![Document::from(45)]);
+    assert_eq!(query(".users | sort_by(.name) | map(.name) | join(\",\")"), vec!["Ann,Bob,Cy"]);
+    assert_eq!(query("[range(3)]"), vec![json("[0, 1, 2]")]);
+    assert_eq!(query("[range(1048576)] | length"), vec![Document::from(1048576)]);
+    assert!(doc.query("range(1e300)").is_err());
+    assert!(doc.query("range(1e12)").is_err());
+    assert!(doc.query("range(1048576), range(1)").is_ok());
+    assert!(doc.query("range(1, 2; 1048576)").is_err());
+    assert_eq!(query("\"a,b\" | split(\",\")"), vec![json(r#"["a", "b"]"#)]);
+    assert_eq!(query(r#"[.. | select(type == "number")] | length"#), vec![Document::from(5)]);
+    assert_eq!(query("{a: 1} * {a: {b: 2}} | .a.b"), vec![Document::from(2)]);
+    assert_eq!(
+        query(r#"[{}, [1], "a", 1, null] | sort | map(type)"#),
+        vec![json(r#"["null", "number", "string", "array", "object"]"#)]
+    );
+    assert_eq!(query("1 == 1.0"), vec![Document::from(true)]);
+    assert_eq!(query("-3, 2.5 | length"), vec![Document::from(3), Document::from(2.5)]);
+
+    assert!(doc.query(".users[").is_err());
+    let err = doc.query("nope(1)").unwrap_err();
+    assert!(matches!(&err, Error::Select(msg) if msg.contains("Unknown function nope/1")));
+    let err = doc.query(".users.name").unwrap_err();
+    assert!(matches!(err, Error::Select(_)));
+    assert_eq!(err.to_string(), r#"Cannot index array with string ("name")"#);
+    assert!(doc.query("1 / 0").is_err());
+
+    let compiled = Query::compile(".a + 1").unwrap();
+    let inputs: Vec<Document> = vec![json(r#"{"a": 1}"#), json(r#"{"a": 2.5}"#)];
+    let results: Vec<_> = inputs.iter().map(|doc| compiled.run(doc).unwrap()).collect();
+    assert_eq!(results, vec![vec![Document::from(2)], vec![Document::from(3.5)]]);
+
+    // Deeply nested queries are rejected rather than overflowing the stack
+    let nest = |open: &str, inner: &str, close: &str, n: usize| {
+        format!("{}{}{}", open.repeat(n), inner, close.repeat(n))
+    };
+    assert_eq!(query(&nest("(", ".limits.cpu", ")", 120)), vec![Document::from(2)]);
+    assert_eq!(query(&nest("[", "1", "]", 120)).len(), 1);
+    let deep = [
+        nest("(", ".", ")", 300),
+        nest("[", "1", "]", 300),
+        nest("{a: ", "1", "}", 300),
+        nest("-", "1", "", 5000),
+        nest("if ", ". then . else .", " end", 300),
+        nest("\"\\(", ".", ")\"", 300),
+        nest("", ".", "[]", 5000),
+        vec![".a"; 5000].concat(),
+        vec!["."; 5000].join(" | "),
+        vec!["1"; 5000].join(" + "),
+        format!("if . then . {} end", "elif . then . ".repeat(5000)),
+    ];
+    for q in deep.iter() {
+        let err = doc.query(q).unwrap_err();
+        assert!(matches!(&err, Error::Parse(msg) if msg.contains("128 levels")), "{}", err);
+    }
+}
+
+#[cfg(feature = "jmespath")]
+#[test]
+fn jmespath_test() {
+    let json = |s: &str| serde_json::from_str::<Document>(s).unwrap();
+    let doc = json(
+        r#"{
+        "locations": [
+            {"name": "Seattle", "state": "WA", "pop": 750},
+            {"name": "New York", "state": "NY", "pop": 8300},
+            {"name": "Bellevue", "state": "WA", "pop": 150}
+        ],
+        "nested": [[1, 2], [3], 4],
+        "ops": {"a": {"n": 1}, "b": {"n": 2}, "c": {"x": 3}}
+    }"#,
+    );
+    let eval = |expression: &str| doc.jmespath(expression).unwrap();
+    assert_eq!(
+        eval("locations[?state=='WA'].name"),
+        json(r#"["Seattle", "Bellevue"]"#)
+    );
+    assert_eq!(eval("locations[0].name"), Document::from("Seattle"));
+    assert_eq!(eval("locations[-1].pop"), Document::from(150));
+    assert_eq!(eval("locations[*].state | [0]"), Document::from("WA"));
+    assert_eq!(eval("locations[1:].name"), json(r#"["New York", "Bellevue"]"#));
+    assert_eq!(eval("locations[::-2].name"), json(r#"["Bellevue", "Seattle"]"#));
+    assert_eq!(eval("locations[1::9223372036854775807].name"), json(r#"["New York"]"#));
+    assert_eq!(eval("locations[1::-9223372036854775807].name"), json(r#"["New York"]"#));
+    assert_eq!(eval("locations[::-9223372036854775808].name"), json(r#"["Bellevue"]"#));
+    assert_eq!(eval("nested[]"), json(r#"[1, 2, 3, 4]"#));
+    assert_eq!(eval("sort(ops.*.n)"), json(r#"[1, 2]"#));
+    assert_eq!(eval("missing.key"), Document::Null);
+    assert_eq!(eval("locations[?pop > `500` && state == 'WA'] | length(@)"), Document::from(1));
+    assert_eq!(eval("locations[?!(pop < `500`)].name"), json(r#"["Seattle", "New York"]"#));
+    assert_eq!(eval("max_by(locations, &pop).name"), Document::from("New York"));
+    assert_eq!(
+        eval("sort_by(locations, &pop)[].name"),
+        json(r#"["Bellevue", "Seattle", "New York"]"#)
+    );
+    assert_eq!(eval("sum(locations[].pop)"), Document::from(9200));
+    assert_eq!(eval("join(', ', locations[:2].name)"), Document::from("Seattle, New York"));
+    assert_eq!(eval("missing || 'fallback'"), Document::from("fallback"));
+    assert_eq!(
+        eval("locations[0].[name, state]"),
+        json(r#"["Seattle", "WA"]"#)
+    );
+    let hash = eval("locations[0].{city: name, size: pop}");
+    assert_eq!(hash["city"], Document::from("Seattle"));
+    assert_eq!(hash["size"], Document::from(750));
+    assert_eq!(eval("map(&length(@), nested[:2])"), json(r#"[2, 1]"#));
+    assert_eq!(eval("`[1, 2]` == `[1, 2]`"), Document::from(true));
+    assert_eq!(eval("to_string(nested[0])"), Document::from("[1,2]"));
+    assert!(doc.jmespath("length(`1`)").is_err());
+    assert!(doc.jmespath("locations[").is_err());
+
+    let compiled = JmesPath::compile("a.b").unwrap();
+    assert_eq!(compiled.search(&json(r#"{"a": {"b": true}}"#)).unwrap(), Document::from(true));
+
+    let wide = |n: Number| Document::Number(n);
+    assert!(wide(Number::I128(i128::MIN)).jmespath("abs(@)").is_err());
+    let max = wide(Number::U128(u128::MAX)).jmespath("abs(@)").unwrap();
+    assert_eq!(max, wide(Number::U128(u128::MAX)));
+    assert_eq!(eval("abs(`-3`)"), Document::from(3));
+    let seq = |a: Number, b: Number| Document::Seq(vec![wide(a), wide(b)].into());
+    let sum = seq(Number::I128(i128::MAX), Number::U8(1)).jmespath("sum(@)").unwrap();
+    assert_eq!(sum, Document::from(i128::MAX as f64 + 1.0));
+    let sum = seq(Number::U128(u128::MAX), Number::I8(-1)).jmespath("sum(@)").unwrap();
+    assert_eq!(sum, Document::from(u128::MAX as f64 - 1.0));
+    assert_eq!(eval("sum(locations[*].pop)"), Document::from(9200));
+
+    // Deeply nested expressions are rejected rather than overflowing the stack
+    let nest = |open: &str, inner: &str, close: &str, n: usize| {
+        format!("{}{}{}", open.repeat(n), inner, close.repeat(n))
+    };
+    assert_eq!(eval(&nest("(", "locations[0].pop", ")", 120)), Document::from(750));
+    assert_eq!(eval(&nest("!", "missing", "", 121)), Document::from(true));
+    let deep = [
+        nest("(", "a", ")", 300),
+        nest("[", "a", "]", 300),
+        nest("!", "a", "", 5000),
+        nest("!(", "a", ")", 100),
+        nest("map(&", "a", ", @)", 300),
+        nest("", "a", "[]", 5000),
+        vec!["a"; 5000].join("."),
+        vec!["a"; 5000].join(" || "),
+    ];
+    for expression in deep.iter() {
+        let err = doc.jmespath(expression).unwrap_err();
+        assert!(matches!(&err, Error::Parse(msg) if msg.contains("128 levels")), "{}", err);
+    }
+}
+
+#[test]
+fn project_test() {
+    let json = |s: &str| serde_json::from_str::<Document>(s).unwrap();
+    let doc = json(
+        r#"{
+        "id": 7,
+        "password": "hunter2",
+        "owner": {"name": "a", "email": "a@example.com"},
+        "items": [{"sku": "x", "price": 1}, {"sku": "y", "price": 2}],
+        "matrix": [[1, 2], [3]]
+    }"#,
+    );
+
+    let mut shape = Mapping::new();
+    shape.insert("id".into(), Document::new(()).unwrap());
+    shape.insert("missing".into(), Document::Null);
+    let projected = doc.project(&Document::from(shape));
+    assert_eq!(projected, json(r#"{"id": 7}"#));
+
+    let shape = json(
+        r#"{"owner": {"name": null, "password": true}, "items": {"sku": null},
+            "matrix": [[]], "password": false}"#,
+    );
+    assert_eq!(
+        doc.project(&shape),
+        json(
+            r#"{"owner": {"name": "a"}, "items": [{"sku": "x"}, {"sku": "y"}],
+                "matrix": [[1, 2], [3]]}"#
+        )
+    );
+    assert_eq!(doc.project(&json(r#"{"id": {"deeper": null}}"#)), json(r#"{"id": null}"#));
+    assert_eq!(doc.project(&Document::Null), doc);
+}
+
+#[test]
+fn cursor_test() {
+    let mut doc: Document =
+        serde_json::from_str(r#"{"a": {"b": [1, 2, 3], "c": "x"}, "d": true}"#).unwrap();
+
+    let root = doc.cursor();
+    assert!(root.up().is_none());
+    assert!(root.siblings().is_empty());
+    assert!(root.down("missing").is_none());
+    let b = root.down("a").and_then(|a| a.down("b")).unwrap();
+    assert_eq!(b.path().to_string(), "/a/b");
+    assert_eq!(b.children().len(), 3);
+    let siblings = b.siblings();
+    assert_eq!(siblings.len(), 1);
+    assert_eq!(siblings[0].path().to_string(), "/a/c");
+    let last = b.down(2).unwrap();
+    assert_eq!(*last.value(), 3);
+    assert_eq!(last.up().and_then(|b| b.up()).unwrap().path().to_string(), "/a");
+    assert!(last.down(0).is_none());
+
+    let mut cursor = doc.cursor_mut();
+    assert!(cursor.down("a").and_then(|a| a.down("nope")).is_none());
+    assert_eq!(cursor.path().to_string(), "/a");
+    cursor.down("b").and_then(|b| b.down(1)).unwrap();
+    *cursor.value_mut() = 20.into();
+    let siblings = cursor.siblings();
+    let indices: Vec<Segment> = siblings.iter().map(|(segment, _)| segment.clone()).collect();
+    assert_eq!(indices, vec![Segment::Index(0), Segment::Index(2)]);
+    for (_, sibling) in siblings {
+        *sibling = Document::Null;
+    }
+    cursor.up().and_then(|b| b.up()).and_then(|a| a.down("c")).unwrap();
+    *cursor.into_mut() = "y".into();
+    assert_eq!(doc["a"]["b"][0], Document::Null);
+    assert_eq!(doc["a"]["b"][1], 20);
+    assert_eq!(doc["a"]["c"], "y");
+}
+
+#[cfg(feature = "selector")]
+#[test]
+fn view_and_splice_test() {
+    #[derive(Debug, Deserialize, Serialize, PartialEq)]
+    struct Limits {
+        cpu: u32,
+        mem: Option<u64>,
+    }
+
+    let mut doc: Document =
+        serde_json::from_str(r#"{"services": {"api": {"limits": {"cpu": 2}}}}"#).unwrap();
+    let limits: Limits = doc.view_as(".services.api.limits").unwrap();
+    assert_eq!(limits, Limits { cpu: 2, mem: None });
+    assert_eq!(doc.view_as::<u32>("/services/api/limits/cpu").unwrap(), 2);
+    match doc.view_as::<Limits>(".services.api") {
+        Err(ViewError::Deserialize(_)) => {}
+        other => panic!("Expected a deserialize error, got {:?}", other),
+    }
+    match doc.view_as::<Limits>(".services[") {
+        Err(ViewError::Selector(e)) => assert_eq!(e.selector, ".services["),
+        other => panic!("Expected a selector error, got {:?}", other),
+    }
+
+    let limits = Limits { cpu: 4, mem: Some(512) };
+    doc.splice(".services.worker.limits", &limits).unwrap();
+    assert_eq!(doc["services"]["worker"]["limits"]["mem"], 512);
+    assert_eq!(doc.view_as::<Limits>(".services.worker.limits").unwrap(), limits);
+    doc.splice(".services.api", "disabled").unwrap();
+    assert_eq!(doc["services"]["api"], "disabled");
+}
+
+#[test]
+fn extras_test() {
+    #[derive(Debug, Deserialize, Serialize, PartialEq)]
+    struct Meta {
+        version: u32,
+        #[serde(flatten)]
+        extras: Extras,
+    }
+
+    #[derive(Debug, Deserialize, Serialize, PartialEq)]
+    struct Config {
+        name: String,
+        meta: Meta,
+        #[serde(flatten)]
+        extras: Extras,
+    }
+
+    let doc: Document = serde_json::from_str(
+        r#"{"name": "a", "debug": true, "meta": {"version": 2, "owner": {"team": "x"}}}"#,
+    )
+    .unwrap();
+    let config: Config = doc.clone().try_into().unwrap();
+    assert_eq!(config.extras.get("debug"), Some(&Document::Bool(true)));
+    assert_eq!(config.extras.len(), 1);
+    assert_eq!(config.meta.extras.get("owner").unwrap()["team"], "x");
+    assert_eq!(Document::new(&config).unwrap(), doc);
+    let json = serde_json::to_string(&config).unwrap();
+    assert_eq!(serde_json::from_str::<Config>(&json).unwrap(), config);
+
+    let bare: Config = serde_json::from_str(r#"{"name": "b", "meta": {"version": 1}}"#).unwrap();
+    assert!(bare.extras.is_empty());
+    assert_eq!(bare.meta.extras.clone().into_document(), Document::from(Mapping::new()));
+
+    let mut extras = Extras::new();
+    extras.insert("k".into(), 1.into());
+    let standalone = Document::new(&extras).unwrap();
+    assert_eq!(standalone["k"], 1);
+    assert_eq!(standalone.try_into::<Extras>().unwrap(), extras);
+}
+
+#[test]
+fn flatten_round_trip_test() {
+    #[derive(Debug, Deserialize, Serialize, PartialEq)]
+    struct Address {
+        city: String,
+        #[serde(flatten)]
+        geo: Geo,
+    }
+
+    #[derive(Debug, Deserialize, Serialize, PartialEq)]
+    struct Geo {
+        lat: f64,
+        lon: f64,
+    }
+
+    #[derive(Debug, Deserialize, Serialize, PartialEq)]
+    #[serde(tag = "kind")]
+    enum Internal {
+        Person { age: u8 },
+        Company,
+    }
+
+    #[derive(Debug, Deserialize, Serialize, PartialEq)]
+    #[serde(tag = "t", content = "c")]
+    enum Adjacent {
+        Id(u32),
+        Named { name: String },
+        Unknown,
+    }
+
+    #[derive(Debug, Deserialize, Serialize, PartialEq)]
+    enum External {
+        Plain,
+        Wrapped(i64),
+        Pair(u8, u8),
+        Fields { x: i8 },
+    }
+
+    #[derive(Debug, Deserialize, Serialize, PartialEq)]
+    struct Contact<E> {
+        id: u64,
+        #[serde(flatten)]
+        address: Address,
+        #[serde(flatten)]
+        extra: E,
+    }
+
+    fn round_trip<E>(contact: Contact<E>, expected: &str)
+    where
+        E: Serialize + serde::de::DeserializeOwned + PartialEq + std::fmt::Debug,
+    {
+        let doc = Document::new(&contact).unwrap();
+        assert_eq!(doc, serde_json::from_str::<Document>(expected).unwrap());
+        assert_eq!(doc.try_into::<Contact<E>>().unwrap(), contact);
+    }
+
+    fn contact<E>(extra: E) -> Contact<E> {
+        let geo = Geo { lat: 1.5, lon: -2.0 };
+        let address = Address { city: "x".into(), geo };
+        Contact { id: 1, address, extra }
+    }
+    let expect = |fields: &str| {
+        format!(r#"{{"id": 1, "city": "x", "lat": 1.5, "lon": -2.0{}}}"#, fields)
+    };
+    round_trip(contact(Internal::Person { age: 30 }), &expect(r#", "kind": "Person", "age": 30"#));
+    round_trip(contact(Internal::Company), &expect(r#", "kind": "Company""#));
+    round_trip(contact(Adjacent::Id(7)), &expect(r#", "t": "Id", "c": 7"#));
+    let named = Adjacent::Named { name: "n".into() };
+    round_trip(contact(named), &expect(r#", "t": "Named", "c": {"name": "n"}"#));
+    round_trip(contact(Adjacent::Unknown), &expect(r#", "t": "Unknown""#));
+    round_trip(contact(External::Wrapped(-3)), &expect(r#", "Wrapped": -3"#));
+    round_trip(contact(External::Pair(1, 2)), &expect(r#", "Pair": [1, 2]"#));
+    round_trip(contact(External::Fields { x: 4 }), &expect(r#", "Fields": {"x": 4}"#));
+    round_trip(contact(Some(Internal::Company)), &expect(r#", "kind": "Company""#));
+    round_trip(contact(None::<Internal>), &expect(""));
+
+    // Enums that are values of a flattened map keep their variant
+    #[derive(Debug, Deserialize, Serialize, PartialEq)]
+    struct Modes {
+        id: u64,
+        #[serde(flatten)]
+        modes: std::collections::BTreeMap<String, External>,
+    }
+    let mut modes = Modes { id: 1, modes: Default::default() };
+    modes.modes.insert("a".to_string(), External::Plain);
+    modes.modes.insert("b".to_string(), External::Wrapped(9));
+    let doc = Document::new(&modes).unwrap();
+    let expected = r#"{"id": 1, "a": "Plain", "b": {"Wrapped": 9}}"#;
+    assert_eq!(doc, serde_json::from_str::<Document>(expected).unwrap());
+    assert_eq!(doc.try_into::<Modes>().unwrap(), modes);
+}
+
+#[test]
+fn enum_representations_test() {
+    #[derive(Debug, Deserialize, Serialize, PartialEq)]
+    struct Inner {
+        y: u8,
+    }
+
+    #[derive(Debug, Deserialize, Serialize, PartialEq)]
+    #[serde(tag = "type")]
+    enum Internal {
+        Fields { x: u8 },
+        Unit,
+        Wrapped(Inner),
+    }
+
+    #[derive(Debug, Deserialize, Serialize, PartialEq)]
+    #[serde(tag = "t", content = "c")]
+    enum Adjacent {
+        Fields { x: u8 },
+        Unit,
+        Wrapped(u8),
+        Pair(u8, u8),
+    }
+
+    #[derive(Debug, Deserialize, Serialize, PartialEq)]
+    #[serde(untagged)]
+    enum Untagged {
+        Fields { x: u8 },
+        Wrapped(u8),
+        Pair(u8, u8),
+    }
+
+    #[derive(Debug, Deserialize, Serialize, PartialEq)]
+    enum External {
+        Fields { x: u8 },
+        Unit,
+        Wrapped(u8),
+        Pair(u8, u8),
+    }
+
+    fn check<E>(value: E, expected: &str)
+    where
+        E: Serialize + serde::de::DeserializeOwned + PartialEq + std::fmt::Debug,
+    {
+        let parsed = serde_json::from_str::<Document>(expected).unwrap();
+        let doc = Document::new(&value).unwrap();
+        assert_eq!(doc, parsed);
+        assert_eq!(parsed.try_into::<E>().unwrap(), value);
+        let items = Document::new([Some(&value), None]).unwrap();
+        assert_eq!(items.try_into::<Vec<Option<E>>>().unwrap(), vec![Some(value), None]);
+    }
+
+    check(Internal::Fields { x: 1 }, r#"{"type": "Fields", "x": 1}"#);
+    check(Internal::Unit, r#"{"type": "Unit"}"#);
+    check(Internal::Wrapped(Inner { y: 2 }), r#"{"type": "Wrapped", "y": 2}"#);
+    check(Adjacent::Fields { x: 1 }, r#"{"t": "Fields", "c": {"x": 1}}"#);
+    check(Adjacent::Unit, r#"{"t": "Unit"}"#);
+    check(Adjacent::Wrapped(3), r#"{"t": "Wrapped", "c": 3}"#);
+    check(Adjacent::Pair(1, 2), r#"{"t": "Pair", "c": [1, 2]}"#);
+    check(Untagged::Fields { x: 1 }, r#"{"x": 1}"#);
+    check(Untagged::Wrapped(3), "3");
+    check(Untagged::Pair(1, 2), "[1, 2]");
+    check(External::Fields { x: 1 }, r#"{"Fields": {"x": 1}}"#);
+    check(External::Unit, r#""Unit""#);
+    check(External::Wrapped(3), r#"{"Wrapped": 3}"#);
+    check(External::Pair(1, 2), r#"{"Pair": [1, 2]}"#);
+
+    // Wrapped documents and struct variants given as sequences
+    let wrapped = Document::Option(Some(Box::new(Document::from("Unit"))));
+    assert_eq!(wrapped.try_into::<External>().unwrap(), External::Unit);
+    let wrapped = Document::Newtype(Box::new(Document::from("Unit")));
+    assert_eq!(wrapped.try_into::<External>().unwrap(), External::Unit);
+    let seq = serde_json::from_str::<Document>(r#"{"Fields": [4]}"#).unwrap();
+    assert_eq!(seq.try_into::<External>().unwrap(), External::Fields { x: 4 });
+}
+
+#[test]
+fn deserialize_error_path_test() {
+    #[derive(Debug, Deserialize)]
+    struct User {
+        #[allow(dead_code)]
+        age: u32,
+    }
+
+    #[derive(Debug, Deserialize)]
+    struct Users {
+        #[allow(dead_code)]
+        users: Vec<Option<User>>,
+    }
+
+    let json = |s: &str| serde_json::from_str::<Document>(s).unwrap();
+    let doc = json(r#"{"users": [{"age": 1}, null, {"age": 2}, {"age": "x"}]}"#);
+    let err = doc.try_into::<Users>().unwrap_err();
+    assert_eq!(err.path(), Some(&"/users/3/age".parse().unwrap()));
+    assert_eq!(
+        err.to_string(),
+        r#"Invalid type string "x". Expected u32 at /users/3/age"#
+    );
+    assert!(std::error::Error::source(&err).is_some());
+
+    let err = json(r#"{"users": [{}]}"#).try_into::<Users>().unwrap_err();
+    assert_eq!(err.to_string(), "Missing field age at /users/0");
+
+    // Deserializing by reference reports the same paths
+    #[derive(Debug, Deserialize)]
+    struct Server {
+        #[allow(dead_code)]
+        port: u16,
+    }
+
+    #[derive(Debug, Deserialize)]
+    struct Servers {
+        #[allow(dead_code)]
+        servers: Vec<Server>,
+    }
+
+    let doc = json(r#"{"servers": [{"port": "x"}]}"#);
+    let err = doc.to_value::<Servers>().unwrap_err();
+    assert_eq!(err.path(), Some(&"/servers/0/port".parse().unwrap()));
+
+    // Errors at the root have no path
+    let err = json(r#""x""#).try_into::<u32>().unwrap_err();
+    assert_eq!(err.path(), None);
+    assert!(matches!(&err, Error::Deserialize(e) if std::error::Error::source(e).is_none()));
+}
+
+#[test]
+fn error_test() {
+    use std::error::Error as _;
+
+    let doc: Document = serde_json::from_str(r#"{"a": [1, 2]}"#).unwrap();
+    assert!(matches!(doc.select(".a["), Err(Error::Parse(_))));
+    assert!(matches!(doc.select(".a[*]"), Err(Error::Select(_))));
+    assert!(matches!(doc.select_all(".a["), Err(Error::Parse(_))));
+    assert!(matches!(CompiledSelector::compile("[?("), Err(Error::Parse(_))));
+    let docs = [doc.clone()];
+    assert!(matches!(Document::filter(&docs, "[0"), Err(Error::Parse(_))));
+    assert!(matches!(Document::filter(&docs, "[3].a"), Err(Error::Filter(_))));
+
+    let err = doc.clone().try_into::<u8>().unwrap_err();
+    assert!(matches!(err, Error::Deserialize(_)));
+    assert!(err.source().is_some());
+    assert!(matches!(Path::parse(".a[x]"), Err(Error::Parse(_))));
+    assert!(matches!("/a~2".parse::<Path>(), Err(Error::Parse(_))));
+    assert!(matches!(Document::from_base64("a*"), Err(Error::Parse(_))));
+    assert!(matches!(Document::from(1).unflatten(), Err(Error::Build(_))));
+    let events = vec![DocumentEvent::StartSeq];
+    assert!(matches!(Document::from_events(events), Err(Error::Build(_))));
+    let mut seq: Document = serde_json::from_str("[1]").unwrap();
+    assert!(matches!(seq.pointer_or_insert("/18446744073709551615"), Err(Error::Select(_))));
+    let err: Error = Document::from("x").try_cast::<u8>().unwrap_err().into();
+    assert!(matches!(err, Error::Cast(_)));
+    let err: Error = doc.view_as::<u8>(".a[").unwrap_err().into();
+    assert!(matches!(err, Error::Parse(_)));
+    assert!(err.source().is_none());
+    let err: Error = doc.view_as::<u8>(".a[*]").unwrap_err().into();
+    assert!(matches!(err, Error::Select(_)));
+    let deep = format!("[?{}@{}]", "(".repeat(1000), ")".repeat(1000));
+    let err: Error = doc.clone().redact(&[&deep], ReplaceWith::Remove).unwrap_err().into();
+    assert!(matches!(err, Error::Select(_)));
+    let err: Error = doc.extract(&[".a", "[?("]).unwrap_err().into();
+    assert!(matches!(err, Error::Parse(msg) if msg.starts_with("Invalid selector [?(")));
+}
+
+#[test]
+fn to_value_test() {
+    #[derive(Debug, Deserialize, PartialEq)]
+    struct Borrowed<'a> {
+        name: &'a str,
+        #[serde(borrow)]
+        tags: Vec<&'a str>,
+        kind: Kind,
+        shape: Option<Shape>,
+    }
+
+    #[derive(Debug, Deserialize, PartialEq)]
+    enum Kind {
+        Unit,
+        Pair(u8, u8),
+    }
+
+    #[derive(Debug, Deserialize, PartialEq)]
+    #[serde(tag = "type")]
+    enum Shape {
+        Circle { r: f64 },
+    }
+
+    let json = |s: &str| serde_json::from_str::<Document>(s).unwrap();
+    let doc = json(
+        r#"{"name": "n", "tags": ["a", "b"], "kind": {"Pair": [1, 2]},
+            "shape": {"type": "Circle", "r": 1.5}}"#,
+    );
+    let original = doc.clone();
+    let value = doc.to_value::<Borrowed>().unwrap();
+    assert_eq!(
+        value,
+        Borrowed {
+            name: "n",
+            tags: vec!["a", "b"],
+            kind: Kind::Pair(1, 2),
+            shape: Some(Shape::Circle { r: 1.5 }),
+        }
+    );
+    match &doc["name"] {
+        Document::String(name) => assert!(std::ptr::eq(value.name.as_ptr(), name.as_ptr())),
+        other => panic!("unexpected {:?}", other),
+    }
+    let bytes = Document::Bytes(vec![1, 2]);
+    assert_eq!(bytes.to_value::<&[u8]>().unwrap(), &[1, 2]);
+    assert_eq!(doc, original);
+    assert_eq!(Document::from("Unit").to_value::<Kind>().unwrap(), Kind::Unit);
+    assert_eq!(doc.to_value::<Document>().unwrap(), doc);
+
+    type Nested = std::collections::BTreeMap<String, std::collections::BTreeMap<String, u8>>;
+    let nested = json(r#"{"a": {"c": 1}, "b": {"c": "x"}}"#);
+    let err = nested.to_value::<Nested>().unwrap_err();
+    assert_eq!(err.path(), Some(&"/b/c".parse().unwrap()));
+}
+
+#[test]
+fn serializer_capacity_test() {
+    #[derive(Serialize)]
+    struct Point {
+        x: u8,
+        y: u8,
+    }
+
+    let items: Vec<u32> = (0..1000).collect();
+    let doc = Document::new(&items).unwrap();
+    match &doc {
+        Document::Seq(seq) => {
+            assert_eq!(seq.len(), 1000);
+            assert_eq!(seq.capacity(), 1000);
+        }
+        other => panic!("unexpected {:?}", other),
+    }
+
+    let mut map = std::collections::HashMap::new();
+    map.insert("a", Point { x: 1, y: 2 });
+    let doc = Document::new(&map).unwrap();
+    assert_eq!(doc["a"]["y"], 2);
+    assert_eq!(Document::new((1u8, "b")).unwrap()[1], "b");
+}
+
+#[test]
+fn missing_vs_null_test() {
+    let json = |s: &str| serde_json::from_str::<Document>(s).unwrap();
+    let doc = json(r#"{"a": null, "b": [1, null], "c": {"d": 1}}"#);
+
+    assert!(doc["a"].is_null());
+    assert!(!doc["a"].is_missing());
+    assert_eq!(doc.get("a"), Some(&Document::Null));
+    assert!(doc["x"].is_missing());
+    assert!(!doc["x"].is_null());
+    assert_eq!(doc.get("x"), None);
+    assert_ne!(doc["a"], doc["x"]);
+
+    assert!(doc["b"][1].is_null());
+    assert!(doc["b"][2].is_missing());
+    assert!(doc["b"][-3].is_missing());
+    assert!(doc["c"]["d"]["e"].is_missing());
+    assert!(doc["x"]["y"][0].is_missing());
+    assert_eq!(doc["b"].get(2), None);
+
+    // Missing values read as None, the same as null
+    assert_eq!(doc["x"].to_value::<Option<u8>>().unwrap(), None);
+    assert_eq!(doc["a"].clone().try_into::<Option<u8>>().unwrap(), None);
+    assert_eq!(Document::filter(std::slice::from_ref(&doc), "[0].x").unwrap(), json("{}"));
+}
+
+#[test]
+fn missing_sentinel_test() {
+    #[derive(Clone)]
+    struct Custom;
+
+    impl UnstructuredDataTrait for Custom {
+        type ErrorType = std::fmt::Error;
+        type OtherType = String;
+    }
+
+    let mut doc = Unstructured::<Custom>::Null;
+    doc["a"] = Unstructured::<Custom>::Other("x".to_string());
+    doc["b"][0] = 1u8.into();
+    assert!(matches!(doc["a"], Unstructured::<Custom>::Other(ref o) if o == "x"));
+    assert!(doc["missing"].is_missing());
+    assert!(doc["b"][3].is_missing());
+    assert!(doc["a"]["nested"][0].is_missing());
+    assert!(Unstructured::<Custom>::missing_ref().is_missing());
+    assert!(Unstructured::<Custom>::MISSING.is_missing());
+
+    fn first_tag<T: UnstructuredDataTrait>(doc: &Unstructured<T>) -> &Unstructured<T> {
+        &doc["tags"][0]
+    }
+    assert!(first_tag(&doc).is_missing());
+    assert!(first_tag(&Document::Null).is_missing());
+
+    // Implementations may borrow, they don't have to be 'static
+    #[derive(Clone)]
+    struct Borrowed<'a>(std::marker::PhantomData<&'a str>);
+
+    impl<'a> UnstructuredDataTrait for Borrowed<'a> {
+        type ErrorType = std::fmt::Error;
+        type OtherType = &'a str;
+    }
+
+    let local = String::from("local");
+    let mut doc = Unstructured::<Borrowed>::Null;
+    doc["a"] = Unstructured::<Borrowed>::Other(&local);
+    assert!(matches!(doc["a"], Unstructured::<Borrowed>::Other("local")));
+    assert!(doc["a"]["missing"].is_missing());
+    assert!(first_tag(&doc).is_missing());
+}
+
+#[test]
+fn bytes_format_test() {
+    let bytes = Document::Bytes(vec![0, 15, 255]);
+    assert_eq!(bytes.to_string(), "000fff");
+    assert_eq!(bytes.display_bytes_as(BytesFormat::Base64).to_string(), "AA//");
+    let nested = Document::from(vec![Document::Option(Some(Box::new(bytes.clone())))]);
+    assert_eq!(nested.display_bytes_as(BytesFormat::Hex).to_string(), "[000fff]");
+    assert_eq!(nested.display_bytes_as(BytesFormat::Base64).to_string(), "[AA//]");
+
+    for len in 0..8u8 {
+        let data: Vec<u8> = (0..len).map(|i| i.wrapping_mul(97)).collect();
+        let encoded = Document::Bytes(data.clone()).bytes_as_base64().unwrap();
+        assert_eq!(Document::from_base64(&encoded).unwrap(), Document::Bytes(data.clone()));
+        let unpadded = encoded.trim_end_matches('=');
+        assert_eq!(Document::from_base64(unpadded).unwrap(), Document::Bytes(data));
+    }
+    assert_eq!(Document::from_base64("aGVsbG8=").unwrap(), Document::Bytes(b"hello".to_vec()));
+    assert!(Document::from_base64("a").is_err());
+    assert!(Document::from_base64("aGk").is_ok());
+    assert!(Document::from_base64("aGk==").is_err());
+    assert!(Document::from_base64("a=Gk").is_err());
+    assert!(Document::from_base64("aG-k").is_err());
+    assert_eq!(Document::from("x").bytes_as_base64(), None);
+
+    // Bytes written as base64 strings for JSON can be read back
+    let mut doc = Document::Null;
+    doc["raw"] = bytes.clone();
+    let options = SerializerOptions {
+        bytes_as_base64: true,
+        ..Default::default()
+    };
+    let json = serde_json::to_string(&doc.with_options(options)).unwrap();
+    assert_eq!(json, r#"{"raw":"AA//"}"#);
+    let parsed: Document = serde_json::from_str(&json).unwrap();
+    let raw: String = parsed["raw"].clone().unwrap();
+    assert_eq!(Document::from_base64(&raw).unwrap(), bytes);
+}
+
+#[test]
+fn pretty_display_test() {
+    let json = |s: &str| serde_json::from_str::<Document>(s).unwrap();
+    let doc = json(r#"{"name": "a \"b\"\n\u0001", "items": [1, [], {}, {"x": null}], "ok": true}"#);
+    let expected = r#"{
+  "items": [
+    1,
+    [],
+    {},
+    {
+      "x": null
+    }
+  ],
+  "name": "a \"b\"\n\u{0001}",
+  "ok": true
+}"#;
+    #[cfg(not(feature = "preserve_order"))]
+    assert_eq!(format!("{:#}", doc), expected);
+    assert_eq!(format!("{:#}", doc).lines().count(), expected.lines().count());
+
+    assert_eq!(format!("{:#}", Document::from("x")), r#""x""#);
+    assert_eq!(format!("{:#}", Document::Char('\'')), r"'\''");
+    assert_eq!(format!("{:#}", json("[]")), "[]");
+    let bytes = Document::from(vec![Document::Bytes(vec![1, 2])]);
+    assert_eq!(format!("{:#}", bytes), "[\n  0102\n]");
+    assert_eq!(format!("{:#}", bytes.display_bytes_as(BytesFormat::Base64)), "[\n  AQI=\n]");
+    // The plain format is unchanged
+    assert_eq!(json(r#"{"a": [1, 2]}"#).to_string(), "{a => [1,2]}");
+}
+
+#[test]
+fn document_formatter_test() {
+    let json = |s: &str| serde_json::from_str::<Document>(s).unwrap();
+    let doc = json(r#"{"z": [1, 2, 3, 4], "a": {"b": {"c": "long string"}}}"#);
+
+    let formatter = DocumentFormatter::new()
+        .indent("    ")
+        .max_elements(2)
+        .max_string_len(4)
+        .sort_keys(true);
+    let expected = r#"{
+    "a": {
+        "b": {
+            "c": "long"...
+        }
+    },
+    "z": [
+        1,
+        2,
+        ... 2 more
+    ]
+}"#;
+    assert_eq!(formatter.format(&doc), expected);
+
+    let compact = DocumentFormatter::new().indent("").max_depth(1).sort_keys(true);
+    assert_eq!(compact.format(&doc), r#"{"a": {...}, "z": [...]}"#);
+    let root_only = DocumentFormatter::new().indent("").max_depth(0);
+    assert_eq!(root_only.format(&doc), "{...}");
+    let none_shown = DocumentFormatter::new().indent("").max_elements(0);
+    assert_eq!(none_shown.format(&json("[1, 2]")), "[... 2 more]");
+    assert_eq!(none_shown.format(&json("[]")), "[]");
+    let short = DocumentFormatter::new().max_string_len(2);
+    assert_eq!(short.format(&Document::from("héllo")), r#""hé"..."#);
+    assert_eq!(short.format(&Document::from("hé")), r#""hé""#);
+
+    let mut out = String::new();
+    compact.write_to(&mut out, &doc).unwrap();
+    assert_eq!(out, compact.format(&doc));
+    let mut bytes = vec![];
+    compact.write_io(&mut bytes, &doc).unwrap();
+    assert_eq!(String::from_utf8(bytes).unwrap(), out);
+    let mut full: &mut [u8] = &mut [0; 4];
+    assert!(compact.write_io(&mut full, &doc).is_err());
+
+    assert_eq!(DocumentFormatter::new().format(&doc), format!("{:#}", doc));
+}
+
+#[cfg(feature = "hash")]
+#[test]
+fn redact_hash_test() {
+    let input = r#"{"auth": {"token": "t"}, "sessions": [{"token": "s1"}, {"token": "s2"}]}"#;
+    let mut doc: Document = serde_json::from_str(input).unwrap();
+    doc.redact(&["..token"], ReplaceWith::Hash(b"secret".to_vec())).unwrap();
+    let hashed = doc["auth"]["token"].clone();
+    assert!(matches!(&hashed, Document::String(s) if s.len() == 64 && s != "t"));
+    assert_ne!(doc["sessions"][0]["token"], doc["sessions"][1]["token"]);
+
+    let mut again = Document::from("t");
+    again.redact(&["."], ReplaceWith::Hash(b"secret".to_vec())).unwrap();
+    assert_eq!(again, hashed);
+    let mut other_key = Document::from("t");
+    other_key.redact(&["."], ReplaceWith::Hash(b"other".to_vec())).unwrap();
+    assert_ne!(other_key, hashed);
+}
+
+#[cfg(feature = "hash")]
+#[test]
+fn canonical_hash_test() {
+    let json = |s: &str| serde_json::from_str::<Document>(s).unwrap();
+    let hex = |hash: [u8; 32]| hash.iter().map(|b| format!("{:02x}", b)).collect::<String>();
+
+    // The encoding is fixed, so this must never change between releases
+    assert_eq!(
+        hex(Document::from("hi").canonical_hash()),
+        "abc70029954e01099f4af4507b2b2e897389493f1a4e23f154842d72a5457bcb"
+    );
+
+    let mut reordered = Mapping::new();
+    reordered.insert(Document::from("b"), json("[2, {\"c\": null}]"));
+    reordered.insert(Document::from("a"), Document::Newtype(Box::new(Document::from(1i64))));
+    let original = json(r#"{"a": 1.0, "b": [2, {"c": null}]}"#);
+    assert_eq!(original.canonical_hash(), Document::Map(reordered.into()).canonical_hash());
+
+    assert_ne!(json("[1, 2]").canonical_hash(), json("[2, 1]").canonical_hash());
+    assert_ne!(json("1").canonical_hash(), json("-1").canonical_hash());
+    assert_ne!(json("0.5").canonical_hash(), json("1").canonical_hash());
+    assert_ne!(json(r#""1""#).canonical_hash(), json("1").canonical_hash());
+    assert_ne!(Document::from('a').canonical_hash(), Document::from("a").canonical_hash());
+    assert_ne!(Document::Bytes(b"a".to_vec()).canonical_hash(), json(r#""a""#).canonical_hash());
+    assert_ne!(json(r#"["ab"]"#).canonical_hash(), json(r#"["a", "b"]"#).canonical_hash());
+    assert_eq!(Document::Option(None).canonical_hash(), Document::Null.canonical_hash());
+    assert_eq!(
+        Document::Number(Number::F64(f64::NAN)).canonical_hash(),
+        Document::Number(Number::F32(-f32::NAN)).canonical_hash()
+    );
+
+    // The same instant in a different offset is the same value
+    #[cfg(feature = "chrono")]
+    {
+        let at = |s: &str| Document::DateTime(chrono::DateTime::parse_from_rfc3339(s).unwrap());
+        let utc = at("2024-01-01T12:00:00Z");
+        let offset = at("2024-01-01T14:00:00+02:00");
+        assert_eq!(utc, offset);
+        assert_eq!(utc.canonical_hash(), offset.canonical_hash());
+        assert_ne!(utc.canonical_hash(), at("2024-01-01T12:00:00+02:00").canonical_hash());
+    }
+}
+
+#[test]
+fn dedup_subtrees_test() {
+    let json = |s: &str| serde_json::from_str::<Document>(s).unwrap();
+    let block = r#"{"meta": {"host": "a", "tags": [1, 2]}, "ok": true}"#;
+    let doc = json(&format!("[{0}, {0}, {0}, [1, 2]]", block));
+
+    // Each block has 3 containers and 11 values, the outer sequence is the last container
+    let stats = doc.subtree_stats();
+    assert_eq!(stats.containers, 11);
+    assert_eq!(stats.duplicates, 7);
+    assert_eq!(stats.values_saved, 2 * 11 + 3);
+
+    // Equal but not identical values are left alone
+    let numbers = Document::from(vec![
+        Document::from(vec![Document::from(1u8)]),
+        Document::from(vec![Document::from(1u64)]),
+    ]);
+    assert_eq!(numbers.subtree_stats().duplicates, 0);
+    assert_eq!(json("[[], [], {}]").subtree_stats(), DedupStats {
+        containers: 1,
+        duplicates: 0,
+        values_saved: 0
+    });
+
+    #[cfg(feature = "shared")]
+    {
+        let mut shared = doc.clone();
+        assert_eq!(shared.dedup_subtrees(), stats);
+        assert_eq!(shared, doc);
+        match (&shared[0], &shared[2]) {
+            (Document::Map(l), Document::Map(r)) => assert!(Shared::ptr_eq(l, r)),
+            _ => unreachable!(),
+        }
+        shared[2]["meta"]["host"] = Document::from("b");
+        assert_eq!(shared[0]["meta"]["host"], "a");
+        assert_eq!(shared[1]["meta"]["host"], "a");
+    }
+}
+
+#[test]
+fn approximate_size_test() {
+    let json = |s: &str| serde_json::from_str::<Document>(s).unwrap();
+    let doc_size = std::mem::size_of::<Document>();
+
+    assert_eq!(Document::Null.approximate_size(), doc_size);
+    assert_eq!(Document::from(5).approximate_size(), doc_size);
+    assert_eq!(Document::String(String::with_capacity(100)).approximate_size(), doc_size + 100);
+    assert_eq!(Document::Bytes(vec![0; 10]).approximate_size(), doc_size + 10);
+    assert_eq!(
+        Document::Newtype(Box::new(Document::from("abc"))).approximate_size(),
+        2 * doc_size + 3
+    );
+
+    // Sequences count their spare capacity as well as their elements
+    let empty = Document::Seq(Sequence::new().into()).approximate_size();
+    let mut seq = Sequence::with_capacity(8);
+    seq.push(Document::from("abcd"));
+    assert_eq!(Document::Seq(seq.into()).approximate_size(), empty + 8 * doc_size + 4);
+
+    let small = json(r#"{"a": "b"}"#);
+    let large = json(r#"{"a": "b", "c": {"d": ["e", "f", "g"]}}"#);
+    assert!(small.approximate_size() > doc_size + 2 * doc_size);
+    assert!(large.approximate_size() > small.approximate_size() + 5 * doc_size);
+
+    // Contents shared by several values are only counted once
+    let copy = large.clone();
+    let twice = Document::Seq(vec![copy.clone(), copy.clone()].into());
+    let pair = Document::Seq(vec![copy.clone(), Document::Null].into()).approximate_size();
+    #[cfg(feature = "shared")]
+    assert_eq!(twice.approximate_size(), pair);
+    #[cfg(not(feature = "shared"))]
+    assert_eq!(twice.approximate_size(), pair + copy.approximate_size() - doc_size);
+}
+
+#[test]
+fn deserialize_with_limits_test() {
+    let parse = |s: &str, limits: Limits| {
+        Document::deserialize_with_limits(&mut serde_json::Deserializer::from_str(s), limits)
+    };
+    let input = r#"{"a": [1, {"b": "hello"}], "c": null}"#;
+
+    let unlimited = parse(input, Limits::default()).unwrap();
+    assert_eq!(unlimited, serde_json::from_str::<Document>(input).unwrap());
+    let exact = Limits {
+        max_depth: Some(3),
+        max_nodes: Some(9),
+        max_string_len: Some(5),
+    };
+    assert_eq!(parse(input, exact).unwrap(), unlimited);
+
+    let err = parse(input, Limits { max_depth: Some(2), ..exact }).unwrap_err();
+    assert!(err.to_string().contains("nested deeper than 2"), "{}", err);
+    let err = parse(input, Limits { max_nodes: Some(8), ..exact }).unwrap_err();
+    assert!(err.to_string().contains("more than 8 values"), "{}", err);
+    let err = parse(input, Limits { max_string_len: Some(4), ..exact }).unwrap_err();
+    assert!(err.to_string().contains("longer than 4"), "{}", err);
+
+    // Scalars at the root have no depth
+    let scalar_only = Limits { max_depth: Some(0), ..Limits::default() };
+    assert_eq!(parse("1", scalar_only).unwrap(), 1);
+    assert!(parse("[]", scalar_only).is_err());
+
+    // Deep input is rejected before the parser's own recursion limit is reached
+    let deep = format!("{}{}", "[".repeat(100), "]".repeat(100));
+    let err = parse(&deep, Limits { max_depth: Some(64), ..Limits::default() }).unwrap_err();
+    assert!(err.to_string().contains("nested deeper than 64"), "{}", err);
+}
+
+#[test]
+fn deep_nesting_test() {
+    // Deep enough to overflow the test thread's stack if any of these recursed
+    let nest = |leaf: Document| {
+        let mut doc = leaf;
+        for i in 0..100_000 {
+            doc = match i % 4 {
+                0 => Document::from(vec![doc]),
+                1 => {
+                    let mut map = Mapping::new();
+                    map.insert(Document::from("k"), doc);
+                    Document::Map(map.into())
+                }
+                2 => Document::Newtype(Box::new(doc)),
+                _ => Document::Option(Some(Box::new(doc))),
+            };
+        }
+        doc
+    };
+
+    let hash = |doc: &Document| {
+        use std::hash::{Hash, Hasher};
+        let mut hasher = std::collections::hash_map::DefaultHasher::new();
+        doc.hash(&mut hasher);
+        hasher.finish()
+    };
+
+    let doc = nest(Document::from(1));
+    let copy = doc.clone();
+    let other = nest(Document::from(2));
+    assert!(doc == copy);
+    assert!(doc != other);
+    assert!(doc != Document::Null);
+    assert_eq!(doc.cmp(&copy), std::cmp::Ordering::Equal);
+    assert!(doc < other);
+    assert_eq!(hash(&doc), hash(&copy));
+    assert_ne!(hash(&doc), hash(&other));
+    assert!(doc.approximate_size() > 100_000 * std::mem::size_of::<Document>());
+
+    drop(doc);
+    drop(copy);
+    drop(other);
+
+    // A chain of wrappers with nothing between them still needs drop_deep
+    let mut wrapped = Document::Null;
+    for _ in 0..100_000 {
+        wrapped = Document::Newtype(Box::new(wrapped));
+    }
+    wrapped.drop_deep();
+}
+
+#[cfg(feature = "arbitrary")]
+#[test]
+fn arbitrary_document_test() {
+    use arbitrary::Arbitrary;
+
+    // Returns the depth, number of values and longest string or byte buffer
+    fn measure(doc: &Document) -> (usize, usize, usize) {
+        let children: Vec<&Document> = match doc {
+            Document::Seq(seq) => seq.iter().collect(),
+            Document::Map(map) => map.iter().flat_map(|(k, v)| vec![k, v]).collect(),
+            Document::Option(Some(v)) | Document::Newtype(v) => vec![v],
+            Document::String(s) => return (0, 1, s.len()),
+            Document::Bytes(b) => return (0, 1, b.len()),
+            _ => vec![],
+        };
+        children.into_iter().fold((0, 1, 0), |(depth, nodes, len), child| {
+            let (d, n, l) = measure(child);
+            (depth.max(d + 1), nodes + n, len.max(l))
+        })
+    }
+
+    let limits = Limits {
+        max_depth: Some(4),
+        max_nodes: Some(40),
+        max_string_len: Some(8),
+    };
+    let mut kinds = std::collections::BTreeSet::new();
+    for seed in 0..200u32 {
+        let bytes: Vec<u8> = (0..2048u32)
+            .map(|i| (i * 31 + seed * 17 + i * i * seed) as u8)
+            .collect();
+        let mut input = arbitrary::Unstructured::new(&bytes);
+        let doc = Document::arbitrary_with_limits(&mut input, limits).unwrap();
+        let (depth, nodes, len) = measure(&doc);
+        assert!(depth <= 4 && nodes <= 40 && len <= 8, "{:?}", doc);
+        assert!(serde_json::to_string(&doc).is_ok());
+        kinds.insert(doc.type_name());
+
+        let doc = Document::arbitrary(&mut arbitrary::Unstructured::new(&bytes)).unwrap();
+        assert_eq!(doc.clone(), doc);
+    }
+    assert!(kinds.len() > 5, "{:?}", kinds);
+}
+
+#[test]
+fn custom_other_type_test() {
+    #[derive(Clone, Debug, PartialEq)]
+    struct Point(i64, i64);
+
+    impl std::fmt::Display for Point {
+        fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+            write!(f, "({}, {})", self.0, self.1)
+        }
+    }
+
+    #[derive(Clone, Debug)]
+    struct Geo;
+
+    impl UnstructuredDataTrait for Geo {
+        type ErrorType = UnstructuredError;
+        type OtherType = Point;
+
+        fn serialize_other<S: serde::Serializer>(p: &Point, s: S) -> Result<S::Ok, S::Error> {
+            [p.0, p.1].serialize(s)
+        }
+
+        fn deserialize_other(value: &Unstructured<Self>) -> Option<Point> {
+            match value {
+                Unstructured::Map(map) if map.len() == 2 => {
+                    let x = map.get(&Unstructured::from("x"))?.to_value().ok()?;
+                    let y = map.get(&Unstructured::from("y"))?.to_value().ok()?;
+                    Some(Point(x, y))
+                }
+                _ => None,
+            }
+        }
+
+        fn display_other(p: &Point, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+            write!(f, "<{}>", p)
+        }
+    }
+
+    #[derive(Deserialize, PartialEq, Debug)]
+    struct Place {
+        name: String,
+        at: (i64, i64),
+    }
+
+    let doc: Unstructured<Geo> =
+        serde_json::from_str(r#"{"name": "home", "at": {"x": 1, "y": -2}}"#).unwrap();
+    assert!(matches!(&doc["at"], Unstructured::Other(Point(1, -2))));
+    assert_eq!(doc["at"].to_string(), "<(1, -2)>");
+    assert_eq!(DocumentFormatter::new().indent("").format(&doc["at"]), "<(1, -2)>");
+    assert_eq!(serde_json::to_string(&doc["at"]).unwrap(), "[1,-2]");
+
+    let expected = Place { name: "home".into(), at: (1, -2) };
+    assert_eq!(doc.to_value::<Place>().unwrap(), expected);
+    assert_eq!(doc.try_into::<Place>().unwrap(), expected);
+
+    // The defaults are unchanged for documents
+    let other: Document = Document::Other(DefaultOther);
+    assert_eq!(serde_json::to_string(&other).unwrap(), r#""other""#);
+    assert_eq!(other.to_value::<String>().unwrap(), "other");
+}
+
+#[test]
+fn error_propagation_test() {
+    let json = |s: &str| serde_json::from_str::<Document>(s).unwrap();
+    let doc = json(r#"{"price": 12, "qty": 0, "name": "widget", "tags": ["a"]}"#);
+
+    let err = doc["price"].clone() / doc["qty"].clone();
+    let by_zero = |doc: &Document| {
+        matches!(doc, Document::Err(UnstructuredError::Message(m)) if m == "Division by zero")
+    };
+    assert!(by_zero(&err));
+    assert!((doc["price"].clone() % doc["qty"].clone()).ok().is_err());
+    assert_eq!((doc["price"].clone() - doc["qty"].clone()).ok().unwrap(), 12);
+    let float = Document::from(1.0) / doc["qty"].clone();
+    assert!(float.ok().unwrap().unwrap::<f64>().is_infinite());
+
+    // Errors carry through further arithmetic, indexing and selection
+    let total = (err.clone() + Document::from(1)) * Document::from(2);
+    assert_eq!(total.ok().unwrap_err().to_string(), "Division by zero");
+    assert!(err["a"][0].clone().ok().is_err());
+    assert!(err.select_or_err(".a.b").ok().is_err());
+
+    let mismatch = doc["name"].clone() - doc["price"].clone();
+    assert_eq!(mismatch.ok().unwrap_err().to_string(), "Cannot subtract string and number");
+    assert!(doc.select_or_err(".tags[").ok().is_err());
+    assert_eq!(doc.select_or_err(".tags[0]"), "a");
+
+    // ok finds errors anywhere in the document
+    let mut nested = doc.clone();
+    nested["tags"][1] = doc["name"].clone() * Document::from(2);
+    assert!(nested.clone().ok().is_err());
+    assert_eq!(doc.clone().ok().unwrap(), doc);
+
+    // Without error support the old behaviour is kept
+    #[derive(Clone, Debug)]
+    struct Plain;
+    impl UnstructuredDataTrait for Plain {
+        type ErrorType = UnstructuredError;
+        type OtherType = DefaultOther;
+    }
+    let plain = Unstructured::<Plain>::from("x") - Unstructured::<Plain>::from(1);
+    assert_eq!(plain, Unstructured::<Plain>::Null);
+    let zero = Unstructured::<Plain>::from(0);
+    assert_eq!(Unstructured::<Plain>::from(12) / zero.clone(), Unstructured::<Plain>::Null);
+    assert_eq!(Unstructured::<Plain>::from(12) % zero, Unstructured::<Plain>::Null);
+}
+
+#[test]
+fn document_builder_test() {
+    let json = |s: &str| serde_json::from_str::<Document>(s).unwrap();
+
+    let keys = ["x", "y"];
+    let doc = keys
+        .iter()
+        .enumerate()
+        .fold(DocumentBuilder::new(), |b, (i, k)| b.key(*k).value(i as u64))
+        .key("items")
+        .seq(|s| s.push(1).map(|m| m.key("z").value(Document::Null)).seq(|s| s))
+        .build()
+        .unwrap();
+    assert_eq!(doc, json(r#"{"x": 0, "y": 1, "items": [1, {"z": null}, []]}"#));
+
+    let new = DocumentBuilder::<UnstructuredType>::new;
+    assert_eq!(new().value("a").build().unwrap(), "a");
+    assert_eq!(new().push(1).value(2).build().unwrap(), json("[1, 2]"));
+    assert!(new().build().unwrap().is_missing());
+
+    // Misuse is an error from build, and the first one is kept
+    let build_err = |b: DocumentBuilder| match b.build() {
+        Err(Error::Build(msg)) => msg,
+        other => panic!("expected a build error, got {:?}", other),
+    };
+    assert!(build_err(new().key("a")).contains("waiting for a value"));
+    assert_eq!(build_err(new().value(1).value(2)), "Cannot add a value to a number");
+    assert_eq!(build_err(new().value(1).key("a").value(2).push(3)), "Cannot add key a to number");
+    assert!(build_err(new().key("a").key("b").key("c")).contains("before a value for a"));
+    assert!(build_err(new().key("a").push(1)).starts_with("Cannot push"));
+    let nested = new().key("a").map(|m| m.key("b")).key("c").value(1);
+    assert!(build_err(nested).contains("key b"));
+}